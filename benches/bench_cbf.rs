@@ -6,9 +6,9 @@ pub fn bench_add_entries(c: &mut Criterion) {
     let mut cbf = CountingBloomFilter::<metro::Hasher64_1>::new(250_000, 4).unwrap();
     c.bench_function("bench_add_entries", |b| {
         b.iter(|| {
-            std::hint::black_box(for i in 0..1_000_000_u64 {
-                cbf.add(&i.to_ne_bytes());
-            });
+            for i in 0..1_000_000_u64 {
+                std::hint::black_box(cbf.add(i.to_ne_bytes()));
+            }
         })
     });
 }
@@ -16,23 +16,49 @@ pub fn bench_add_entries(c: &mut Criterion) {
 pub fn bench_contains(c: &mut Criterion) {
     let mut cbf = CountingBloomFilter::<metro::Hasher64_1>::new(250_000, 4).unwrap();
     for i in 0..1_000_000_u64 {
-        cbf.add(&i.to_ne_bytes());
+        cbf.add(i.to_ne_bytes());
     }
     c.bench_function("bench_contains_existing", |b| {
         b.iter(|| {
-            std::hint::black_box(for i in 0..1_000_000_u64 {
-                cbf.contains(&i.to_ne_bytes());
-            })
+            for i in 0..1_000_000_u64 {
+                std::hint::black_box(cbf.contains(i.to_ne_bytes()));
+            }
         })
     });
     c.bench_function("bench_contains_nonexisting", |b| {
         b.iter(|| {
-            std::hint::black_box(for i in 1_000_000_u64..2_000_000_u64 {
-                cbf.contains(&i.to_ne_bytes());
-            })
+            for i in 1_000_000_u64..2_000_000_u64 {
+                std::hint::black_box(cbf.contains(i.to_ne_bytes()));
+            }
         })
     });
 }
 
-criterion_group!(bench_cbf, bench_add_entries, bench_contains);
+pub fn bench_contains_batch(c: &mut Criterion) {
+    // far larger than the CPU cache, so lookups wait on memory
+    let mut cbf = CountingBloomFilter::<metro::Hasher64_1>::new(256_000_000, 4).unwrap();
+    for i in 0..1_000_000_u64 {
+        cbf.add(i.to_ne_bytes());
+    }
+    let entries = (0..100_000_u64)
+        .map(|i| (i * 7).to_ne_bytes())
+        .collect::<Vec<_>>();
+    c.bench_function("bench_contains_large", |b| {
+        b.iter(|| {
+            for entry in &entries {
+                std::hint::black_box(cbf.contains(entry));
+            }
+        })
+    });
+    c.bench_function("bench_contains_batch_large", |b| {
+        b.iter(|| std::hint::black_box(cbf.contains_batch(&entries)))
+    });
+}
+
+criterion_group!(
+    bench_cbf,
+    bench_add_entries,
+    bench_contains,
+    bench_contains_batch
+);
 criterion_main!(bench_cbf);
@@ -0,0 +1,106 @@
+use std::{sync::Mutex, thread};
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use fasthash::metro;
+use probably_filters::{ConcurrentCuckooFilter, CountingBloomFilter, SharedFilter};
+
+const THREADS: u64 = 4;
+const OPS_PER_THREAD: u64 = 100_000;
+
+#[repr(align(64))]
+struct Padded<T>(T);
+
+// every thread locks a stripe of its own, so any slowdown of the packed stripes over the padded
+// ones comes from neighbouring stripes sharing a cache line
+pub fn bench_lock_stripes(c: &mut Criterion) {
+    let mut group = c.benchmark_group("bench_lock_stripes");
+    group.bench_function("padded", |b| {
+        let stripes = (0..THREADS)
+            .map(|_| Padded(Mutex::new(0_u64)))
+            .collect::<Vec<_>>();
+        b.iter(|| hammer_stripes(|t| &stripes[t as usize].0))
+    });
+    group.bench_function("packed", |b| {
+        let stripes = (0..THREADS).map(|_| Mutex::new(0_u64)).collect::<Vec<_>>();
+        b.iter(|| hammer_stripes(|t| &stripes[t as usize]))
+    });
+    group.finish();
+}
+
+fn hammer_stripes<'a, F>(stripe: F)
+where
+    F: Fn(u64) -> &'a Mutex<u64> + Sync,
+{
+    thread::scope(|scope| {
+        for t in 0..THREADS {
+            let stripe = &stripe;
+            scope.spawn(move || {
+                for _ in 0..OPS_PER_THREAD {
+                    *stripe(t).lock().unwrap() += 1;
+                }
+            });
+        }
+    });
+}
+
+// every thread adds and looks up its own keys, so the only contention is on shared cache lines
+pub fn bench_concurrent_cuckoo(c: &mut Criterion) {
+    c.bench_function("bench_concurrent_cuckoo", |b| {
+        b.iter_batched(
+            || ConcurrentCuckooFilter::<metro::Hasher64_1>::new(1 << 18).unwrap(),
+            |filter| {
+                thread::scope(|scope| {
+                    for t in 0..THREADS {
+                        let filter = &filter;
+                        scope.spawn(move || {
+                            for i in t * OPS_PER_THREAD..(t + 1) * OPS_PER_THREAD {
+                                let _ = filter.add(i.to_ne_bytes());
+                                std::hint::black_box(filter.contains(i.to_ne_bytes()));
+                            }
+                        });
+                    }
+                });
+            },
+            BatchSize::LargeInput,
+        )
+    });
+}
+
+pub fn bench_shared_filter(c: &mut Criterion) {
+    let mut group = c.benchmark_group("bench_shared_filter");
+    for (name, padded) in [("padded", true), ("packed", false)] {
+        group.bench_function(name, |b| {
+            b.iter_batched(
+                || {
+                    SharedFilter::with_padding(16, padded, || {
+                        CountingBloomFilter::<metro::Hasher64_1>::new(100_000, 4).unwrap()
+                    })
+                    .unwrap()
+                },
+                |filter| {
+                    thread::scope(|scope| {
+                        for t in 0..THREADS {
+                            let filter = filter.clone();
+                            scope.spawn(move || {
+                                for i in t * OPS_PER_THREAD..(t + 1) * OPS_PER_THREAD {
+                                    filter.update(i.to_ne_bytes());
+                                    std::hint::black_box(filter.contains(i.to_ne_bytes()));
+                                }
+                            });
+                        }
+                    });
+                },
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(
+    bench_concurrent,
+    bench_lock_stripes,
+    bench_concurrent_cuckoo,
+    bench_shared_filter
+);
+criterion_main!(bench_concurrent);
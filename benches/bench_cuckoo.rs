@@ -3,33 +3,33 @@ use fasthash::metro;
 use probably_filters::CuckooFilter;
 
 pub fn bench_add(c: &mut Criterion) {
-    let mut cbf = CuckooFilter::<metro::Hasher64_1>::new(500_000);
+    let mut cbf = CuckooFilter::<metro::Hasher64_1>::new(500_000).unwrap();
     c.bench_function("bench_add_entries", |b| {
         b.iter(|| {
-            std::hint::black_box(for i in 0..1_000_000_u64 {
-                cbf.add(&i.to_ne_bytes());
-            });
+            for i in 0..1_000_000_u64 {
+                let _ = std::hint::black_box(cbf.add(i.to_ne_bytes()));
+            }
         })
     });
 }
 
 pub fn bench_contains(c: &mut Criterion) {
-    let mut cbf = CuckooFilter::<metro::Hasher64_1>::new(500_000);
+    let mut cbf = CuckooFilter::<metro::Hasher64_1>::new(500_000).unwrap();
     for i in 0..1_000_000_u64 {
-        cbf.add(&i.to_ne_bytes());
+        cbf.add(i.to_ne_bytes()).unwrap();
     }
     c.bench_function("bench_contains_existing", |b| {
         b.iter(|| {
-            std::hint::black_box(for i in 0..1_000_000_u64 {
-                cbf.contains(&i.to_ne_bytes());
-            })
+            for i in 0..1_000_000_u64 {
+                std::hint::black_box(cbf.contains(i.to_ne_bytes()));
+            }
         })
     });
     c.bench_function("bench_contains_nonexisting", |b| {
         b.iter(|| {
-            std::hint::black_box(for i in 1_000_000_u64..2_000_000_u64 {
-                cbf.contains(&i.to_ne_bytes());
-            })
+            for i in 1_000_000_u64..2_000_000_u64 {
+                std::hint::black_box(cbf.contains(i.to_ne_bytes()));
+            }
         })
     });
 }
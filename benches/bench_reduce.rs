@@ -0,0 +1,45 @@
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+
+const HASHES: u64 = 1_000_000;
+// a prime, so modulo and fastrange can't be strength reduced to a mask
+const LEN: u64 = 1_000_003;
+
+// splitmix64, so every bit of the hashes is random
+fn hashes() -> Vec<u64> {
+    (0..HASHES)
+        .map(|i| {
+            let mut z = i.wrapping_mul(0x9e37_79b9_7f4a_7c15);
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+            z ^ (z >> 31)
+        })
+        .collect()
+}
+
+// the three ways of reducing a hash to an index that filters choose between, each summing the
+// indexes of a million hashes into a table of about a million positions
+pub fn bench_reduce(c: &mut Criterion) {
+    let hashes = hashes();
+    c.bench_function("bench_reduce_modulo", |b| {
+        let len = black_box(LEN);
+        b.iter(|| hashes.iter().map(|&h| h % len).sum::<u64>())
+    });
+    c.bench_function("bench_reduce_mask", |b| {
+        let mask = black_box(LEN.next_power_of_two() - 1);
+        b.iter(|| hashes.iter().map(|&h| h & mask).sum::<u64>())
+    });
+    c.bench_function("bench_reduce_fastrange", |b| {
+        let len = black_box(LEN);
+        b.iter(|| {
+            hashes
+                .iter()
+                .map(|&h| ((h as u128 * len as u128) >> 64) as u64)
+                .sum::<u64>()
+        })
+    });
+}
+
+criterion_group!(benches, bench_reduce);
+criterion_main!(benches);
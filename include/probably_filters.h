@@ -0,0 +1,140 @@
+#ifndef PROBABLY_FILTERS_H
+#define PROBABLY_FILTERS_H
+
+/* Generated by cbindgen from src/ffi.rs, do not edit. */
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+// Opaque handle to a [BloomFilter].
+typedef struct PfBloomFilter PfBloomFilter;
+
+// Opaque handle to a [CountingBloomFilter].
+typedef struct PfCountingBloomFilter PfCountingBloomFilter;
+
+// Opaque handle to a [CuckooFilter].
+typedef struct PfCuckooFilter PfCuckooFilter;
+
+// Create a bloom filter with `num_bits` bits and `num_hashes` hashes, or null if either is
+// invalid.
+struct PfBloomFilter *pf_bloom_new(size_t num_bits, uint32_t num_hashes);
+
+// Load a bloom filter serialized by [pf_bloom_to_bytes], or null if the bytes are malformed.
+//
+// # Safety
+//
+// `bytes` must point to `len` readable bytes, or be null if `len` is 0.
+struct PfBloomFilter *pf_bloom_from_bytes(const uint8_t *bytes, size_t len);
+
+// Add an entry, returning true if any bit was set.
+//
+// # Safety
+//
+// `filter` must come from this library and not have been freed, and `entry` must point to
+// `len` readable bytes, or be null if `len` is 0.
+bool pf_bloom_add(struct PfBloomFilter *filter, const uint8_t *entry, size_t len);
+
+// # Safety
+//
+// As for [pf_bloom_add].
+bool pf_bloom_contains(const struct PfBloomFilter *filter, const uint8_t *entry, size_t len);
+
+// Serialize a bloom filter, storing the length in `len`.  The buffer must be released with
+// [pf_bytes_free].
+//
+// # Safety
+//
+// `filter` must come from this library and not have been freed, and `len` must be writable.
+uint8_t *pf_bloom_to_bytes(const struct PfBloomFilter *filter, size_t *len);
+
+// # Safety
+//
+// `filter` must come from this library, or be null, and must not be used afterwards.
+void pf_bloom_free(struct PfBloomFilter *filter);
+
+// Create a counting bloom filter with `num_counters` 4 bit counters and `num_hashes` hashes,
+// or null if either is invalid.
+struct PfCountingBloomFilter *pf_counting_bloom_new(size_t num_counters, uint32_t num_hashes);
+
+// Add an entry, returning false if a counter was already at its maximum.
+//
+// # Safety
+//
+// `filter` must come from this library and not have been freed, and `entry` must point to
+// `len` readable bytes, or be null if `len` is 0.
+bool pf_counting_bloom_add(struct PfCountingBloomFilter *filter, const uint8_t *entry, size_t len);
+
+// Remove an entry, returning false if it wasn't present.
+//
+// # Safety
+//
+// As for [pf_counting_bloom_add].
+bool pf_counting_bloom_remove(struct PfCountingBloomFilter *filter,
+                              const uint8_t *entry,
+                              size_t len);
+
+// # Safety
+//
+// As for [pf_counting_bloom_add].
+bool pf_counting_bloom_contains(const struct PfCountingBloomFilter *filter,
+                                const uint8_t *entry,
+                                size_t len);
+
+// # Safety
+//
+// `filter` must come from this library, or be null, and must not be used afterwards.
+void pf_counting_bloom_free(struct PfCountingBloomFilter *filter);
+
+// Create a cuckoo filter with at least `num_bins` bins, or null if `num_bins` is invalid.
+struct PfCuckooFilter *pf_cuckoo_new(size_t num_bins);
+
+// Load a cuckoo filter serialized by [pf_cuckoo_to_bytes], or null if the bytes are malformed.
+//
+// # Safety
+//
+// `bytes` must point to `len` readable bytes, or be null if `len` is 0.
+struct PfCuckooFilter *pf_cuckoo_from_bytes(const uint8_t *bytes, size_t len);
+
+// Add an entry, returning false if the filter is full.
+//
+// # Safety
+//
+// `filter` must come from this library and not have been freed, and `entry` must point to
+// `len` readable bytes, or be null if `len` is 0.
+bool pf_cuckoo_add(struct PfCuckooFilter *filter, const uint8_t *entry, size_t len);
+
+// Remove an entry, returning false if it wasn't found.
+//
+// # Safety
+//
+// As for [pf_cuckoo_add].
+bool pf_cuckoo_remove(struct PfCuckooFilter *filter, const uint8_t *entry, size_t len);
+
+// # Safety
+//
+// As for [pf_cuckoo_add].
+bool pf_cuckoo_contains(const struct PfCuckooFilter *filter, const uint8_t *entry, size_t len);
+
+// Serialize a cuckoo filter, storing the length in `len`.  The buffer must be released with
+// [pf_bytes_free].
+//
+// # Safety
+//
+// `filter` must come from this library and not have been freed, and `len` must be writable.
+uint8_t *pf_cuckoo_to_bytes(const struct PfCuckooFilter *filter, size_t *len);
+
+// # Safety
+//
+// `filter` must come from this library, or be null, and must not be used afterwards.
+void pf_cuckoo_free(struct PfCuckooFilter *filter);
+
+// Release a buffer returned by one of the `*_to_bytes` functions.
+//
+// # Safety
+//
+// `bytes` and `len` must be exactly as returned by a `*_to_bytes` function, or `bytes` must be
+// null, and the buffer must not be used afterwards.
+void pf_bytes_free(uint8_t *bytes, size_t len);
+
+#endif  /* PROBABLY_FILTERS_H */
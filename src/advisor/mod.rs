@@ -0,0 +1,244 @@
+use std::time::Duration;
+use thiserror::Error;
+
+use crate::{
+    bloom, cuckoo, hasher::SeedableHasher, sizing, BloomFilter, CountingBloomFilter, CuckooFilter,
+};
+
+#[cfg(test)]
+mod test;
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("Invalid false positive probability {0}: must be 0 < fpp < 1")]
+    InvalidFpp(f64),
+
+    #[error("Invalid expected entries {0}: must be >= 1 and fit in usize")]
+    InvalidCapacity(f64),
+
+    #[error("Invalid insert rate {0}: must be finite and >= 0")]
+    InvalidInsertRate(f64),
+
+    #[error("Invalid fraction {0}: must be 0 <= fraction <= 1")]
+    InvalidFraction(f64),
+
+    #[error("Invalid multiplicity {0}: must be > 0")]
+    InvalidMultiplicity(u64),
+
+    #[error("Failed to size the filter: {0}")]
+    Sizing(#[from] sizing::Error),
+
+    #[error("Failed to build the bloom filter: {0}")]
+    Bloom(#[from] bloom::Error),
+
+    #[error("Failed to build the cuckoo filter: {0}")]
+    Cuckoo(#[from] cuckoo::Error),
+}
+
+// bits per counter that divide evenly into a u64, narrowest first
+const COUNTER_WIDTHS: [u32; 5] = [4, 8, 16, 32, 64];
+// a lookup-heavy workload takes a cuckoo filter up to this much larger than a bloom filter, as
+// a lookup reads two bins rather than a cache line per hash
+const LOOKUP_HEAVY_FRACTION: f64 = 0.5;
+const LOOKUP_HEAVY_SLACK: f64 = 1.25;
+
+/// Observed behaviour of the workload a filter serves, for [advise].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Workload {
+    /// Distinct entries already in the filter, e.g. from a HyperLogLog.
+    pub distinct_entries: usize,
+    /// New distinct entries per second.
+    pub insert_rate: f64,
+    /// How long the filter must keep taking new entries before it's rebuilt.
+    pub horizon: Duration,
+    /// Fraction of operations that are lookups.
+    pub lookup_fraction: f64,
+    /// Fraction of operations that remove an entry.
+    pub remove_fraction: f64,
+    /// Most times the same entry is added while present, if the filter must count them.
+    pub max_multiplicity: u64,
+    /// False positive rate measured against a filter built for the same target, if any.
+    pub measured_fpp: Option<f64>,
+}
+
+impl Default for Workload {
+    fn default() -> Self {
+        Workload {
+            distinct_entries: 0,
+            insert_rate: 0.0,
+            horizon: Duration::ZERO,
+            lookup_fraction: 0.0,
+            remove_fraction: 0.0,
+            max_multiplicity: 1,
+            measured_fpp: None,
+        }
+    }
+}
+
+/// The filter and parameters [advise] recommends for a workload.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Recommendation {
+    /// A [BloomFilter], when entries are only added.
+    Bloom { num_bits: usize, num_hashes: u32 },
+    /// A [CountingBloomFilter], when entries are counted.
+    CountingBloom {
+        num_counters: usize,
+        num_hashes: u32,
+        bits_per_counter: u32,
+    },
+    /// A [CuckooFilter], when entries are removed, or it's smaller than a bloom filter.
+    Cuckoo {
+        num_bins: usize,
+        entries_per_bin: usize,
+        fingerprint_bits: u32,
+    },
+}
+
+/// A filter built from a [Recommendation].
+pub enum AdvisedFilter<T>
+where
+    T: SeedableHasher,
+{
+    Bloom(BloomFilter<T>),
+    CountingBloom(CountingBloomFilter<T>),
+    // boxed, as it's much larger than the bloom filters
+    Cuckoo(Box<CuckooFilter<T>>),
+}
+
+impl Recommendation {
+    /// Bits of memory the recommended filter's positions, counters or bins take.
+    pub fn mem_bits(&self) -> u64 {
+        match *self {
+            Recommendation::Bloom { num_bits, .. } => num_bits as u64,
+            Recommendation::CountingBloom {
+                num_counters,
+                bits_per_counter,
+                ..
+            } => num_counters as u64 * bits_per_counter as u64,
+            Recommendation::Cuckoo {
+                num_bins,
+                entries_per_bin,
+                fingerprint_bits,
+            } => num_bins as u64 * entries_per_bin as u64 * fingerprint_bits as u64,
+        }
+    }
+
+    /// Build the recommended filter.
+    pub fn build<T>(&self) -> Result<AdvisedFilter<T>, Error>
+    where
+        T: SeedableHasher,
+    {
+        Ok(match *self {
+            Recommendation::Bloom {
+                num_bits,
+                num_hashes,
+            } => AdvisedFilter::Bloom(BloomFilter::new(num_bits, num_hashes)?),
+            Recommendation::CountingBloom {
+                num_counters,
+                num_hashes,
+                bits_per_counter,
+            } => AdvisedFilter::CountingBloom(CountingBloomFilter::with_bits_per_counter(
+                num_counters,
+                num_hashes,
+                bits_per_counter,
+            )?),
+            Recommendation::Cuckoo {
+                num_bins,
+                entries_per_bin,
+                fingerprint_bits,
+            } => AdvisedFilter::Cuckoo(Box::new(CuckooFilter::with_fingerprint_bits(
+                num_bins,
+                entries_per_bin,
+                cuckoo::DEFAULT_MAX_KICKS,
+                fingerprint_bits,
+            )?)),
+        })
+    }
+}
+
+/// Recommend a filter and its parameters for `workload`, with a false positive probability of
+/// at most `target_fpp` once it holds every entry expected over the workload's horizon.
+///
+/// A workload that counts entries gets a counting bloom filter with the narrowest counters that
+/// hold `max_multiplicity`, one that removes them gets a cuckoo filter, and otherwise the
+/// smaller of a bloom and a cuckoo filter is chosen, leaning towards the cuckoo filter when
+/// most operations are lookups.
+///
+/// A `measured_fpp` above `target_fpp` is taken to mean the filter holds more entries than
+/// expected, and the capacity is scaled up by as much as it would take a bloom filter to
+/// degrade that far.
+pub fn advise(workload: &Workload, target_fpp: f64) -> Result<Recommendation, Error> {
+    let entries = expected_entries(workload, target_fpp)?;
+    if workload.max_multiplicity > 1 {
+        let num_counters = sizing::optimal_bits(entries, target_fpp)?;
+        let num_hashes = sizing::optimal_hashes(num_counters, entries)?;
+        let max = workload.max_multiplicity;
+        let bits_per_counter = COUNTER_WIDTHS
+            .into_iter()
+            .find(|&bits| bits == u64::BITS || max < 1 << bits)
+            .unwrap();
+        return Ok(Recommendation::CountingBloom {
+            num_counters,
+            num_hashes,
+            bits_per_counter,
+        });
+    }
+    let entries_per_bin = cuckoo::entries_per_bin_for_fpp(target_fpp);
+    let slots = sizing::cuckoo_slots(entries, target_fpp, entries_per_bin)?;
+    let cuckoo = Recommendation::Cuckoo {
+        num_bins: slots.num_bins,
+        entries_per_bin,
+        fingerprint_bits: slots.fingerprint_bits,
+    };
+    if workload.remove_fraction > 0.0 {
+        return Ok(cuckoo);
+    }
+    let num_bits = sizing::optimal_bits(entries, target_fpp)?;
+    let num_hashes = sizing::optimal_hashes(num_bits, entries)?;
+    let bloom = Recommendation::Bloom {
+        num_bits,
+        num_hashes,
+    };
+    let slack = match workload.lookup_fraction >= LOOKUP_HEAVY_FRACTION {
+        true => LOOKUP_HEAVY_SLACK,
+        false => 1.0,
+    };
+    match cuckoo.mem_bits() as f64 <= bloom.mem_bits() as f64 * slack {
+        true => Ok(cuckoo),
+        false => Ok(bloom),
+    }
+}
+
+// Entries the filter must hold over the workload's horizon, corrected by the measured fpp.
+fn expected_entries(workload: &Workload, target_fpp: f64) -> Result<usize, Error> {
+    if !(target_fpp > 0.0 && target_fpp < 1.0) {
+        return Err(Error::InvalidFpp(target_fpp));
+    }
+    if !(workload.insert_rate.is_finite() && workload.insert_rate >= 0.0) {
+        return Err(Error::InvalidInsertRate(workload.insert_rate));
+    }
+    for fraction in [workload.lookup_fraction, workload.remove_fraction] {
+        if !(0.0..=1.0).contains(&fraction) {
+            return Err(Error::InvalidFraction(fraction));
+        }
+    }
+    if workload.max_multiplicity == 0 {
+        return Err(Error::InvalidMultiplicity(workload.max_multiplicity));
+    }
+    let mut entries =
+        workload.distinct_entries as f64 + workload.insert_rate * workload.horizon.as_secs_f64();
+    if let Some(measured) = workload.measured_fpp {
+        if !(0.0..1.0).contains(&measured) {
+            return Err(Error::InvalidFpp(measured));
+        }
+        // a bloom filter's fpp is exp(-c * bits per entry), so its log scales inversely with
+        // the entries it holds
+        if measured > target_fpp {
+            entries *= target_fpp.ln() / measured.ln();
+        }
+    }
+    match entries.ceil() {
+        entries if entries >= 1.0 && entries < usize::MAX as f64 => Ok(entries as usize),
+        entries => Err(Error::InvalidCapacity(entries)),
+    }
+}
@@ -0,0 +1,154 @@
+use fasthash::metro;
+use std::time::Duration;
+
+use super::*;
+
+fn workload(distinct_entries: usize) -> Workload {
+    Workload {
+        distinct_entries,
+        ..Workload::default()
+    }
+}
+
+#[test]
+fn test_advise_bloom() {
+    let rec = advise(&workload(1000), 0.01).unwrap();
+    assert_eq!(
+        Recommendation::Bloom {
+            num_bits: 9586,
+            num_hashes: 7
+        },
+        rec
+    );
+    let AdvisedFilter::Bloom(mut filter) = rec.build::<metro::Hasher64_1>().unwrap() else {
+        panic!("expected a bloom filter");
+    };
+    filter.add("hello");
+    assert!(filter.contains("hello"));
+}
+
+#[test]
+fn test_advise_horizon() {
+    let growing = Workload {
+        insert_rate: 10.0,
+        horizon: Duration::from_secs(90),
+        ..workload(100)
+    };
+    assert_eq!(
+        advise(&workload(1000), 0.01).unwrap(),
+        advise(&growing, 0.01).unwrap()
+    );
+}
+
+#[test]
+fn test_advise_measured_fpp() {
+    let rec = advise(&workload(1000), 0.01).unwrap();
+    let degraded = Workload {
+        measured_fpp: Some(0.1),
+        ..workload(1000)
+    };
+    // ln(0.01) / ln(0.1) = 2, so it's sized for twice the entries
+    assert_eq!(
+        advise(&workload(2000), 0.01).unwrap(),
+        advise(&degraded, 0.01).unwrap()
+    );
+    assert!(advise(&degraded, 0.01).unwrap().mem_bits() > rec.mem_bits());
+    // a filter doing better than its target doesn't shrink
+    let better = Workload {
+        measured_fpp: Some(0.001),
+        ..workload(1000)
+    };
+    assert_eq!(rec, advise(&better, 0.01).unwrap());
+}
+
+#[test]
+fn test_advise_counting() {
+    let counted = Workload {
+        max_multiplicity: 200,
+        ..workload(1000)
+    };
+    let rec = advise(&counted, 0.01).unwrap();
+    assert_eq!(
+        Recommendation::CountingBloom {
+            num_counters: 9586,
+            num_hashes: 7,
+            bits_per_counter: 8
+        },
+        rec
+    );
+    assert!(matches!(
+        rec.build::<metro::Hasher64_1>().unwrap(),
+        AdvisedFilter::CountingBloom(_)
+    ));
+    let widest = Workload {
+        max_multiplicity: u64::MAX,
+        ..workload(1000)
+    };
+    assert!(matches!(
+        advise(&widest, 0.01).unwrap(),
+        Recommendation::CountingBloom {
+            bits_per_counter: 64,
+            ..
+        }
+    ));
+}
+
+#[test]
+fn test_advise_cuckoo() {
+    let removing = Workload {
+        remove_fraction: 0.1,
+        ..workload(1000)
+    };
+    let rec = advise(&removing, 0.01).unwrap();
+    assert_eq!(
+        Recommendation::Cuckoo {
+            num_bins: 1024,
+            entries_per_bin: 2,
+            fingerprint_bits: 9
+        },
+        rec
+    );
+    let AdvisedFilter::Cuckoo(mut filter) = rec.build::<metro::Hasher64_1>().unwrap() else {
+        panic!("expected a cuckoo filter");
+    };
+    filter.add("hello").unwrap();
+    assert!(filter.contains("hello"));
+    // at low fpp, a cuckoo filter's fingerprints beat a bloom filter's bits per entry
+    assert!(matches!(
+        advise(&workload(1000), 0.00001).unwrap(),
+        Recommendation::Cuckoo { .. }
+    ));
+}
+
+#[test]
+fn test_advise_invalid() {
+    assert!(matches!(
+        advise(&workload(1000), 0.0),
+        Err(Error::InvalidFpp(_))
+    ));
+    assert!(matches!(
+        advise(&workload(0), 0.01),
+        Err(Error::InvalidCapacity(_))
+    ));
+    let invalid = [
+        Workload {
+            insert_rate: f64::NAN,
+            ..workload(1)
+        },
+        Workload {
+            lookup_fraction: 1.5,
+            ..workload(1)
+        },
+        Workload {
+            max_multiplicity: 0,
+            ..workload(1)
+        },
+        Workload {
+            measured_fpp: Some(1.0),
+            ..workload(1)
+        },
+    ];
+    for workload in invalid {
+        assert!(advise(&workload, 0.01).is_err(), "{workload:?}");
+    }
+}
@@ -0,0 +1,92 @@
+use std::{
+    collections::TryReserveError,
+    fmt,
+    ops::{Deref, DerefMut},
+    slice,
+};
+
+use crate::mem::{heap_size, try_filled};
+
+/// Bytes in a cache line on the platforms we care about.
+const CACHE_LINE: usize = 64;
+const WORDS_PER_LINE: usize = CACHE_LINE / 8;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[repr(C, align(64))]
+struct Line([u64; WORDS_PER_LINE]);
+
+/// u64 words whose allocation starts on a cache line, so every aligned block of 8 words is a
+/// single line, and a lookup touches as few lines as its positions need.
+#[derive(Clone)]
+pub(crate) struct AlignedWords {
+    lines: Vec<Line>,
+    len: usize,
+}
+
+impl AlignedWords {
+    /// `len` zeroed words.
+    pub(crate) fn new(len: usize) -> Self {
+        AlignedWords {
+            lines: vec![Line([0; WORDS_PER_LINE]); len.div_ceil(WORDS_PER_LINE)],
+            len,
+        }
+    }
+
+    /// `len` zeroed words, or an error if they can't be allocated.
+    pub(crate) fn try_new(len: usize) -> Result<Self, TryReserveError> {
+        Ok(AlignedWords {
+            lines: try_filled(len.div_ceil(WORDS_PER_LINE), Line([0; WORDS_PER_LINE]))?,
+            len,
+        })
+    }
+
+    /// Bytes allocated on the heap, including the padding of the last line.
+    pub(crate) fn heap_size(&self) -> usize {
+        heap_size(&self.lines)
+    }
+}
+
+impl Deref for AlignedWords {
+    type Target = [u64];
+
+    fn deref(&self) -> &[u64] {
+        // SAFETY: a Line is exactly WORDS_PER_LINE u64s with no padding, and the lines hold at
+        // least `len` words
+        unsafe { slice::from_raw_parts(self.lines.as_ptr().cast(), self.len) }
+    }
+}
+
+impl DerefMut for AlignedWords {
+    fn deref_mut(&mut self) -> &mut [u64] {
+        // SAFETY: as for deref
+        unsafe { slice::from_raw_parts_mut(self.lines.as_mut_ptr().cast(), self.len) }
+    }
+}
+
+impl PartialEq for AlignedWords {
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+impl Eq for AlignedWords {}
+
+impl fmt::Debug for AlignedWords {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+/// A value padded to a cache line of its own, so threads writing neighbouring values don't
+/// invalidate each other's lines.
+#[derive(Default)]
+#[repr(align(64))]
+pub(crate) struct CachePadded<T>(pub(crate) T);
+
+impl<T> Deref for CachePadded<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
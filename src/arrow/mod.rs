@@ -0,0 +1,105 @@
+use arrow_array::{cast::AsArray, types::*, Array, RecordBatch};
+use arrow_schema::DataType;
+use thiserror::Error;
+
+use crate::Sketch;
+
+#[cfg(test)]
+mod test;
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("Unsupported column type {0}")]
+    UnsupportedType(DataType),
+
+    #[error("No column named {0:?}")]
+    NoSuchColumn(String),
+}
+
+// Insert each non-null value of a primitive array as its little endian bytes.
+macro_rules! fill_primitive {
+    ($sketch:expr, $array:expr, $($data_type:ident => $arrow_type:ty,)*) => {
+        match $array.data_type() {
+            $(
+                DataType::$data_type => Some(fill(
+                    $sketch,
+                    $array.as_primitive::<$arrow_type>().iter().flatten(),
+                    |value, f| f(&value.to_le_bytes()),
+                )),
+            )*
+            _ => None,
+        }
+    };
+}
+
+/// Insert every non-null value of an Arrow array into `sketch`, returning how many were
+/// inserted.
+///
+/// Binary and string arrays, in every offset and view variant, insert the bytes of each value
+/// straight from the array's buffers, without allocating per row.  Integers and floats insert
+/// their little endian bytes, the same bytes the `_item` methods of the filters hash, so
+/// `contains_item(&42_i64)` finds a value inserted from an `Int64Array`.
+pub fn fill_from_array<S>(sketch: &mut S, array: &dyn Array) -> Result<usize, Error>
+where
+    S: Sketch + ?Sized,
+{
+    let bytes_of = |value: &[u8], f: &mut dyn FnMut(&[u8])| f(value);
+    let str_of = |value: &str, f: &mut dyn FnMut(&[u8])| f(value.as_bytes());
+    let filled = match array.data_type() {
+        DataType::Binary => fill(sketch, array.as_binary::<i32>().iter().flatten(), bytes_of),
+        DataType::LargeBinary => fill(sketch, array.as_binary::<i64>().iter().flatten(), bytes_of),
+        DataType::BinaryView => fill(sketch, array.as_binary_view().iter().flatten(), bytes_of),
+        DataType::FixedSizeBinary(_) => fill(
+            sketch,
+            array.as_fixed_size_binary().iter().flatten(),
+            bytes_of,
+        ),
+        DataType::Utf8 => fill(sketch, array.as_string::<i32>().iter().flatten(), str_of),
+        DataType::LargeUtf8 => fill(sketch, array.as_string::<i64>().iter().flatten(), str_of),
+        DataType::Utf8View => fill(sketch, array.as_string_view().iter().flatten(), str_of),
+        _ => fill_primitive!(sketch, array,
+            Int8 => Int8Type,
+            Int16 => Int16Type,
+            Int32 => Int32Type,
+            Int64 => Int64Type,
+            UInt8 => UInt8Type,
+            UInt16 => UInt16Type,
+            UInt32 => UInt32Type,
+            UInt64 => UInt64Type,
+            Float32 => Float32Type,
+            Float64 => Float64Type,
+        )
+        .ok_or_else(|| Error::UnsupportedType(array.data_type().clone()))?,
+    };
+    Ok(filled)
+}
+
+/// Insert every non-null value of the column named `column` into `sketch`, see
+/// [fill_from_array].
+pub fn fill_from_column<S>(
+    sketch: &mut S,
+    batch: &RecordBatch,
+    column: &str,
+) -> Result<usize, Error>
+where
+    S: Sketch + ?Sized,
+{
+    let array = batch
+        .column_by_name(column)
+        .ok_or_else(|| Error::NoSuchColumn(column.to_string()))?;
+    fill_from_array(sketch, array.as_ref())
+}
+
+fn fill<S, V, I, F>(sketch: &mut S, values: I, with_bytes: F) -> usize
+where
+    S: Sketch + ?Sized,
+    I: Iterator<Item = V>,
+    F: Fn(V, &mut dyn FnMut(&[u8])),
+{
+    let mut count = 0;
+    for value in values {
+        with_bytes(value, &mut |bytes| sketch.update(bytes));
+        count += 1;
+    }
+    count
+}
@@ -0,0 +1,67 @@
+use std::sync::Arc;
+
+use arrow_array::{
+    types::Int32Type, BinaryArray, Float64Array, Int64Array, LargeStringArray, ListArray,
+    RecordBatch, StringArray, StringViewArray,
+};
+use fasthash::metro;
+
+use super::{fill_from_array, fill_from_column, Error};
+use crate::{BloomFilter, CuckooFilter};
+
+#[test]
+fn test_fill_from_strings() {
+    let mut bf = BloomFilter::<metro::Hasher64_1>::new(10_000, 3).unwrap();
+    let strings = StringArray::from(vec![Some("alpha"), None, Some("beta")]);
+    assert_eq!(2, fill_from_array(&mut bf, &strings).unwrap());
+    let large = LargeStringArray::from(vec!["gamma"]);
+    assert_eq!(1, fill_from_array(&mut bf, &large).unwrap());
+    let views = StringViewArray::from(vec!["a string long enough to be stored out of line"]);
+    assert_eq!(1, fill_from_array(&mut bf, &views).unwrap());
+    let binary = BinaryArray::from(vec![&b"\x00\x01"[..]]);
+    assert_eq!(1, fill_from_array(&mut bf, &binary).unwrap());
+
+    for entry in ["alpha", "beta", "gamma"] {
+        assert!(bf.contains(entry), "{}", entry);
+    }
+    assert!(bf.contains("a string long enough to be stored out of line"));
+    assert!(bf.contains([0, 1]));
+}
+
+#[test]
+fn test_fill_from_primitives() {
+    let mut cf = CuckooFilter::<metro::Hasher64_1>::new(1024).unwrap();
+    let ints = Int64Array::from(vec![Some(42), None, Some(-7)]);
+    assert_eq!(2, fill_from_array(&mut cf, &ints).unwrap());
+    assert!(cf.contains_item(&42_i64));
+    assert!(cf.contains((-7_i64).to_le_bytes()));
+
+    let floats = Float64Array::from(vec![1.5]);
+    fill_from_array(&mut cf, &floats).unwrap();
+    assert!(cf.contains(1.5_f64.to_le_bytes()));
+}
+
+#[test]
+fn test_fill_from_column() {
+    let mut bf = BloomFilter::<metro::Hasher64_1>::new(10_000, 3).unwrap();
+    let batch = RecordBatch::try_from_iter([
+        ("id", Arc::new(Int64Array::from(vec![1, 2, 3])) as _),
+        (
+            "name",
+            Arc::new(StringArray::from(vec!["a", "b", "c"])) as _,
+        ),
+    ])
+    .unwrap();
+    assert_eq!(3, fill_from_column(&mut bf, &batch, "name").unwrap());
+    assert!(bf.contains("b"));
+    assert!(matches!(
+        fill_from_column(&mut bf, &batch, "missing"),
+        Err(Error::NoSuchColumn(_))
+    ));
+
+    let lists = ListArray::from_iter_primitive::<Int32Type, _, _>(vec![Some(vec![Some(1)])]);
+    assert!(matches!(
+        fill_from_array(&mut bf, &lists),
+        Err(Error::UnsupportedType(_))
+    ));
+}
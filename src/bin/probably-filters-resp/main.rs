@@ -0,0 +1,81 @@
+//! Server for a subset of the RedisBloom commands over RESP, the Redis protocol, for when a
+//! full Redis isn't wanted.
+//!
+//! Supports `BF.RESERVE`, `BF.ADD`, `BF.MADD`, `BF.EXISTS` and `BF.MEXISTS`, backed by
+//! [CountingBloomFilter](probably_filters::CountingBloomFilter)s, and `CF.RESERVE`, `CF.ADD`, `CF.ADDNX`, `CF.EXISTS`, `CF.MEXISTS`
+//! and `CF.DEL`, backed by
+//! [CuckooFilter](probably_filters::CuckooFilter)s, along with `PING`, `DEL`, `EXISTS` and `QUIT`.
+//! Adding to a key that doesn't exist creates a filter with RedisBloom's default parameters.
+//! Filters live only in memory, and are hashed with
+//! [metro::Hasher64_1](fasthash::metro::Hasher64_1).
+//!
+//! Inline commands, one per line, are accepted too, so the server can be poked with `nc`.
+use clap::Parser;
+use std::{
+    io::{self, BufReader, BufWriter, Write},
+    net::{TcpListener, TcpStream},
+    process::ExitCode,
+    thread,
+};
+
+use self::resp::{execute, read_command, Filters, Reply};
+
+mod resp;
+
+#[derive(Parser)]
+#[command(version, about)]
+struct Cli {
+    /// Address to listen on
+    #[arg(long, default_value = "127.0.0.1:6379")]
+    bind: String,
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let listener = match TcpListener::bind(&cli.bind) {
+        Ok(listener) => listener,
+        Err(e) => {
+            eprintln!("error: can't listen on {}: {}", cli.bind, e);
+            return ExitCode::FAILURE;
+        }
+    };
+    let filters = Filters::default();
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                let filters = filters.clone();
+                thread::spawn(move || {
+                    if let Err(e) = serve(stream, &filters) {
+                        eprintln!("error: {}", e);
+                    }
+                });
+            }
+            Err(e) => eprintln!("error: {}", e),
+        }
+    }
+    ExitCode::SUCCESS
+}
+
+fn serve(stream: TcpStream, filters: &Filters) -> io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = BufWriter::new(stream);
+    while let Some(args) = read_command(&mut reader)? {
+        if args.is_empty() {
+            continue;
+        }
+        let quit = args[0].eq_ignore_ascii_case(b"QUIT");
+        let reply = match quit {
+            true => Reply::Ok,
+            false => execute(&args, filters),
+        };
+        reply.write_to(&mut writer)?;
+        // only flush once every pipelined command has been answered
+        if quit || reader.buffer().is_empty() {
+            writer.flush()?;
+        }
+        if quit {
+            break;
+        }
+    }
+    Ok(())
+}
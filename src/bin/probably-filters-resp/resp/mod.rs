@@ -0,0 +1,272 @@
+//! RESP framing and the commands the server runs, apart from the connection handling so they
+//! can be tested without a socket.
+use fasthash::metro;
+use probably_filters::{sizing, CountingBloomFilter, CuckooFilter};
+use std::{
+    collections::HashMap,
+    io::{self, BufRead, Read, Write},
+    sync::{Arc, Mutex},
+};
+
+#[cfg(test)]
+mod test;
+
+// RedisBloom's defaults
+const DEFAULT_BF_ERROR_RATE: f64 = 0.01;
+const DEFAULT_BF_CAPACITY: usize = 100;
+const DEFAULT_CF_CAPACITY: usize = 1024;
+// bounds the memory a single request can claim
+const MAX_ARGS: usize = 1 << 20;
+const MAX_BULK_LEN: usize = 512 << 20;
+const MAX_CAPACITY: usize = 100_000_000;
+// a tiny error rate needs far more counters than the capacity suggests: 512MB of 4 bit counters
+const MAX_BF_COUNTERS: usize = 1 << 30;
+// as Redis limits inline commands, and the lines framing RESP arrays
+const MAX_LINE_LEN: usize = 64 << 10;
+
+const WRONG_TYPE: &str = "WRONGTYPE Operation against a key holding the wrong kind of value";
+
+pub(super) enum Filter {
+    Bloom(CountingBloomFilter<metro::Hasher64_1>),
+    Cuckoo(Box<CuckooFilter<metro::Hasher64_1>>),
+}
+
+pub(super) type Filters = Arc<Mutex<HashMap<Vec<u8>, Filter>>>;
+
+pub(super) enum Reply {
+    Ok,
+    Pong,
+    Integer(i64),
+    Array(Vec<Reply>),
+    Error(String),
+}
+
+impl Reply {
+    pub(super) fn write_to(&self, out: &mut impl Write) -> io::Result<()> {
+        match self {
+            Reply::Ok => out.write_all(b"+OK\r\n"),
+            Reply::Pong => out.write_all(b"+PONG\r\n"),
+            Reply::Integer(i) => write!(out, ":{}\r\n", i),
+            Reply::Array(items) => {
+                write!(out, "*{}\r\n", items.len())?;
+                items.iter().try_for_each(|item| item.write_to(out))
+            }
+            Reply::Error(message) => write!(out, "-{}\r\n", message),
+        }
+    }
+}
+
+/// Read one command as a RESP array of bulk strings, or an inline command.  Returns None
+/// when the client disconnects.
+pub(super) fn read_command(reader: &mut impl BufRead) -> io::Result<Option<Vec<Vec<u8>>>> {
+    let Some(line) = read_line(reader)? else {
+        return Ok(None);
+    };
+    let Some(count) = line.strip_prefix(b"*") else {
+        let args = line
+            .split(|b| b.is_ascii_whitespace())
+            .filter(|arg| !arg.is_empty())
+            .map(<[u8]>::to_vec)
+            .collect();
+        return Ok(Some(args));
+    };
+    let count = parse_len(count, MAX_ARGS)?;
+    let mut args = Vec::with_capacity(count);
+    for _ in 0..count {
+        let line = read_line(reader)?.ok_or_else(|| protocol_error("truncated command"))?;
+        let len = match line.strip_prefix(b"$") {
+            Some(len) => parse_len(len, MAX_BULK_LEN)?,
+            None => return Err(protocol_error("expected a bulk string")),
+        };
+        let mut arg = vec![0; len + 2];
+        reader.read_exact(&mut arg)?;
+        if !arg.ends_with(b"\r\n") {
+            return Err(protocol_error("bulk string isn't terminated"));
+        }
+        arg.truncate(len);
+        args.push(arg);
+    }
+    Ok(Some(args))
+}
+
+fn read_line(reader: &mut impl BufRead) -> io::Result<Option<Vec<u8>>> {
+    let mut line = vec![];
+    let limit = MAX_LINE_LEN as u64 + 1;
+    if reader.take(limit).read_until(b'\n', &mut line)? == 0 {
+        return Ok(None);
+    }
+    if line.len() > MAX_LINE_LEN && !line.ends_with(b"\n") {
+        return Err(protocol_error("too big inline request"));
+    }
+    while line.last().is_some_and(|b| *b == b'\n' || *b == b'\r') {
+        line.pop();
+    }
+    Ok(Some(line))
+}
+
+fn parse_len(digits: &[u8], max: usize) -> io::Result<usize> {
+    std::str::from_utf8(digits)
+        .ok()
+        .and_then(|digits| digits.parse().ok())
+        .filter(|&len| len <= max)
+        .ok_or_else(|| protocol_error("invalid length"))
+}
+
+fn protocol_error(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+pub(super) fn execute(args: &[Vec<u8>], filters: &Filters) -> Reply {
+    let command = String::from_utf8_lossy(&args[0]).to_ascii_uppercase();
+    let args = &args[1..];
+    let mut filters = filters.lock().unwrap();
+    match (command.as_str(), args) {
+        ("PING", []) => Reply::Pong,
+        ("DEL", [_, ..]) => {
+            let removed = args.iter().filter(|k| filters.remove(*k).is_some()).count();
+            Reply::Integer(removed as i64)
+        }
+        ("EXISTS", [_, ..]) => {
+            let found = args.iter().filter(|k| filters.contains_key(*k)).count();
+            Reply::Integer(found as i64)
+        }
+        ("BF.RESERVE", [key, error_rate, capacity]) => {
+            let (Some(error_rate), Some(capacity)) = (parse(error_rate), parse_capacity(capacity))
+            else {
+                return Reply::Error("ERR bad error rate or capacity".to_string());
+            };
+            let counters = sizing::optimal_bits(capacity, error_rate);
+            if !counters.is_ok_and(|counters| counters <= MAX_BF_COUNTERS) {
+                return Reply::Error("ERR bad error rate or capacity".to_string());
+            }
+            reserve(&mut filters, key, || {
+                CountingBloomFilter::try_with_capacity_and_fpp(capacity, error_rate)
+                    .map(Filter::Bloom)
+                    .map_err(|e| e.to_string())
+            })
+        }
+        ("CF.RESERVE", [key, capacity]) => {
+            let Some(capacity) = parse_capacity(capacity) else {
+                return Reply::Error("ERR bad capacity".to_string());
+            };
+            reserve(&mut filters, key, || new_cuckoo(capacity))
+        }
+        ("BF.ADD" | "BF.MADD", [key, items @ ..]) if !items.is_empty() => {
+            let filter = match filters.get_mut(key) {
+                Some(filter) => filter,
+                None => match CountingBloomFilter::try_with_capacity_and_fpp(
+                    DEFAULT_BF_CAPACITY,
+                    DEFAULT_BF_ERROR_RATE,
+                ) {
+                    Ok(cbf) => filters.entry(key.clone()).or_insert(Filter::Bloom(cbf)),
+                    Err(e) => return Reply::Error(format!("ERR {}", e)),
+                },
+            };
+            let Filter::Bloom(cbf) = filter else {
+                return Reply::Error(WRONG_TYPE.to_string());
+            };
+            // adding only if absent keeps repeated adds from saturating counters
+            reply_each(&command, items, |item| cbf.insert_if_absent(item) as i64)
+        }
+        ("BF.EXISTS" | "BF.MEXISTS", [key, items @ ..]) if !items.is_empty() => {
+            match filters.get(key) {
+                Some(Filter::Bloom(cbf)) => {
+                    reply_each(&command, items, |item| cbf.contains(item) as i64)
+                }
+                Some(_) => Reply::Error(WRONG_TYPE.to_string()),
+                None => reply_each(&command, items, |_| 0),
+            }
+        }
+        ("CF.ADD" | "CF.ADDNX", [key, item]) => {
+            let filter = match filters.get_mut(key) {
+                Some(filter) => filter,
+                None => match new_cuckoo(DEFAULT_CF_CAPACITY) {
+                    Ok(filter) => filters.entry(key.clone()).or_insert(filter),
+                    Err(e) => return Reply::Error(format!("ERR {}", e)),
+                },
+            };
+            let Filter::Cuckoo(cf) = filter else {
+                return Reply::Error(WRONG_TYPE.to_string());
+            };
+            if command == "CF.ADDNX" && cf.contains(item) {
+                return Reply::Integer(0);
+            }
+            match cf.add(item) {
+                Ok(()) => Reply::Integer(1),
+                Err(_) => Reply::Error("ERR Filter is full".to_string()),
+            }
+        }
+        ("CF.EXISTS" | "CF.MEXISTS", [key, items @ ..]) if !items.is_empty() => {
+            match filters.get(key) {
+                Some(Filter::Cuckoo(cf)) => {
+                    reply_each(&command, items, |item| cf.contains(item) as i64)
+                }
+                Some(_) => Reply::Error(WRONG_TYPE.to_string()),
+                None => reply_each(&command, items, |_| 0),
+            }
+        }
+        ("CF.DEL", [key, item]) => match filters.get_mut(key) {
+            Some(Filter::Cuckoo(cf)) => Reply::Integer(cf.remove(item) as i64),
+            Some(_) => Reply::Error(WRONG_TYPE.to_string()),
+            None => Reply::Error("ERR Not found".to_string()),
+        },
+        (
+            "PING" | "DEL" | "EXISTS" | "BF.RESERVE" | "BF.ADD" | "BF.MADD" | "BF.EXISTS"
+            | "BF.MEXISTS" | "CF.RESERVE" | "CF.ADD" | "CF.ADDNX" | "CF.EXISTS" | "CF.MEXISTS"
+            | "CF.DEL",
+            _,
+        ) => Reply::Error(format!(
+            "ERR wrong number of arguments for '{}' command",
+            command.to_ascii_lowercase()
+        )),
+        _ => Reply::Error(format!("ERR unknown command '{}'", command)),
+    }
+}
+
+fn reserve<F>(filters: &mut HashMap<Vec<u8>, Filter>, key: &[u8], new_filter: F) -> Reply
+where
+    F: FnOnce() -> Result<Filter, String>,
+{
+    if filters.contains_key(key) {
+        return Reply::Error("ERR item exists".to_string());
+    }
+    match new_filter() {
+        Ok(filter) => {
+            filters.insert(key.to_vec(), filter);
+            Reply::Ok
+        }
+        Err(e) => Reply::Error(format!("ERR {}", e)),
+    }
+}
+
+fn new_cuckoo(capacity: usize) -> Result<Filter, String> {
+    // 4 entries per bin
+    CuckooFilter::try_new(capacity.div_ceil(4))
+        .map(|cf| Filter::Cuckoo(Box::new(cf)))
+        .map_err(|e| e.to_string())
+}
+
+/// The single reply for `BF.ADD` style commands, or an array for the `M` variants.
+fn reply_each<F>(command: &str, items: &[Vec<u8>], mut f: F) -> Reply
+where
+    F: FnMut(&[u8]) -> i64,
+{
+    let mut replies = items.iter().map(|item| Reply::Integer(f(item)));
+    match command.contains(".M") {
+        true => Reply::Array(replies.collect()),
+        false if items.len() == 1 => replies.next().unwrap(),
+        false => Reply::Error(format!(
+            "ERR wrong number of arguments for '{}' command",
+            command.to_ascii_lowercase()
+        )),
+    }
+}
+
+fn parse<V: std::str::FromStr>(arg: &[u8]) -> Option<V> {
+    std::str::from_utf8(arg).ok()?.parse().ok()
+}
+
+// capacities come from clients, so they're bounded like the requests themselves
+fn parse_capacity(arg: &[u8]) -> Option<usize> {
+    parse(arg).filter(|&capacity| capacity <= MAX_CAPACITY)
+}
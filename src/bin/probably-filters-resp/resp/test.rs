@@ -0,0 +1,125 @@
+use std::io::{self, Cursor};
+
+use super::{execute, read_command, Filters};
+
+// Run every command in `input` the way a connection would, returning the replies.
+fn run(input: &[u8], filters: &Filters) -> io::Result<String> {
+    let mut reader = Cursor::new(input);
+    let mut out = vec![];
+    while let Some(args) = read_command(&mut reader)? {
+        if !args.is_empty() {
+            execute(&args, filters).write_to(&mut out)?;
+        }
+    }
+    Ok(String::from_utf8(out).unwrap())
+}
+
+fn run_inline(lines: &[&str]) -> String {
+    let input = lines
+        .iter()
+        .map(|line| format!("{}\r\n", line))
+        .collect::<String>();
+    run(input.as_bytes(), &Filters::default()).unwrap()
+}
+
+#[test]
+fn test_pipelining() {
+    // several commands in one read, mixing RESP arrays, inline commands and blank lines
+    let input = b"*1\r\n$4\r\nPING\r\n*3\r\n$6\r\nBF.ADD\r\n$1\r\nk\r\n$1\r\na\r\n\
+        *3\r\n$9\r\nBF.EXISTS\r\n$1\r\nk\r\n$1\r\na\r\nPING\r\n\r\nEXISTS k j\r\n";
+    assert_eq!(
+        "+PONG\r\n:1\r\n:1\r\n+PONG\r\n:1\r\n",
+        run(input, &Filters::default()).unwrap()
+    );
+}
+
+#[test]
+fn test_bad_lengths() {
+    for input in [
+        &b"*x\r\n"[..],
+        b"*-1\r\n",
+        b"*2000000\r\n",
+        b"*1\r\n$-1\r\n",
+        b"*1\r\n4\r\nPING\r\n",
+        b"*1\r\n$999999999\r\n",
+        b"*1\r\n$2\r\nPING\r\n",
+        b"*2\r\n$4\r\nPING\r\n",
+    ] {
+        let err = run(input, &Filters::default()).unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidData, err.kind(), "{:?}", input);
+    }
+    let err = run(b"*1\r\n$4\r\nPI", &Filters::default()).unwrap_err();
+    assert_eq!(io::ErrorKind::UnexpectedEof, err.kind());
+
+    // an inline command without an end can't grow forever
+    let err = run(&vec![b'a'; 128 << 10], &Filters::default()).unwrap_err();
+    assert_eq!(io::ErrorKind::InvalidData, err.kind());
+    let long = format!("EXISTS {}\r\n", "k".repeat(60 << 10));
+    assert_eq!(":0\r\n", run(long.as_bytes(), &Filters::default()).unwrap());
+}
+
+#[test]
+fn test_wrong_type() {
+    let wrong_type = "-WRONGTYPE Operation against a key holding the wrong kind of value\r\n";
+    assert_eq!(
+        format!(":1\r\n{}{}", wrong_type, wrong_type),
+        run_inline(&["BF.ADD bloom a", "CF.ADD bloom a", "CF.MEXISTS bloom a"])
+    );
+    assert_eq!(
+        format!(":1\r\n{}{}{}", wrong_type, wrong_type, wrong_type),
+        run_inline(&[
+            "CF.ADD cuckoo a",
+            "BF.ADD cuckoo a",
+            "BF.MEXISTS cuckoo a b",
+            "BF.EXISTS cuckoo a",
+        ])
+    );
+}
+
+#[test]
+fn test_multi_replies() {
+    assert_eq!(
+        "*3\r\n:1\r\n:1\r\n:0\r\n*3\r\n:1\r\n:0\r\n:1\r\n*2\r\n:0\r\n:0\r\n",
+        run_inline(&[
+            "BF.MADD k a b a",
+            "BF.MEXISTS k a z b",
+            "BF.MEXISTS missing a b"
+        ])
+    );
+    assert_eq!(
+        ":1\r\n*3\r\n:1\r\n:0\r\n:0\r\n",
+        run_inline(&["CF.ADD c a", "CF.MEXISTS c a b c"])
+    );
+    // only the M variants take several items
+    assert_eq!(
+        "-ERR wrong number of arguments for 'bf.add' command\r\n\
+         -ERR wrong number of arguments for 'bf.exists' command\r\n",
+        run_inline(&["BF.ADD k a b", "BF.EXISTS k a b"])
+    );
+}
+
+#[test]
+fn test_reserve_bounds() {
+    assert_eq!(
+        "+OK\r\n-ERR item exists\r\n+OK\r\n",
+        run_inline(&[
+            "BF.RESERVE k 0.001 100000",
+            "BF.RESERVE k 0.001 100000",
+            "CF.RESERVE c 100000",
+        ])
+    );
+    // a tiny error rate would need far more counters than the capacity suggests
+    for reserve in [
+        "BF.RESERVE k 1e-300 100000000",
+        "BF.RESERVE k 1e-30 100000000",
+        "BF.RESERVE k 0.01 100000001",
+        "BF.RESERVE k 2 100",
+        "CF.RESERVE c 100000001",
+    ] {
+        assert!(
+            run_inline(&[reserve]).starts_with("-ERR bad "),
+            "{}",
+            reserve
+        );
+    }
+}
@@ -0,0 +1,139 @@
+//! Command line tool for building and querying bloom filters.
+//!
+//! Filters are stored in the format of [BloomFilter::to_bytes], hashed with
+//! [metro::Hasher64_1], so they can be loaded by any program using this crate with the same
+//! hasher.
+use clap::{Parser, Subcommand};
+use fasthash::metro;
+use probably_filters::BloomFilter;
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+type Filter = BloomFilter<metro::Hasher64_1>;
+
+#[derive(Parser)]
+#[command(version, about)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Build a filter from newline delimited keys
+    Build {
+        /// Target false positive probability
+        #[arg(long, default_value_t = 0.01)]
+        fpp: f64,
+        /// Expected number of keys, defaults to the number of keys read.  Filters can only be
+        /// merged if they were built with the same capacity and fpp
+        #[arg(long)]
+        capacity: Option<usize>,
+        /// Read keys from this file instead of stdin
+        #[arg(long)]
+        input: Option<PathBuf>,
+        /// Where to write the filter
+        #[arg(long, short)]
+        output: PathBuf,
+    },
+    /// Check keys against a filter, printing one "<key>\t<true|false>" line per key
+    Query {
+        /// Filter to query
+        filter: PathBuf,
+        /// Keys to check, read from stdin if none are given
+        keys: Vec<String>,
+    },
+    /// Add keys to an existing filter, rewriting it in place
+    Add {
+        /// Filter to update
+        filter: PathBuf,
+        /// Keys to add, read from stdin if none are given
+        keys: Vec<String>,
+    },
+    /// Merge filters built with the same parameters into one
+    Merge {
+        /// Where to write the merged filter
+        #[arg(long, short)]
+        output: PathBuf,
+        /// Filters to merge
+        #[arg(required = true)]
+        inputs: Vec<PathBuf>,
+    },
+}
+
+fn main() -> ExitCode {
+    match run(Cli::parse().command) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(command: Command) -> Result<(), Box<dyn std::error::Error>> {
+    match command {
+        Command::Build {
+            fpp,
+            capacity,
+            input,
+            output,
+        } => {
+            let keys = match input {
+                Some(path) => read_lines(BufReader::new(File::open(path)?))?,
+                None => read_lines(io::stdin().lock())?,
+            };
+            let mut filter =
+                Filter::with_capacity_and_fpp(capacity.unwrap_or(keys.len().max(1)), fpp)?;
+            keys.iter().for_each(|k| {
+                filter.add(k);
+            });
+            save(&filter, &output)
+        }
+        Command::Query { filter, keys } => {
+            let filter = load(&filter)?;
+            let mut out = BufWriter::new(io::stdout().lock());
+            for key in keys_or_stdin(keys)? {
+                writeln!(out, "{}\t{}", key, filter.contains(&key))?;
+            }
+            Ok(out.flush()?)
+        }
+        Command::Add { filter: path, keys } => {
+            let mut filter = load(&path)?;
+            keys_or_stdin(keys)?.iter().for_each(|k| {
+                filter.add(k);
+            });
+            save(&filter, &path)
+        }
+        Command::Merge { output, inputs } => {
+            let mut merged = load(&inputs[0])?;
+            for path in &inputs[1..] {
+                merged.union(&load(path)?)?;
+            }
+            save(&merged, &output)
+        }
+    }
+}
+
+fn read_lines(reader: impl BufRead) -> io::Result<Vec<String>> {
+    reader.lines().collect()
+}
+
+fn keys_or_stdin(keys: Vec<String>) -> io::Result<Vec<String>> {
+    if keys.is_empty() {
+        return read_lines(io::stdin().lock());
+    }
+    Ok(keys)
+}
+
+fn load(path: &Path) -> Result<Filter, Box<dyn std::error::Error>> {
+    Ok(Filter::read_from(File::open(path)?)?)
+}
+
+fn save(filter: &Filter, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    Ok(filter.write_to(File::create(path)?)?)
+}
@@ -0,0 +1,101 @@
+use std::mem;
+
+use super::{BloomFilter, Error};
+use crate::hasher::SeedableHasher;
+
+const MAX_LEVEL_STEP: u32 = 16;
+
+/// Point and range filter over `u64` keys, based on [bloomRF](https://www.cidrdb.org/cidr2023/papers/p42-mossner.pdf).
+///
+/// Every key is added to a bloom filter once per level, with levels every `level_step` bits.
+/// At level `l` the key is stored as `key >> l`, so each entry stands for an aligned dyadic
+/// interval of 2^`l` keys.  A range query is decomposed into the fewest such intervals, and
+/// succeeds if any of them is found in the filter.
+///
+/// Larger steps mean fewer entries per key, but range queries probe up to 2^`level_step`
+/// intervals per level.  Every probe is subject to the bloom filter's false positive rate, so
+/// wide ranges have a higher false positive rate than point queries.
+pub struct BloomRf<T>
+where
+    T: SeedableHasher,
+{
+    filter: BloomFilter<T>,
+    level_step: u32,
+}
+
+impl<T> BloomRf<T>
+where
+    T: SeedableHasher,
+{
+    /// Create a new range filter on top of `filter`.  Every key adds ceil(64 / `level_step`)
+    /// entries to `filter`, which should be sized accordingly.
+    ///
+    /// `level_step` must be greater than 0 and less than, or equal to, 16.
+    pub fn new(filter: BloomFilter<T>, level_step: u32) -> Result<Self, Error> {
+        if level_step == 0 || level_step > MAX_LEVEL_STEP {
+            return Err(Error::InvalidLevelStep(level_step));
+        }
+        Ok(BloomRf { filter, level_step })
+    }
+
+    /// Add a key to the filter.
+    pub fn add(&mut self, key: u64) {
+        for level in (0..u64::BITS).step_by(self.level_step as usize) {
+            self.filter.add(Self::interval(level, key >> level));
+        }
+    }
+
+    /// Determine if filter contains the provided key.
+    pub fn contains(&self, key: u64) -> bool {
+        self.filter.contains(Self::interval(0, key))
+    }
+
+    /// Determine if filter may contain any key in `lo..=hi`.
+    pub fn contains_range(&self, lo: u64, hi: u64) -> bool {
+        if lo > hi {
+            return false;
+        }
+        let step = self.level_step;
+        let width = 1_i128 << step;
+        // signed, so the upper bound can move below the lower one without wrapping
+        let (mut lo, mut hi) = (lo as i128, hi as i128);
+        let mut level = 0;
+        while level + step < u64::BITS {
+            while lo <= hi && lo % width != 0 {
+                if self.probe(level, lo) {
+                    return true;
+                }
+                lo += 1;
+            }
+            while lo <= hi && hi % width != width - 1 {
+                if self.probe(level, hi) {
+                    return true;
+                }
+                hi -= 1;
+            }
+            if lo > hi {
+                return false;
+            }
+            lo >>= step;
+            hi >>= step;
+            level += step;
+        }
+        (lo..=hi).any(|v| self.probe(level, v))
+    }
+
+    /// Bytes used by the filter, including the bits of the underlying [BloomFilter].
+    pub fn mem_usage(&self) -> usize {
+        mem::size_of::<Self>() - mem::size_of::<BloomFilter<T>>() + self.filter.mem_usage()
+    }
+
+    fn probe(&self, level: u32, value: i128) -> bool {
+        self.filter.contains(Self::interval(level, value as u64))
+    }
+
+    fn interval(level: u32, value: u64) -> [u8; 9] {
+        let mut bytes = [0; 9];
+        bytes[0] = level as u8;
+        bytes[1..].copy_from_slice(&value.to_le_bytes());
+        bytes
+    }
+}
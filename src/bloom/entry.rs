@@ -0,0 +1,43 @@
+use super::{index::HashedKey, CountingBloomFilter};
+use crate::hasher::SeedableHasher;
+
+/// One entry of a [CountingBloomFilter], from [CountingBloomFilter::entry].  The entry is
+/// hashed once, however many times it's read and updated, so check-then-update logic can't
+/// look at one entry and update another.
+pub struct CountingBloomEntry<'a, T>
+where
+    T: SeedableHasher,
+{
+    filter: &'a mut CountingBloomFilter<T>,
+    hashed: HashedKey<T>,
+}
+
+impl<'a, T> CountingBloomEntry<'a, T>
+where
+    T: SeedableHasher,
+{
+    pub(super) fn new(filter: &'a mut CountingBloomFilter<T>, hashed: HashedKey<T>) -> Self {
+        CountingBloomEntry { filter, hashed }
+    }
+
+    /// Estimate how many times the entry has been added, as [CountingBloomFilter::estimate]
+    /// does.
+    pub fn count(&self) -> usize {
+        self.filter.estimate_prehashed(&self.hashed)
+    }
+
+    /// Determine if the filter contains the entry.
+    pub fn contains(&self) -> bool {
+        self.filter.contains_prehashed(&self.hashed)
+    }
+
+    /// Add the entry, as [CountingBloomFilter::add] does.
+    pub fn insert(&mut self) -> bool {
+        self.filter.add_prehashed(&self.hashed)
+    }
+
+    /// Remove the entry, as [CountingBloomFilter::remove] does.
+    pub fn remove(&mut self) -> bool {
+        self.filter.remove_prehashed(&self.hashed)
+    }
+}
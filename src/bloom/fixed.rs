@@ -0,0 +1,120 @@
+use fasthash::{metro::Hash64_1, FastHash};
+use std::mem;
+
+/// A bloom filter of `WORDS * 64` bits and `K` hashes, stored inline with no heap allocation,
+/// for small per-connection or per-packet filters in latency sensitive code.
+///
+/// Each entry is hashed once with metrohash, and the `K` bit positions are derived from the two
+/// halves of the hash by double hashing, so lookups cost one hash regardless of `K`.
+///
+/// `WORDS` and `K` must both be greater than 0, which is checked when the filter is created.
+///
+/// Filters can be built ahead of time and embedded in a binary as a `static`, with no startup
+/// cost, using [FixedBloomFilter::static_source] from a build script and
+/// [FixedBloomFilter::from_words] to initialize the static.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FixedBloomFilter<const WORDS: usize, const K: u32> {
+    bits: [u64; WORDS],
+}
+
+impl<const WORDS: usize, const K: u32> FixedBloomFilter<WORDS, K> {
+    const VALID: () = assert!(WORDS > 0 && K > 0, "WORDS and K must be greater than 0");
+    const NUM_BITS: u64 = WORDS as u64 * u64::BITS as u64;
+
+    /// Create an empty filter.
+    pub const fn new() -> Self {
+        #[allow(clippy::let_unit_value)]
+        let () = Self::VALID;
+        FixedBloomFilter { bits: [0; WORDS] }
+    }
+
+    /// Create a filter from the words of another filter with the same `WORDS` and `K`, as
+    /// returned by [FixedBloomFilter::words].  This is a `const fn`, so it can initialize a
+    /// `static`.
+    pub const fn from_words(bits: [u64; WORDS]) -> Self {
+        #[allow(clippy::let_unit_value)]
+        let () = Self::VALID;
+        FixedBloomFilter { bits }
+    }
+
+    /// The bit array, with bit `i` at `words[i / 64] & (1 << (i % 64))`.
+    pub fn words(&self) -> &[u64; WORDS] {
+        &self.bits
+    }
+
+    /// Generate Rust source declaring `static {name}` as a filter holding `entries`, for use
+    /// from a build script with `include!`.  The bits are computed on the heap, so this works
+    /// for filters too large for the stack.
+    pub fn static_source<I, E>(name: &str, entries: I) -> String
+    where
+        I: IntoIterator<Item = E>,
+        E: AsRef<[u8]>,
+    {
+        let mut bits = vec![0_u64; WORDS];
+        for entry in entries {
+            for (word, mask) in Self::positions(entry.as_ref()) {
+                bits[word] |= mask;
+            }
+        }
+        let words = bits
+            .iter()
+            .map(|w| format!("    {:#018x},\n", w))
+            .collect::<String>();
+        format!(
+            "pub static {name}: probably_filters::FixedBloomFilter<{WORDS}, {K}> =\n\
+             probably_filters::FixedBloomFilter::from_words([\n{words}]);\n"
+        )
+    }
+
+    /// Add an entry to the filter, returning true if any bit was set by this call.
+    pub fn add<I>(&mut self, entry: I) -> bool
+    where
+        I: AsRef<[u8]>,
+    {
+        let mut added = false;
+        for (word, mask) in Self::positions(entry.as_ref()) {
+            added |= self.bits[word] & mask == 0;
+            self.bits[word] |= mask;
+        }
+        added
+    }
+
+    /// Determine if filter contains the provided entry.
+    pub fn contains<I>(&self, entry: I) -> bool
+    where
+        I: AsRef<[u8]>,
+    {
+        Self::positions(entry.as_ref()).all(|(word, mask)| self.bits[word] & mask != 0)
+    }
+
+    /// Remove every entry.
+    pub fn clear(&mut self) {
+        self.bits = [0; WORDS];
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bits.iter().all(|&w| w == 0)
+    }
+
+    /// Bytes used by the filter, which holds its bits inline and never allocates.
+    pub const fn mem_usage(&self) -> usize {
+        mem::size_of::<Self>()
+    }
+
+    // (word, mask) for each of the K bits, using h1 + i * h2 with an odd h2 so the positions
+    // don't collapse when the bit count is a power of two
+    fn positions(entry: &[u8]) -> impl Iterator<Item = (usize, u64)> {
+        let hash = Hash64_1::hash(entry);
+        let (h1, h2) = (hash & 0xffff_ffff, hash >> 32 | 1);
+        (0..K as u64).map(move |i| {
+            let bit = h1.wrapping_add(i.wrapping_mul(h2)) % Self::NUM_BITS;
+            ((bit / 64) as usize, 1 << (bit % 64))
+        })
+    }
+}
+
+impl<const WORDS: usize, const K: u32> Default for FixedBloomFilter<WORDS, K> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
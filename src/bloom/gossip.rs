@@ -0,0 +1,342 @@
+use super::{
+    check_hash_scheme,
+    index::{self, KEY_LEN, SERIALIZED_KEY_LEN},
+    CountingBloomFilter, Error,
+};
+use crate::{
+    format::{self, CHECKSUM_LEN, MAGIC_LEN},
+    hasher::{HashSchemeVersion, SeedableHasher},
+};
+
+const SUMMARY_MAGIC: [u8; MAGIC_LEN] = *b"PFDS";
+const DIFF_MAGIC: [u8; MAGIC_LEN] = *b"PFFD";
+const FORMAT_VERSION: u8 = 1;
+// num_bins, n_hashes, bits_per_counter, morris_base (NaN if disabled), key, hash scheme
+const PARAMS_LEN: usize = 8 + 4 + 4 + 8 + SERIALIZED_KEY_LEN + 1;
+// magic, version, params, block_size
+const SUMMARY_HEADER_LEN: usize = MAGIC_LEN + 1 + PARAMS_LEN + 8;
+// magic, version, params
+const DIFF_HEADER_LEN: usize = MAGIC_LEN + 1 + PARAMS_LEN;
+// a u64 takes at most 10 bytes as a varint
+const MAX_VARINT_LEN: usize = 10;
+
+/// Parameters a summary or diff shares with the filter that produced it, and must match on the
+/// filter it's applied to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct DiffParams {
+    num_bins: usize,
+    bits_per_counter: u32,
+    n_hashes: u32,
+    // compared bitwise, as the filters compare their bases when merging
+    morris_base: Option<u64>,
+    key: Option<[u8; KEY_LEN]>,
+}
+
+impl DiffParams {
+    fn write(&self, bytes: &mut Vec<u8>) {
+        bytes.extend_from_slice(&(self.num_bins as u64).to_le_bytes());
+        bytes.extend_from_slice(&self.n_hashes.to_le_bytes());
+        bytes.extend_from_slice(&self.bits_per_counter.to_le_bytes());
+        let morris_base = self.morris_base.map_or(f64::NAN, f64::from_bits);
+        bytes.extend_from_slice(&morris_base.to_le_bytes());
+        bytes.extend_from_slice(&index::key_to_bytes(self.key.as_ref()));
+        bytes.push(HashSchemeVersion::CURRENT.as_u8());
+    }
+
+    fn read(bytes: &[u8]) -> Result<Self, Error> {
+        let num_bins = u64::from_le_bytes(bytes[..8].try_into().unwrap());
+        let n_hashes = u32::from_le_bytes(bytes[8..12].try_into().unwrap());
+        let bits_per_counter = u32::from_le_bytes(bytes[12..16].try_into().unwrap());
+        let morris_base = f64::from_le_bytes(bytes[16..24].try_into().unwrap());
+        let key = index::key_from_bytes(&bytes[24..])?;
+        check_hash_scheme(bytes[PARAMS_LEN - 1])?;
+        if bits_per_counter == 0 || !(morris_base.is_nan() || morris_base > 1.0) {
+            return Err(Error::Malformed);
+        }
+        Ok(DiffParams {
+            num_bins: usize::try_from(num_bins).map_err(|_| Error::Malformed)?,
+            bits_per_counter,
+            n_hashes,
+            morris_base: (!morris_base.is_nan()).then(|| morris_base.to_bits()),
+            key,
+        })
+    }
+}
+
+/// Compact summary of a [CountingBloomFilter], holding one digest per block of counter bins.
+///
+/// A peer sends its summary, and the other side answers with [CountingBloomFilter::diff_for],
+/// which only contains the blocks whose digests don't match.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiffSummary {
+    params: DiffParams,
+    block_size: usize,
+    pub(super) digests: Vec<u64>,
+}
+
+impl DiffSummary {
+    /// Serialize the summary.
+    ///
+    /// The format is the magic number `PFDS`, a version byte, the filter's parameters as for
+    /// [CountingBloomFilter::to_bytes], the block size as a u64, then the digests as u64s and
+    /// the CRC32C of everything before it, all little endian.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes =
+            Vec::with_capacity(SUMMARY_HEADER_LEN + self.digests.len() * 8 + CHECKSUM_LEN);
+        bytes.extend_from_slice(&SUMMARY_MAGIC);
+        bytes.push(FORMAT_VERSION);
+        self.params.write(&mut bytes);
+        bytes.extend_from_slice(&(self.block_size as u64).to_le_bytes());
+        for digest in &self.digests {
+            bytes.extend_from_slice(&digest.to_le_bytes());
+        }
+        format::push_checksum(&mut bytes);
+        bytes
+    }
+
+    /// Deserialize a summary produced by [DiffSummary::to_bytes].
+    ///
+    /// The number of digests is implied by the number of bins and the block size, so a
+    /// truncated or padded summary is rejected.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        format::check_header(bytes, &SUMMARY_MAGIC, FORMAT_VERSION)?;
+        if bytes.len() < SUMMARY_HEADER_LEN {
+            return Err(Error::Truncated);
+        }
+        let params = DiffParams::read(&bytes[MAGIC_LEN + 1..SUMMARY_HEADER_LEN - 8])?;
+        let block_size = u64::from_le_bytes(
+            bytes[SUMMARY_HEADER_LEN - 8..SUMMARY_HEADER_LEN]
+                .try_into()
+                .unwrap(),
+        );
+        if block_size == 0 {
+            return Err(Error::Malformed);
+        }
+        let num_blocks = (params.num_bins as u64).div_ceil(block_size);
+        let len = num_blocks
+            .saturating_mul(8)
+            .saturating_add((SUMMARY_HEADER_LEN + CHECKSUM_LEN) as u64);
+        format::check_body(bytes, len)?;
+
+        let digests = bytes[SUMMARY_HEADER_LEN..bytes.len() - CHECKSUM_LEN]
+            .chunks_exact(8)
+            .map(|digest| u64::from_le_bytes(digest.try_into().unwrap()))
+            .collect();
+        Ok(DiffSummary {
+            params,
+            // a block larger than the filter holds every bin
+            block_size: usize::try_from(block_size).unwrap_or(usize::MAX),
+            digests,
+        })
+    }
+}
+
+/// Counter bins that a peer is missing, produced by [CountingBloomFilter::diff_for].
+///
+/// Only non-zero bins from mismatched blocks are carried, as merging takes the maximum of
+/// each counter and a zero bin can never raise one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FilterDiff {
+    params: DiffParams,
+    bins: Vec<(usize, u64)>,
+}
+
+impl FilterDiff {
+    /// Returns true if there is nothing to merge.
+    pub fn is_empty(&self) -> bool {
+        self.bins.is_empty()
+    }
+
+    /// Number of bins carried by this diff.
+    pub fn len(&self) -> usize {
+        self.bins.len()
+    }
+
+    /// Serialize the diff.
+    ///
+    /// The format is the magic number `PFFD`, a version byte and the filter's parameters as
+    /// for [DiffSummary::to_bytes], then the number of bins and, for each bin, the gap from
+    /// the previous bin's index and the bin's value, all as LEB128 varints, followed by the
+    /// CRC32C of everything before it.  Sparse diffs take a few bytes per bin.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(DIFF_HEADER_LEN + self.bins.len() * 4 + CHECKSUM_LEN);
+        bytes.extend_from_slice(&DIFF_MAGIC);
+        bytes.push(FORMAT_VERSION);
+        self.params.write(&mut bytes);
+        write_varint(&mut bytes, self.bins.len() as u64);
+        let mut next = 0;
+        for &(bin, value) in &self.bins {
+            write_varint(&mut bytes, (bin - next) as u64);
+            write_varint(&mut bytes, value);
+            next = bin + 1;
+        }
+        format::push_checksum(&mut bytes);
+        bytes
+    }
+
+    /// Deserialize a diff produced by [FilterDiff::to_bytes].
+    ///
+    /// Returns [Error::Malformed] if a bin lies outside the filter.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        format::check_header(bytes, &DIFF_MAGIC, FORMAT_VERSION)?;
+        if bytes.len() < DIFF_HEADER_LEN + CHECKSUM_LEN {
+            return Err(Error::Truncated);
+        }
+        format::check_body(bytes, bytes.len() as u64)?;
+        let params = DiffParams::read(&bytes[MAGIC_LEN + 1..DIFF_HEADER_LEN])?;
+
+        let mut body = &bytes[DIFF_HEADER_LEN..bytes.len() - CHECKSUM_LEN];
+        let count = read_varint(&mut body)?;
+        // every bin takes at least two bytes, so don't trust a count the body can't hold
+        if count > (body.len() / 2) as u64 {
+            return Err(Error::Malformed);
+        }
+        let mut bins = Vec::with_capacity(count as usize);
+        let mut next = 0_u64;
+        for _ in 0..count {
+            let bin = next
+                .checked_add(read_varint(&mut body)?)
+                .filter(|&bin| bin < params.num_bins as u64)
+                .ok_or(Error::Malformed)?;
+            bins.push((bin as usize, read_varint(&mut body)?));
+            next = bin + 1;
+        }
+        if !body.is_empty() {
+            return Err(Error::Malformed);
+        }
+        Ok(FilterDiff { params, bins })
+    }
+}
+
+impl<T> CountingBloomFilter<T>
+where
+    T: SeedableHasher,
+{
+    /// Summarize this filter for anti-entropy exchange, hashing every `block_size` bins into
+    /// a single digest.  Smaller blocks produce larger summaries but smaller diffs.
+    ///
+    /// `block_size` must be greater than 0.
+    pub fn summary(&self, block_size: usize) -> Result<DiffSummary, Error> {
+        if block_size == 0 {
+            return Err(Error::InvalidBlockSize(block_size));
+        }
+        Ok(DiffSummary {
+            params: self.diff_params(),
+            block_size,
+            digests: self
+                .counter_bins
+                .chunks(block_size)
+                .map(Self::block_digest)
+                .collect(),
+        })
+    }
+
+    /// Build the updates a peer is missing, given that peer's [DiffSummary].
+    ///
+    /// Returns an error if the summary was produced by a filter with different parameters, or
+    /// doesn't hold a digest for every block.
+    pub fn diff_for(&self, summary: &DiffSummary) -> Result<FilterDiff, Error> {
+        if summary.params != self.diff_params() {
+            return Err(Error::IncompatibleFilter);
+        }
+        let num_blocks = self.counter_bins.len().div_ceil(summary.block_size);
+        if summary.digests.len() != num_blocks {
+            return Err(Error::DigestCountMismatch(
+                summary.digests.len(),
+                num_blocks,
+            ));
+        }
+
+        let mut bins = vec![];
+        for (block, (chunk, digest)) in self
+            .counter_bins
+            .chunks(summary.block_size)
+            .zip(summary.digests.iter())
+            .enumerate()
+        {
+            if Self::block_digest(chunk) == *digest {
+                continue;
+            }
+            let offset = block * summary.block_size;
+            bins.extend(
+                chunk
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, &v)| v != 0)
+                    .map(|(i, &v)| (offset + i, v)),
+            );
+        }
+
+        Ok(FilterDiff {
+            params: summary.params,
+            bins,
+        })
+    }
+
+    /// Merge updates received from a peer.  Each counter is set to the maximum of the local and
+    /// remote value, so repeated exchanges converge regardless of order.
+    ///
+    /// Returns an error if the diff was produced by a filter with different parameters,
+    /// including a different key or Morris base.
+    pub fn merge_diff(&mut self, diff: &FilterDiff) -> Result<(), Error> {
+        if diff.params != self.diff_params() {
+            return Err(Error::IncompatibleFilter);
+        }
+
+        for &(bin, remote) in &diff.bins {
+            let local = self.counter_bins[bin];
+            let mut merged = 0;
+            for i in 0..self.counters_per_bin as usize {
+                let bitshift = i * self.bits_per_counter as usize;
+                let counter_mask = self.counter_max << bitshift;
+                merged |= (local & counter_mask).max(remote & counter_mask);
+            }
+            self.counter_bins[bin] = merged;
+        }
+        self.recount_saturated();
+        Ok(())
+    }
+
+    fn diff_params(&self) -> DiffParams {
+        DiffParams {
+            num_bins: self.counter_bins.len(),
+            bits_per_counter: self.bits_per_counter,
+            n_hashes: self.n_hashes,
+            morris_base: self.morris_base.map(f64::to_bits),
+            key: self.key,
+        }
+    }
+
+    fn block_digest(block: &[u64]) -> u64 {
+        let mut h = T::new();
+        for bin in block {
+            h.write(&bin.to_le_bytes());
+        }
+        h.finish()
+    }
+}
+
+fn write_varint(bytes: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        bytes.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+// Read a varint from the front of `bytes`, advancing past it.
+fn read_varint(bytes: &mut &[u8]) -> Result<u64, Error> {
+    let mut value = 0_u64;
+    for (i, &byte) in bytes.iter().take(MAX_VARINT_LEN).enumerate() {
+        let bits = u64::from(byte & 0x7f);
+        if i == MAX_VARINT_LEN - 1 && bits > 1 {
+            return Err(Error::Malformed);
+        }
+        value |= bits << (7 * i);
+        if byte & 0x80 == 0 {
+            *bytes = &bytes[i + 1..];
+            return Ok(value);
+        }
+    }
+    Err(Error::Malformed)
+}
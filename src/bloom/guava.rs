@@ -0,0 +1,165 @@
+use fasthash::{murmur3::Hash128_x64, FastHash};
+use std::mem;
+
+use super::Error;
+use crate::mem::heap_size;
+
+// strategy, hash count, number of longs
+const HEADER_LEN: usize = 1 + 1 + 4;
+
+/// How Guava maps an entry to bit positions, recorded as the first byte of its serialized form.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GuavaStrategy {
+    /// `MURMUR128_MITZ_32`, the default of old Guava versions, which only uses 64 bits of the
+    /// hash and can't address more than 2^31 bits.
+    Murmur128Mitz32,
+    /// `MURMUR128_MITZ_64`, the default since Guava 16.
+    #[default]
+    Murmur128Mitz64,
+}
+
+/// A bloom filter that hashes, and serializes, exactly like Google Guava's `BloomFilter`, so
+/// filters can be passed back and forth with JVM services.
+///
+/// Guava hashes the bytes an entry's `Funnel` writes with 128 bit murmur3, so entries here
+/// must be the same bytes: strings as UTF-8 for `Funnels.stringFunnel(UTF_8)`, raw bytes for
+/// `Funnels.byteArrayFunnel()`, and little endian integers for `Funnels.longFunnel()`.
+///
+/// The bit array always holds a whole number of u64 words, all of which are used.
+pub struct GuavaBloomFilter {
+    bits: Vec<u64>,
+    n_hashes: u32,
+    strategy: GuavaStrategy,
+}
+
+impl GuavaBloomFilter {
+    /// Create a new filter with at least `num_bits` bits, rounded up to a multiple of 64.
+    ///
+    /// `num_hashes` must be between 1 and 255, as Guava stores it in a byte.
+    pub fn new(num_bits: usize, num_hashes: u32, strategy: GuavaStrategy) -> Result<Self, Error> {
+        let words = num_bits.div_ceil(u64::BITS as usize);
+        if words == 0 || words > i32::MAX as usize {
+            return Err(Error::InvalidBinCount(num_bits));
+        }
+        if num_hashes == 0 || num_hashes > u8::MAX as u32 {
+            return Err(Error::InvalidHashCount(num_hashes));
+        }
+        Ok(GuavaBloomFilter {
+            bits: vec![0; words],
+            n_hashes: num_hashes,
+            strategy,
+        })
+    }
+
+    /// Create a new filter the way `BloomFilter.create(funnel, expectedInsertions, fpp)` does,
+    /// with the `MURMUR128_MITZ_64` strategy.
+    ///
+    /// `expected_entries` must be greater than 0, and `fpp` must be between 0 and 1 (exclusive).
+    pub fn with_capacity_and_fpp(expected_entries: usize, fpp: f64) -> Result<Self, Error> {
+        if expected_entries == 0 {
+            return Err(Error::InvalidCapacity(expected_entries));
+        }
+        if !(fpp > 0.0 && fpp < 1.0) {
+            return Err(Error::InvalidFpp(fpp));
+        }
+        let ln2 = std::f64::consts::LN_2;
+        let num_bits = (-(expected_entries as f64) * fpp.ln() / (ln2 * ln2)) as usize;
+        let num_hashes = (-fpp.ln() / ln2).round().max(1.0) as u32;
+        Self::new(num_bits, num_hashes, GuavaStrategy::Murmur128Mitz64)
+    }
+
+    /// Add an entry to the filter, returning true if any bit was set by this call.
+    pub fn add<I>(&mut self, entry: I) -> bool
+    where
+        I: AsRef<[u8]>,
+    {
+        let mut added = false;
+        for bit in self.positions(entry.as_ref()) {
+            let (word, mask) = (bit / 64, 1 << (bit % 64));
+            added |= self.bits[word] & mask == 0;
+            self.bits[word] |= mask;
+        }
+        added
+    }
+
+    /// Determine if filter contains the provided entry.
+    pub fn contains<I>(&self, entry: I) -> bool
+    where
+        I: AsRef<[u8]>,
+    {
+        self.positions(entry.as_ref())
+            .into_iter()
+            .all(|bit| self.bits[bit / 64] & (1 << (bit % 64)) != 0)
+    }
+
+    /// Bytes used by the filter, its own size plus the heap allocated for its bits, including
+    /// spare capacity.
+    pub fn mem_usage(&self) -> usize {
+        mem::size_of::<Self>() + heap_size(&self.bits)
+    }
+
+    /// Serialize the filter as Guava's `BloomFilter.writeTo` does: the strategy and hash count
+    /// as single bytes, then the number of words as an i32 and the words as i64s, all big
+    /// endian.
+    pub fn to_guava_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_LEN + self.bits.len() * 8);
+        bytes.push(match self.strategy {
+            GuavaStrategy::Murmur128Mitz32 => 0,
+            GuavaStrategy::Murmur128Mitz64 => 1,
+        });
+        bytes.push(self.n_hashes as u8);
+        bytes.extend_from_slice(&(self.bits.len() as u32).to_be_bytes());
+        bytes.extend(self.bits.iter().flat_map(|w| w.to_be_bytes()));
+        bytes
+    }
+
+    /// Deserialize a filter written by Guava's `BloomFilter.writeTo`, or by
+    /// [GuavaBloomFilter::to_guava_bytes].
+    pub fn from_guava_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        if bytes.len() < HEADER_LEN {
+            return Err(Error::Malformed);
+        }
+        let strategy = match bytes[0] {
+            0 => GuavaStrategy::Murmur128Mitz32,
+            1 => GuavaStrategy::Murmur128Mitz64,
+            other => return Err(Error::UnsupportedVersion(other)),
+        };
+        let words = u32::from_be_bytes(bytes[2..6].try_into().unwrap()) as usize;
+        let payload = &bytes[HEADER_LEN..];
+        if payload.len() != words * 8 {
+            return Err(Error::Malformed);
+        }
+        let mut bf = Self::new(words * 64, bytes[1] as u32, strategy)?;
+        for (word, chunk) in bf.bits.iter_mut().zip(payload.chunks(8)) {
+            *word = u64::from_be_bytes(chunk.try_into().unwrap());
+        }
+        Ok(bf)
+    }
+
+    // Bit positions, following Guava's BloomFilterStrategies with Java's wrapping arithmetic.
+    fn positions(&self, entry: &[u8]) -> Vec<usize> {
+        let hash = Hash128_x64::hash_with_seed(entry, 0);
+        let bit_size = self.bits.len() as u64 * 64;
+        match self.strategy {
+            GuavaStrategy::Murmur128Mitz32 => {
+                let (hash1, hash2) = (hash as i32, (hash >> 32) as i32);
+                (1..=self.n_hashes as i32)
+                    .map(|i| {
+                        let combined = hash1.wrapping_add(i.wrapping_mul(hash2));
+                        let combined = if combined < 0 { !combined } else { combined };
+                        (combined as u64 % bit_size) as usize
+                    })
+                    .collect()
+            }
+            GuavaStrategy::Murmur128Mitz64 => {
+                let (hash1, hash2) = (hash as u64, (hash >> 64) as u64);
+                (0..self.n_hashes as u64)
+                    .map(|i| {
+                        let combined = hash1.wrapping_add(i.wrapping_mul(hash2));
+                        ((combined & i64::MAX as u64) % bit_size) as usize
+                    })
+                    .collect()
+            }
+        }
+    }
+}
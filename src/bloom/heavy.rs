@@ -0,0 +1,51 @@
+use crate::keyheap::KeyHeap;
+
+/// The keys of a [super::CountingBloomFilter] whose estimates have reached a threshold, from
+/// [super::CountingBloomFilter::with_heavy_hitters].  When it's full, a key crossing the
+/// threshold replaces the lightest one if it's heavier, found in O(log capacity).
+#[derive(Clone)]
+pub(super) struct HeavyHitters {
+    threshold: usize,
+    capacity: usize,
+    counts: KeyHeap<()>,
+}
+
+impl HeavyHitters {
+    pub(super) fn new(threshold: usize, capacity: usize) -> Self {
+        HeavyHitters {
+            threshold,
+            capacity,
+            counts: KeyHeap::new(),
+        }
+    }
+
+    /// Record that `key` is now estimated at `count`.
+    pub(super) fn update(&mut self, key: &[u8], count: usize) {
+        if count < self.threshold {
+            self.counts.remove(key);
+            return;
+        }
+        if self.counts.set_count(key, count as u64) {
+            return;
+        }
+        if self.counts.len() == self.capacity {
+            let (_, min) = self.counts.peek_min().expect("capacity is > 0");
+            if min >= count as u64 {
+                return;
+            }
+            self.counts.pop_min();
+        }
+        self.counts.insert(key.to_vec(), count as u64, ());
+    }
+
+    /// The tracked keys and their estimates, heaviest first.
+    pub(super) fn sorted(&self) -> Vec<(&[u8], usize)> {
+        let mut sorted = self
+            .counts
+            .iter()
+            .map(|(key, count, _)| (key, count as usize))
+            .collect::<Vec<_>>();
+        sorted.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        sorted
+    }
+}
@@ -0,0 +1,171 @@
+use siphasher::sip128::{Hasher128, SipHasher13};
+use std::{any, fmt, hash::Hasher, marker::PhantomData};
+
+use super::Error;
+use crate::hasher::{fastrange, mix, SeedableHasher};
+
+/// Length of a secret key for [super::BloomFilter::with_key].
+pub(super) const KEY_LEN: usize = 16;
+// a flag byte, then the key, or zeros if there isn't one
+pub(super) const SERIALIZED_KEY_LEN: usize = 1 + KEY_LEN;
+
+/// The hashes of an entry, computed once so the entry can be added to, or looked up in,
+/// several filters without hashing it again.
+///
+/// A key hashed with [HashedKey::new] can be used with any unkeyed [super::BloomFilter],
+/// [super::CountingBloomFilter] or [super::BloomFilterView] built with the hasher `T`, whatever
+/// its size, and one hashed with [HashedKey::with_key] with those using the same key.  Using it
+/// with any other filter panics.
+pub struct HashedKey<T> {
+    h1: u64,
+    h2: u64,
+    key: Option<[u8; KEY_LEN]>,
+    _hasher: PhantomData<T>,
+}
+
+impl<T> HashedKey<T>
+where
+    T: SeedableHasher,
+{
+    /// Hash `entry` for unkeyed filters.
+    pub fn new<I>(entry: I) -> Self
+    where
+        I: AsRef<[u8]>,
+    {
+        Self::hash(entry.as_ref(), None)
+    }
+
+    /// Hash `entry` for filters using the secret `key`.
+    pub fn with_key<I>(entry: I, key: [u8; KEY_LEN]) -> Self
+    where
+        I: AsRef<[u8]>,
+    {
+        Self::hash(entry.as_ref(), Some(&key))
+    }
+
+    /// Hash `entry` to 128 bits.  Without a key, the 64 bit hash is mixed into two 64 bit
+    /// halves, with a key, it's SipHash-1-3 keyed with it, so positions can't be predicted
+    /// without the key.
+    pub(super) fn hash(entry: &[u8], key: Option<&[u8; KEY_LEN]>) -> Self {
+        let (h1, h2) = match key {
+            Some(key) => {
+                let mut h = SipHasher13::new_with_key(key);
+                h.write(entry);
+                let hash = h.finish128();
+                (hash.h1, hash.h2)
+            }
+            None => {
+                let mut h = T::with_seed(0);
+                h.write(entry);
+                split(h.finish())
+            }
+        };
+        HashedKey {
+            h1,
+            h2,
+            key: key.copied(),
+            _hasher: PhantomData,
+        }
+    }
+
+    /// Positions in a filter of `m` positions, one for each of `n_hashes` hashes, using enhanced
+    /// double hashing
+    /// ([Dillinger & Manolios](https://www.khoury.northeastern.edu/~pete/pub/bloom-filters-verification.pdf)):
+    /// `x_i = h1 + i * h2 + (i^3 - i) / 6 mod 2^64`, each mapped onto `0..m` by
+    /// [fastrange].  This keeps the false positive probability of independent hashes, for the
+    /// cost of hashing the entry once rather than `n_hashes` times, and a multiply rather than a
+    /// division per position.
+    ///
+    /// Panics if the filter's `key` isn't the one this was hashed with.
+    pub(super) fn indexes(
+        &self,
+        key: Option<&[u8; KEY_LEN]>,
+        n_hashes: u32,
+        m: u64,
+    ) -> impl Iterator<Item = u64> {
+        assert!(
+            self.key.as_ref() == key,
+            "HashedKey was hashed with a different key than the filter's"
+        );
+        // reduce in 64 bits, so the same positions are chosen on 32 bit platforms
+        let mut x = self.h1;
+        let mut y = self.h2;
+        (0..n_hashes as u64).map(move |i| {
+            let index = fastrange(x, m);
+            x = x.wrapping_add(y);
+            y = y.wrapping_add(i + 1);
+            index
+        })
+    }
+}
+
+/// A 64 bit hash computed by the caller, which is mixed into 128 bits as the hasher's would
+/// be, so for an unkeyed filter it's the same as adding the entry it's the hash of with `T`.
+impl<T> From<u64> for HashedKey<T> {
+    fn from(hash: u64) -> Self {
+        let (h1, h2) = split(hash);
+        HashedKey {
+            h1,
+            h2,
+            key: None,
+            _hasher: PhantomData,
+        }
+    }
+}
+
+/// A 128 bit hash computed by the caller, whose low and high halves are used as they are.
+impl<T> From<u128> for HashedKey<T> {
+    fn from(hash: u128) -> Self {
+        HashedKey {
+            h1: hash as u64,
+            h2: (hash >> 64) as u64,
+            key: None,
+            _hasher: PhantomData,
+        }
+    }
+}
+
+impl<T> fmt::Debug for HashedKey<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HashedKey")
+            .field("hasher", &any::type_name::<T>())
+            .field("hash", &format_args!("{:#018x}{:016x}", self.h1, self.h2))
+            .field("keyed", &self.key.is_some())
+            .finish()
+    }
+}
+
+// derived impls would require T: Clone, which hashers don't implement
+impl<T> Clone for HashedKey<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for HashedKey<T> {}
+
+/// Serialize an optional key as [SERIALIZED_KEY_LEN] bytes.
+pub(super) fn key_to_bytes(key: Option<&[u8; KEY_LEN]>) -> [u8; SERIALIZED_KEY_LEN] {
+    let mut bytes = [0; SERIALIZED_KEY_LEN];
+    if let Some(key) = key {
+        bytes[0] = 1;
+        bytes[1..].copy_from_slice(key);
+    }
+    bytes
+}
+
+/// Deserialize a key written by [key_to_bytes].
+pub(super) fn key_from_bytes(bytes: &[u8]) -> Result<Option<[u8; KEY_LEN]>, Error> {
+    match bytes[0] {
+        0 => Ok(None),
+        1 => Ok(Some(bytes[1..SERIALIZED_KEY_LEN].try_into().unwrap())),
+        _ => Err(Error::Malformed),
+    }
+}
+
+// Mix a 64 bit hash into two, both mixed, as fastrange reads their high bits and 32 bit
+// hashers leave those 0.
+fn split(hash: u64) -> (u64, u64) {
+    let h1 = mix(hash);
+    (h1, mix(h1))
+}
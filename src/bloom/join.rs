@@ -0,0 +1,69 @@
+use super::{BloomFilter, Error};
+use crate::hasher::SeedableHasher;
+
+/// The side of a [bloom_join] whose keys were filtered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JoinSide {
+    Left,
+    Right,
+}
+
+/// Keys of the larger side of a [bloom_join] that may have a match on the smaller side.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BloomJoin<K> {
+    /// The side the survivors came from.
+    pub filtered: JoinSide,
+    /// Its keys the filter over the other side contains, in their original order.
+    pub survivors: Vec<K>,
+}
+
+/// Semi-join two sides by key before an exact join: builds a [BloomFilter] over the smaller
+/// side's keys with a false positive probability of `fpp`, and keeps the keys of the larger
+/// side it contains.  Every key with a match survives, along with about `fpp` of those
+/// without.
+///
+/// The left side is filtered if both are the same size, and nothing survives if the smaller
+/// side is empty.  Fails if `fpp` isn't 0 < fpp < 1.
+pub fn bloom_join<T, K, L, R>(left: L, right: R, fpp: f64) -> Result<BloomJoin<K>, Error>
+where
+    T: SeedableHasher,
+    K: AsRef<[u8]>,
+    L: IntoIterator<Item = K>,
+    L::IntoIter: ExactSizeIterator,
+    R: IntoIterator<Item = K>,
+    R::IntoIter: ExactSizeIterator,
+{
+    // checked up front, as an empty side never builds a filter to check it
+    if !(fpp > 0.0 && fpp < 1.0) {
+        return Err(Error::InvalidFpp(fpp));
+    }
+    let (left, right) = (left.into_iter(), right.into_iter());
+    match left.len() < right.len() {
+        true => Ok(BloomJoin {
+            filtered: JoinSide::Right,
+            survivors: semi_join::<T, _, _, _>(left, right, fpp)?,
+        }),
+        false => Ok(BloomJoin {
+            filtered: JoinSide::Left,
+            survivors: semi_join::<T, _, _, _>(right, left, fpp)?,
+        }),
+    }
+}
+
+// Keys of `larger` the filter over `smaller` contains.
+fn semi_join<T, K, S, L>(smaller: S, larger: L, fpp: f64) -> Result<Vec<K>, Error>
+where
+    T: SeedableHasher,
+    K: AsRef<[u8]>,
+    S: ExactSizeIterator<Item = K>,
+    L: Iterator<Item = K>,
+{
+    if smaller.len() == 0 {
+        return Ok(Vec::new());
+    }
+    let mut filter = BloomFilter::<T>::with_capacity_and_fpp(smaller.len(), fpp)?;
+    for key in smaller {
+        filter.add(key);
+    }
+    Ok(larger.filter(|key| filter.contains(key)).collect())
+}
@@ -0,0 +1,71 @@
+use std::mem;
+
+use super::BloomFilter;
+use crate::hasher::SeedableHasher;
+
+/// Implementation of a sandwiched [learned bloom filter](https://arxiv.org/abs/1803.01474).
+///
+/// A user provided model scores each entry, and entries scoring at or above `threshold` are
+/// considered present.  The model is placed between two bloom filters:
+/// * the initial filter holds every entry, screening out most negatives before the model runs
+/// * the backup filter holds only the entries the model scores below `threshold`
+///
+/// As every entry the model would reject is stored in the backup filter, there are no false
+/// negatives.  A good model lets the backup filter be much smaller than a single bloom filter
+/// holding every entry.
+pub struct LearnedBloomFilter<T, M>
+where
+    T: SeedableHasher,
+    M: Fn(&[u8]) -> f64,
+{
+    initial: BloomFilter<T>,
+    model: M,
+    threshold: f64,
+    backup: BloomFilter<T>,
+}
+
+impl<T, M> LearnedBloomFilter<T, M>
+where
+    T: SeedableHasher,
+    M: Fn(&[u8]) -> f64,
+{
+    /// Create a new learned bloom filter from a model and the two filters surrounding it.
+    pub fn new(model: M, threshold: f64, initial: BloomFilter<T>, backup: BloomFilter<T>) -> Self {
+        LearnedBloomFilter {
+            initial,
+            model,
+            threshold,
+            backup,
+        }
+    }
+
+    /// Add an entry to the filter.  Entries the model rejects are also added to the backup
+    /// filter.
+    pub fn add<I>(&mut self, entry: I)
+    where
+        I: AsRef<[u8]>,
+    {
+        self.initial.add(entry.as_ref());
+        if (self.model)(entry.as_ref()) < self.threshold {
+            self.backup.add(entry.as_ref());
+        }
+    }
+
+    /// Determine if filter contains the provided entry.
+    pub fn contains<I>(&self, entry: I) -> bool
+    where
+        I: AsRef<[u8]>,
+    {
+        self.initial.contains(entry.as_ref())
+            && ((self.model)(entry.as_ref()) >= self.threshold
+                || self.backup.contains(entry.as_ref()))
+    }
+
+    /// Bytes used by the filter, including the bits of both underlying [BloomFilter]s, but not
+    /// anything the model owns on the heap.
+    pub fn mem_usage(&self) -> usize {
+        mem::size_of::<Self>() - 2 * mem::size_of::<BloomFilter<T>>()
+            + self.initial.mem_usage()
+            + self.backup.mem_usage()
+    }
+}
@@ -1,9 +1,57 @@
-use fasthash::FastHasher;
-use std::{collections::HashMap, marker::PhantomData};
+use rand::{thread_rng, Rng, RngCore};
+use std::{
+    any,
+    collections::{HashMap, TryReserveError},
+    fmt,
+    io::{self, Read, Write},
+    marker::PhantomData,
+    mem,
+};
 use thiserror::Error;
 
+use crate::{
+    aligned::AlignedWords,
+    event::{EventHook, FilterEvent},
+    format::{self, ChecksumReader, ChecksumWriter, FrameError, CHECKSUM_LEN, MAGIC_LEN},
+    hasher::{HashSchemeVersion, SeedableHasher},
+    measure::{self, FppMeasurement},
+    morris, prefetch, sizing, stream,
+};
+
+mod bloomrf;
+mod entry;
+mod fixed;
+mod gossip;
+mod guava;
+mod heavy;
+mod index;
+mod join;
+mod learned;
+mod ops;
+mod prefix;
+mod removals;
+mod standard;
 #[cfg(test)]
 mod test;
+mod view;
+mod word;
+
+pub use self::bloomrf::BloomRf;
+pub use self::entry::CountingBloomEntry;
+pub use self::fixed::FixedBloomFilter;
+pub use self::gossip::{DiffSummary, FilterDiff};
+pub use self::guava::{GuavaBloomFilter, GuavaStrategy};
+pub use self::index::HashedKey;
+pub use self::join::{bloom_join, BloomJoin, JoinSide};
+pub use self::learned::LearnedBloomFilter;
+pub use self::prefix::{FixedPrefix, PrefixBloomFilter, PrefixExtractor};
+pub use self::removals::RemovalBloomFilter;
+pub use self::standard::BloomFilter;
+pub use self::view::BloomFilterView;
+pub use self::word::Word;
+
+use self::heavy::HeavyHitters;
+use self::index::{KEY_LEN, SERIALIZED_KEY_LEN};
 
 #[derive(Error, Debug)]
 pub enum Error {
@@ -13,45 +61,205 @@ pub enum Error {
     #[error("Invalid bin count {0}: must be > 0")]
     InvalidBinCount(usize),
 
-    #[error("Invalid bits per counter {0}: must be < usize::BITS ({1})")]
+    #[error("Invalid bits per counter {0}: must be <= u64::BITS ({1})")]
     BitsPerCounterTooLarge(u32, u32),
 
-    #[error("Invalid bits per counter {0}: must divide evenly into usize::BITS ({1})")]
+    #[error("Invalid bits per counter {0}: must divide evenly into u64::BITS ({1})")]
     BitsPerCounterUnaligned(u32, u32),
+
+    #[error("Invalid block size {0}: must be > 0")]
+    InvalidBlockSize(usize),
+
+    #[error("Summary has {0} digests for {1} blocks")]
+    DigestCountMismatch(usize, usize),
+
+    #[error("Invalid shard count {0}: must be > 0")]
+    InvalidShardCount(usize),
+
+    #[error("Invalid widening threshold {0}: must be > 0")]
+    InvalidWidenThreshold(usize),
+
+    #[error("Invalid heavy hitter threshold {0} or capacity {1}: both must be > 0")]
+    InvalidHeavyHitters(usize, usize),
+
+    #[error(
+        "Invalid projection target {0}: must be a whole number of bins, dividing the counters by a power of two"
+    )]
+    InvalidProjectionTarget(usize),
+
+    #[error("Filters were created with different parameters")]
+    IncompatibleFilter,
+
+    #[error("Invalid level step {0}: must be 0 < level_step <= 16")]
+    InvalidLevelStep(u32),
+
+    #[error("Invalid Morris counter base {0}: must be > 1")]
+    InvalidMorrisBase(f64),
+
+    #[error("Invalid scale factor {0}: must be finite and >= 0")]
+    InvalidScaleFactor(f64),
+
+    #[error("Invalid capacity {0}: must be > 0")]
+    InvalidCapacity(usize),
+
+    #[error("Invalid false positive probability {0}: must be 0 < fpp < 1")]
+    InvalidFpp(f64),
+
+    #[error("Unsupported format version {0}")]
+    UnsupportedVersion(u8),
+
+    #[error("Unsupported hash scheme version {0}")]
+    UnsupportedHashScheme(u8),
+
+    #[error("Serialized filter is truncated or malformed")]
+    Malformed,
+
+    #[error("Serialized filter is truncated")]
+    Truncated,
+
+    #[error("Not a serialized filter of this type")]
+    InvalidMagic,
+
+    #[error("Checksum mismatch: expected {expected:#010x}, computed {actual:#010x}")]
+    ChecksumMismatch { expected: u32, actual: u32 },
+
+    #[error("Failed to allocate the filter: {0}")]
+    AllocationFailed(#[from] TryReserveError),
+
+    #[error("I/O error: {0}")]
+    Io(std::io::Error),
+}
+
+// A stream that ends early holds a truncated filter.
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        match err.kind() {
+            std::io::ErrorKind::UnexpectedEof => Error::Truncated,
+            _ => Error::Io(err),
+        }
+    }
+}
+
+impl From<FrameError> for Error {
+    fn from(err: FrameError) -> Self {
+        match err {
+            FrameError::Truncated => Error::Truncated,
+            FrameError::InvalidMagic => Error::InvalidMagic,
+            FrameError::UnsupportedVersion(version) => Error::UnsupportedVersion(version),
+            FrameError::TrailingBytes => Error::Malformed,
+            FrameError::ChecksumMismatch { expected, actual } => {
+                Error::ChecksumMismatch { expected, actual }
+            }
+        }
+    }
 }
 
 const DEFAULT_BITS_PER_COUNTER: u32 = 4;
+const MAGIC: [u8; MAGIC_LEN] = *b"PFCB";
+const FORMAT_VERSION: u8 = 6;
+// magic, version, num_bins, n_hashes, bits_per_counter, morris_base (NaN if disabled), key,
+// hash scheme
+const HEADER_LEN: usize = MAGIC_LEN + 1 + 8 + 4 + 4 + 8 + SERIALIZED_KEY_LEN + 1;
+// probability that the lower bound of estimate_bounds holds
+const BOUNDS_CONFIDENCE: f64 = 0.99;
+// wider counters share the last bucket of the histogram
+const MAX_HISTOGRAM_LEN: u64 = 256;
+
+/// Statistics for a [CountingBloomFilter], from [CountingBloomFilter::stats].
+///
+/// The counts are kept as the filter is used, the rest is computed from the counters when the
+/// stats are taken.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CountingBloomStats {
+    /// Number of adds that incremented an entry's counters.
+    pub inserted: u64,
+    /// Number of adds refused because one of the entry's counters was saturated.
+    pub saturation_events: u64,
+    /// Number of counters holding each value, up to 255, with the last value also counting
+    /// counters above it.  With Morris counters, the values are exponents.
+    pub histogram: Vec<u64>,
+    /// Fraction of counters that are nonzero.
+    pub fill_ratio: f64,
+    /// False positive probability implied by the fill ratio, `fill_ratio^k` for `k` hashes.
+    pub estimated_fpp: f64,
+}
 
 /// Implementation of a [counting bloom filter](https://en.wikipedia.org/wiki/Counting_Bloom_filter).
+///
+/// Counters are packed into u64 bins regardless of the platform's word size, so a filter maps
+/// entries to the same counters everywhere, and [CountingBloomFilter::to_bytes] is portable.
 pub struct CountingBloomFilter<T>
 where
-    T: FastHasher<Seed = u32>,
+    T: SeedableHasher,
 {
-    counter_bins: Vec<usize>,
-    counter_max: usize,
+    // cache line aligned, so the counters a lookup reads span as few lines as possible
+    counter_bins: AlignedWords,
+    counter_max: u64,
     counters_per_bin: u32,
     bits_per_counter: u32,
     n_hashes: u32,
+    // when set, counters hold Morris counter exponents in this base
+    morris_base: Option<f64>,
+    key: Option<[u8; KEY_LEN]>,
+    // since the filter was created or deserialized, for stats
+    inserted: u64,
+    saturation_events: u64,
+    // counters at counter_max, counted while widening is enabled, and how many trigger it
+    saturated_counters: usize,
+    widen_at: Option<usize>,
+    // boxed, as most filters don't track them
+    heavy_hitters: Option<Box<HeavyHitters>>,
+    hook: EventHook,
     _hasher: PhantomData<T>,
 }
 
 impl<T> CountingBloomFilter<T>
 where
-    T: FastHasher<Seed = u32>,
+    T: SeedableHasher,
 {
     /// Create a new counting bloom filter with 4 bits per counter.
     pub fn new(num_counters: usize, num_hashes: u32) -> Result<Self, Error> {
         Self::with_bits_per_counter(num_counters, num_hashes, DEFAULT_BITS_PER_COUNTER)
     }
 
+    /// Create a new counting bloom filter as [CountingBloomFilter::new] does, but return
+    /// [Error::AllocationFailed] rather than aborting if the counters can't be allocated.
+    pub fn try_new(num_counters: usize, num_hashes: u32) -> Result<Self, Error> {
+        let bits_per_counter = DEFAULT_BITS_PER_COUNTER;
+        let num_bins = check_counter_params(num_counters, num_hashes, bits_per_counter)?;
+        Ok(Self::with_bins(
+            AlignedWords::try_new(num_bins)?,
+            num_hashes,
+            bits_per_counter,
+        ))
+    }
+
+    /// Create a new counting bloom filter with 4 bits per counter, sized to hold
+    /// `expected_entries` with a false positive probability of `fpp`, using the optimal number
+    /// of counters and hashes.
+    ///
+    /// `expected_entries` must be greater than 0, and `fpp` must be between 0 and 1 (exclusive).
+    pub fn with_capacity_and_fpp(expected_entries: usize, fpp: f64) -> Result<Self, Error> {
+        let (num_counters, num_hashes) = optimal_params(expected_entries, fpp)?;
+        Self::new(num_counters, num_hashes)
+    }
+
+    /// Create a new counting bloom filter as [CountingBloomFilter::with_capacity_and_fpp]
+    /// does, but return [Error::AllocationFailed] rather than aborting if the counters can't
+    /// be allocated.
+    pub fn try_with_capacity_and_fpp(expected_entries: usize, fpp: f64) -> Result<Self, Error> {
+        let (num_counters, num_hashes) = optimal_params(expected_entries, fpp)?;
+        Self::try_new(num_counters, num_hashes)
+    }
+
     /// Create a new counting bloom filter with specified bits per counter.
     ///
     /// `bits_per_counter` must
-    /// * be less than or equal to [usize::BITS]
-    /// * divide evenly into [usize::BITS] ([usize::BITS] % `bits_per_counter` == 0)
+    /// * be less than or equal to [u64::BITS]
+    /// * divide evenly into [u64::BITS] ([u64::BITS] % `bits_per_counter` == 0)
     ///
     /// `num_counters` is a lower bound, number of bins actually allocated will be
-    /// ceil(`num_counters` * `bits_per_counter` / [usize::BITS])
+    /// ceil(`num_counters` * `bits_per_counter` / [u64::BITS])
     ///
     /// `num_hashes` must be greater than 0 and less than, or equal to, `num_counters`
     pub fn with_bits_per_counter(
@@ -59,49 +267,296 @@ where
         num_hashes: u32,
         bits_per_counter: u32,
     ) -> Result<Self, Error> {
-        if bits_per_counter > usize::BITS {
-            return Err(Error::BitsPerCounterTooLarge(bits_per_counter, usize::BITS));
+        let num_bins = check_counter_params(num_counters, num_hashes, bits_per_counter)?;
+        Ok(Self::with_bins(
+            AlignedWords::new(num_bins),
+            num_hashes,
+            bits_per_counter,
+        ))
+    }
+
+    fn with_bins(counter_bins: AlignedWords, num_hashes: u32, bits_per_counter: u32) -> Self {
+        CountingBloomFilter {
+            counter_bins,
+            counter_max: calc_max_counter(&bits_per_counter),
+            counters_per_bin: u64::BITS / bits_per_counter,
+            bits_per_counter,
+            n_hashes: num_hashes,
+            morris_base: None,
+            key: None,
+            inserted: 0,
+            saturation_events: 0,
+            saturated_counters: 0,
+            widen_at: None,
+            heavy_hitters: None,
+            hook: EventHook::default(),
+            _hasher: PhantomData,
         }
-        if usize::BITS % bits_per_counter != 0 {
-            return Err(Error::BitsPerCounterUnaligned(
-                bits_per_counter,
-                usize::BITS,
-            ));
+    }
+
+    /// Create a new counting bloom filter whose counters are [Morris counters](crate::MorrisCounter)
+    /// in powers of `base`, so they can count far beyond 2^`bits_per_counter` at the cost of
+    /// exactness.
+    ///
+    /// Counters are incremented (or decremented) probabilistically, [CountingBloomFilter::estimate]
+    /// decodes the approximate count.  `base` must be greater than 1, other parameters are
+    /// checked as in [CountingBloomFilter::with_bits_per_counter].
+    pub fn with_morris_counters(
+        num_counters: usize,
+        num_hashes: u32,
+        bits_per_counter: u32,
+        base: f64,
+    ) -> Result<Self, Error> {
+        if base.is_nan() || base <= 1.0 {
+            return Err(Error::InvalidMorrisBase(base));
         }
-        if num_counters == 0 {
-            return Err(Error::InvalidBinCount(num_counters));
+        let mut cbf = Self::with_bits_per_counter(num_counters, num_hashes, bits_per_counter)?;
+        cbf.morris_base = Some(base);
+        Ok(cbf)
+    }
+
+    /// Derive counters from SipHash keyed with a secret `key`, as for [BloomFilter::with_key].
+    pub fn with_key(mut self, key: [u8; KEY_LEN]) -> Self {
+        self.key = Some(key);
+        self
+    }
+
+    /// Returns true if counters are derived from a secret key.
+    pub fn is_keyed(&self) -> bool {
+        self.key.is_some()
+    }
+
+    /// Double the bits per counter once `saturated_counters` counters have reached their
+    /// maximum, carrying their values over, so heavy hitters keep being counted in long running
+    /// workloads.  Counters keep their positions, so the filter's memory doubles each time, up
+    /// to 64 bit counters.
+    ///
+    /// Fails unless `saturated_counters` > 0.
+    pub fn with_auto_widen(mut self, saturated_counters: usize) -> Result<Self, Error> {
+        if saturated_counters == 0 {
+            return Err(Error::InvalidWidenThreshold(saturated_counters));
         }
-        if num_hashes == 0 || num_hashes as usize > num_counters {
-            return Err(Error::InvalidHashCount(num_hashes));
+        self.widen_at = Some(saturated_counters);
+        self.recount_saturated();
+        Ok(self)
+    }
+
+    /// Bits per counter, which grows if the filter widens.
+    pub fn bits_per_counter(&self) -> u32 {
+        self.bits_per_counter
+    }
+
+    /// Number of counters, which is rounded up to fill the last u64 bin.
+    pub fn num_counters(&self) -> usize {
+        self.counter_bins.len() * self.counters_per_bin as usize
+    }
+
+    /// Counters packed into each u64 bin.
+    pub fn counters_per_bin(&self) -> u32 {
+        self.counters_per_bin
+    }
+
+    pub fn num_hashes(&self) -> u32 {
+        self.n_hashes
+    }
+
+    /// Base of the Morris counters, or None if counters count exactly.
+    pub fn morris_base(&self) -> Option<f64> {
+        self.morris_base
+    }
+
+    // Count the saturated counters again, after writes that don't keep the count.
+    fn recount_saturated(&mut self) {
+        if self.widen_at.is_none() {
+            return;
         }
+        self.saturated_counters = self
+            .all_counter_values()
+            .filter(|&v| v == self.counter_max)
+            .count();
+        self.widen_if_saturated();
+    }
 
-        let counters_per_bin = usize::BITS / bits_per_counter;
-        let num_bins = num_counters.div_ceil(counters_per_bin as usize);
-        Ok(CountingBloomFilter {
-            counter_bins: vec![0; num_bins],
-            counter_max: calc_max_counter(&bits_per_counter),
-            counters_per_bin,
+    fn widen_if_saturated(&mut self) {
+        match self.widen_at {
+            Some(widen_at) if self.saturated_counters >= widen_at => self.widen(),
+            _ => {}
+        }
+    }
+
+    // Every counter keeps its index, which the counters per bin halving doesn't change, as the
+    // number of counters was a multiple of it.
+    fn widen(&mut self) {
+        if self.bits_per_counter == u64::BITS {
+            self.widen_at = None;
+            return;
+        }
+        let bits_per_counter = self.bits_per_counter * 2;
+        let mut widened = Self::with_bins(
+            AlignedWords::new(self.counter_bins.len() * 2),
+            self.n_hashes,
             bits_per_counter,
-            n_hashes: num_hashes,
-            _hasher: PhantomData,
+        );
+        for (index, value) in self.all_counter_values().enumerate() {
+            let c = widened.counter(index as u64);
+            let bin = &mut widened.counter_bins[c.bin];
+            *bin = c.set(*bin, value);
+        }
+        self.counter_bins = widened.counter_bins;
+        self.counter_max = widened.counter_max;
+        self.counters_per_bin = widened.counters_per_bin;
+        self.bits_per_counter = bits_per_counter;
+        // nothing is at the new maximum
+        self.saturated_counters = 0;
+        #[cfg(feature = "tracing")]
+        tracing::debug!(bits_per_counter, "widened counting bloom filter");
+        self.hook
+            .emit(FilterEvent::CountersWidened { bits_per_counter });
+    }
+
+    /// Keep the keys whose estimates reach `threshold`, up to `capacity` of them, so the heaviest
+    /// can be listed with [CountingBloomFilter::heavy_hitters], which the counters alone can't
+    /// do as they never store keys.  When it's full, a key crossing the threshold replaces the
+    /// lightest tracked key if it's heavier.
+    ///
+    /// Keys are tracked by the methods taking an entry, not by those taking a hash or
+    /// [CountingBloomFilter::entry], and aren't serialized.  Fails unless `threshold` and
+    /// `capacity` are > 0.
+    pub fn with_heavy_hitters(mut self, threshold: usize, capacity: usize) -> Result<Self, Error> {
+        if threshold == 0 || capacity == 0 {
+            return Err(Error::InvalidHeavyHitters(threshold, capacity));
+        }
+        self.heavy_hitters = Some(Box::new(HeavyHitters::new(threshold, capacity)));
+        Ok(self)
+    }
+
+    /// The keys tracked by [CountingBloomFilter::with_heavy_hitters] and their estimates,
+    /// heaviest first, or nothing if they aren't tracked.
+    pub fn heavy_hitters(&self) -> Vec<(&[u8], usize)> {
+        self.heavy_hitters
+            .as_deref()
+            .map(HeavyHitters::sorted)
+            .unwrap_or_default()
+    }
+
+    // Update the heavy hitters with the estimate of `entry`, after adding or removing it.
+    fn track(&mut self, entry: &[u8], hashed: &HashedKey<T>) {
+        if self.heavy_hitters.is_none() {
+            return;
+        }
+        let count = self.estimate_prehashed(hashed);
+        if let Some(heavy_hitters) = &mut self.heavy_hitters {
+            heavy_hitters.update(entry, count);
+        }
+    }
+
+    /// Call `hook` with [FilterEvent::CounterSaturated] when an add is refused, so saturation
+    /// can be noticed before estimates drift, and with [FilterEvent::CountersWidened] when
+    /// counters widen.  Clones of the filter share the hook, and it isn't serialized.
+    pub fn with_event_hook<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(FilterEvent) + Send + Sync + 'static,
+    {
+        self.hook = EventHook::new(hook);
+        self
+    }
+
+    fn nonzero_counters(&self) -> usize {
+        self.all_counter_values().filter(|&v| v != 0).count()
+    }
+
+    // The value of every counter, including those padding the last bin.
+    fn all_counter_values(&self) -> impl Iterator<Item = u64> + '_ {
+        let bits = self.bits_per_counter as usize;
+        self.counter_bins.iter().flat_map(move |&bin| {
+            (0..self.counters_per_bin as usize)
+                .map(move |slot| (bin >> (slot * bits)) & self.counter_max)
         })
     }
 
-    fn offsets(&self, hash: &usize) -> (usize, usize, usize) {
+    /// Statistics on how full the filter is, and how often adds were refused, for dashboards
+    /// and tuning.  This reads every counter.
+    pub fn stats(&self) -> CountingBloomStats {
+        let last = self.counter_max.min(MAX_HISTOGRAM_LEN - 1);
+        let mut histogram = vec![0; last as usize + 1];
+        for value in self.all_counter_values() {
+            histogram[value.min(last) as usize] += 1;
+        }
+        let fill_ratio = 1.0 - histogram[0] as f64 / self.num_counters() as f64;
+        CountingBloomStats {
+            inserted: self.inserted,
+            saturation_events: self.saturation_events,
+            histogram,
+            fill_ratio,
+            estimated_fpp: fill_ratio.powf(self.n_hashes as f64),
+        }
+    }
+
+    fn counter(&self, index: u64) -> Counter {
         // layout of counters
         // --------------- bin 0 ----------------- | --------------- bin 1 -----------------
-        // 7    6    5    4    3    2    1    0    | 7    6    5    4    3    2    1    0
+        // 7    6    5    4    3    2    1    0    | 15   14   13   12   11   10   9    8
         // 1111 1111 1111 1111 1111 1111 1111 1111 | 1111 1111 1111 1111 1111 1111 1111 1111
         //
-        // example for hash of 11 and default 4 bit counters
-        // bin = hash (11) / counters_per_bin(8) = 1
-        // shift = hash (11) % counters_per_bin(8) = 3 * bits_per_coutner(4) = 12
+        // example for counter 11 and 8 counters per bin
+        // bin = index (11) / counters_per_bin (8) = 1
+        // shift = index (11) % counters_per_bin (8) = 3 * bits_per_counter (4) = 12
         // counter_mask = counter_max_val (15) << shift (12) = 0 1111 0000 0000 0000
-        let bin = hash % self.counter_bins.len();
-        // TODO: we know we are dealing with powers of 2 here, check if faster with bitwise ops
-        let bitshift = (hash % self.counters_per_bin as usize) * self.bits_per_counter as usize;
-        let counter_mask = self.counter_max << bitshift;
-        (bin, bitshift, counter_mask)
+        let counters_per_bin = self.counters_per_bin as u64;
+        let shift = (index % counters_per_bin) as usize * self.bits_per_counter as usize;
+        Counter {
+            bin: (index / counters_per_bin) as usize,
+            shift,
+            mask: self.counter_max << shift,
+        }
+    }
+
+    /// Fold the filter into a smaller, coarser one of `target_counters` counters, for shipping
+    /// to memory constrained nodes.  It finds the same entries, with the false positive
+    /// probability of its size.
+    ///
+    /// Entries map to counters by scaling their hashes to the number of counters, so each
+    /// counter of the result is the sum of the adjacent counters it covers, saturating.  With
+    /// Morris counters, their estimates are summed and encoded again.
+    ///
+    /// `target_counters` must divide the number of counters by a power of two, and be a
+    /// multiple of the counters in a bin (16 for 4 bit counters).
+    pub fn project(&self, target_counters: usize) -> Result<Self, Error> {
+        let num_counters = self.num_counters();
+        if target_counters == 0
+            || !target_counters.is_multiple_of(self.counters_per_bin as usize)
+            || !num_counters.is_multiple_of(target_counters)
+            || !(num_counters / target_counters).is_power_of_two()
+        {
+            return Err(Error::InvalidProjectionTarget(target_counters));
+        }
+        let num_bins = check_counter_params(target_counters, self.n_hashes, self.bits_per_counter)?;
+        let mut projected = Self::with_bins(
+            AlignedWords::new(num_bins),
+            self.n_hashes,
+            self.bits_per_counter,
+        );
+        projected.morris_base = self.morris_base;
+        projected.key = self.key;
+
+        let ratio = num_counters / target_counters;
+        let mut values = self.all_counter_values();
+        for index in 0..target_counters as u64 {
+            let covered = values.by_ref().take(ratio);
+            let sum = match self.morris_base {
+                Some(base) => {
+                    let count = covered
+                        .map(|v| morris::estimate(v as usize, base))
+                        .sum::<f64>();
+                    morris::exponent_for(count, base) as u64
+                }
+                None => covered.fold(0_u64, u64::saturating_add),
+            };
+            let c = projected.counter(index);
+            let bin = &mut projected.counter_bins[c.bin];
+            *bin = c.set(*bin, sum.min(self.counter_max));
+        }
+        Ok(projected)
     }
 
     /// Add an entry to the filter.  An entry can be added repeatedly, and each time
@@ -109,36 +564,275 @@ where
     /// once coutners have reached their max, they will no longer increase.
     ///
     /// This returns true if the entry was added or false if the counter was saturated (hence not added).
+    /// With Morris counters, each counter is only incremented with probability base^-counter.
     pub fn add<I>(&mut self, entry: I) -> bool
     where
         I: AsRef<[u8]>,
     {
-        let mut updates = HashMap::<usize, usize>::new();
-        for mut h in (0..self.n_hashes).map(|seed| T::with_seed(seed)) {
-            h.write(entry.as_ref());
-            let hash = h.finish();
-            let (bin, bitshift, counter_mask) = self.offsets(&(hash as usize));
-            let mut counter = updates.get_mut(&bin).map_or_else(
-                || (counter_mask & self.counter_bins[bin]) >> bitshift,
-                |v| (counter_mask & *v) >> bitshift,
-            );
+        let hashed = self.hash(entry.as_ref());
+        let added = self.add_prehashed(&hashed);
+        self.track(entry.as_ref(), &hashed);
+        added
+    }
 
-            // if saturated, skip update
-            if counter == self.counter_max {
-                return false;
+    /// Add an entry hashed ahead of time, as [CountingBloomFilter::add] does.
+    ///
+    /// Panics if `hashed` was hashed with a different key than this filter's.
+    pub fn add_prehashed(&mut self, hashed: &HashedKey<T>) -> bool {
+        let counters = self.counters(hashed).collect::<Vec<_>>();
+        self.increment(&counters)
+    }
+
+    /// Add an entry by a hash computed by the caller, a u64 or a u128, as for
+    /// [BloomFilter::add_hashed].
+    ///
+    /// Panics if the filter is keyed.
+    pub fn add_hashed<H>(&mut self, hash: H) -> bool
+    where
+        H: Into<HashedKey<T>>,
+    {
+        self.add_prehashed(&hash.into())
+    }
+
+    /// The entry `entry`, hashed once for any number of reads and updates through it.
+    pub fn entry<I>(&mut self, entry: I) -> CountingBloomEntry<'_, T>
+    where
+        I: AsRef<[u8]>,
+    {
+        let hashed = self.hash(entry.as_ref());
+        CountingBloomEntry::new(self, hashed)
+    }
+
+    /// Add an entry as [CountingBloomFilter::add] does, returning the estimate of its count from
+    /// before it was added, with one hashing pass.  An estimate of 0 means the entry is new.
+    ///
+    /// The entry isn't added if one of its counters is saturated, so the estimate doesn't move.
+    pub fn add_and_estimate<I>(&mut self, entry: I) -> usize
+    where
+        I: AsRef<[u8]>,
+    {
+        let hashed = self.hash(entry.as_ref());
+        let counters = self.counters(&hashed).collect::<Vec<_>>();
+        let prior = counters
+            .iter()
+            .map(|c| c.get(self.counter_bins[c.bin]))
+            .min()
+            .unwrap_or_default();
+        self.increment(&counters);
+        self.track(entry.as_ref(), &hashed);
+        self.decode(prior)
+    }
+
+    /// Add an entry `n` times in one pass, for aggregating counts gathered elsewhere rather than
+    /// adding an entry in a loop.
+    ///
+    /// Unlike [CountingBloomFilter::add], counters saturate at their maximum rather than refusing
+    /// the add.  Returns false if any counter was capped, so the entry is undercounted.  With
+    /// Morris counters, each counter's estimate grows by `n` and is encoded as the nearest
+    /// exponent.
+    pub fn add_n<I>(&mut self, entry: I, n: u64) -> bool
+    where
+        I: AsRef<[u8]>,
+    {
+        let hashed = self.hash(entry.as_ref());
+        let counters = self.counters(&hashed).collect::<Vec<_>>();
+        let (counter_max, morris_base) = (self.counter_max, self.morris_base);
+        let (mut capped, mut saturating) = (false, 0);
+        self.update(&counters, |counter| {
+            let wanted = match morris_base {
+                Some(base) => {
+                    let count = morris::estimate(counter as usize, base) + n as f64;
+                    morris::exponent_for(count, base) as u64
+                }
+                None => counter.saturating_add(n),
+            };
+            let next = wanted.min(counter_max);
+            capped |= wanted > counter_max;
+            saturating += usize::from(next == counter_max && counter != counter_max);
+            Some(next)
+        });
+        self.inserted += n;
+        if capped {
+            self.saturation_events += 1;
+            self.hook.emit(FilterEvent::CounterSaturated);
+        }
+        if self.widen_at.is_some() {
+            self.saturated_counters += saturating;
+            self.widen_if_saturated();
+        }
+        self.track(entry.as_ref(), &hashed);
+        !capped
+    }
+
+    /// Remove an entry `n` times in one pass, the inverse of [CountingBloomFilter::add_n].
+    ///
+    /// As [CountingBloomFilter::remove] does, this changes nothing and returns false if the entry
+    /// isn't found.  Counters stop at 0 rather than wrapping.
+    pub fn remove_n<I>(&mut self, entry: I, n: u64) -> bool
+    where
+        I: AsRef<[u8]>,
+    {
+        let hashed = self.hash(entry.as_ref());
+        let counters = self.counters(&hashed).collect::<Vec<_>>();
+        let removed = self.decrement_by(&counters, n);
+        self.track(entry.as_ref(), &hashed);
+        removed
+    }
+
+    /// Remove every count of an entry, decrementing its counters by its estimate in one pass,
+    /// to purge a known hot key.  Returns the count removed, 0 if the entry wasn't found.
+    ///
+    /// Counters shared with other entries keep their counts.
+    pub fn remove_all<I>(&mut self, entry: I) -> usize
+    where
+        I: AsRef<[u8]>,
+    {
+        let hashed = self.hash(entry.as_ref());
+        let counters = self.counters(&hashed).collect::<Vec<_>>();
+        let min = counters
+            .iter()
+            .map(|c| c.get(self.counter_bins[c.bin]))
+            .min()
+            .unwrap_or_default();
+        let estimate = self.decode(min);
+        let removed = min > 0 && self.decrement_by(&counters, estimate as u64);
+        self.track(entry.as_ref(), &hashed);
+        match removed {
+            true => estimate,
+            false => 0,
+        }
+    }
+
+    /// Subtract `factor` times the counts of `other` from this filter, counter by counter, for
+    /// maintaining "recent minus baseline" profiles such as an exponentially smoothed baseline.
+    /// Each counter drops by `factor` times the other's count, rounded, and stops at 0.  With
+    /// Morris counters, their estimates are subtracted and encoded again.
+    ///
+    /// Tracked heavy hitters are estimated again.  Returns [Error::IncompatibleFilter] unless
+    /// both filters have the same parameters and key, and [Error::InvalidScaleFactor] unless
+    /// `factor` is finite and >= 0.
+    pub fn subtract_scaled(&mut self, other: &Self, factor: f64) -> Result<(), Error> {
+        if !factor.is_finite() || factor < 0.0 {
+            return Err(Error::InvalidScaleFactor(factor));
+        }
+        if self.counter_bins.len() != other.counter_bins.len()
+            || self.bits_per_counter != other.bits_per_counter
+            || self.n_hashes != other.n_hashes
+            || self.morris_base.map(f64::to_bits) != other.morris_base.map(f64::to_bits)
+            || self.key != other.key
+        {
+            return Err(Error::IncompatibleFilter);
+        }
+        let mut unsaturating = 0;
+        for (index, theirs) in other.all_counter_values().enumerate() {
+            if theirs == 0 {
+                continue;
             }
-            counter += 1;
-            updates
-                .entry(bin)
-                .and_modify(|v| *v = (*v & !counter_mask) | (counter << bitshift))
-                .or_insert((self.counter_bins[bin] & !counter_mask) | (counter << bitshift));
+            let c = self.counter(index as u64);
+            let ours = c.get(self.counter_bins[c.bin]);
+            let next = match self.morris_base {
+                Some(base) => {
+                    let count = morris::estimate(ours as usize, base)
+                        - factor * morris::estimate(theirs as usize, base);
+                    morris::exponent_for(count.max(0.0), base) as u64
+                }
+                None => ours.saturating_sub((factor * theirs as f64).round() as u64),
+            };
+            unsaturating += usize::from(ours == self.counter_max && next != ours);
+            self.counter_bins[c.bin] = c.set(self.counter_bins[c.bin], next);
         }
+        if self.widen_at.is_some() {
+            self.saturated_counters = self.saturated_counters.saturating_sub(unsaturating);
+        }
+        if let Some(tracked) = &self.heavy_hitters {
+            let keys = tracked
+                .sorted()
+                .into_iter()
+                .map(|(key, _)| key.to_vec())
+                .collect::<Vec<_>>();
+            for key in keys {
+                let hashed = self.hash(&key);
+                self.track(&key, &hashed);
+            }
+        }
+        Ok(())
+    }
 
-        // update with new values
-        for (bin, new_val) in updates {
-            self.counter_bins[bin] = new_val;
+    // Decrement `counters` by `n` in one pass, as [CountingBloomFilter::remove_n] does.
+    fn decrement_by(&mut self, counters: &[Counter], n: u64) -> bool {
+        let (counter_max, morris_base) = (self.counter_max, self.morris_base);
+        let mut unsaturating = 0;
+        let removed = self.update(counters, |counter| {
+            if counter == 0 {
+                return None;
+            }
+            let next = match morris_base {
+                Some(base) => {
+                    let count = (morris::estimate(counter as usize, base) - n as f64).max(0.0);
+                    morris::exponent_for(count, base) as u64
+                }
+                None => counter.saturating_sub(n),
+            };
+            unsaturating += usize::from(counter == counter_max && next != counter);
+            Some(next)
+        });
+        if removed && self.widen_at.is_some() {
+            self.saturated_counters = self.saturated_counters.saturating_sub(unsaturating);
         }
-        true
+        removed
+    }
+
+    /// Add an entry only if the filter doesn't already contain it, hashing the entry once for
+    /// both the check and the insert.
+    ///
+    /// Returns true if the entry was added, or false if it was already present (subject to the
+    /// false positive probability) or a counter was saturated.
+    pub fn insert_if_absent<I>(&mut self, entry: I) -> bool
+    where
+        I: AsRef<[u8]>,
+    {
+        let hashed = self.hash(entry.as_ref());
+        let counters = self.counters(&hashed).collect::<Vec<_>>();
+        let present = counters
+            .iter()
+            .all(|&c| c.get(self.counter_bins[c.bin]) > 0);
+        let added = !present && self.increment(&counters);
+        self.track(entry.as_ref(), &hashed);
+        added
+    }
+
+    fn increment(&mut self, counters: &[Counter]) -> bool {
+        let draw = self.morris_draw();
+        let (counter_max, morris_base) = (self.counter_max, self.morris_base);
+        let mut saturating = 0;
+        let added = self.update(counters, |counter| {
+            // if saturated, skip update
+            if counter == counter_max {
+                return None;
+            }
+            let next = match morris_base {
+                Some(base) if draw >= morris::increment_probability(counter as usize, base) => {
+                    counter
+                }
+                _ => counter + 1,
+            };
+            saturating += usize::from(next == counter_max);
+            Some(next)
+        });
+        match added {
+            true => {
+                self.inserted += 1;
+                if self.widen_at.is_some() {
+                    self.saturated_counters += saturating;
+                    self.widen_if_saturated();
+                }
+            }
+            false => {
+                self.saturation_events += 1;
+                self.hook.emit(FilterEvent::CounterSaturated);
+            }
+        }
+        added
     }
 
     /// Remove an entry from the filter.
@@ -149,34 +843,67 @@ where
     /// these checks are subject to the false positive probability.
     /// This method uses a saturating subtraction, so counters do not wrap.
     ///
+    /// With Morris counters, each counter is only decremented with probability base^-(counter - 1).
+    ///
     /// This method returns false if the entry was not found (hence not removed), or true if it was.
     pub fn remove<I>(&mut self, entry: I) -> bool
     where
         I: AsRef<[u8]>,
     {
-        let mut updates = HashMap::<usize, usize>::new();
-        for seed in 0..self.n_hashes {
-            let mut h = T::with_seed(seed);
-            h.write(entry.as_ref());
-            let hash = h.finish();
-            let (bin, bitshift, counter_mask) = self.offsets(&(hash as usize));
-            let mut counter = updates.get_mut(&bin).map_or_else(
-                || (counter_mask & self.counter_bins[bin]) >> bitshift,
-                |v| (counter_mask & *v) >> bitshift,
-            );
+        let hashed = self.hash(entry.as_ref());
+        let removed = self.remove_prehashed(&hashed);
+        self.track(entry.as_ref(), &hashed);
+        removed
+    }
 
+    /// Remove an entry hashed ahead of time, as [CountingBloomFilter::remove] does.
+    ///
+    /// Panics if `hashed` was hashed with a different key than this filter's.
+    pub fn remove_prehashed(&mut self, hashed: &HashedKey<T>) -> bool {
+        let counters = self.counters(hashed).collect::<Vec<_>>();
+        self.decrement(&counters)
+    }
+
+    fn decrement(&mut self, counters: &[Counter]) -> bool {
+        let draw = self.morris_draw();
+        let (counter_max, morris_base) = (self.counter_max, self.morris_base);
+        let mut unsaturating = 0;
+        let removed = self.update(counters, |counter| {
+            // one of the counters is 0, which means this key doesn't exist
             if counter == 0 {
-                // one of the counters is 0, which means this key doesn't exist
-                return false;
+                return None;
             }
+            let next = match morris_base {
+                Some(base) if draw >= morris::decrement_probability(counter as usize, base) => {
+                    counter
+                }
+                _ => counter - 1,
+            };
+            unsaturating += usize::from(counter == counter_max && next != counter);
+            Some(next)
+        });
+        if removed && self.widen_at.is_some() {
+            self.saturated_counters = self.saturated_counters.saturating_sub(unsaturating);
+        }
+        removed
+    }
 
-            counter -= 1;
-            updates
-                .entry(bin)
-                .and_modify(|v| *v = dbg!((*v & !counter_mask) | (counter << bitshift)))
-                .or_insert(dbg!(
-                    (self.counter_bins[bin] & !counter_mask) | (counter << bitshift)
-                ));
+    // Set each of `counters` to `step` of its value, or change nothing if `step` returns None
+    // for any of them.  A counter an entry maps to twice is stepped twice.
+    fn update<F>(&mut self, counters: &[Counter], mut step: F) -> bool
+    where
+        F: FnMut(u64) -> Option<u64>,
+    {
+        let mut updates = HashMap::<usize, u64>::new();
+        for &c in counters {
+            let bin = updates
+                .get(&c.bin)
+                .copied()
+                .unwrap_or(self.counter_bins[c.bin]);
+            match step(c.get(bin)) {
+                Some(counter) => updates.insert(c.bin, c.set(bin, counter)),
+                None => return false,
+            };
         }
 
         // update with new values
@@ -191,7 +918,52 @@ where
     where
         I: AsRef<[u8]>,
     {
-        self.iterator_over_hashes(entry.as_ref()).all(|v| v > 0)
+        self.contains_prehashed(&self.hash(entry.as_ref()))
+    }
+
+    /// Determine if filter contains an entry hashed ahead of time.
+    ///
+    /// Panics if `hashed` was hashed with a different key than this filter's.
+    pub fn contains_prehashed(&self, hashed: &HashedKey<T>) -> bool {
+        self.counter_values(hashed).all(|v| v > 0)
+    }
+
+    /// Determine if filter contains an entry by a hash computed by the caller, as for
+    /// [BloomFilter::add_hashed].
+    pub fn contains_hashed<H>(&self, hash: H) -> bool
+    where
+        H: Into<HashedKey<T>>,
+    {
+        self.contains_prehashed(&hash.into())
+    }
+
+    /// Determine which of `entries` the filter contains, prefetching counters ahead of each
+    /// lookup as [BloomFilter::contains_batch] does.
+    pub fn contains_batch<I>(&self, entries: &[I]) -> Vec<bool>
+    where
+        I: AsRef<[u8]>,
+    {
+        let hashed = entries
+            .iter()
+            .map(|entry| self.hash(entry.as_ref()))
+            .collect::<Vec<_>>();
+        prefetch::pipeline(
+            &hashed,
+            |hashed| {
+                for c in self.counters(hashed) {
+                    prefetch::prefetch(&self.counter_bins[c.bin]);
+                }
+            },
+            |hashed| self.contains_prehashed(hashed),
+        )
+    }
+
+    /// Probe the filter with `trials` random entries, as [BloomFilter::measure_fpp] does.
+    pub fn measure_fpp<R>(&self, rng: &mut R, trials: u64) -> FppMeasurement
+    where
+        R: RngCore + ?Sized,
+    {
+        measure::measure_fpp(rng, trials, |probe| self.contains(probe))
     }
 
     /// Returns an estimate of the number of time entry exists in the filter.
@@ -199,33 +971,392 @@ where
     /// The estimate is determined as the minimum of counters for bins associated with this key.
     /// Counters support a maximum value of 255. If a key is added more than 255 times, it will
     /// increase the error rate of this filter and the estimate.  This estimate is also subject
-    /// to the false positive probability
+    /// to the false positive probability.  With Morris counters, the minimum counter is decoded
+    /// into an approximate count.
     pub fn estimate<I>(&self, entry: I) -> usize
     where
         I: AsRef<[u8]>,
     {
-        self.iterator_over_hashes(entry.as_ref())
-            .min()
-            .unwrap_or_default()
+        self.estimate_prehashed(&self.hash(entry.as_ref()))
     }
 
-    fn iterator_over_hashes<'a, 'b: 'a>(
-        &'a self,
-        key: &'b [u8],
-    ) -> impl Iterator<Item = usize> + 'a {
-        (0..self.n_hashes).map(|seed| {
-            let mut h = T::with_seed(seed);
-            h.write(key);
-            let hash = h.finish();
-            let (bin, bitshift, counter_mask) = self.offsets(&(hash as usize));
-            (counter_mask & self.counter_bins[bin]) >> bitshift
-        })
+    /// Estimate the count of an entry hashed ahead of time, as [CountingBloomFilter::estimate]
+    /// does.
+    ///
+    /// Panics if `hashed` was hashed with a different key than this filter's.
+    pub fn estimate_prehashed(&self, hashed: &HashedKey<T>) -> usize {
+        self.decode(self.counter_values(hashed).min().unwrap_or_default())
+    }
+
+    /// The largest of an entry's counters, decoded as [CountingBloomFilter::estimate] decodes
+    /// the smallest.  It overcounts by every collision on its counter, so the gap between the
+    /// two shows how crowded the entry's counters are.
+    pub fn estimate_max<I>(&self, entry: I) -> usize
+    where
+        I: AsRef<[u8]>,
+    {
+        let hashed = self.hash(entry.as_ref());
+        self.decode(self.counter_values(&hashed).max().unwrap_or_default())
+    }
+
+    /// Bounds `(lower, upper)` on how many times an entry has been added.
+    ///
+    /// The upper bound is [CountingBloomFilter::estimate], as other entries only add to its
+    /// counters, or [usize::MAX] once its smallest counter has saturated.  The lower bound holds
+    /// with 99% probability when entries hash independently: the collisions on a counter are
+    /// Poisson distributed, with the filter's mean counter value as their mean, and the
+    /// smallest of the entry's counters only holds more than the 99% quantile if all of them
+    /// do.  This reads every counter.  Removing entries that were never added breaks both
+    /// bounds, and Morris counters add their own counting error.
+    pub fn estimate_bounds<I>(&self, entry: I) -> (usize, usize)
+    where
+        I: AsRef<[u8]>,
+    {
+        let hashed = self.hash(entry.as_ref());
+        let min = self.counter_values(&hashed).min().unwrap_or_default();
+        let upper = self.decode(min);
+        let total = self
+            .all_counter_values()
+            .map(|v| self.decode(v) as f64)
+            .sum::<f64>();
+        let collisions = collision_quantile(total / self.num_counters() as f64, self.n_hashes);
+        let lower = upper.saturating_sub(collisions);
+        match self.morris_base.is_none() && min == self.counter_max {
+            true => (lower, usize::MAX),
+            false => (lower, upper),
+        }
+    }
+
+    // The count a counter's value stands for.
+    fn decode(&self, counter: u64) -> usize {
+        match self.morris_base {
+            Some(base) => morris::estimate(counter as usize, base).round() as usize,
+            None => usize::try_from(counter).unwrap_or(usize::MAX),
+        }
+    }
+
+    /// Bytes used by the filter, its own size plus the heap allocated for its counters, including
+    /// spare capacity.
+    pub fn mem_usage(&self) -> usize {
+        mem::size_of::<Self>() + self.counter_bins.heap_size()
+    }
+
+    /// Serialize the filter.
+    ///
+    /// The format is the magic number `PFCB`, a version byte, the number of bins as a u64, the
+    /// number of hashes and bits per counter as u32s, the Morris base as an f64 (NaN if unused)
+    /// and the key and hash scheme as for [BloomFilter::to_bytes], followed by the counter bins
+    /// as u64 words and the CRC32C of everything before it, all little endian, so it loads on
+    /// any platform.  The hasher isn't recorded, so the filter must be deserialized with the
+    /// hasher it was built with.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_LEN + self.counter_bins.len() * 8 + CHECKSUM_LEN);
+        self.write_to(&mut bytes)
+            .expect("writing to a Vec can't fail");
+        bytes
+    }
+
+    /// A 128 bit digest of the filter's parameters and contents, so replicas can cheaply check
+    /// they hold identical filters.  Filters with the same serialized form have the same
+    /// digest on every platform; the hasher isn't part of it.
+    pub fn digest(&self) -> u128 {
+        format::digest(&self.to_bytes())
+    }
+
+    /// Stream the filter in the format of [CountingBloomFilter::to_bytes], a chunk at a time,
+    /// so a large filter can be written without holding a serialized copy in memory.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all, fields(num_counters = self.num_counters(), num_hashes = self.n_hashes), err))]
+    pub fn write_to<W>(&self, writer: W) -> io::Result<()>
+    where
+        W: Write,
+    {
+        let mut writer = ChecksumWriter::new(writer);
+        writer.write_all(&MAGIC)?;
+        writer.write_all(&[FORMAT_VERSION])?;
+        writer.write_all(&(self.counter_bins.len() as u64).to_le_bytes())?;
+        writer.write_all(&self.n_hashes.to_le_bytes())?;
+        writer.write_all(&self.bits_per_counter.to_le_bytes())?;
+        writer.write_all(&self.morris_base.unwrap_or(f64::NAN).to_le_bytes())?;
+        writer.write_all(&index::key_to_bytes(self.key.as_ref()))?;
+        writer.write_all(&[HashSchemeVersion::CURRENT.as_u8()])?;
+        stream::write_words(&mut writer, self.counter_bins.iter().copied())?;
+        writer.finish()
+    }
+
+    /// Deserialize a filter produced by [CountingBloomFilter::to_bytes], with the same errors
+    /// as [BloomFilter::from_bytes].
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all, fields(len = bytes.len()), err))]
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        format::check_header(bytes, &MAGIC, FORMAT_VERSION)?;
+        if bytes.len() < HEADER_LEN {
+            return Err(Error::Truncated);
+        }
+        // check the length and checksum before allocating anything
+        let num_bins = u64::from_le_bytes(bytes[5..13].try_into().unwrap());
+        let len = num_bins
+            .saturating_mul(8)
+            .saturating_add((HEADER_LEN + CHECKSUM_LEN) as u64);
+        format::check_body(bytes, len)?;
+        Self::read_unchecked(bytes)
+    }
+
+    /// Serialize the filter as [CountingBloomFilter::to_bytes] does, compressed with zstd.
+    /// Mostly empty counters compress several times over.
+    #[cfg(feature = "zstd")]
+    pub fn to_bytes_compressed(&self) -> Vec<u8> {
+        format::compress(|encoder| self.write_to(encoder))
+    }
+
+    /// Deserialize a filter produced by either [CountingBloomFilter::to_bytes] or
+    /// [CountingBloomFilter::to_bytes_compressed].
+    #[cfg(feature = "zstd")]
+    pub fn from_bytes_auto(bytes: &[u8]) -> Result<Self, Error> {
+        match format::is_compressed(bytes) {
+            true => format::read_compressed(bytes, |reader| Self::read_from(reader)),
+            false => Self::from_bytes(bytes),
+        }
+    }
+
+    /// Read a filter written by [CountingBloomFilter::write_to], a chunk at a time.  Reading
+    /// stops at the end of the filter, so it may be followed by other data.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, err)
+    )]
+    pub fn read_from<R>(reader: R) -> Result<Self, Error>
+    where
+        R: Read,
+    {
+        let mut reader = ChecksumReader::new(reader);
+        let cbf = Self::read_unchecked(&mut reader)?;
+        reader.finish::<Error>()?;
+        Ok(cbf)
+    }
+
+    // Read the header and bins, leaving the checksum to the caller.
+    fn read_unchecked<R>(mut reader: R) -> Result<Self, Error>
+    where
+        R: Read,
+    {
+        let header: [u8; HEADER_LEN] = stream::read_array(&mut reader)?;
+        format::check_header(&header, &MAGIC, FORMAT_VERSION)?;
+        let num_bins = u64::from_le_bytes(header[5..13].try_into().unwrap());
+        let num_hashes = u32::from_le_bytes(header[13..17].try_into().unwrap());
+        let bits_per_counter = u32::from_le_bytes(header[17..21].try_into().unwrap());
+        let morris_base = f64::from_le_bytes(header[21..29].try_into().unwrap());
+        let key = index::key_from_bytes(&header[29..])?;
+        check_hash_scheme(header[HEADER_LEN - 1])?;
+        if bits_per_counter == 0 || !(morris_base.is_nan() || morris_base > 1.0) {
+            return Err(Error::Malformed);
+        }
+
+        let num_counters = usize::try_from(num_bins)
+            .ok()
+            .and_then(|bins| bins.checked_mul((u64::BITS / bits_per_counter) as usize))
+            .ok_or(Error::Malformed)?;
+        let num_bins = check_counter_params(num_counters, num_hashes, bits_per_counter)?;
+        // the header is only checksummed once the bins are read, so don't trust it to abort
+        let mut cbf = Self::with_bins(
+            AlignedWords::try_new(num_bins)?,
+            num_hashes,
+            bits_per_counter,
+        );
+        cbf.morris_base = (!morris_base.is_nan()).then_some(morris_base);
+        cbf.key = key;
+        let mut bins = cbf.counter_bins.iter_mut();
+        stream::read_words(&mut reader, bins.len(), |word| {
+            *bins.next().unwrap() = word;
+        })?;
+        Ok(cbf)
+    }
+
+    // A single draw is shared by all counters of an entry, so they move together.
+    fn morris_draw(&self) -> f64 {
+        match self.morris_base {
+            Some(_) => thread_rng().gen(),
+            None => 0.0,
+        }
+    }
+
+    fn hash(&self, entry: &[u8]) -> HashedKey<T> {
+        HashedKey::hash(entry, self.key.as_ref())
+    }
+
+    // The counters of `hashed`.  Every operation on an entry goes through here, so they all
+    // agree on where its counters are.
+    fn counters<'a>(&'a self, hashed: &HashedKey<T>) -> impl Iterator<Item = Counter> + 'a {
+        hashed
+            .indexes(self.key.as_ref(), self.n_hashes, self.num_counters() as u64)
+            .map(|index| self.counter(index))
+    }
+
+    fn counter_values<'a>(&'a self, hashed: &HashedKey<T>) -> impl Iterator<Item = u64> + 'a {
+        self.counters(hashed)
+            .map(|c| c.get(self.counter_bins[c.bin]))
+    }
+}
+
+/// Where a counter is: its bin, and the shift and mask selecting it within the bin.
+#[derive(Clone, Copy)]
+struct Counter {
+    bin: usize,
+    shift: usize,
+    mask: u64,
+}
+
+impl Counter {
+    fn get(self, bin: u64) -> u64 {
+        (bin & self.mask) >> self.shift
+    }
+
+    fn set(self, bin: u64, counter: u64) -> u64 {
+        (bin & !self.mask) | (counter << self.shift)
+    }
+}
+
+impl<T> fmt::Debug for CountingBloomFilter<T>
+where
+    T: SeedableHasher,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CountingBloomFilter")
+            .field("hasher", &any::type_name::<T>())
+            .field("num_counters", &self.num_counters())
+            .field("bits_per_counter", &self.bits_per_counter)
+            .field("num_hashes", &self.n_hashes)
+            .field("morris_base", &self.morris_base)
+            .field("keyed", &self.is_keyed())
+            .field("nonzero_counters", &self.nonzero_counters())
+            .finish()
+    }
+}
+
+/// A one line summary: the number of distinct entries estimated from the nonzero counters, as
+/// for [BloomFilter::estimate_len], the fraction of nonzero counters, and the false positive
+/// probability that implies.
+impl<T> fmt::Display for CountingBloomFilter<T>
+where
+    T: SeedableHasher,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let m = self.num_counters() as f64;
+        let fill = self.nonzero_counters() as f64 / m;
+        let k = self.n_hashes as f64;
+        write!(
+            f,
+            "CountingBloomFilter: items≈{:.0}, fill {:.1}%, fpp≈{:.2e}",
+            (m / k) * (1.0 / (1.0 - fill)).ln(),
+            fill * 100.0,
+            fill.powf(k)
+        )
+    }
+}
+
+// derived impls would require T: Clone/PartialEq, which hashers don't implement
+impl<T> Clone for CountingBloomFilter<T>
+where
+    T: SeedableHasher,
+{
+    fn clone(&self) -> Self {
+        CountingBloomFilter {
+            counter_bins: self.counter_bins.clone(),
+            counter_max: self.counter_max,
+            counters_per_bin: self.counters_per_bin,
+            bits_per_counter: self.bits_per_counter,
+            n_hashes: self.n_hashes,
+            morris_base: self.morris_base,
+            key: self.key,
+            inserted: self.inserted,
+            saturation_events: self.saturation_events,
+            saturated_counters: self.saturated_counters,
+            widen_at: self.widen_at,
+            heavy_hitters: self.heavy_hitters.clone(),
+            hook: self.hook.clone(),
+            _hasher: PhantomData,
+        }
+    }
+}
+
+/// Filters are equal if they have the same parameters and the same counters.
+impl<T> PartialEq for CountingBloomFilter<T>
+where
+    T: SeedableHasher,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.bits_per_counter == other.bits_per_counter
+            && self.n_hashes == other.n_hashes
+            && self.morris_base.map(f64::to_bits) == other.morris_base.map(f64::to_bits)
+            && self.key == other.key
+            && self.counter_bins == other.counter_bins
+    }
+}
+
+impl<T> Eq for CountingBloomFilter<T> where T: SeedableHasher {}
+
+/// Number of bins and hashes that hold `expected_entries` with a false positive probability of
+/// `fpp`, for one bit or counter per position.
+fn optimal_params(expected_entries: usize, fpp: f64) -> Result<(usize, u32), Error> {
+    let invalid = |err| match err {
+        sizing::Error::InvalidFpp(fpp) => Error::InvalidFpp(fpp),
+        _ => Error::InvalidCapacity(expected_entries),
+    };
+    let num_bins = sizing::optimal_bits(expected_entries, fpp).map_err(invalid)?;
+    let num_hashes = sizing::optimal_hashes(num_bins, expected_entries).map_err(invalid)?;
+    Ok((num_bins, num_hashes))
+}
+
+// The most collisions the smallest of `k` counters holds, with probability BOUNDS_CONFIDENCE,
+// when collisions on each counter are Poisson distributed with mean `mean`.  The smallest holds
+// more than q only if all k do, so each may exceed q with probability (1 - confidence)^(1/k).
+fn collision_quantile(mean: f64, k: u32) -> usize {
+    let tail = (1.0 - BOUNDS_CONFIDENCE).powf(1.0 / k.max(1) as f64);
+    let mut pmf = (-mean).exp();
+    if pmf == 0.0 {
+        // too many to sum term by term, so use Bernstein's bound on the Poisson tail
+        let l = -tail.ln();
+        return (mean + l / 3.0 + (l * l / 9.0 + 2.0 * l * mean).sqrt()).ceil() as usize;
+    }
+    let (mut q, mut cdf) = (0, pmf);
+    while 1.0 - cdf > tail {
+        q += 1;
+        pmf *= mean / q as f64;
+        cdf += pmf;
+    }
+    q
+}
+
+// Serialized filters must have been hashed with a scheme this release implements.
+fn check_hash_scheme(byte: u8) -> Result<(), Error> {
+    match HashSchemeVersion::from_u8(byte) {
+        Some(_) => Ok(()),
+        None => Err(Error::UnsupportedHashScheme(byte)),
+    }
+}
+
+// Check the parameters of a counting bloom filter, returning the number of u64 bins needed.
+fn check_counter_params(
+    num_counters: usize,
+    num_hashes: u32,
+    bits_per_counter: u32,
+) -> Result<usize, Error> {
+    if bits_per_counter > u64::BITS {
+        return Err(Error::BitsPerCounterTooLarge(bits_per_counter, u64::BITS));
+    }
+    if bits_per_counter == 0 || u64::BITS % bits_per_counter != 0 {
+        return Err(Error::BitsPerCounterUnaligned(bits_per_counter, u64::BITS));
+    }
+    if num_counters == 0 {
+        return Err(Error::InvalidBinCount(num_counters));
+    }
+    if num_hashes == 0 || num_hashes as usize > num_counters {
+        return Err(Error::InvalidHashCount(num_hashes));
     }
+    Ok(num_counters.div_ceil((u64::BITS / bits_per_counter) as usize))
 }
 
-fn calc_max_counter(n_bits: &u32) -> usize {
+fn calc_max_counter(n_bits: &u32) -> u64 {
     match n_bits {
-        &usize::BITS => !0_usize,
-        _ => 2_usize.pow(*n_bits) - 1,
+        &u64::BITS => !0_u64,
+        _ => 2_u64.pow(*n_bits) - 1,
     }
 }
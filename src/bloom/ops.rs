@@ -0,0 +1,82 @@
+//! Set algebra operators for [BloomFilter].  Operators can't return an error, so they panic if
+//! the filters were created with different parameters, use [BloomFilter::union],
+//! [BloomFilter::intersect] and [BloomFilter::difference] to handle that instead.
+use std::ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign, Sub, SubAssign};
+
+use super::{BloomFilter, Word};
+use crate::hasher::SeedableHasher;
+
+const INCOMPATIBLE: &str = "can only combine bloom filters with the same number of bits and hashes";
+
+impl<T, W> BitOrAssign<&BloomFilter<T, W>> for BloomFilter<T, W>
+where
+    T: SeedableHasher,
+    W: Word,
+{
+    fn bitor_assign(&mut self, other: &BloomFilter<T, W>) {
+        self.union(other).expect(INCOMPATIBLE)
+    }
+}
+
+impl<T, W> BitOr for &BloomFilter<T, W>
+where
+    T: SeedableHasher,
+    W: Word,
+{
+    type Output = BloomFilter<T, W>;
+
+    fn bitor(self, other: Self) -> BloomFilter<T, W> {
+        let mut union = self.clone();
+        union |= other;
+        union
+    }
+}
+
+impl<T, W> BitAndAssign<&BloomFilter<T, W>> for BloomFilter<T, W>
+where
+    T: SeedableHasher,
+    W: Word,
+{
+    fn bitand_assign(&mut self, other: &BloomFilter<T, W>) {
+        self.intersect(other).expect(INCOMPATIBLE)
+    }
+}
+
+impl<T, W> BitAnd for &BloomFilter<T, W>
+where
+    T: SeedableHasher,
+    W: Word,
+{
+    type Output = BloomFilter<T, W>;
+
+    fn bitand(self, other: Self) -> BloomFilter<T, W> {
+        let mut intersection = self.clone();
+        intersection &= other;
+        intersection
+    }
+}
+
+impl<T, W> SubAssign<&BloomFilter<T, W>> for BloomFilter<T, W>
+where
+    T: SeedableHasher,
+    W: Word,
+{
+    fn sub_assign(&mut self, other: &BloomFilter<T, W>) {
+        self.difference(other).expect(INCOMPATIBLE)
+    }
+}
+
+/// See [BloomFilter::difference] for why the result can have false negatives.
+impl<T, W> Sub for &BloomFilter<T, W>
+where
+    T: SeedableHasher,
+    W: Word,
+{
+    type Output = BloomFilter<T, W>;
+
+    fn sub(self, other: Self) -> BloomFilter<T, W> {
+        let mut difference = self.clone();
+        difference -= other;
+        difference
+    }
+}
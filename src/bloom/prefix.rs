@@ -0,0 +1,115 @@
+use std::mem;
+
+use super::BloomFilter;
+use crate::hasher::SeedableHasher;
+
+/// Extracts the prefix of a key used by [PrefixBloomFilter].
+///
+/// Returning `None` means the key is out of the extractor's domain, and only the whole key
+/// will be added to the filter.
+pub trait PrefixExtractor {
+    fn prefix<'a>(&self, key: &'a [u8]) -> Option<&'a [u8]>;
+}
+
+/// Uses the first `n` bytes of a key as its prefix.  Keys shorter than `n` bytes have no prefix.
+pub struct FixedPrefix(pub usize);
+
+impl PrefixExtractor for FixedPrefix {
+    fn prefix<'a>(&self, key: &'a [u8]) -> Option<&'a [u8]> {
+        key.get(..self.0)
+    }
+}
+
+impl<F> PrefixExtractor for F
+where
+    F: for<'a> Fn(&'a [u8]) -> Option<&'a [u8]>,
+{
+    fn prefix<'a>(&self, key: &'a [u8]) -> Option<&'a [u8]> {
+        self(key)
+    }
+}
+
+/// A bloom filter holding both keys and their prefixes, in the style of RocksDB's prefix bloom.
+///
+/// Scans over a range of keys sharing a prefix can be skipped when
+/// [PrefixBloomFilter::may_contain_prefix] returns false.  Keys and prefixes share a single
+/// filter, so a prefix that happens to equal a whole key will match it.
+pub struct PrefixBloomFilter<T, P>
+where
+    T: SeedableHasher,
+    P: PrefixExtractor,
+{
+    filter: BloomFilter<T>,
+    extractor: P,
+    whole_keys: bool,
+}
+
+impl<T, P> PrefixBloomFilter<T, P>
+where
+    T: SeedableHasher,
+    P: PrefixExtractor,
+{
+    /// Create a new prefix bloom filter, adding both whole keys and prefixes to `filter`.
+    pub fn new(filter: BloomFilter<T>, extractor: P) -> Self {
+        PrefixBloomFilter {
+            filter,
+            extractor,
+            whole_keys: true,
+        }
+    }
+
+    /// Create a new prefix bloom filter which only adds prefixes to `filter`.  Point lookups
+    /// with [PrefixBloomFilter::contains] fall back to checking the key's prefix.
+    pub fn prefix_only(filter: BloomFilter<T>, extractor: P) -> Self {
+        PrefixBloomFilter {
+            filter,
+            extractor,
+            whole_keys: false,
+        }
+    }
+
+    /// Add a key, and its prefix if it has one, to the filter.
+    pub fn add<I>(&mut self, key: I)
+    where
+        I: AsRef<[u8]>,
+    {
+        let key = key.as_ref();
+        if self.whole_keys {
+            self.filter.add(key);
+        }
+        if let Some(prefix) = self.extractor.prefix(key) {
+            self.filter.add(prefix);
+        }
+    }
+
+    /// Determine if filter contains the provided key.
+    pub fn contains<I>(&self, key: I) -> bool
+    where
+        I: AsRef<[u8]>,
+    {
+        let key = key.as_ref();
+        if self.whole_keys {
+            return self.filter.contains(key);
+        }
+        self.may_contain_prefix(key)
+    }
+
+    /// Determine if any key sharing the prefix of `key` may be in the filter.
+    ///
+    /// `key` can be a whole key or the prefix itself.  If `key` is outside the extractor's
+    /// domain nothing can be ruled out, so this returns true.
+    pub fn may_contain_prefix<I>(&self, key: I) -> bool
+    where
+        I: AsRef<[u8]>,
+    {
+        match self.extractor.prefix(key.as_ref()) {
+            Some(prefix) => self.filter.contains(prefix),
+            None => true,
+        }
+    }
+
+    /// Bytes used by the filter, including the bits of the underlying [BloomFilter].
+    pub fn mem_usage(&self) -> usize {
+        mem::size_of::<Self>() - mem::size_of::<BloomFilter<T>>() + self.filter.mem_usage()
+    }
+}
@@ -0,0 +1,106 @@
+use std::mem;
+
+use super::{index::HashedKey, BloomFilter, Word};
+use crate::hasher::SeedableHasher;
+
+/// A [BloomFilter] paired with a second filter of removed entries, for approximate removal
+/// without the memory of a [CountingBloomFilter](super::CountingBloomFilter).
+///
+/// An entry is found if it's in the main filter and not in the removed one.  A false positive
+/// of the removed filter hides an entry that was never removed, so removing can cause false
+/// negatives, and a removed entry stays hidden if it's added again.  Both get worse as entries
+/// are removed, until [RemovalBloomFilter::compact] rebuilds the main filter from the entries
+/// still live and clears the removed one.
+pub struct RemovalBloomFilter<T, W = u64>
+where
+    T: SeedableHasher,
+    W: Word,
+{
+    filter: BloomFilter<T, W>,
+    removed: BloomFilter<T, W>,
+}
+
+impl<T, W> RemovalBloomFilter<T, W>
+where
+    T: SeedableHasher,
+    W: Word,
+{
+    /// Track removals from `filter`, in a filter with the same parameters and key.
+    pub fn new(filter: BloomFilter<T, W>) -> Self {
+        let mut removed = filter.clone();
+        removed.clear();
+        RemovalBloomFilter { filter, removed }
+    }
+
+    /// Add an entry, as [BloomFilter::add] does.
+    pub fn add<I>(&mut self, entry: I) -> bool
+    where
+        I: AsRef<[u8]>,
+    {
+        self.filter.add(entry)
+    }
+
+    /// Remove an entry by adding it to the removed filter.
+    ///
+    /// Returns false, and changes nothing, if the entry isn't found.
+    pub fn remove<I>(&mut self, entry: I) -> bool
+    where
+        I: AsRef<[u8]>,
+    {
+        let hashed = self.hash(entry.as_ref());
+        if !self.contains_prehashed(&hashed) {
+            return false;
+        }
+        self.removed.add_prehashed(&hashed);
+        true
+    }
+
+    /// Determine if the filter contains an entry that hasn't been removed.
+    pub fn contains<I>(&self, entry: I) -> bool
+    where
+        I: AsRef<[u8]>,
+    {
+        self.contains_prehashed(&self.hash(entry.as_ref()))
+    }
+
+    /// Estimate the number of entries removed since the filter was created or compacted, to
+    /// decide when to compact.
+    pub fn estimate_removed_len(&self) -> f64 {
+        self.removed.estimate_len()
+    }
+
+    /// Rebuild the main filter from `live`, the entries that should still be found, and clear
+    /// the removed filter.
+    pub fn compact<I, L>(&mut self, live: L)
+    where
+        I: AsRef<[u8]>,
+        L: IntoIterator<Item = I>,
+    {
+        self.filter.clear();
+        self.removed.clear();
+        for entry in live {
+            self.filter.add(entry);
+        }
+    }
+
+    /// The main filter, which still holds removed entries.
+    pub fn filter(&self) -> &BloomFilter<T, W> {
+        &self.filter
+    }
+
+    /// Bytes used by the filter, including the bits of both underlying filters.
+    pub fn mem_usage(&self) -> usize {
+        mem::size_of::<Self>() - 2 * mem::size_of::<BloomFilter<T, W>>()
+            + self.filter.mem_usage()
+            + self.removed.mem_usage()
+    }
+
+    // both filters have the same key, so an entry is hashed once for both
+    fn hash(&self, entry: &[u8]) -> HashedKey<T> {
+        HashedKey::hash(entry, self.filter.key.as_ref())
+    }
+
+    fn contains_prehashed(&self, hashed: &HashedKey<T>) -> bool {
+        self.filter.contains_prehashed(hashed) && !self.removed.contains_prehashed(hashed)
+    }
+}
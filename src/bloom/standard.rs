@@ -0,0 +1,679 @@
+use rand::RngCore;
+use std::{
+    any, fmt,
+    io::{self, Read, Write},
+    marker::PhantomData,
+    mem, panic, thread,
+};
+
+use super::{
+    check_hash_scheme,
+    index::{self, HashedKey, KEY_LEN, SERIALIZED_KEY_LEN},
+    optimal_params, Error, Word,
+};
+use crate::{
+    format::{self, ChecksumReader, ChecksumWriter, CHECKSUM_LEN, MAGIC_LEN},
+    hasher::{HashSchemeVersion, SeedableHasher},
+    measure::{self, FppMeasurement},
+    mem::{heap_size, try_filled},
+    prefetch, stream,
+};
+
+pub(super) const MAGIC: [u8; MAGIC_LEN] = *b"PFBF";
+pub(super) const FORMAT_VERSION: u8 = 6;
+// magic, version, num_bits, n_hashes, key, hash scheme
+pub(super) const HEADER_LEN: usize = MAGIC_LEN + 1 + 8 + 4 + SERIALIZED_KEY_LEN + 1;
+
+/// Implementation of a standard [bloom filter](https://en.wikipedia.org/wiki/Bloom_filter),
+/// using a single bit per position.  Entries can't be removed.
+///
+/// Bits are stored in words of type `W`, see [Word].
+pub struct BloomFilter<T, W = u64>
+where
+    T: SeedableHasher,
+    W: Word,
+{
+    pub(super) bits: Vec<W>,
+    pub(super) num_bits: usize,
+    pub(super) n_hashes: u32,
+    pub(super) key: Option<[u8; KEY_LEN]>,
+    _hasher: PhantomData<T>,
+}
+
+impl<T, W> BloomFilter<T, W>
+where
+    T: SeedableHasher,
+    W: Word,
+{
+    /// Create a new bloom filter.
+    ///
+    /// `num_bits` must be greater than 0, the backing storage is rounded up to a multiple of
+    /// [Word::BITS] but only `num_bits` positions are used.
+    ///
+    /// `num_hashes` must be greater than 0 and less than, or equal to, `num_bits`
+    pub fn new(num_bits: usize, num_hashes: u32) -> Result<Self, Error> {
+        check_params(num_bits, num_hashes)?;
+        let bits = vec![W::ZERO; num_bits.div_ceil(W::BITS as usize)];
+        Ok(Self::with_bits(bits, num_bits, num_hashes))
+    }
+
+    /// Create a new bloom filter as [BloomFilter::new] does, but return
+    /// [Error::AllocationFailed] rather than aborting if the bits can't be allocated.
+    pub fn try_new(num_bits: usize, num_hashes: u32) -> Result<Self, Error> {
+        check_params(num_bits, num_hashes)?;
+        let bits = try_filled(num_bits.div_ceil(W::BITS as usize), W::ZERO)?;
+        Ok(Self::with_bits(bits, num_bits, num_hashes))
+    }
+
+    fn with_bits(bits: Vec<W>, num_bits: usize, num_hashes: u32) -> Self {
+        BloomFilter {
+            bits,
+            num_bits,
+            n_hashes: num_hashes,
+            key: None,
+            _hasher: PhantomData,
+        }
+    }
+
+    /// Derive positions from SipHash keyed with a secret `key` rather than from the hasher, so
+    /// an attacker who controls the entries can't choose ones that collide.  Use it when
+    /// entries come from untrusted input, e.g. deduplicating requests.
+    ///
+    /// Set the key before adding entries, as entries added under another key are no longer
+    /// found.  The key is part of the serialized filter, so keep serialized filters as secret
+    /// as the key.
+    pub fn with_key(mut self, key: [u8; KEY_LEN]) -> Self {
+        self.key = Some(key);
+        self
+    }
+
+    /// Returns true if positions are derived from a secret key.
+    pub fn is_keyed(&self) -> bool {
+        self.key.is_some()
+    }
+
+    pub fn num_bits(&self) -> usize {
+        self.num_bits
+    }
+
+    pub fn num_hashes(&self) -> u32 {
+        self.n_hashes
+    }
+
+    /// Create a new bloom filter sized to hold `expected_entries` with a false positive
+    /// probability of `fpp`, using the optimal number of bits and hashes.
+    ///
+    /// `expected_entries` must be greater than 0, and `fpp` must be between 0 and 1 (exclusive).
+    pub fn with_capacity_and_fpp(expected_entries: usize, fpp: f64) -> Result<Self, Error> {
+        let (num_bits, num_hashes) = optimal_params(expected_entries, fpp)?;
+        Self::new(num_bits, num_hashes)
+    }
+
+    /// Create a new bloom filter as [BloomFilter::with_capacity_and_fpp] does, but return
+    /// [Error::AllocationFailed] rather than aborting if the bits can't be allocated.
+    pub fn try_with_capacity_and_fpp(expected_entries: usize, fpp: f64) -> Result<Self, Error> {
+        let (num_bits, num_hashes) = optimal_params(expected_entries, fpp)?;
+        Self::try_new(num_bits, num_hashes)
+    }
+
+    /// Add an entry to the filter.
+    ///
+    /// This returns true if any bit was set by this call, or false if the entry was
+    /// (possibly) already present.
+    pub fn add<I>(&mut self, entry: I) -> bool
+    where
+        I: AsRef<[u8]>,
+    {
+        self.add_prehashed(&HashedKey::hash(entry.as_ref(), self.key.as_ref()))
+    }
+
+    /// Add an entry hashed ahead of time, as [BloomFilter::add] does.
+    ///
+    /// Panics if `hashed` was hashed with a different key than this filter's.
+    pub fn add_prehashed(&mut self, hashed: &HashedKey<T>) -> bool {
+        let mut added = false;
+        for bit in hashed.indexes(self.key.as_ref(), self.n_hashes, self.num_bits as u64) {
+            let (word, mask) = offsets::<W>(bit);
+            added |= self.bits[word] & mask == W::ZERO;
+            self.bits[word] |= mask;
+        }
+        added
+    }
+
+    /// Add an entry by a hash computed by the caller, a u64 or a u128, without hashing it
+    /// again.  A u64 hash from `T` with seed 0 sets the same bits as adding the entry.
+    ///
+    /// Panics if the filter is keyed, as its positions can only come from the key.
+    pub fn add_hashed<H>(&mut self, hash: H) -> bool
+    where
+        H: Into<HashedKey<T>>,
+    {
+        self.add_prehashed(&hash.into())
+    }
+
+    /// Determine if filter contains an entry by a hash computed by the caller, as for
+    /// [BloomFilter::add_hashed].
+    pub fn contains_hashed<H>(&self, hash: H) -> bool
+    where
+        H: Into<HashedKey<T>>,
+    {
+        self.contains_prehashed(&hash.into())
+    }
+
+    /// Add `keys` using `num_shards` threads, for bulk loads too large to add one at a time.
+    /// The keys are split into a shard per thread, each thread sets the bits of its shard in a
+    /// bit array of its own, and the shards are merged into this filter, also in parallel.
+    ///
+    /// Each shard needs memory the size of the filter while building.  `num_shards` must be
+    /// greater than 0.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all, fields(keys = keys.len(), num_shards = num_shards, num_bits = self.num_bits), err))]
+    pub fn build_parallel<I>(mut self, keys: &[I], num_shards: usize) -> Result<Self, Error>
+    where
+        I: AsRef<[u8]> + Sync,
+    {
+        if num_shards == 0 {
+            return Err(Error::InvalidShardCount(num_shards));
+        }
+        let (key, n_hashes, num_bits) = (self.key, self.n_hashes, self.num_bits as u64);
+        let words = self.bits.len();
+        let shards = thread::scope(|scope| {
+            let handles = keys
+                .chunks(keys.len().div_ceil(num_shards).max(1))
+                .map(|shard| {
+                    scope.spawn(move || {
+                        let mut bits = vec![W::ZERO; words];
+                        for entry in shard {
+                            let hashed = HashedKey::<T>::hash(entry.as_ref(), key.as_ref());
+                            for bit in hashed.indexes(key.as_ref(), n_hashes, num_bits) {
+                                let (word, mask) = offsets::<W>(bit);
+                                bits[word] |= mask;
+                            }
+                        }
+                        bits
+                    })
+                })
+                .collect::<Vec<_>>();
+            join_all(handles)
+        });
+
+        // each thread merges the same range of words from every shard
+        let range_len = words.div_ceil(num_shards).max(1);
+        thread::scope(|scope| {
+            let handles = self
+                .bits
+                .chunks_mut(range_len)
+                .enumerate()
+                .map(|(i, range)| {
+                    let shards = &shards;
+                    scope.spawn(move || {
+                        for shard in shards {
+                            let words = &shard[i * range_len..];
+                            for (a, b) in range.iter_mut().zip(words) {
+                                *a |= *b;
+                            }
+                        }
+                    })
+                })
+                .collect::<Vec<_>>();
+            join_all(handles)
+        });
+        Ok(self)
+    }
+
+    /// Determine if filter contains the provided entry.
+    pub fn contains<I>(&self, entry: I) -> bool
+    where
+        I: AsRef<[u8]>,
+    {
+        self.contains_prehashed(&HashedKey::hash(entry.as_ref(), self.key.as_ref()))
+    }
+
+    /// Determine if filter contains an entry hashed ahead of time.
+    ///
+    /// Panics if `hashed` was hashed with a different key than this filter's.
+    pub fn contains_prehashed(&self, hashed: &HashedKey<T>) -> bool {
+        hashed
+            .indexes(self.key.as_ref(), self.n_hashes, self.num_bits as u64)
+            .all(|bit| {
+                let (word, mask) = offsets::<W>(bit);
+                self.bits[word] & mask != W::ZERO
+            })
+    }
+
+    /// Determine which of `entries` the filter contains.  Entries are hashed up front, and the
+    /// words of the entries a few places ahead are prefetched while each one is looked up, so
+    /// lookups in a filter much larger than the CPU cache overlap their memory accesses rather
+    /// than waiting for each in turn.
+    pub fn contains_batch<I>(&self, entries: &[I]) -> Vec<bool>
+    where
+        I: AsRef<[u8]>,
+    {
+        let hashed = entries
+            .iter()
+            .map(|entry| HashedKey::hash(entry.as_ref(), self.key.as_ref()))
+            .collect::<Vec<_>>();
+        prefetch::pipeline(
+            &hashed,
+            |hashed| {
+                for bit in hashed.indexes(self.key.as_ref(), self.n_hashes, self.num_bits as u64) {
+                    prefetch::prefetch(&self.bits[offsets::<W>(bit).0]);
+                }
+            },
+            |hashed| self.contains_prehashed(hashed),
+        )
+    }
+
+    /// Probe the filter with `trials` random entries, which almost certainly weren't added,
+    /// and report the rate of false positives, to check a hasher or parameters deliver the
+    /// expected false positive probability.
+    pub fn measure_fpp<R>(&self, rng: &mut R, trials: u64) -> FppMeasurement
+    where
+        R: RngCore + ?Sized,
+    {
+        measure::measure_fpp(rng, trials, |probe| self.contains(probe))
+    }
+
+    /// Add every entry of `other` to this filter.
+    ///
+    /// Returns an error if the filters were created with different parameters.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all, fields(num_bits = self.num_bits), err))]
+    pub fn union(&mut self, other: &Self) -> Result<(), Error> {
+        self.check_compatible(other)?;
+        for (a, b) in self.bits.iter_mut().zip(other.bits.iter()) {
+            *a |= *b;
+        }
+        Ok(())
+    }
+
+    /// Keep only the bits set in both filters.  The result contains every entry added to both,
+    /// with a false positive rate at most that of either filter.
+    ///
+    /// Returns an error if the filters were created with different parameters.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all, fields(num_bits = self.num_bits), err))]
+    pub fn intersect(&mut self, other: &Self) -> Result<(), Error> {
+        self.check_compatible(other)?;
+        for (a, b) in self.bits.iter_mut().zip(other.bits.iter()) {
+            *a &= *b;
+        }
+        Ok(())
+    }
+
+    /// Clear every bit set in `other`.  Entries of `other` are no longer found, but neither are
+    /// entries of this filter that share a bit with any entry of `other`, so the result can
+    /// have false negatives.
+    ///
+    /// Returns an error if the filters were created with different parameters.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all, fields(num_bits = self.num_bits), err))]
+    pub fn difference(&mut self, other: &Self) -> Result<(), Error> {
+        self.check_compatible(other)?;
+        for (a, b) in self.bits.iter_mut().zip(other.bits.iter()) {
+            *a &= !*b;
+        }
+        Ok(())
+    }
+
+    /// Remove every entry, keeping the parameters and key.
+    pub fn clear(&mut self) {
+        self.bits.fill(W::ZERO);
+    }
+
+    /// Bytes used by the filter, its own size plus the heap allocated for its bits, including
+    /// spare capacity.
+    pub fn mem_usage(&self) -> usize {
+        mem::size_of::<Self>() + heap_size(&self.bits)
+    }
+
+    /// Serialize the filter.
+    ///
+    /// The format is the magic number `PFBF`, a version byte, the number of bits as a u64, the
+    /// number of hashes as a u32, the key (a flag byte then 16 bytes, zeros if unkeyed) and the
+    /// [HashSchemeVersion] byte, followed by the bit array as u64 words and the CRC32C of
+    /// everything before it, all little endian.  The hasher isn't recorded, so the filter must
+    /// be deserialized with the hasher it was built with.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let payload_len = self.num_bits.div_ceil(u64::BITS as usize) * 8;
+        let mut bytes = Vec::with_capacity(HEADER_LEN + payload_len + CHECKSUM_LEN);
+        self.write_to(&mut bytes)
+            .expect("writing to a Vec can't fail");
+        bytes
+    }
+
+    /// A 128 bit digest of the filter's parameters and contents, so replicas can cheaply check
+    /// they hold identical filters.  Filters with the same serialized form have the same
+    /// digest on every platform; the hasher isn't part of it.
+    pub fn digest(&self) -> u128 {
+        format::digest(&self.to_bytes())
+    }
+
+    /// Stream the filter in the format of [BloomFilter::to_bytes], a chunk at a time, so a
+    /// large filter can be written without holding a serialized copy in memory.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all, fields(num_bits = self.num_bits, num_hashes = self.n_hashes), err))]
+    pub fn write_to<O>(&self, writer: O) -> io::Result<()>
+    where
+        O: Write,
+    {
+        let mut writer = ChecksumWriter::new(writer);
+        writer.write_all(&MAGIC)?;
+        writer.write_all(&[FORMAT_VERSION])?;
+        writer.write_all(&(self.num_bits as u64).to_le_bytes())?;
+        writer.write_all(&self.n_hashes.to_le_bytes())?;
+        writer.write_all(&index::key_to_bytes(self.key.as_ref()))?;
+        writer.write_all(&[HashSchemeVersion::CURRENT.as_u8()])?;
+        let words = self.bits.chunks(words_per_u64::<W>()).map(|chunk| {
+            chunk.iter().enumerate().fold(0_u64, |acc, (i, &word)| {
+                acc | word.to_u64() << (i * W::BITS as usize)
+            })
+        });
+        stream::write_words(&mut writer, words)?;
+        writer.finish()
+    }
+
+    /// Deserialize a filter produced by [BloomFilter::to_bytes].
+    ///
+    /// Returns [Error::Truncated] if `bytes` is too short, [Error::ChecksumMismatch] if it was
+    /// corrupted, and [Error::InvalidMagic] or [Error::UnsupportedVersion] if it isn't a bloom
+    /// filter this version can read.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all, fields(len = bytes.len()), err))]
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        format::check_header(bytes, &MAGIC, FORMAT_VERSION)?;
+        if bytes.len() < HEADER_LEN {
+            return Err(Error::Truncated);
+        }
+        // check the length and checksum before allocating anything
+        format::check_body(bytes, serialized_len(bytes))?;
+        Self::read_unchecked(bytes)
+    }
+
+    /// Serialize the filter as [BloomFilter::to_bytes] does, compressed with zstd.  Sparse
+    /// filters compress well, which matters when shipping them between regions.
+    #[cfg(feature = "zstd")]
+    pub fn to_bytes_compressed(&self) -> Vec<u8> {
+        format::compress(|encoder| self.write_to(encoder))
+    }
+
+    /// Deserialize a filter produced by either [BloomFilter::to_bytes] or
+    /// [BloomFilter::to_bytes_compressed], telling them apart by the zstd magic number.
+    #[cfg(feature = "zstd")]
+    pub fn from_bytes_auto(bytes: &[u8]) -> Result<Self, Error> {
+        match format::is_compressed(bytes) {
+            true => format::read_compressed(bytes, |reader| Self::read_from(reader)),
+            false => Self::from_bytes(bytes),
+        }
+    }
+
+    /// Read a filter written by [BloomFilter::write_to], a chunk at a time.  Reading stops at
+    /// the end of the filter, so it may be followed by other data.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, err)
+    )]
+    pub fn read_from<R>(reader: R) -> Result<Self, Error>
+    where
+        R: Read,
+    {
+        let mut reader = ChecksumReader::new(reader);
+        let bf = Self::read_unchecked(&mut reader)?;
+        reader.finish::<Error>()?;
+        Ok(bf)
+    }
+
+    // Read the header and bit array, leaving the checksum to the caller.
+    fn read_unchecked<R>(mut reader: R) -> Result<Self, Error>
+    where
+        R: Read,
+    {
+        let header: [u8; HEADER_LEN] = stream::read_array(&mut reader)?;
+        let (num_bits, num_hashes, key) = parse_header(&header)?;
+
+        let mut bf = Self::try_new(num_bits, num_hashes)?;
+        bf.key = key;
+        let mut words = bf.bits.iter_mut();
+        stream::read_words(
+            &mut reader,
+            num_bits.div_ceil(u64::BITS as usize),
+            |value| {
+                for (i, word) in words.by_ref().take(words_per_u64::<W>()).enumerate() {
+                    *word = W::from_u64(value >> (i * W::BITS as usize));
+                }
+            },
+        )?;
+        Ok(bf)
+    }
+
+    /// Union the filters written by [BloomFilter::write_to] to `readers` into `writer`, a chunk
+    /// of words at a time, so an aggregator can merge many shard filters holding only a chunk
+    /// of each in memory.  `writer` gets what serializing the union would write.
+    ///
+    /// Returns [Error::IncompatibleFilter] unless every filter has the same parameters and key,
+    /// or any error [BloomFilter::read_from] would.  Checksums are only checked at the end, so on
+    /// error `writer` may hold part of a filter.
+    pub fn union_streams<R, O>(readers: &mut [R], writer: O) -> Result<(), Error>
+    where
+        R: Read,
+        O: Write,
+    {
+        if readers.is_empty() {
+            return Err(Error::InvalidShardCount(readers.len()));
+        }
+        let mut readers = readers
+            .iter_mut()
+            .map(ChecksumReader::new)
+            .collect::<Vec<_>>();
+        let mut headers = readers
+            .iter_mut()
+            .map(stream::read_array::<_, HEADER_LEN>)
+            .collect::<Result<Vec<_>, _>>()?;
+        let header = headers.pop().unwrap();
+        if headers.iter().any(|other| *other != header) {
+            return Err(Error::IncompatibleFilter);
+        }
+        let (num_bits, _, _) = parse_header(&header)?;
+
+        let mut writer = ChecksumWriter::new(writer);
+        writer.write_all(&header)?;
+        stream::merge_words(
+            &mut readers,
+            &mut writer,
+            num_bits.div_ceil(u64::BITS as usize),
+            |a, b| a | b,
+        )?;
+        for reader in readers {
+            reader.finish::<Error>()?;
+        }
+        Ok(writer.finish()?)
+    }
+
+    /// Estimate the number of distinct entries added to the filter from the number of set bits,
+    /// `-(m / k) * ln(1 - X / m)` for `m` bits, `k` hashes and `X` set bits.
+    ///
+    /// The standard deviation of the estimate is roughly
+    /// `sqrt(m * (e^(k * n / m) - 1 - k * n / m)) / k` for `n` entries, so it degrades quickly
+    /// as the filter fills up.  A filter with every bit set returns [f64::INFINITY].
+    pub fn estimate_len(&self) -> f64 {
+        self.cardinality(self.set_bits())
+    }
+
+    /// Estimate the number of distinct entries in the union of two filters.
+    ///
+    /// Returns an error if the filters were created with different parameters.
+    pub fn estimate_union_len(&self, other: &Self) -> Result<f64, Error> {
+        self.check_compatible(other)?;
+        let set_bits = self
+            .bits
+            .iter()
+            .zip(other.bits.iter())
+            .map(|(&a, &b)| (a | b).count_ones() as usize)
+            .sum();
+        Ok(self.cardinality(set_bits))
+    }
+
+    /// Estimate the number of distinct entries in the intersection of two filters, using
+    /// inclusion-exclusion: `|A ∩ B| = |A| + |B| - |A ∪ B|`.
+    ///
+    /// Each term carries the error described in [BloomFilter::estimate_len], and the errors
+    /// don't cancel out, so the absolute error is on the order of the error of the union
+    /// estimate.  Small intersections of large sets have a high relative error, and filters
+    /// should be sized for the union rather than for each set.  The estimate is clamped at 0.
+    ///
+    /// Returns an error if the filters were created with different parameters.
+    pub fn estimate_intersection_len(&self, other: &Self) -> Result<f64, Error> {
+        let union = self.estimate_union_len(other)?;
+        Ok((self.estimate_len() + other.estimate_len() - union).max(0.0))
+    }
+
+    fn set_bits(&self) -> usize {
+        self.bits.iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    fn cardinality(&self, set_bits: usize) -> f64 {
+        let m = self.num_bits as f64;
+        // ln(m / (m - X)) rather than -ln(1 - X / m), so an empty filter estimates 0, not -0
+        (m / self.n_hashes as f64) * (m / (m - set_bits as f64)).ln()
+    }
+
+    fn check_compatible(&self, other: &Self) -> Result<(), Error> {
+        if self.num_bits != other.num_bits
+            || self.n_hashes != other.n_hashes
+            || self.key != other.key
+        {
+            return Err(Error::IncompatibleFilter);
+        }
+        Ok(())
+    }
+}
+
+// Wait for every thread, passing on the first panic.
+fn join_all<R>(handles: Vec<thread::ScopedJoinHandle<'_, R>>) -> Vec<R> {
+    handles
+        .into_iter()
+        .map(|handle| {
+            handle
+                .join()
+                .unwrap_or_else(|err| panic::resume_unwind(err))
+        })
+        .collect()
+}
+
+fn check_params(num_bits: usize, num_hashes: u32) -> Result<(), Error> {
+    if num_bits == 0 {
+        return Err(Error::InvalidBinCount(num_bits));
+    }
+    if num_hashes == 0 || num_hashes as usize > num_bits {
+        return Err(Error::InvalidHashCount(num_hashes));
+    }
+    Ok(())
+}
+
+/// The number of bits, number of hashes and key in a serialized header.
+fn parse_header(header: &[u8; HEADER_LEN]) -> Result<(usize, u32, Option<[u8; KEY_LEN]>), Error> {
+    format::check_header(header, &MAGIC, FORMAT_VERSION)?;
+    let num_bits = u64::from_le_bytes(header[5..13].try_into().unwrap());
+    let num_hashes = u32::from_le_bytes(header[13..17].try_into().unwrap());
+    let key = index::key_from_bytes(&header[17..])?;
+    check_hash_scheme(header[HEADER_LEN - 1])?;
+    let num_bits = usize::try_from(num_bits).map_err(|_| Error::Malformed)?;
+    check_params(num_bits, num_hashes)?;
+    Ok((num_bits, num_hashes, key))
+}
+
+/// The word holding `bit`, and the mask selecting it.
+fn offsets<W>(bit: u64) -> (usize, W)
+where
+    W: Word,
+{
+    (
+        (bit / W::BITS as u64) as usize,
+        W::bit((bit % W::BITS as u64) as u32),
+    )
+}
+
+// little endian words of any size concatenate to the same bit array
+const fn words_per_u64<W>() -> usize
+where
+    W: Word,
+{
+    (u64::BITS / W::BITS) as usize
+}
+
+impl<T, W> fmt::Debug for BloomFilter<T, W>
+where
+    T: SeedableHasher,
+    W: Word,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BloomFilter")
+            .field("hasher", &any::type_name::<T>())
+            .field("num_bits", &self.num_bits)
+            .field("num_hashes", &self.n_hashes)
+            .field("keyed", &self.is_keyed())
+            .field("set_bits", &self.set_bits())
+            .finish()
+    }
+}
+
+/// A one line summary: the estimated number of entries, the fraction of bits set, and the
+/// false positive probability that implies.
+impl<T, W> fmt::Display for BloomFilter<T, W>
+where
+    T: SeedableHasher,
+    W: Word,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let fill = self.set_bits() as f64 / self.num_bits as f64;
+        write!(
+            f,
+            "BloomFilter: items≈{:.0}, fill {:.1}%, fpp≈{:.2e}",
+            self.estimate_len(),
+            fill * 100.0,
+            fill.powi(self.n_hashes as i32)
+        )
+    }
+}
+
+// derived impls would require T: Clone/PartialEq, which hashers don't implement
+impl<T, W> Clone for BloomFilter<T, W>
+where
+    T: SeedableHasher,
+    W: Word,
+{
+    fn clone(&self) -> Self {
+        BloomFilter {
+            bits: self.bits.clone(),
+            num_bits: self.num_bits,
+            n_hashes: self.n_hashes,
+            key: self.key,
+            _hasher: PhantomData,
+        }
+    }
+}
+
+/// Filters are equal if they have the same parameters, including the key, and the same bits
+/// set.
+impl<T, W> PartialEq for BloomFilter<T, W>
+where
+    T: SeedableHasher,
+    W: Word,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.num_bits == other.num_bits
+            && self.n_hashes == other.n_hashes
+            && self.key == other.key
+            && self.bits == other.bits
+    }
+}
+
+impl<T, W> Eq for BloomFilter<T, W>
+where
+    T: SeedableHasher,
+    W: Word,
+{
+}
+
+/// Length of the serialized filter whose header starts `bytes`, including the checksum.
+pub(super) fn serialized_len(bytes: &[u8]) -> u64 {
+    let num_bits = u64::from_le_bytes(bytes[5..13].try_into().unwrap());
+    num_bits
+        .div_ceil(u64::BITS as u64)
+        .saturating_mul(8)
+        .saturating_add((HEADER_LEN + CHECKSUM_LEN) as u64)
+}
@@ -1,6 +1,7 @@
 use fasthash::{metro, murmur3};
 
 use super::*;
+use crate::FilterEvent;
 
 macro_rules! test_add {
     ($($name:ident: $bits:expr,)*) => {
@@ -47,7 +48,7 @@ macro_rules! test_rm {
                 assert!(cbf.remove(s2));
                 assert!(!cbf.contains(s1));
                 assert!(!cbf.contains(s2));
-                assert_eq!(0_usize, cbf.counter_bins.iter().sum());
+                assert_eq!(0_u64, cbf.counter_bins.iter().sum::<u64>());
             }
         )*
     };
@@ -66,10 +67,10 @@ test_rm! {
 #[test]
 fn test_remove_from_empty() {
     let mut cbf = CountingBloomFilter::<metro::Hasher64_1>::new(9, 3).unwrap();
-    assert_eq!(0_usize, cbf.counter_bins.iter().sum());
+    assert_eq!(0_u64, cbf.counter_bins.iter().sum::<u64>());
     let s = "mystring".as_bytes();
     assert!(!cbf.remove(s));
-    assert_eq!(0_usize, cbf.counter_bins.iter().sum());
+    assert_eq!(0_u64, cbf.counter_bins.iter().sum::<u64>());
 }
 
 // adding to filter that has been saturated doesn't cause counter to wrap
@@ -100,15 +101,15 @@ fn test_add_to_full() {
 #[test]
 fn test_rm_only_if_exists() {
     let mut cbf = CountingBloomFilter::<metro::Hasher64_1>::new(9, 3).unwrap();
-    assert_eq!(0_usize, cbf.counter_bins.iter().sum());
+    assert_eq!(0_u64, cbf.counter_bins.iter().sum::<u64>());
     let s = "mystring".as_bytes();
     assert!(cbf.add(s));
-    let expected: usize = cbf.counter_bins.iter().sum();
+    let expected: u64 = cbf.counter_bins.iter().sum();
 
     // this passes so long as extra values do not collide with the initial
     for extra in ["redfish", "bluefish", "onefish", "twofish"] {
         assert!(!cbf.remove(extra.as_bytes()));
-        assert_eq!(expected, cbf.counter_bins.iter().sum());
+        assert_eq!(expected, cbf.counter_bins.iter().sum::<u64>());
     }
 }
 
@@ -128,6 +129,16 @@ fn test_estimate() {
     assert_eq!(0, cbf.estimate(s));
 }
 
+#[test]
+fn test_insert_if_absent() {
+    let mut cbf = CountingBloomFilter::<metro::Hasher64_1>::new(1024, 3).unwrap();
+    assert!(cbf.insert_if_absent("wow"));
+    assert!(!cbf.insert_if_absent("wow"));
+    assert_eq!(1, cbf.estimate("wow"));
+    assert!(cbf.insert_if_absent("such"));
+    assert_eq!(1, cbf.estimate("such"));
+}
+
 #[test]
 fn test_invalid_hash_count() {
     macro_rules! test_invalid_hash_param {
@@ -151,10 +162,169 @@ fn test_invalid_bin_count() {
     assert!(matches!(cbf, Err(Error::InvalidBinCount(_))));
 }
 
+#[test]
+fn test_auto_widen() {
+    let mut cbf = CountingBloomFilter::<metro::Hasher64_1>::with_bits_per_counter(1024, 3, 2)
+        .unwrap()
+        .with_auto_widen(3)
+        .unwrap();
+    for i in 0..100_u64 {
+        cbf.add(i.to_le_bytes());
+    }
+    // one hot entry saturates its 2 bit counters, which widen to 4 bits
+    for _ in 0..3 {
+        assert!(cbf.add("hot"));
+    }
+    assert_eq!(4, cbf.bits_per_counter());
+    assert_eq!(1024, cbf.num_counters());
+    assert_eq!(3, cbf.estimate("hot"));
+    assert!((0..100_u64).all(|i| cbf.contains(i.to_le_bytes())));
+    for _ in 0..100 {
+        cbf.add("hot");
+    }
+    assert_eq!(8, cbf.bits_per_counter());
+    assert_eq!(103, cbf.estimate("hot"));
+    assert_eq!(0, cbf.stats().saturation_events);
+
+    // removing from a saturated counter means it no longer counts towards widening
+    let mut cbf = CountingBloomFilter::<metro::Hasher64_1>::with_bits_per_counter(1024, 3, 2)
+        .unwrap()
+        .with_auto_widen(6)
+        .unwrap();
+    for _ in 0..3 {
+        cbf.add("a");
+    }
+    cbf.remove("a");
+    for _ in 0..3 {
+        cbf.add("b");
+    }
+    assert_eq!(2, cbf.bits_per_counter());
+
+    assert!(matches!(
+        CountingBloomFilter::<metro::Hasher64_1>::new(1024, 3)
+            .unwrap()
+            .with_auto_widen(0),
+        Err(Error::InvalidWidenThreshold(0))
+    ));
+}
+
+#[test]
+fn test_project() {
+    let mut cbf = CountingBloomFilter::<metro::Hasher64_1>::new(4096, 3).unwrap();
+    for i in 0..200_u64 {
+        cbf.add(i.to_le_bytes());
+    }
+    for _ in 0..3 {
+        cbf.add("hot");
+    }
+    assert_eq!(cbf, cbf.project(4096).unwrap());
+
+    let projected = cbf.project(1024).unwrap();
+    assert_eq!(1024, projected.num_counters());
+    assert!((0..200_u64).all(|i| projected.contains(i.to_le_bytes())));
+    assert!(projected.estimate("hot") >= 3);
+    // every count lands in the smaller filter, up to saturation
+    let total = |f: &CountingBloomFilter<_>| f.all_counter_values().sum::<u64>();
+    assert_eq!(total(&cbf), total(&projected));
+
+    let mut morris =
+        CountingBloomFilter::<metro::Hasher64_1>::with_morris_counters(4096, 3, 4, 1.5).unwrap();
+    for i in 0..200_u64 {
+        morris.add(i.to_le_bytes());
+    }
+    let projected = morris.project(512).unwrap();
+    assert!((0..200_u64).all(|i| projected.contains(i.to_le_bytes())));
+
+    for target in [0, 1000, 1024 + 512, 8192] {
+        assert!(
+            matches!(cbf.project(target), Err(Error::InvalidProjectionTarget(t)) if t == target),
+            "{}",
+            target
+        );
+    }
+    // 4 bit counters come 16 to a bin
+    assert!(matches!(
+        cbf.project(8),
+        Err(Error::InvalidProjectionTarget(8))
+    ));
+}
+
+#[test]
+fn test_counting_stats() {
+    let mut cbf =
+        CountingBloomFilter::<metro::Hasher64_1>::with_bits_per_counter(1024, 3, 2).unwrap();
+    let stats = cbf.stats();
+    assert_eq!((0, 0), (stats.inserted, stats.saturation_events));
+    assert_eq!(vec![1024, 0, 0, 0], stats.histogram);
+    assert_eq!((0.0, 0.0), (stats.fill_ratio, stats.estimated_fpp));
+
+    // counters hold at most 3
+    for _ in 0..5 {
+        cbf.add("a");
+    }
+    cbf.add("b");
+    let stats = cbf.stats();
+    assert_eq!((4, 2), (stats.inserted, stats.saturation_events));
+    assert_eq!(1024, stats.histogram.iter().sum::<u64>());
+    assert!(stats.histogram[3] >= 1, "{:?}", stats.histogram);
+    assert_eq!(cbf.nonzero_counters() as f64 / 1024.0, stats.fill_ratio);
+    assert_eq!(stats.fill_ratio.powi(3), stats.estimated_fpp);
+
+    // wide counters share the last bucket
+    let cbf = CountingBloomFilter::<metro::Hasher64_1>::with_bits_per_counter(64, 3, 16).unwrap();
+    assert_eq!(256, cbf.stats().histogram.len());
+}
+
+#[test]
+fn test_event_hook() {
+    use std::sync::{Arc, Mutex};
+
+    let events = Arc::new(Mutex::new(vec![]));
+    let sink = Arc::clone(&events);
+    let mut cbf = CountingBloomFilter::<metro::Hasher64_1>::with_bits_per_counter(1024, 3, 2)
+        .unwrap()
+        .with_event_hook(move |event| sink.lock().unwrap().push(event));
+    for _ in 0..3 {
+        cbf.add("a");
+    }
+    assert!(events.lock().unwrap().is_empty());
+    cbf.add("a");
+    cbf.clone().add("a");
+    assert_eq!(
+        vec![FilterEvent::CounterSaturated; 2],
+        *events.lock().unwrap()
+    );
+}
+
+#[test]
+fn test_counting_with_capacity_and_fpp() {
+    let mut cbf =
+        CountingBloomFilter::<metro::Hasher64_1>::with_capacity_and_fpp(1_000, 0.01).unwrap();
+    assert_eq!(7, cbf.n_hashes);
+    // 9586 counters, 16 to a bin
+    assert_eq!(600, cbf.counter_bins.len());
+    for i in 0..1_000_u64 {
+        cbf.add(i.to_le_bytes());
+    }
+    assert!((0..1_000_u64).all(|i| cbf.contains(i.to_le_bytes())));
+    let fp = (1000..101_000_u64)
+        .filter(|i| cbf.contains(i.to_le_bytes()))
+        .count();
+    assert!(fp < 1300, "{}", fp);
+    assert!(matches!(
+        CountingBloomFilter::<metro::Hasher64_1>::with_capacity_and_fpp(0, 0.01),
+        Err(Error::InvalidCapacity(0))
+    ));
+    assert!(matches!(
+        CountingBloomFilter::<metro::Hasher64_1>::with_capacity_and_fpp(10, 1.0),
+        Err(Error::InvalidFpp(_))
+    ));
+}
+
 #[test]
 fn test_max_counter() {
-    let mut input = usize::BITS;
-    let mut expected = !0_usize;
+    let mut input = u64::BITS;
+    let mut expected = !0_u64;
     let mut shift_bits = 0;
     while input > 0 {
         assert_eq!(expected, calc_max_counter(&input));
@@ -163,3 +333,1571 @@ fn test_max_counter() {
         expected = (expected << shift_bits) >> shift_bits;
     }
 }
+
+#[test]
+fn test_gossip_exchange() {
+    let mut a = CountingBloomFilter::<metro::Hasher64_1>::new(1024, 3).unwrap();
+    let mut b = CountingBloomFilter::<metro::Hasher64_1>::new(1024, 3).unwrap();
+    assert!(a.add("alpha"));
+    assert!(a.add("alpha"));
+    assert!(b.add("beta"));
+
+    // a -> b: b sends what a is missing
+    let diff = b.diff_for(&a.summary(8).unwrap()).unwrap();
+    assert!(!diff.is_empty());
+    a.merge_diff(&diff).unwrap();
+    // b -> a: a sends what b is missing
+    let diff = a.diff_for(&b.summary(8).unwrap()).unwrap();
+    b.merge_diff(&diff).unwrap();
+
+    for cbf in [&a, &b] {
+        assert_eq!(2, cbf.estimate("alpha"));
+        assert_eq!(1, cbf.estimate("beta"));
+    }
+    assert_eq!(a.counter_bins, b.counter_bins);
+    assert!(a.diff_for(&b.summary(8).unwrap()).unwrap().is_empty());
+}
+
+#[test]
+fn test_gossip_incompatible() {
+    let a = CountingBloomFilter::<metro::Hasher64_1>::new(1024, 3).unwrap();
+    let b = CountingBloomFilter::<metro::Hasher64_1>::new(1024, 4).unwrap();
+    assert!(matches!(a.summary(0), Err(Error::InvalidBlockSize(0))));
+    assert!(matches!(
+        b.diff_for(&a.summary(8).unwrap()),
+        Err(Error::IncompatibleFilter)
+    ));
+}
+
+#[test]
+fn test_gossip_wire_format() {
+    let mut a = CountingBloomFilter::<metro::Hasher64_1>::new(1024, 3).unwrap();
+    let mut b = CountingBloomFilter::<metro::Hasher64_1>::new(1024, 3).unwrap();
+    for i in 0..5_u64 {
+        assert!(b.add(i.to_le_bytes()));
+    }
+
+    let summary = a.summary(7).unwrap();
+    let bytes = summary.to_bytes();
+    assert_eq!(summary, DiffSummary::from_bytes(&bytes).unwrap());
+    assert!(matches!(
+        DiffSummary::from_bytes(&bytes[..bytes.len() - 9]),
+        Err(Error::Truncated)
+    ));
+
+    let diff = b
+        .diff_for(&DiffSummary::from_bytes(&bytes).unwrap())
+        .unwrap();
+    let mut bytes = diff.to_bytes();
+    // sparse bins cost a few bytes each, far less than the filter
+    assert!(bytes.len() < b.to_bytes().len() / 2);
+    let restored = FilterDiff::from_bytes(&bytes).unwrap();
+    assert_eq!(diff, restored);
+    a.merge_diff(&restored).unwrap();
+    assert_eq!(a.counter_bins, b.counter_bins);
+
+    let last = bytes.len() - 5;
+    bytes[last] ^= 1;
+    assert!(matches!(
+        FilterDiff::from_bytes(&bytes),
+        Err(Error::ChecksumMismatch { .. })
+    ));
+}
+
+#[test]
+fn test_gossip_rejects_mismatches() {
+    let a = CountingBloomFilter::<metro::Hasher64_1>::new(1024, 3).unwrap();
+    let mut b = CountingBloomFilter::<metro::Hasher64_1>::new(1024, 3).unwrap();
+    assert!(b.add("beta"));
+
+    let mut summary = a.summary(8).unwrap();
+    summary.digests.pop();
+    assert!(matches!(
+        b.diff_for(&summary),
+        Err(Error::DigestCountMismatch(7, 8))
+    ));
+
+    let diff = b.diff_for(&a.summary(8).unwrap()).unwrap();
+    let mut keyed = CountingBloomFilter::<metro::Hasher64_1>::new(1024, 3)
+        .unwrap()
+        .with_key(*b"0123456789abcdef");
+    assert!(matches!(
+        keyed.merge_diff(&diff),
+        Err(Error::IncompatibleFilter)
+    ));
+    let mut morris =
+        CountingBloomFilter::<metro::Hasher64_1>::with_morris_counters(1024, 3, 4, 1.5).unwrap();
+    assert!(matches!(
+        morris.merge_diff(&diff),
+        Err(Error::IncompatibleFilter)
+    ));
+}
+
+#[test]
+fn test_bloom_filter() {
+    let mut bf = BloomFilter::<murmur3::Hasher32>::new(100, 3).unwrap();
+    assert!(bf.add("Hello, world!"));
+    assert!(!bf.add("Hello, world!"));
+    assert!(bf.contains("Hello, world!"));
+    assert!(!bf.contains("hello, world!"));
+    assert!(matches!(
+        BloomFilter::<murmur3::Hasher32>::new(0, 1),
+        Err(Error::InvalidBinCount(0))
+    ));
+    assert!(matches!(
+        BloomFilter::<murmur3::Hasher32>::new(2, 3),
+        Err(Error::InvalidHashCount(3))
+    ));
+}
+
+#[test]
+fn test_learned_no_false_negatives() {
+    // the "model" accepts even numbers, so odd keys must land in the backup filter
+    let model = |key: &[u8]| (u64::from_le_bytes(key.try_into().unwrap()) % 2 == 0) as u8 as f64;
+    let mut lbf = LearnedBloomFilter::new(
+        model,
+        0.5,
+        BloomFilter::<metro::Hasher64_1>::new(8192, 4).unwrap(),
+        BloomFilter::<metro::Hasher64_1>::new(4096, 4).unwrap(),
+    );
+    for i in 0..500_u64 {
+        lbf.add(i.to_le_bytes());
+    }
+    for i in 0..500_u64 {
+        assert!(lbf.contains(i.to_le_bytes()), "{}", i);
+    }
+    // negatives are screened by the initial filter, even when the model accepts them
+    assert!(
+        (500..1000_u64)
+            .filter(|i| lbf.contains(i.to_le_bytes()))
+            .count()
+            < 50
+    );
+}
+
+#[test]
+fn test_prefix_bloom() {
+    let mut pbf = PrefixBloomFilter::new(
+        BloomFilter::<metro::Hasher64_1>::new(1024, 3).unwrap(),
+        FixedPrefix(4),
+    );
+    pbf.add("user:1001");
+    pbf.add("user:1002");
+    pbf.add("ab");
+    assert!(pbf.contains("user:1001"));
+    assert!(!pbf.contains("user:1003"));
+    assert!(pbf.may_contain_prefix("user"));
+    assert!(pbf.may_contain_prefix("user:9999"));
+    assert!(!pbf.may_contain_prefix("item"));
+    // out of domain, nothing can be ruled out
+    assert!(pbf.may_contain_prefix("it"));
+}
+
+#[test]
+fn test_removal_bloom() {
+    let mut rbf = RemovalBloomFilter::new(
+        BloomFilter::<metro::Hasher64_1>::with_capacity_and_fpp(1000, 0.01).unwrap(),
+    );
+    for i in 0..1000_u64 {
+        rbf.add(i.to_le_bytes());
+    }
+    assert!(!rbf.remove(5000_u64.to_le_bytes()));
+    for i in 0..100_u64 {
+        assert!(rbf.remove(i.to_le_bytes()));
+    }
+    assert!((0..100_u64).all(|i| !rbf.contains(i.to_le_bytes())));
+    // false positives of the removed filter hide a few live entries
+    let hidden = (100..1000_u64)
+        .filter(|i| !rbf.contains(i.to_le_bytes()))
+        .count();
+    assert!(hidden < 30, "{}", hidden);
+    let removed = rbf.estimate_removed_len();
+    assert!((removed - 100.0).abs() < 10.0, "{}", removed);
+
+    rbf.compact((100..1000_u64).map(u64::to_le_bytes));
+    assert_eq!(0.0, rbf.estimate_removed_len());
+    assert!((100..1000_u64).all(|i| rbf.contains(i.to_le_bytes())));
+    assert!(rbf.filter().contains(999_u64.to_le_bytes()));
+    // compacting lets a removed entry be added back
+    rbf.add(5_u64.to_le_bytes());
+    assert!(rbf.contains(5_u64.to_le_bytes()));
+}
+
+#[test]
+fn test_prefix_bloom_prefix_only() {
+    fn up_to_colon(key: &[u8]) -> Option<&[u8]> {
+        key.iter().position(|&b| b == b':').map(|i| &key[..i])
+    }
+    let mut pbf = PrefixBloomFilter::prefix_only(
+        BloomFilter::<metro::Hasher64_1>::new(1024, 3).unwrap(),
+        up_to_colon,
+    );
+    pbf.add("session:abc");
+    assert!(pbf.may_contain_prefix("session"));
+    // falls back to prefix checks
+    assert!(pbf.contains("session:xyz"));
+    assert!(!pbf.contains("user:abc"));
+}
+
+#[test]
+fn test_bloomrf() {
+    for step in [1, 3, 4, 8] {
+        let mut rf = BloomRf::new(
+            BloomFilter::<metro::Hasher64_1>::new(1 << 16, 3).unwrap(),
+            step,
+        )
+        .unwrap();
+        for key in [0, 1000, 1_000_000, u64::MAX] {
+            rf.add(key);
+        }
+        assert!(rf.contains(1000));
+        assert!(!rf.contains(1001));
+        assert!(rf.contains_range(0, 0));
+        assert!(rf.contains_range(990, 1010));
+        assert!(rf.contains_range(1000, 1000));
+        assert!(rf.contains_range(999_000, 2_000_000));
+        assert!(rf.contains_range(u64::MAX - 5, u64::MAX));
+        assert!(rf.contains_range(0, u64::MAX));
+        assert!(!rf.contains_range(1001, 5000), "{}", step);
+        assert!(!rf.contains_range(2_000_000, 3_000_000), "{}", step);
+        assert!(!rf.contains_range(10, 5));
+    }
+}
+
+#[test]
+fn test_bloomrf_invalid_step() {
+    let bf = BloomFilter::<metro::Hasher64_1>::new(1024, 3).unwrap();
+    assert!(matches!(
+        BloomRf::new(bf, 17),
+        Err(Error::InvalidLevelStep(17))
+    ));
+}
+
+#[test]
+fn test_bloomrf_no_false_negatives() {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+    let mut rng = StdRng::seed_from_u64(7);
+    let mut rf = BloomRf::new(
+        BloomFilter::<metro::Hasher64_1>::new(1 << 16, 3).unwrap(),
+        4,
+    )
+    .unwrap();
+    let keys = (0..200).map(|_| rng.gen::<u64>() >> 40).collect::<Vec<_>>();
+    keys.iter().for_each(|&k| rf.add(k));
+    for _ in 0..1000 {
+        let lo = rng.gen::<u64>() >> 40;
+        let hi = lo + (rng.gen::<u64>() >> 50);
+        if keys.iter().any(|k| (lo..=hi).contains(k)) {
+            assert!(rf.contains_range(lo, hi), "{}..={}", lo, hi);
+        }
+    }
+}
+
+#[test]
+fn test_morris_counters() {
+    let mut cbf =
+        CountingBloomFilter::<metro::Hasher64_1>::with_morris_counters(1024, 3, 4, 2.0).unwrap();
+    let s = "popular";
+    assert!(cbf.add(s));
+    // the first increment always happens
+    assert_eq!(1, cbf.estimate(s));
+    for _ in 0..5_000 {
+        assert!(cbf.add(s));
+    }
+    // a 4 bit linear counter would have saturated at 15
+    assert!(cbf.estimate(s) > 500, "{}", cbf.estimate(s));
+    assert!(!cbf.contains("unpopular"));
+
+    assert!(matches!(
+        CountingBloomFilter::<metro::Hasher64_1>::with_morris_counters(1024, 3, 4, 0.5),
+        Err(Error::InvalidMorrisBase(_))
+    ));
+}
+
+#[test]
+fn test_bloom_intersection_estimate() {
+    let mut a = BloomFilter::<metro::Hasher64_1>::new(1 << 16, 4).unwrap();
+    let mut b = BloomFilter::<metro::Hasher64_1>::new(1 << 16, 4).unwrap();
+    for i in 0..3000_u64 {
+        a.add(i.to_le_bytes());
+    }
+    for i in 2000..6000_u64 {
+        b.add(i.to_le_bytes());
+    }
+    let within = |estimate: f64, expected: f64| (estimate - expected).abs() < expected * 0.05;
+    assert!(within(a.estimate_len(), 3000.0), "{}", a.estimate_len());
+    let union = a.estimate_union_len(&b).unwrap();
+    assert!(within(union, 6000.0), "{}", union);
+    let intersection = a.estimate_intersection_len(&b).unwrap();
+    assert!(within(intersection, 1000.0), "{}", intersection);
+
+    let other = BloomFilter::<metro::Hasher64_1>::new(1 << 16, 3).unwrap();
+    assert!(matches!(
+        a.estimate_intersection_len(&other),
+        Err(Error::IncompatibleFilter)
+    ));
+}
+
+#[test]
+fn test_bloom_with_capacity_and_fpp() {
+    let mut bf = BloomFilter::<metro::Hasher64_1>::with_capacity_and_fpp(1000, 0.01).unwrap();
+    assert_eq!(9586, bf.num_bits);
+    assert_eq!(7, bf.n_hashes);
+    for i in 0..1000_u64 {
+        bf.add(i.to_le_bytes());
+    }
+    let fp = (1000..11_000_u64)
+        .filter(|i| bf.contains(i.to_le_bytes()))
+        .count();
+    assert!(fp < 200, "{}", fp);
+    assert!(matches!(
+        BloomFilter::<metro::Hasher64_1>::with_capacity_and_fpp(0, 0.01),
+        Err(Error::InvalidCapacity(0))
+    ));
+    assert!(matches!(
+        BloomFilter::<metro::Hasher64_1>::with_capacity_and_fpp(10, 0.0),
+        Err(Error::InvalidFpp(_))
+    ));
+}
+
+// Positions are derived from a single hash, so check the false positive rate still matches
+// that of independent hashes, (1 - e^(-k * n / m))^k, including for sizes that aren't powers of
+// two and for many hashes.
+#[test]
+fn test_fpp_matches_theory() {
+    for (num_bits, num_hashes, entries) in [
+        (10_007, 1, 2_000),
+        (10_007, 4, 1_500),
+        (1 << 14, 7, 1_500),
+        (50_000, 12, 5_000),
+    ] {
+        let mut bf = BloomFilter::<metro::Hasher64_1>::new(num_bits, num_hashes).unwrap();
+        let mut cbf = CountingBloomFilter::<metro::Hasher64_1>::with_bits_per_counter(
+            num_bits, num_hashes, 64,
+        )
+        .unwrap();
+        for i in 0..entries as u64 {
+            bf.add(i.to_le_bytes());
+            cbf.add(i.to_le_bytes());
+        }
+        let k = num_hashes as f64;
+        let expected = (1.0 - (-k * entries as f64 / num_bits as f64).exp()).powf(k);
+        let trials = 200_000;
+        let fpp = |contains: &dyn Fn(&[u8]) -> bool| {
+            (1_000_000..1_000_000 + trials)
+                .filter(|i: &u64| contains(&i.to_le_bytes()))
+                .count() as f64
+                / trials as f64
+        };
+        let observed = fpp(&|e| bf.contains(e));
+        assert!(
+            (observed - expected).abs() < expected * 0.15,
+            "m={} k={}: {} vs {}",
+            num_bits,
+            num_hashes,
+            observed,
+            expected
+        );
+        let observed = fpp(&|e| cbf.contains(e));
+        assert!(
+            (observed - expected).abs() < expected * 0.15,
+            "counting m={} k={}: {} vs {}",
+            num_bits,
+            num_hashes,
+            observed,
+            expected
+        );
+    }
+}
+
+#[test]
+fn test_bloom_union() {
+    let mut a = BloomFilter::<metro::Hasher64_1>::new(1000, 3).unwrap();
+    let mut b = BloomFilter::<metro::Hasher64_1>::new(1000, 3).unwrap();
+    a.add("a");
+    b.add("b");
+    a.union(&b).unwrap();
+    assert!(a.contains("a"));
+    assert!(a.contains("b"));
+    let c = BloomFilter::<metro::Hasher64_1>::new(999, 3).unwrap();
+    assert!(matches!(a.union(&c), Err(Error::IncompatibleFilter)));
+}
+
+#[test]
+fn test_union_streams() {
+    // large enough to take several chunks
+    let shards = (0..3_u64)
+        .map(|shard| {
+            let mut bf = BloomFilter::<metro::Hasher64_1>::new(1_000_000, 3).unwrap();
+            for i in shard * 1000..(shard + 1) * 1000 {
+                bf.add(i.to_le_bytes());
+            }
+            bf
+        })
+        .collect::<Vec<_>>();
+    let mut union = shards[0].clone();
+    union.union(&shards[1]).unwrap();
+    union.union(&shards[2]).unwrap();
+
+    let blobs = shards.iter().map(BloomFilter::to_bytes).collect::<Vec<_>>();
+    let mut readers = blobs.iter().map(Vec::as_slice).collect::<Vec<_>>();
+    let mut merged = vec![];
+    BloomFilter::<metro::Hasher64_1>::union_streams(&mut readers, &mut merged).unwrap();
+    assert_eq!(union.to_bytes(), merged);
+    assert!(readers.iter().all(|reader| reader.is_empty()));
+
+    let other = BloomFilter::<metro::Hasher64_1>::new(1_000_000, 4)
+        .unwrap()
+        .to_bytes();
+    let mut readers = [blobs[0].as_slice(), other.as_slice()];
+    assert!(matches!(
+        BloomFilter::<metro::Hasher64_1>::union_streams(&mut readers, vec![]),
+        Err(Error::IncompatibleFilter)
+    ));
+    let mut corrupted = blobs[1].clone();
+    corrupted[100] ^= 1;
+    let mut readers = [blobs[0].as_slice(), corrupted.as_slice()];
+    assert!(matches!(
+        BloomFilter::<metro::Hasher64_1>::union_streams(&mut readers, vec![]),
+        Err(Error::ChecksumMismatch { .. })
+    ));
+    assert!(matches!(
+        BloomFilter::<metro::Hasher64_1>::union_streams::<&[u8], _>(&mut [], vec![]),
+        Err(Error::InvalidShardCount(0))
+    ));
+}
+
+#[test]
+fn test_bloom_serialization() {
+    let mut bf = BloomFilter::<metro::Hasher64_1>::new(1000, 3).unwrap();
+    for i in 0..100_u64 {
+        bf.add(i.to_le_bytes());
+    }
+    let bytes = bf.to_bytes();
+    assert_eq!(35 + 16 * 8 + 4, bytes.len());
+    let restored = BloomFilter::<metro::Hasher64_1>::from_bytes(&bytes).unwrap();
+    assert_eq!(bf.bits, restored.bits);
+    assert_eq!(bf.num_bits, restored.num_bits);
+    assert_eq!(bf.n_hashes, restored.n_hashes);
+
+    assert!(matches!(
+        BloomFilter::<metro::Hasher64_1>::from_bytes(&bytes[..bytes.len() - 8]),
+        Err(Error::Truncated)
+    ));
+    assert!(matches!(
+        BloomFilter::<metro::Hasher64_1>::from_bytes(&bytes[..10]),
+        Err(Error::Truncated)
+    ));
+    let mut trailing = bytes.clone();
+    trailing.push(0);
+    assert!(matches!(
+        BloomFilter::<metro::Hasher64_1>::from_bytes(&trailing),
+        Err(Error::Malformed)
+    ));
+    let mut corrupted = bytes.clone();
+    corrupted[40] ^= 1;
+    assert!(matches!(
+        BloomFilter::<metro::Hasher64_1>::from_bytes(&corrupted),
+        Err(Error::ChecksumMismatch { .. })
+    ));
+    let mut bad_magic = bytes.clone();
+    bad_magic[..4].copy_from_slice(b"PFCF");
+    assert!(matches!(
+        BloomFilter::<metro::Hasher64_1>::from_bytes(&bad_magic),
+        Err(Error::InvalidMagic)
+    ));
+    let mut bad_version = bytes.clone();
+    bad_version[4] = 0;
+    assert!(matches!(
+        BloomFilter::<metro::Hasher64_1>::from_bytes(&bad_version),
+        Err(Error::UnsupportedVersion(0))
+    ));
+    let mut bad_scheme = bytes[..bytes.len() - CHECKSUM_LEN].to_vec();
+    bad_scheme[34] = 9;
+    format::push_checksum(&mut bad_scheme);
+    assert!(matches!(
+        BloomFilter::<metro::Hasher64_1>::from_bytes(&bad_scheme),
+        Err(Error::UnsupportedHashScheme(9))
+    ));
+    assert!(matches!(
+        BloomFilterView::<metro::Hasher64_1>::new(&bad_scheme),
+        Err(Error::UnsupportedHashScheme(9))
+    ));
+}
+
+#[test]
+fn test_hash_scheme_stable() {
+    // persisted filters depend on these never changing for HashSchemeVersion::V1
+    let hashed = HashedKey::<metro::Hasher64_1>::new("stable");
+    assert_eq!(
+        vec![357760, 630884, 904008, 128556],
+        hashed.indexes(None, 4, 1 << 20).collect::<Vec<_>>()
+    );
+    let keyed = HashedKey::<metro::Hasher64_1>::with_key("stable", [7; KEY_LEN]);
+    assert_eq!(
+        vec![201403, 767786, 285593, 851976],
+        keyed
+            .indexes(Some(&[7; KEY_LEN]), 4, 1 << 20)
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(
+        HashSchemeVersion::CURRENT,
+        HashSchemeVersion::from_u8(1).unwrap()
+    );
+}
+
+#[test]
+fn test_counting_bloom_serialization() {
+    let mut cbf = CountingBloomFilter::<metro::Hasher64_1>::new(1000, 3).unwrap();
+    for i in 0..100_u64 {
+        cbf.add(i.to_le_bytes());
+    }
+    let bytes = cbf.to_bytes();
+    assert_eq!(47 + cbf.counter_bins.len() * 8 + 4, bytes.len());
+    let restored = CountingBloomFilter::<metro::Hasher64_1>::from_bytes(&bytes).unwrap();
+    assert_eq!(cbf.counter_bins, restored.counter_bins);
+    assert_eq!(1, restored.estimate(7_u64.to_le_bytes()));
+
+    let mut morris =
+        CountingBloomFilter::<metro::Hasher64_1>::with_morris_counters(1024, 3, 4, 2.0).unwrap();
+    morris.add("popular");
+    let restored =
+        CountingBloomFilter::<metro::Hasher64_1>::from_bytes(&morris.to_bytes()).unwrap();
+    assert_eq!(morris.morris_base, restored.morris_base);
+
+    assert!(matches!(
+        CountingBloomFilter::<metro::Hasher64_1>::from_bytes(&bytes[..bytes.len() - 8]),
+        Err(Error::Truncated)
+    ));
+    let mut zero_bits = bytes.clone();
+    zero_bits[17..21].copy_from_slice(&0_u32.to_le_bytes());
+    assert!(matches!(
+        CountingBloomFilter::<metro::Hasher64_1>::from_bytes(&zero_bits),
+        Err(Error::ChecksumMismatch { .. })
+    ));
+    // a valid checksum over invalid parameters
+    zero_bits.truncate(zero_bits.len() - 4);
+    format::push_checksum(&mut zero_bits);
+    assert!(matches!(
+        CountingBloomFilter::<metro::Hasher64_1>::from_bytes(&zero_bits),
+        Err(Error::Malformed)
+    ));
+    assert!(matches!(
+        CountingBloomFilter::<metro::Hasher64_1>::from_bytes(
+            &BloomFilter::<metro::Hasher64_1>::new(1000, 3)
+                .unwrap()
+                .to_bytes()
+        ),
+        Err(Error::InvalidMagic)
+    ));
+    let mut bad_version = bytes;
+    bad_version[4] = 0;
+    assert!(matches!(
+        CountingBloomFilter::<metro::Hasher64_1>::from_bytes(&bad_version),
+        Err(Error::UnsupportedVersion(0))
+    ));
+}
+
+#[test]
+fn test_stream_roundtrip() {
+    let mut bf = BloomFilter::<metro::Hasher64_1>::new(1_000_000, 3).unwrap();
+    let mut cbf = CountingBloomFilter::<metro::Hasher64_1>::new(1000, 3).unwrap();
+    for i in 0..100_u64 {
+        bf.add(i.to_le_bytes());
+        cbf.add(i.to_le_bytes());
+    }
+    let mut stream = vec![];
+    bf.write_to(&mut stream).unwrap();
+    assert_eq!(bf.to_bytes(), stream);
+    cbf.write_to(&mut stream).unwrap();
+
+    let mut reader = stream.as_slice();
+    let restored = BloomFilter::<metro::Hasher64_1>::read_from(&mut reader).unwrap();
+    assert_eq!(bf.bits, restored.bits);
+    let restored = CountingBloomFilter::<metro::Hasher64_1>::read_from(&mut reader).unwrap();
+    assert_eq!(cbf.counter_bins, restored.counter_bins);
+    assert!(reader.is_empty());
+
+    assert!(matches!(
+        BloomFilter::<metro::Hasher64_1>::read_from(&stream[..1000]),
+        Err(Error::Truncated)
+    ));
+    let mut corrupted = cbf.to_bytes();
+    corrupted[40] ^= 1;
+    assert!(matches!(
+        CountingBloomFilter::<metro::Hasher64_1>::read_from(corrupted.as_slice()),
+        Err(Error::ChecksumMismatch { .. })
+    ));
+}
+
+#[test]
+fn test_no_panic() {
+    // a header claiming a huge filter fails to allocate rather than aborting
+    let mut bytes = BloomFilter::<metro::Hasher64_1>::new(64, 3)
+        .unwrap()
+        .to_bytes();
+    bytes[5..13].copy_from_slice(&(u64::MAX >> 8).to_le_bytes());
+    assert!(matches!(
+        BloomFilter::<metro::Hasher64_1>::read_from(bytes.as_slice()),
+        Err(Error::AllocationFailed(_))
+    ));
+    let mut bytes = CountingBloomFilter::<metro::Hasher64_1>::new(64, 3)
+        .unwrap()
+        .to_bytes();
+    bytes[5..13].copy_from_slice(&(u64::MAX >> 8).to_le_bytes());
+    assert!(matches!(
+        CountingBloomFilter::<metro::Hasher64_1>::read_from(bytes.as_slice()),
+        Err(Error::AllocationFailed(_))
+    ));
+
+    assert!(matches!(
+        CountingBloomFilter::<metro::Hasher64_1>::with_bits_per_counter(64, 3, 0),
+        Err(Error::BitsPerCounterUnaligned(0, 64))
+    ));
+}
+
+#[test]
+fn test_bloom_view() {
+    let mut bf = BloomFilter::<metro::Hasher64_1>::new(1000, 3).unwrap();
+    for i in 0..100_u64 {
+        bf.add(i.to_le_bytes());
+    }
+    // misalign the bit array to check it's read byte by byte
+    let mut buf = vec![0];
+    buf.extend(bf.to_bytes());
+    let view = BloomFilterView::<metro::Hasher64_1>::new(&buf[1..]).unwrap();
+    assert_eq!(1000, view.num_bits());
+    for i in 0..1000_u64 {
+        assert_eq!(bf.contains(i.to_le_bytes()), view.contains(i.to_le_bytes()));
+    }
+
+    assert!(matches!(
+        BloomFilterView::<metro::Hasher64_1>::new(&buf[1..buf.len() - 1]),
+        Err(Error::Truncated)
+    ));
+    buf[14..18].copy_from_slice(&0_u32.to_le_bytes());
+    assert!(matches!(
+        BloomFilterView::<metro::Hasher64_1>::new(&buf[1..]),
+        Err(Error::InvalidHashCount(0))
+    ));
+}
+
+#[test]
+fn test_guava_hash_matches_murmur3_x64_128() {
+    use fasthash::{murmur3::Hash128_x64, FastHash};
+
+    // reference values for MurmurHash3_x64_128 with seed 0, which Guava reads as (h1, h2)
+    let hash = Hash128_x64::hash_with_seed("The quick brown fox jumps over the lazy dog", 0);
+    assert_eq!(0xe34bbc7bbc071b6c, hash as u64);
+    assert_eq!(0x7a433ca9c49a9347, (hash >> 64) as u64);
+}
+
+#[test]
+fn test_guava_roundtrip() {
+    for strategy in [
+        GuavaStrategy::Murmur128Mitz32,
+        GuavaStrategy::Murmur128Mitz64,
+    ] {
+        let mut bf = GuavaBloomFilter::new(1000, 7, strategy).unwrap();
+        for i in 0..100u64 {
+            bf.add(i.to_le_bytes());
+        }
+        let bytes = bf.to_guava_bytes();
+        assert_eq!(6 + 16 * 8, bytes.len());
+        assert_eq!(7, bytes[1]);
+        let restored = GuavaBloomFilter::from_guava_bytes(&bytes).unwrap();
+        assert_eq!(bytes, restored.to_guava_bytes());
+        for i in 0..100u64 {
+            assert!(restored.contains(i.to_le_bytes()));
+        }
+        let false_positives = (100..10_100u64)
+            .filter(|i| restored.contains(i.to_le_bytes()))
+            .count();
+        assert!(false_positives < 300, "{}", false_positives);
+    }
+}
+
+#[test]
+fn test_guava_golden() {
+    // what BloomFilter.writeTo writes for create(Funnels.stringFunnel(UTF_8), 10, 0.03) with
+    // the entries below put, with MURMUR128_MITZ_32 then MURMUR128_MITZ_64.  Generated by a
+    // standalone Java port of Guava's murmur3_128, strategies and writeTo.
+    let golden: [&[u8]; 2] = [
+        &[
+            0x00, 0x05, 0x00, 0x00, 0x00, 0x02, 0x80, 0x40, 0x6c, 0x10, 0x4c, 0x82, 0x10, 0x00,
+            0x06, 0x40, 0x20, 0x08, 0x04, 0x08, 0x84, 0x11,
+        ],
+        &[
+            0x01, 0x05, 0x00, 0x00, 0x00, 0x02, 0x10, 0x48, 0x03, 0x22, 0x04, 0x2a, 0xb0, 0x21,
+            0x01, 0x20, 0x80, 0x10, 0x28, 0x03, 0x00, 0x08,
+        ],
+    ];
+    let entries = ["alpha", "beta", "gamma", "delta", "epsilon"];
+    for (strategy, bytes) in [
+        GuavaStrategy::Murmur128Mitz32,
+        GuavaStrategy::Murmur128Mitz64,
+    ]
+    .into_iter()
+    .zip(golden)
+    {
+        let bf = GuavaBloomFilter::from_guava_bytes(bytes).unwrap();
+        for entry in entries {
+            assert!(bf.contains(entry), "{:?} {}", strategy, entry);
+        }
+        assert_eq!(bytes, bf.to_guava_bytes());
+
+        let mut built = GuavaBloomFilter::new(72, 5, strategy).unwrap();
+        for entry in entries {
+            built.add(entry);
+        }
+        assert_eq!(bytes, built.to_guava_bytes());
+    }
+    let mut created = GuavaBloomFilter::with_capacity_and_fpp(10, 0.03).unwrap();
+    for entry in entries {
+        created.add(entry);
+    }
+    assert_eq!(golden[1], created.to_guava_bytes());
+}
+
+#[test]
+fn test_guava_malformed() {
+    let bytes = GuavaBloomFilter::with_capacity_and_fpp(100, 0.01)
+        .unwrap()
+        .to_guava_bytes();
+    assert!(matches!(
+        GuavaBloomFilter::from_guava_bytes(&bytes[..bytes.len() - 1]),
+        Err(Error::Malformed)
+    ));
+    let mut bad_strategy = bytes.clone();
+    bad_strategy[0] = 2;
+    assert!(matches!(
+        GuavaBloomFilter::from_guava_bytes(&bad_strategy),
+        Err(Error::UnsupportedVersion(2))
+    ));
+    let mut no_hashes = bytes;
+    no_hashes[1] = 0;
+    assert!(matches!(
+        GuavaBloomFilter::from_guava_bytes(&no_hashes),
+        Err(Error::InvalidHashCount(0))
+    ));
+}
+
+#[test]
+fn test_fixed_bloom() {
+    let mut bf = FixedBloomFilter::<16, 5>::new();
+    assert!(bf.is_empty());
+    assert!(bf.add(0u64.to_le_bytes()));
+    for i in 1..100u64 {
+        bf.add(i.to_le_bytes());
+    }
+    for i in 0..100u64 {
+        assert!(bf.contains(i.to_le_bytes()));
+        assert!(!bf.add(i.to_le_bytes()));
+    }
+    let false_positives = (100..10_100u64)
+        .filter(|i| bf.contains(i.to_le_bytes()))
+        .count();
+    // ~1% expected for 1024 bits, 5 hashes and 100 entries
+    assert!(false_positives < 300, "{}", false_positives);
+
+    let copy = bf;
+    bf.clear();
+    assert!(bf.is_empty());
+    assert!(copy.contains(0u64.to_le_bytes()));
+    assert_eq!(1024 / 8, std::mem::size_of::<FixedBloomFilter<16, 5>>());
+}
+
+#[test]
+fn test_fixed_bloom_static() {
+    static EMBEDDED: FixedBloomFilter<2, 3> = FixedBloomFilter::from_words([0x5, 0x0]);
+    assert!(!EMBEDDED.is_empty());
+
+    let source = FixedBloomFilter::<4, 3>::static_source("DENY", ["a", "b", "c"]);
+    let mut built = FixedBloomFilter::<4, 3>::new();
+    for entry in ["a", "b", "c"] {
+        built.add(entry);
+    }
+    let literals: Vec<u64> = source
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("0x"))
+        .map(|hex| u64::from_str_radix(hex.trim_end_matches(','), 16).unwrap())
+        .collect();
+    assert_eq!(built.words(), &literals[..]);
+    assert_eq!(built, FixedBloomFilter::from_words(*built.words()));
+}
+
+#[cfg(feature = "zstd")]
+#[test]
+fn test_compressed_roundtrip() {
+    let mut cbf = CountingBloomFilter::<metro::Hasher64_1>::new(100_000, 3).unwrap();
+    for i in 0..1000_u64 {
+        cbf.add(i.to_le_bytes());
+    }
+    let bytes = cbf.to_bytes();
+    let compressed = cbf.to_bytes_compressed();
+    assert!(compressed.len() * 5 < bytes.len(), "{}", compressed.len());
+    for bytes in [&bytes, &compressed] {
+        let restored = CountingBloomFilter::<metro::Hasher64_1>::from_bytes_auto(bytes).unwrap();
+        assert_eq!(cbf.counter_bins, restored.counter_bins);
+    }
+
+    let bf = BloomFilter::<metro::Hasher64_1>::new(1000, 3).unwrap();
+    let restored =
+        BloomFilter::<metro::Hasher64_1>::from_bytes_auto(&bf.to_bytes_compressed()).unwrap();
+    assert_eq!(bf.bits, restored.bits);
+    assert!(matches!(
+        BloomFilter::<metro::Hasher64_1>::from_bytes_auto(&compressed[..compressed.len() / 2]),
+        Err(Error::Io(_) | Error::Truncated)
+    ));
+}
+
+#[cfg(feature = "zstd")]
+#[test]
+fn test_compressed_oversized_frame() {
+    use std::io::Write;
+
+    // a small frame inflating to a filter followed by 256MB of zeros, which is read a chunk at
+    // a time and rejected after the filter, rather than decompressed into memory
+    let bf = BloomFilter::<metro::Hasher64_1>::new(1000, 3).unwrap();
+    let mut encoder = zstd::Encoder::new(vec![], 1).unwrap();
+    bf.write_to(&mut encoder).unwrap();
+    let zeros = vec![0; 1 << 20];
+    for _ in 0..256 {
+        encoder.write_all(&zeros).unwrap();
+    }
+    let bomb = encoder.finish().unwrap();
+    assert!(bomb.len() < 1 << 20, "{}", bomb.len());
+    assert!(matches!(
+        BloomFilter::<metro::Hasher64_1>::from_bytes_auto(&bomb),
+        Err(Error::Malformed)
+    ));
+}
+
+#[test]
+fn test_digest() {
+    let mut a = BloomFilter::<metro::Hasher64_1>::new(1000, 3).unwrap();
+    let mut b = BloomFilter::<metro::Hasher64_1>::new(1000, 3).unwrap();
+    assert_eq!(a.digest(), b.digest());
+    a.add("x");
+    assert_ne!(a.digest(), b.digest());
+    b.add("x");
+    assert_eq!(a.digest(), b.digest());
+    assert_ne!(
+        a.digest(),
+        BloomFilter::<metro::Hasher64_1>::new(1000, 4)
+            .unwrap()
+            .digest()
+    );
+
+    let mut cbf = CountingBloomFilter::<metro::Hasher64_1>::new(1000, 3).unwrap();
+    let empty = cbf.digest();
+    cbf.add("x");
+    assert_ne!(empty, cbf.digest());
+    cbf.remove("x");
+    assert_eq!(empty, cbf.digest());
+}
+
+#[test]
+fn test_clone_eq() {
+    let mut bf = BloomFilter::<metro::Hasher64_1>::new(1000, 3).unwrap();
+    bf.add("x");
+    let mut copy = bf.clone();
+    assert!(bf == copy);
+    copy.add("y");
+    assert!(bf != copy);
+    assert!(bf != BloomFilter::<metro::Hasher64_1>::new(1000, 4).unwrap());
+
+    let mut cbf = CountingBloomFilter::<metro::Hasher64_1>::new(1000, 3).unwrap();
+    cbf.add("x");
+    let mut copy = cbf.clone();
+    assert!(cbf == copy);
+    copy.add("x");
+    assert!(cbf != copy);
+    copy.remove("x");
+    assert!(cbf == copy);
+}
+
+#[test]
+fn test_debug_display() {
+    let mut bf = BloomFilter::<metro::Hasher64_1>::new(10_000, 3).unwrap();
+    for i in 0..100_u64 {
+        bf.add(i.to_le_bytes());
+    }
+    let debug = format!("{:?}", bf);
+    assert!(debug.contains("num_bits: 10000"), "{}", debug);
+    assert!(debug.contains("set_bits: "), "{}", debug);
+    assert!(!debug.contains("bits: ["), "{}", debug);
+    let display = bf.to_string();
+    assert!(display.starts_with("BloomFilter: items≈"), "{}", display);
+    assert!(display.contains("fill 3.0%"), "{}", display);
+
+    let mut cbf = CountingBloomFilter::<metro::Hasher64_1>::new(1000, 3).unwrap();
+    assert_eq!(
+        "CountingBloomFilter: items≈0, fill 0.0%, fpp≈0.00e0",
+        cbf.to_string()
+    );
+    cbf.add("x");
+    assert!(format!("{:?}", cbf).contains("nonzero_counters: "));
+    assert!(cbf.to_string().contains("items≈1,"), "{}", cbf);
+}
+
+#[test]
+fn test_set_operators() {
+    let mut a = BloomFilter::<metro::Hasher64_1>::new(10_000, 3).unwrap();
+    let mut b = BloomFilter::<metro::Hasher64_1>::new(10_000, 3).unwrap();
+    a.add("a");
+    a.add("both");
+    b.add("b");
+    b.add("both");
+
+    let union = &a | &b;
+    assert!(["a", "b", "both"].iter().all(|e| union.contains(e)));
+    let mut assigned = a.clone();
+    assigned |= &b;
+    assert_eq!(union, assigned);
+
+    let intersection = &a & &b;
+    assert!(intersection.contains("both"));
+    assert!(!intersection.contains("a"));
+    assert!(!intersection.contains("b"));
+
+    let difference = &a - &b;
+    assert!(!difference.contains("both"));
+    assert!(!difference.contains("b"));
+
+    let other = BloomFilter::<metro::Hasher64_1>::new(1000, 3).unwrap();
+    assert!(matches!(
+        a.intersect(&other),
+        Err(Error::IncompatibleFilter)
+    ));
+    assert!(matches!(
+        a.difference(&other),
+        Err(Error::IncompatibleFilter)
+    ));
+}
+
+#[test]
+#[should_panic(expected = "same number of bits")]
+fn test_set_operators_incompatible() {
+    let a = BloomFilter::<metro::Hasher64_1>::new(10_000, 3).unwrap();
+    let b = BloomFilter::<metro::Hasher64_1>::new(1000, 3).unwrap();
+    let _ = &a | &b;
+}
+
+#[test]
+fn test_mem_usage() {
+    let bf = BloomFilter::<metro::Hasher64_1>::new(1_000_000, 3).unwrap();
+    let bits = 1_000_000_usize.div_ceil(64) * 8;
+    assert_eq!(std::mem::size_of_val(&bf) + bits, bf.mem_usage());
+
+    // 63 bins, padded to 8 cache lines
+    let cbf = CountingBloomFilter::<metro::Hasher64_1>::new(1000, 3).unwrap();
+    assert_eq!(std::mem::size_of_val(&cbf) + 8 * 64, cbf.mem_usage());
+    assert_eq!(0, cbf.counter_bins.as_ptr() as usize % 64);
+
+    let rf = BloomRf::new(bf, 4).unwrap();
+    assert_eq!(std::mem::size_of_val(&rf) + bits, rf.mem_usage());
+    assert_eq!(64, FixedBloomFilter::<8, 3>::new().mem_usage());
+}
+
+// a hasher from outside fasthash, seeded by hashing the seed first
+struct SipHasher(std::collections::hash_map::DefaultHasher);
+
+impl std::hash::Hasher for SipHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        self.0.write(bytes)
+    }
+
+    fn finish(&self) -> u64 {
+        self.0.finish()
+    }
+}
+
+impl SeedableHasher for SipHasher {
+    fn new() -> Self {
+        Self::with_seed(0)
+    }
+
+    fn with_seed(seed: u32) -> Self {
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        std::hash::Hasher::write_u32(&mut hasher, seed);
+        SipHasher(hasher)
+    }
+}
+
+#[test]
+fn test_other_seed_types() {
+    use fasthash::{sea, xx};
+
+    // u64 seeds
+    let mut bf = BloomFilter::<xx::Hasher64>::with_capacity_and_fpp(1000, 0.01).unwrap();
+    // (u64, u64, u64, u64) seeds
+    let mut cbf = CountingBloomFilter::<sea::Hasher64>::with_capacity_and_fpp(1000, 0.01).unwrap();
+    let mut sip = BloomFilter::<SipHasher>::with_capacity_and_fpp(1000, 0.01).unwrap();
+    for i in 0..1000_u64 {
+        bf.add(i.to_le_bytes());
+        cbf.add(i.to_le_bytes());
+        sip.add(i.to_le_bytes());
+    }
+    assert!((0..1000_u64).all(|i| bf.contains(i.to_le_bytes())));
+    assert!((0..1000_u64).all(|i| cbf.contains(i.to_le_bytes())));
+    assert!((0..1000_u64).all(|i| sip.contains(i.to_le_bytes())));
+    let fp = (1000..11_000_u64)
+        .filter(|i| sip.contains(i.to_le_bytes()))
+        .count();
+    assert!(fp < 200, "{}", fp);
+}
+
+#[test]
+fn test_keyed() {
+    let key = *b"0123456789abcdef";
+    let mut plain = BloomFilter::<metro::Hasher64_1>::new(1024, 4).unwrap();
+    let mut keyed = BloomFilter::<metro::Hasher64_1>::new(1024, 4)
+        .unwrap()
+        .with_key(key);
+    let mut cbf = CountingBloomFilter::<metro::Hasher64_1>::new(1024, 4)
+        .unwrap()
+        .with_key(key);
+    assert!(!plain.is_keyed());
+    assert!(keyed.is_keyed());
+    for i in 0..100_u64 {
+        plain.add(i.to_le_bytes());
+        keyed.add(i.to_le_bytes());
+        cbf.add(i.to_le_bytes());
+    }
+    assert!((0..100_u64).all(|i| keyed.contains(i.to_le_bytes())));
+    assert_ne!(plain.bits, keyed.bits);
+    assert!(matches!(
+        plain.union(&keyed),
+        Err(Error::IncompatibleFilter)
+    ));
+    let other = BloomFilter::<metro::Hasher64_1>::new(1024, 4)
+        .unwrap()
+        .with_key(*b"fedcba9876543210");
+    assert!(matches!(
+        keyed.union(&other),
+        Err(Error::IncompatibleFilter)
+    ));
+
+    let bytes = keyed.to_bytes();
+    let restored = BloomFilter::<metro::Hasher64_1>::from_bytes(&bytes).unwrap();
+    assert_eq!(keyed, restored);
+    let view = BloomFilterView::<metro::Hasher64_1>::new(&bytes).unwrap();
+    assert!((0..100_u64).all(|i| view.contains(i.to_le_bytes())));
+    let restored = CountingBloomFilter::<metro::Hasher64_1>::from_bytes(&cbf.to_bytes()).unwrap();
+    assert_eq!(cbf, restored);
+    assert!((0..100_u64).all(|i| restored.contains(i.to_le_bytes())));
+    assert!(!format!("{:?}", keyed).contains("48, 49"));
+
+    let mut bad_flag = bytes[..bytes.len() - CHECKSUM_LEN].to_vec();
+    bad_flag[17] = 2;
+    format::push_checksum(&mut bad_flag);
+    assert!(matches!(
+        BloomFilter::<metro::Hasher64_1>::from_bytes(&bad_flag),
+        Err(Error::Malformed)
+    ));
+}
+
+#[test]
+fn test_hashed_key() {
+    let key = *b"0123456789abcdef";
+    let mut small = BloomFilter::<metro::Hasher64_1>::new(100, 3).unwrap();
+    let mut large = BloomFilter::<metro::Hasher64_1>::new(10_000, 7).unwrap();
+    let mut cbf = CountingBloomFilter::<metro::Hasher64_1>::new(1000, 4).unwrap();
+    let mut keyed = CountingBloomFilter::<metro::Hasher64_1>::new(1000, 4)
+        .unwrap()
+        .with_key(key);
+
+    let hashed = HashedKey::<metro::Hasher64_1>::new("entry");
+    assert!(small.add_prehashed(&hashed));
+    assert!(large.add_prehashed(&hashed));
+    assert!(cbf.add_prehashed(&hashed));
+    cbf.add("entry");
+    assert!(small.contains("entry"));
+    assert!(large.contains_prehashed(&hashed));
+    assert_eq!(2, cbf.estimate_prehashed(&hashed));
+    assert!(cbf.remove_prehashed(&hashed));
+    assert_eq!(1, cbf.estimate("entry"));
+
+    let keyed_hash = HashedKey::<metro::Hasher64_1>::with_key("entry", key);
+    assert!(keyed.add_prehashed(&keyed_hash));
+    assert!(keyed.contains("entry"));
+    let bytes = large.to_bytes();
+    let view = BloomFilterView::<metro::Hasher64_1>::new(&bytes).unwrap();
+    assert!(view.contains_prehashed(&hashed));
+}
+
+#[test]
+#[should_panic(expected = "different key")]
+fn test_hashed_key_wrong_key() {
+    let bf = BloomFilter::<metro::Hasher64_1>::new(100, 3)
+        .unwrap()
+        .with_key(*b"0123456789abcdef");
+    bf.contains_prehashed(&HashedKey::new("entry"));
+}
+
+#[test]
+fn test_add_hashed() {
+    use std::hash::Hasher;
+
+    let mut bf = BloomFilter::<metro::Hasher64_1>::new(1000, 4).unwrap();
+    let mut cbf = CountingBloomFilter::<metro::Hasher64_1>::new(1000, 4).unwrap();
+    let mut hasher = metro::Hasher64_1::with_seed(0);
+    hasher.write(b"entry");
+    let hash = hasher.finish();
+    assert!(bf.add_hashed(hash));
+    assert!(cbf.add_hashed(hash));
+    assert!(bf.contains("entry"));
+    assert!(cbf.contains("entry"));
+    assert!(cbf.contains_hashed(hash));
+
+    let wide = 0x0123_4567_89ab_cdef_fedc_ba98_7654_3210_u128;
+    assert!(bf.add_hashed(wide));
+    assert!(bf.contains_hashed(wide));
+    assert!(!bf.contains_hashed(wide + 1));
+    let bytes = bf.to_bytes();
+    let view = BloomFilterView::<metro::Hasher64_1>::new(&bytes).unwrap();
+    assert!(view.contains_hashed(hash));
+    assert!(view.contains_hashed(wide));
+}
+
+#[test]
+fn test_add_remove_symmetric() {
+    // few counters, so entries map to the same counter more than once
+    let mut cbf = CountingBloomFilter::<metro::Hasher64_1>::with_bits_per_counter(4, 4, 8).unwrap();
+    for i in 0..20_u64 {
+        assert!(cbf.add(i.to_le_bytes()));
+        assert!(cbf.add(i.to_le_bytes()));
+        assert!(cbf.estimate(i.to_le_bytes()) >= 2);
+        assert!(cbf.remove(i.to_le_bytes()));
+        assert!(cbf.remove(i.to_le_bytes()));
+        assert!(cbf.counter_bins.iter().all(|&bin| bin == 0));
+        assert!(!cbf.remove(i.to_le_bytes()));
+    }
+}
+
+#[test]
+fn test_contains_batch() {
+    let mut bf = BloomFilter::<metro::Hasher64_1>::with_capacity_and_fpp(1000, 0.01).unwrap();
+    let mut cbf =
+        CountingBloomFilter::<metro::Hasher64_1>::with_capacity_and_fpp(1000, 0.01).unwrap();
+    for i in (0..1000_u64).step_by(2) {
+        bf.add(i.to_le_bytes());
+        cbf.add(i.to_le_bytes());
+    }
+    let entries = (0..1000_u64).map(u64::to_le_bytes).collect::<Vec<_>>();
+    let expected = entries.iter().map(|e| bf.contains(e)).collect::<Vec<_>>();
+    assert_eq!(expected, bf.contains_batch(&entries));
+    let expected = entries.iter().map(|e| cbf.contains(e)).collect::<Vec<_>>();
+    assert_eq!(expected, cbf.contains_batch(&entries));
+    assert!(bf
+        .contains_batch(&entries[..3])
+        .iter()
+        .step_by(2)
+        .all(|&c| c));
+    assert!(bf.contains_batch::<&[u8]>(&[]).is_empty());
+}
+
+#[test]
+fn test_build_parallel() {
+    let keys = (0..10_000_u64).map(u64::to_le_bytes).collect::<Vec<_>>();
+    let mut expected = BloomFilter::<metro::Hasher64_1>::new(100_000, 5).unwrap();
+    for key in &keys {
+        expected.add(key);
+    }
+    for num_shards in [1, 3, 8, 20_000] {
+        let built = BloomFilter::<metro::Hasher64_1>::new(100_000, 5)
+            .unwrap()
+            .build_parallel(&keys, num_shards)
+            .unwrap();
+        assert_eq!(expected, built);
+    }
+
+    let keyed = BloomFilter::<metro::Hasher64_1>::new(1000, 3)
+        .unwrap()
+        .with_key(*b"0123456789abcdef")
+        .build_parallel(&keys[..100], 4)
+        .unwrap();
+    assert!(keys[..100].iter().all(|key| keyed.contains(key)));
+    assert!(matches!(
+        BloomFilter::<metro::Hasher64_1>::new(1000, 3)
+            .unwrap()
+            .build_parallel(&keys, 0),
+        Err(Error::InvalidShardCount(0))
+    ));
+}
+
+#[test]
+fn test_u32_words() {
+    let mut wide = BloomFilter::<metro::Hasher64_1>::new(1000, 4).unwrap();
+    let mut narrow = BloomFilter::<metro::Hasher64_1, u32>::new(1000, 4).unwrap();
+    assert_eq!(32, narrow.bits.len());
+    for i in 0..100_u64 {
+        wide.add(i.to_le_bytes());
+        narrow.add(i.to_le_bytes());
+    }
+    assert!((0..100_u64).all(|i| narrow.contains(i.to_le_bytes())));
+    assert_eq!(wide.estimate_len(), narrow.estimate_len());
+
+    // the serialized form doesn't depend on the word type
+    let bytes = narrow.to_bytes();
+    assert_eq!(wide.to_bytes(), bytes);
+    assert_eq!(
+        narrow,
+        BloomFilter::<metro::Hasher64_1, u32>::from_bytes(&bytes).unwrap()
+    );
+    let view = BloomFilterView::<metro::Hasher64_1>::new(&bytes).unwrap();
+    assert!((0..100_u64).all(|i| view.contains(i.to_le_bytes())));
+
+    let mut other = BloomFilter::<metro::Hasher64_1, u32>::new(1000, 4).unwrap();
+    other.add("other");
+    narrow |= &other;
+    assert!(narrow.contains("other"));
+}
+
+#[test]
+fn test_try_new() {
+    let bf = BloomFilter::<metro::Hasher64_1>::try_new(1000, 3).unwrap();
+    assert_eq!(BloomFilter::new(1000, 3).unwrap(), bf);
+    let bf = BloomFilter::<metro::Hasher64_1>::try_with_capacity_and_fpp(1000, 0.01).unwrap();
+    assert_eq!(BloomFilter::with_capacity_and_fpp(1000, 0.01).unwrap(), bf);
+    let cbf =
+        CountingBloomFilter::<metro::Hasher64_1>::try_with_capacity_and_fpp(1000, 0.01).unwrap();
+    assert_eq!(
+        CountingBloomFilter::with_capacity_and_fpp(1000, 0.01).unwrap(),
+        cbf
+    );
+
+    assert!(matches!(
+        BloomFilter::<metro::Hasher64_1>::try_new(usize::MAX, 3),
+        Err(Error::AllocationFailed(_))
+    ));
+    assert!(matches!(
+        CountingBloomFilter::<metro::Hasher64_1>::try_new(usize::MAX, 3),
+        Err(Error::AllocationFailed(_))
+    ));
+    assert!(matches!(
+        BloomFilter::<metro::Hasher64_1>::try_new(0, 3),
+        Err(Error::InvalidBinCount(0))
+    ));
+}
+
+#[test]
+fn test_measure_fpp() {
+    use rand::{rngs::StdRng, SeedableRng};
+    let mut rng = StdRng::seed_from_u64(7);
+    let mut bf = BloomFilter::<metro::Hasher64_1>::with_capacity_and_fpp(1000, 0.01).unwrap();
+    let empty = bf.measure_fpp(&mut rng, 1000);
+    assert_eq!((1000, 0), (empty.trials, empty.false_positives));
+    assert!(empty.lower < 1e-12 && empty.upper > 0.0 && empty.upper < 0.01);
+    for i in 0..1000 {
+        bf.add(format!("entry-{i}"));
+    }
+    // the fpp of any one filter varies with the bits its entries happen to set
+    let measured = bf.measure_fpp(&mut rng, 100_000);
+    assert!((measured.rate - 0.01).abs() < 0.002, "{measured:?}");
+    assert!(measured.lower <= measured.rate && measured.rate <= measured.upper);
+
+    let mut cbf =
+        CountingBloomFilter::<metro::Hasher64_1>::with_capacity_and_fpp(1000, 0.01).unwrap();
+    for i in 0..1000 {
+        cbf.add(format!("entry-{i}"));
+    }
+    let measured = cbf.measure_fpp(&mut rng, 100_000);
+    assert!((measured.rate - 0.01).abs() < 0.002, "{measured:?}");
+
+    let none = bf.measure_fpp(&mut rng, 0);
+    assert_eq!((0.0, 0.0, 1.0), (none.rate, none.lower, none.upper));
+}
+
+#[test]
+fn test_config_getters() {
+    let bf = BloomFilter::<metro::Hasher64_1>::new(1000, 3).unwrap();
+    assert_eq!((1000, 3), (bf.num_bits(), bf.num_hashes()));
+    let bytes = bf.to_bytes();
+    let view = BloomFilterView::<metro::Hasher64_1>::new(&bytes).unwrap();
+    assert_eq!((1000, 3), (view.num_bits(), view.num_hashes()));
+
+    let cbf = CountingBloomFilter::<metro::Hasher64_1>::with_bits_per_counter(1000, 5, 8).unwrap();
+    assert_eq!(
+        (1000, 8, 8, 5, None),
+        (
+            cbf.num_counters(),
+            cbf.bits_per_counter(),
+            cbf.counters_per_bin(),
+            cbf.num_hashes(),
+            cbf.morris_base()
+        )
+    );
+    // counters are rounded up to fill the last bin
+    let cbf =
+        CountingBloomFilter::<metro::Hasher64_1>::with_morris_counters(1001, 3, 4, 2.0).unwrap();
+    assert_eq!(
+        (1008, 16, Some(2.0)),
+        (
+            cbf.num_counters(),
+            cbf.counters_per_bin(),
+            cbf.morris_base()
+        )
+    );
+}
+
+#[test]
+fn test_counting_entry() {
+    let mut cbf = CountingBloomFilter::<metro::Hasher64_1>::new(1024, 3).unwrap();
+    let mut entry = cbf.entry("hello");
+    assert!(!entry.contains());
+    if entry.count() == 0 {
+        assert!(entry.insert());
+    }
+    assert!(entry.insert());
+    assert_eq!(2, entry.count());
+    assert!(entry.remove());
+    assert_eq!(1, entry.count());
+    assert_eq!(1, cbf.estimate("hello"));
+
+    // reads and updates keep working after the filter widens under the entry
+    let mut cbf = CountingBloomFilter::<metro::Hasher64_1>::with_bits_per_counter(64, 1, 2)
+        .unwrap()
+        .with_auto_widen(1)
+        .unwrap();
+    let mut entry = cbf.entry("hello");
+    for _ in 0..5 {
+        assert!(entry.insert());
+    }
+    assert_eq!(5, entry.count());
+    assert!(cbf.bits_per_counter() > 2);
+}
+
+#[test]
+fn test_add_and_estimate() {
+    let mut cbf =
+        CountingBloomFilter::<metro::Hasher64_1>::with_bits_per_counter(1024, 3, 2).unwrap();
+    assert_eq!(0, cbf.add_and_estimate("hello"));
+    assert_eq!(1, cbf.add_and_estimate("hello"));
+    assert_eq!(2, cbf.add_and_estimate("hello"));
+    // saturated, so it isn't added
+    assert_eq!(3, cbf.add_and_estimate("hello"));
+    assert_eq!(3, cbf.estimate("hello"));
+    assert_eq!(1, cbf.stats().saturation_events);
+}
+
+#[test]
+fn test_add_n() {
+    let mut cbf = CountingBloomFilter::<metro::Hasher64_1>::new(1024, 3).unwrap();
+    assert!(cbf.add_n("hello", 7));
+    assert_eq!(7, cbf.estimate("hello"));
+    assert!(cbf.add_n("hello", 0));
+    // 4 bit counters cap at 15
+    assert!(!cbf.add_n("hello", 10));
+    assert_eq!(15, cbf.estimate("hello"));
+    let stats = cbf.stats();
+    assert_eq!((17, 1), (stats.inserted, stats.saturation_events));
+
+    assert!(cbf.remove_n("hello", 5));
+    assert_eq!(10, cbf.estimate("hello"));
+    assert!(cbf.remove_n("hello", 100));
+    assert_eq!(0, cbf.estimate("hello"));
+    assert!(!cbf.remove_n("hello", 1));
+
+    let mut morris =
+        CountingBloomFilter::<metro::Hasher64_1>::with_morris_counters(1024, 3, 8, 1.1).unwrap();
+    assert!(morris.add_n("popular", 1000));
+    let estimate = morris.estimate("popular");
+    assert!((950..=1050).contains(&estimate), "{estimate}");
+    assert!(morris.remove_n("popular", 1000));
+    assert_eq!(0, morris.estimate("popular"));
+}
+
+#[test]
+fn test_remove_all() {
+    let mut cbf =
+        CountingBloomFilter::<metro::Hasher64_1>::with_bits_per_counter(1024, 3, 8).unwrap();
+    cbf.add_n("hot", 40);
+    cbf.add_n("cold", 2);
+    assert_eq!(40, cbf.remove_all("hot"));
+    assert!(!cbf.contains("hot"));
+    assert_eq!(2, cbf.estimate("cold"));
+    assert_eq!(0, cbf.remove_all("hot"));
+
+    let mut morris =
+        CountingBloomFilter::<metro::Hasher64_1>::with_morris_counters(1024, 3, 8, 1.1).unwrap();
+    morris.add_n("hot", 500);
+    assert!(morris.remove_all("hot") > 0);
+    assert!(!morris.contains("hot"));
+}
+
+#[test]
+fn test_estimate_bounds() {
+    let mut cbf =
+        CountingBloomFilter::<metro::Hasher64_1>::with_bits_per_counter(1024, 3, 8).unwrap();
+    cbf.add_n("hello", 10);
+    assert_eq!((10, 10), cbf.estimate_bounds("hello"));
+    assert_eq!(10, cbf.estimate_max("hello"));
+    assert_eq!((0, 0), cbf.estimate_bounds("absent"));
+
+    // a colliding entry inflates one of hello's counters
+    let hashed = HashedKey::<metro::Hasher64_1>::new("hello");
+    let first = hashed
+        .indexes(None, 3, cbf.num_counters() as u64)
+        .next()
+        .unwrap();
+    let c = cbf.counter(first);
+    cbf.counter_bins[c.bin] = c.set(cbf.counter_bins[c.bin], 16);
+    assert_eq!(16, cbf.estimate_max("hello"));
+    assert_eq!(10, cbf.estimate("hello"));
+    // collisions are rare in a nearly empty filter, so the smallest counter is trusted
+    assert_eq!((10, 10), cbf.estimate_bounds("hello"));
+
+    // once saturated, there's no upper bound
+    cbf.add_n("hot", 1000);
+    let (lower, upper) = cbf.estimate_bounds("hot");
+    assert_eq!((254, usize::MAX), (lower, upper));
+}
+
+#[test]
+fn test_estimate_bounds_uniform_noise() {
+    // every counter carries the same collisions, so all of an entry's counters overcount
+    for (noise, adds) in [(1, 1), (1, 3), (5, 20), (40, 7)] {
+        let mut cbf =
+            CountingBloomFilter::<metro::Hasher64_1>::with_bits_per_counter(1024, 3, 8).unwrap();
+        let per_bin = (0..cbf.counters_per_bin).fold(0, |bin, i| bin | noise << (i * 8));
+        cbf.counter_bins.iter_mut().for_each(|bin| *bin = per_bin);
+        cbf.add_n("hello", adds);
+
+        let (lower, upper) = cbf.estimate_bounds("hello");
+        assert_eq!(noise as usize + adds as usize, upper);
+        assert!(lower <= adds as usize, "{} {} {}", noise, adds, lower);
+    }
+}
+
+#[test]
+fn test_heavy_hitters() {
+    let mut cbf = CountingBloomFilter::<metro::Hasher64_1>::with_bits_per_counter(4096, 3, 8)
+        .unwrap()
+        .with_heavy_hitters(10, 2)
+        .unwrap();
+    assert!(cbf.heavy_hitters().is_empty());
+    for (key, n) in [("a", 12), ("b", 30), ("c", 5)] {
+        for _ in 0..n {
+            cbf.add(key);
+        }
+    }
+    assert_eq!(vec![(&b"b"[..], 30), (&b"a"[..], 12)], cbf.heavy_hitters());
+    // full, so a heavier key replaces the lightest
+    cbf.add_n("d", 20);
+    assert_eq!(vec![(&b"b"[..], 30), (&b"d"[..], 20)], cbf.heavy_hitters());
+    // and a lighter one doesn't
+    cbf.add_n("e", 15);
+    assert_eq!(2, cbf.heavy_hitters().len());
+    // keys falling below the threshold are dropped
+    cbf.remove_n("d", 15);
+    assert_eq!(vec![(&b"b"[..], 30)], cbf.heavy_hitters());
+    cbf.remove_all("b");
+    assert!(cbf.heavy_hitters().is_empty());
+
+    assert!(matches!(
+        CountingBloomFilter::<metro::Hasher64_1>::new(64, 3)
+            .unwrap()
+            .with_heavy_hitters(0, 2),
+        Err(Error::InvalidHeavyHitters(0, 2))
+    ));
+}
+
+#[test]
+fn test_subtract_scaled() {
+    let mut recent =
+        CountingBloomFilter::<metro::Hasher64_1>::with_bits_per_counter(1024, 3, 8).unwrap();
+    let mut baseline = recent.clone();
+    recent.add_n("spike", 10);
+    recent.add_n("steady", 4);
+    baseline.add_n("steady", 10);
+    baseline.add_n("spike", 4);
+    recent.subtract_scaled(&baseline, 0.5).unwrap();
+    assert_eq!(8, recent.estimate("spike"));
+    assert!(!recent.contains("steady"));
+
+    assert!(matches!(
+        recent.subtract_scaled(&baseline, f64::INFINITY),
+        Err(Error::InvalidScaleFactor(_))
+    ));
+    let narrow =
+        CountingBloomFilter::<metro::Hasher64_1>::with_bits_per_counter(1024, 3, 4).unwrap();
+    assert!(matches!(
+        recent.subtract_scaled(&narrow, 1.0),
+        Err(Error::IncompatibleFilter)
+    ));
+
+    // heavy hitters are estimated again
+    let mut tracked = CountingBloomFilter::<metro::Hasher64_1>::with_bits_per_counter(1024, 3, 8)
+        .unwrap()
+        .with_heavy_hitters(5, 4)
+        .unwrap();
+    tracked.add_n("spike", 10);
+    tracked.add_n("steady", 6);
+    tracked.subtract_scaled(&baseline, 0.5).unwrap();
+    assert_eq!(vec![(b"spike".as_slice(), 8)], tracked.heavy_hitters());
+}
+
+#[test]
+fn test_bloom_join() {
+    let orders = (0..5000)
+        .map(|i| format!("customer{}", i % 2000))
+        .collect::<Vec<_>>();
+    let vip = (0..100)
+        .map(|i| format!("customer{}", i * 30))
+        .collect::<Vec<_>>();
+    let joined = bloom_join::<metro::Hasher64_1, _, _, _>(&orders, &vip, 0.01).unwrap();
+    assert_eq!(JoinSide::Left, joined.filtered);
+    // every order of the 67 vip customers who placed one survives, as do about 1% of the rest
+    let matched = orders.iter().filter(|order| vip.contains(order)).count();
+    let survivors = joined.survivors.iter().copied();
+    assert_eq!(
+        matched,
+        survivors.filter(|&order| vip.contains(order)).count()
+    );
+    assert!(joined.survivors.len() - matched < 250);
+
+    let joined = bloom_join::<metro::Hasher64_1, _, _, _>(&vip, &orders, 0.01).unwrap();
+    assert_eq!(JoinSide::Right, joined.filtered);
+    assert_eq!(
+        JoinSide::Left,
+        bloom_join::<metro::Hasher64_1, _, _, _>(&vip, &vip, 0.01)
+            .unwrap()
+            .filtered
+    );
+
+    let none = Vec::<String>::new();
+    let joined = bloom_join::<metro::Hasher64_1, _, _, _>(&orders, &none, 0.01).unwrap();
+    assert!(joined.survivors.is_empty());
+    assert!(matches!(
+        bloom_join::<metro::Hasher64_1, _, _, _>(&orders, &none, 0.0),
+        Err(Error::InvalidFpp(_))
+    ));
+}
+
+#[test]
+fn test_merge_diff_saturated() {
+    // counters saturated by a merge count towards widening, and removing from them is fine
+    let new = || {
+        CountingBloomFilter::<metro::Hasher64_1>::with_bits_per_counter(1024, 3, 2)
+            .unwrap()
+            .with_auto_widen(100)
+            .unwrap()
+    };
+    let mut remote =
+        CountingBloomFilter::<metro::Hasher64_1>::with_bits_per_counter(1024, 3, 2).unwrap();
+    remote.add_n("hot", 3);
+    let mut local = new();
+    let diff = remote.diff_for(&local.summary(4).unwrap()).unwrap();
+    local.merge_diff(&diff).unwrap();
+    assert_eq!(3, local.saturated_counters);
+    assert!(local.remove("hot"));
+    assert_eq!(0, local.saturated_counters);
+
+    // a merge that saturates enough counters widens them
+    let mut local = new().with_auto_widen(3).unwrap();
+    local.merge_diff(&diff).unwrap();
+    assert_eq!(4, local.bits_per_counter());
+    assert_eq!(3, local.estimate("hot"));
+}
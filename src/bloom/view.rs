@@ -0,0 +1,95 @@
+use std::marker::PhantomData;
+
+use super::{
+    check_hash_scheme,
+    index::{self, HashedKey, KEY_LEN},
+    standard::{serialized_len, FORMAT_VERSION, HEADER_LEN, MAGIC},
+    Error,
+};
+use crate::format::{self, CHECKSUM_LEN};
+use crate::hasher::SeedableHasher;
+
+/// A read-only [super::BloomFilter] that answers lookups straight from the bytes written by
+/// [super::BloomFilter::to_bytes], without copying the bit array.
+///
+/// Opening a view validates the header and the length of the buffer, but not the checksum,
+/// which would mean reading the whole buffer.  Backed by a memory mapped file, only the pages
+/// holding the bits that are looked up get read.
+pub struct BloomFilterView<'a, T>
+where
+    T: SeedableHasher,
+{
+    bits: &'a [u8],
+    num_bits: u64,
+    n_hashes: u32,
+    key: Option<[u8; KEY_LEN]>,
+    _hasher: PhantomData<T>,
+}
+
+impl<'a, T> BloomFilterView<'a, T>
+where
+    T: SeedableHasher,
+{
+    /// Open a view over a serialized filter.  The bit array may start at any alignment.
+    pub fn new(bytes: &'a [u8]) -> Result<Self, Error> {
+        format::check_header(bytes, &MAGIC, FORMAT_VERSION)?;
+        if bytes.len() < HEADER_LEN {
+            return Err(Error::Truncated);
+        }
+        format::check_len(bytes, serialized_len(bytes))?;
+        let num_bits = u64::from_le_bytes(bytes[5..13].try_into().unwrap());
+        let n_hashes = u32::from_le_bytes(bytes[13..17].try_into().unwrap());
+        let key = index::key_from_bytes(&bytes[17..])?;
+        check_hash_scheme(bytes[HEADER_LEN - 1])?;
+        let bits = &bytes[HEADER_LEN..bytes.len() - CHECKSUM_LEN];
+        if num_bits == 0 {
+            return Err(Error::InvalidBinCount(0));
+        }
+        if n_hashes == 0 || n_hashes as u64 > num_bits {
+            return Err(Error::InvalidHashCount(n_hashes));
+        }
+        Ok(BloomFilterView {
+            bits,
+            num_bits,
+            n_hashes,
+            key,
+            _hasher: PhantomData,
+        })
+    }
+
+    /// Number of bits in the filter.
+    pub fn num_bits(&self) -> u64 {
+        self.num_bits
+    }
+
+    pub fn num_hashes(&self) -> u32 {
+        self.n_hashes
+    }
+
+    /// Determine if filter contains the provided entry.
+    pub fn contains<I>(&self, entry: I) -> bool
+    where
+        I: AsRef<[u8]>,
+    {
+        self.contains_prehashed(&HashedKey::hash(entry.as_ref(), self.key.as_ref()))
+    }
+
+    /// Determine if filter contains an entry by a hash computed by the caller, as for
+    /// [super::BloomFilter::add_hashed].
+    pub fn contains_hashed<H>(&self, hash: H) -> bool
+    where
+        H: Into<HashedKey<T>>,
+    {
+        self.contains_prehashed(&hash.into())
+    }
+
+    /// Determine if filter contains an entry hashed ahead of time.
+    ///
+    /// Panics if `hashed` was hashed with a different key than this filter's.
+    pub fn contains_prehashed(&self, hashed: &HashedKey<T>) -> bool {
+        // little endian words put bit i in byte i / 8
+        hashed
+            .indexes(self.key.as_ref(), self.n_hashes, self.num_bits)
+            .all(|bit| self.bits[(bit / 8) as usize] & (1 << (bit % 8)) != 0)
+    }
+}
@@ -0,0 +1,71 @@
+use std::{
+    fmt,
+    ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign, Not},
+};
+
+/// The unsigned integer a [super::BloomFilter]'s bits are stored in, [u64] by default or [u32]
+/// for 32 bit and embedded targets where 64 bit operations are slow.
+///
+/// Either way, bits are numbered the same and serialized as little endian u64 words, so the
+/// word type only changes the in-memory layout, and filters serialized with one load with the
+/// other.
+pub trait Word:
+    Copy
+    + Eq
+    + fmt::Debug
+    + Send
+    + Sync
+    + 'static
+    + BitAnd<Output = Self>
+    + BitOr<Output = Self>
+    + Not<Output = Self>
+    + BitAndAssign
+    + BitOrAssign
+    + private::Sealed
+{
+    /// Number of bits in a word, which divides 64.
+    const BITS: u32;
+    const ZERO: Self;
+
+    /// The word with only bit `i` set.
+    fn bit(i: u32) -> Self;
+    fn count_ones(self) -> u32;
+    fn to_u64(self) -> u64;
+    /// The low [Word::BITS] bits of `value`.
+    fn from_u64(value: u64) -> Self;
+}
+
+macro_rules! impl_word {
+    ($($t:ty),*) => {
+        $(
+            impl private::Sealed for $t {}
+
+            impl Word for $t {
+                const BITS: u32 = <$t>::BITS;
+                const ZERO: Self = 0;
+
+                fn bit(i: u32) -> Self {
+                    1 << i
+                }
+
+                fn count_ones(self) -> u32 {
+                    <$t>::count_ones(self)
+                }
+
+                fn to_u64(self) -> u64 {
+                    self as u64
+                }
+
+                fn from_u64(value: u64) -> Self {
+                    value as $t
+                }
+            }
+        )*
+    };
+}
+
+impl_word!(u32, u64);
+
+mod private {
+    pub trait Sealed {}
+}
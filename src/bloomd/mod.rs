@@ -0,0 +1,453 @@
+//! The [bloomd](https://github.com/armon/bloomd) text protocol, so existing bloomd clients can
+//! talk to filters from this crate, and services can talk to a bloomd server.
+//!
+//! Commands are single lines of whitespace separated words, and keys can't contain whitespace.
+//! Filters are [BloomFilter]s sized for the requested capacity and false positive
+//! probability, rather than bloomd's scalable filters, so they don't grow past their capacity
+//! and live only in memory.  Each filter is limited to 256MB, about 100 million entries at the
+//! default probability, and a server holds 4GB of filters unless told otherwise.
+use std::{
+    collections::BTreeMap,
+    io::{self, BufRead, BufReader, Read, Write},
+    net::{TcpListener, TcpStream, ToSocketAddrs},
+    str,
+    sync::{Arc, Mutex},
+    thread,
+};
+
+use fasthash::metro;
+use thiserror::Error;
+
+use crate::{bloom::Error as BloomError, sizing, BloomFilter};
+
+#[cfg(test)]
+mod test;
+
+const DEFAULT_CAPACITY: usize = 100_000;
+const DEFAULT_PROBABILITY: f64 = 0.0001;
+// so one client can't exhaust the server's memory, neither through the number of entries nor
+// a tiny probability: 256MB, 100 million entries at the default probability
+const MAX_CAPACITY: usize = 100_000_000;
+const MAX_FILTER_BITS: usize = 1 << 31;
+const DEFAULT_MAX_STORAGE: usize = 4 << 30;
+// bulk commands carry many keys, but a line without an end must not grow forever
+const MAX_LINE_LEN: usize = 1 << 20;
+
+const DONE: &str = "Done";
+const EXISTS: &str = "Exists";
+const NO_FILTER: &str = "Filter does not exist";
+const BAD_ARGS: &str = "Client Error: Bad arguments";
+const UNSUPPORTED: &str = "Client Error: Command not supported";
+const ALLOCATION_FAILED: &str = "Internal Error: Failed to allocate the filter";
+const STORAGE_FULL: &str = "Internal Error: Server storage limit reached";
+const LINE_TOO_LONG: &str = "Client Error: Command too long";
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("Filter {0:?} does not exist")]
+    NoSuchFilter(String),
+
+    #[error("Invalid key {0:?}: must be non-empty and contain no whitespace")]
+    InvalidKey(String),
+
+    #[error("Server rejected the command: {0}")]
+    Rejected(String),
+
+    #[error("Unexpected response {0:?}")]
+    UnexpectedResponse(String),
+}
+
+struct NamedFilter {
+    filter: BloomFilter<metro::Hasher64_1>,
+    capacity: usize,
+    probability: f64,
+    storage: usize,
+    size: usize,
+    check_hits: u64,
+    check_misses: u64,
+    set_hits: u64,
+    set_misses: u64,
+}
+
+impl NamedFilter {
+    fn check(&mut self, key: &str) -> bool {
+        let hit = self.filter.contains(key);
+        match hit {
+            true => self.check_hits += 1,
+            false => self.check_misses += 1,
+        }
+        hit
+    }
+
+    fn set(&mut self, key: &str) -> bool {
+        let added = self.filter.add(key);
+        match added {
+            true => {
+                self.set_hits += 1;
+                self.size += 1;
+            }
+            false => self.set_misses += 1,
+        }
+        added
+    }
+}
+
+/// Serves the bloomd protocol from filters held in memory.  Clones share the same filters.
+///
+/// Supports `create`, `list`, `drop`, `close`, `clear`, `check`, `multi`, `set`, `bulk`,
+/// `info` and `flush`, and the one letter aliases of `check`, `multi`, `set` and `bulk`.
+/// Closing, clearing and flushing are no-ops, as nothing is paged out or persisted.
+#[derive(Clone)]
+pub struct BloomdServer {
+    filters: Arc<Mutex<BTreeMap<String, NamedFilter>>>,
+    max_storage: usize,
+}
+
+impl Default for BloomdServer {
+    fn default() -> Self {
+        BloomdServer {
+            filters: Arc::default(),
+            max_storage: DEFAULT_MAX_STORAGE,
+        }
+    }
+}
+
+impl BloomdServer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Limit the bytes held by all filters together, 4GB by default.  Creating a filter that
+    /// would go over it fails.
+    pub fn with_max_storage(mut self, bytes: usize) -> Self {
+        self.max_storage = bytes;
+        self
+    }
+
+    /// Accept connections until the listener fails, serving each on its own thread.
+    pub fn serve(&self, listener: TcpListener) -> io::Result<()> {
+        loop {
+            let (stream, _) = listener.accept()?;
+            let server = self.clone();
+            // a connection that fails only affects its own client
+            thread::spawn(move || server.serve_connection(stream));
+        }
+    }
+
+    /// Answer commands from one client until it disconnects.  A client sending a line longer
+    /// than 1MB is told so and disconnected, as the rest of the line can't be told apart from
+    /// the next command.
+    pub fn serve_connection(&self, stream: TcpStream) -> io::Result<()> {
+        let mut writer = stream.try_clone()?;
+        let mut reader = BufReader::new(stream);
+        let mut line = vec![];
+        loop {
+            line.clear();
+            let limit = MAX_LINE_LEN as u64 + 1;
+            if reader.by_ref().take(limit).read_until(b'\n', &mut line)? == 0 {
+                return Ok(());
+            }
+            let response = match line.strip_suffix(b"\n") {
+                None if line.len() > MAX_LINE_LEN => {
+                    writer.write_all(LINE_TOO_LONG.as_bytes())?;
+                    return writer.write_all(b"\n");
+                }
+                stripped => {
+                    let line = str::from_utf8(stripped.unwrap_or(&line))
+                        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+                    self.handle_line(line)
+                }
+            };
+            writer.write_all(response.as_bytes())?;
+            writer.write_all(b"\n")?;
+        }
+    }
+
+    /// Run one command line, returning the response without its trailing newline, so the
+    /// protocol can be served over other transports.
+    pub fn handle_line(&self, line: &str) -> String {
+        let mut words = line.split_whitespace();
+        let Some(command) = words.next() else {
+            return UNSUPPORTED.to_string();
+        };
+        let args: Vec<&str> = words.collect();
+        let mut filters = self.filters.lock().unwrap();
+        match (command, args.as_slice()) {
+            ("create", [name, options @ ..]) => {
+                let Some((capacity, probability)) = parse_create_options(options) else {
+                    return BAD_ARGS.to_string();
+                };
+                if filters.contains_key(*name) {
+                    return EXISTS.to_string();
+                }
+                let Some(num_bits) = sizing::optimal_bits(capacity, probability)
+                    .ok()
+                    .filter(|&bits| bits <= MAX_FILTER_BITS)
+                else {
+                    return BAD_ARGS.to_string();
+                };
+                let used: usize = filters.values().map(|f| f.storage).sum();
+                if used + num_bits / 8 > self.max_storage {
+                    return STORAGE_FULL.to_string();
+                }
+                let filter = match BloomFilter::try_with_capacity_and_fpp(capacity, probability) {
+                    Ok(filter) => filter,
+                    Err(BloomError::AllocationFailed(_)) => return ALLOCATION_FAILED.to_string(),
+                    Err(_) => return BAD_ARGS.to_string(),
+                };
+                let storage = filter.mem_usage();
+                filters.insert(
+                    name.to_string(),
+                    NamedFilter {
+                        filter,
+                        capacity,
+                        probability,
+                        storage,
+                        size: 0,
+                        check_hits: 0,
+                        check_misses: 0,
+                        set_hits: 0,
+                        set_misses: 0,
+                    },
+                );
+                DONE.to_string()
+            }
+            ("list", [] | [_]) => {
+                let prefix = args.first().copied().unwrap_or("");
+                let mut lines = vec!["START".to_string()];
+                for (name, f) in filters.range(prefix.to_string()..) {
+                    if !name.starts_with(prefix) {
+                        break;
+                    }
+                    lines.push(format!(
+                        "{} {:.6} {} {} {}",
+                        name, f.probability, f.storage, f.capacity, f.size
+                    ));
+                }
+                lines.push("END".to_string());
+                lines.join("\n")
+            }
+            ("drop", [name]) => match filters.remove(*name) {
+                Some(_) => DONE.to_string(),
+                None => NO_FILTER.to_string(),
+            },
+            ("close" | "clear", [name]) => match filters.contains_key(*name) {
+                true => DONE.to_string(),
+                false => NO_FILTER.to_string(),
+            },
+            ("flush", [] | [_]) => DONE.to_string(),
+            ("info", [name]) => match filters.get(*name) {
+                Some(f) => [
+                    "START".to_string(),
+                    format!("capacity {}", f.capacity),
+                    format!("checks {}", f.check_hits + f.check_misses),
+                    format!("check_hits {}", f.check_hits),
+                    format!("check_misses {}", f.check_misses),
+                    "page_ins 0".to_string(),
+                    "page_outs 0".to_string(),
+                    format!("probability {:.6}", f.probability),
+                    format!("sets {}", f.set_hits + f.set_misses),
+                    format!("set_hits {}", f.set_hits),
+                    format!("set_misses {}", f.set_misses),
+                    format!("size {}", f.size),
+                    format!("storage {}", f.storage),
+                    "END".to_string(),
+                ]
+                .join("\n"),
+                None => NO_FILTER.to_string(),
+            },
+            ("check" | "c" | "set" | "s", [name, key]) => match filters.get_mut(*name) {
+                Some(f) if command.starts_with('c') => yes_no(f.check(key)).to_string(),
+                Some(f) => yes_no(f.set(key)).to_string(),
+                None => NO_FILTER.to_string(),
+            },
+            ("multi" | "m" | "bulk" | "b", [name, keys @ ..]) if !keys.is_empty() => {
+                match filters.get_mut(*name) {
+                    Some(f) => keys
+                        .iter()
+                        .map(|key| match command.starts_with('m') {
+                            true => yes_no(f.check(key)),
+                            false => yes_no(f.set(key)),
+                        })
+                        .collect::<Vec<_>>()
+                        .join(" "),
+                    None => NO_FILTER.to_string(),
+                }
+            }
+            (
+                "create" | "list" | "drop" | "close" | "clear" | "flush" | "info" | "check" | "c"
+                | "set" | "s" | "multi" | "m" | "bulk" | "b",
+                _,
+            ) => BAD_ARGS.to_string(),
+            _ => UNSUPPORTED.to_string(),
+        }
+    }
+}
+
+/// A client for a bloomd server, or a [BloomdServer].
+pub struct BloomdClient {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+}
+
+impl BloomdClient {
+    pub fn connect<A>(addr: A) -> Result<Self, Error>
+    where
+        A: ToSocketAddrs,
+    {
+        let writer = TcpStream::connect(addr)?;
+        Ok(BloomdClient {
+            reader: BufReader::new(writer.try_clone()?),
+            writer,
+        })
+    }
+
+    /// Create a filter, using the server's defaults for any parameter that is None.  Returns
+    /// false if the filter already exists.
+    pub fn create(
+        &mut self,
+        name: &str,
+        capacity: Option<usize>,
+        probability: Option<f64>,
+    ) -> Result<bool, Error> {
+        check_key(name)?;
+        let mut command = format!("create {}", name);
+        if let Some(capacity) = capacity {
+            command.push_str(&format!(" capacity={}", capacity));
+        }
+        if let Some(probability) = probability {
+            command.push_str(&format!(" prob={}", probability));
+        }
+        match self.request(&command)?.as_str() {
+            DONE => Ok(true),
+            EXISTS => Ok(false),
+            other => Err(unexpected(other, name)),
+        }
+    }
+
+    /// Names of the filters starting with `prefix`.
+    pub fn list(&mut self, prefix: &str) -> Result<Vec<String>, Error> {
+        let command = match prefix {
+            "" => "list".to_string(),
+            _ => format!("list {}", prefix),
+        };
+        let first = self.request(&command)?;
+        if first != "START" {
+            return Err(unexpected(&first, prefix));
+        }
+        let mut names = vec![];
+        loop {
+            let line = self.read_line()?;
+            if line == "END" {
+                return Ok(names);
+            }
+            match line.split_whitespace().next() {
+                Some(name) => names.push(name.to_string()),
+                None => return Err(Error::UnexpectedResponse(line)),
+            }
+        }
+    }
+
+    pub fn drop(&mut self, name: &str) -> Result<(), Error> {
+        check_key(name)?;
+        match self.request(&format!("drop {}", name))?.as_str() {
+            DONE => Ok(()),
+            other => Err(unexpected(other, name)),
+        }
+    }
+
+    /// Check whether the filter contains a key.
+    pub fn check(&mut self, name: &str, key: &str) -> Result<bool, Error> {
+        Ok(self.keys_command("check", name, &[key])?[0])
+    }
+
+    /// Check several keys at once.
+    pub fn multi(&mut self, name: &str, keys: &[&str]) -> Result<Vec<bool>, Error> {
+        self.keys_command("multi", name, keys)
+    }
+
+    /// Add a key, returning true if it wasn't already present.
+    pub fn set(&mut self, name: &str, key: &str) -> Result<bool, Error> {
+        Ok(self.keys_command("set", name, &[key])?[0])
+    }
+
+    /// Add several keys at once.
+    pub fn bulk(&mut self, name: &str, keys: &[&str]) -> Result<Vec<bool>, Error> {
+        self.keys_command("bulk", name, keys)
+    }
+
+    fn keys_command(
+        &mut self,
+        command: &str,
+        name: &str,
+        keys: &[&str],
+    ) -> Result<Vec<bool>, Error> {
+        check_key(name)?;
+        keys.iter().try_for_each(|key| check_key(key))?;
+        let response = self.request(&format!("{} {} {}", command, name, keys.join(" ")))?;
+        let answers = response
+            .split(' ')
+            .map(|word| match word {
+                "Yes" => Some(true),
+                "No" => Some(false),
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>();
+        match answers {
+            Some(answers) if answers.len() == keys.len() => Ok(answers),
+            _ => Err(unexpected(&response, name)),
+        }
+    }
+
+    fn request(&mut self, command: &str) -> Result<String, Error> {
+        self.writer.write_all(command.as_bytes())?;
+        self.writer.write_all(b"\n")?;
+        self.read_line()
+    }
+
+    fn read_line(&mut self) -> Result<String, Error> {
+        let mut line = String::new();
+        if self.reader.read_line(&mut line)? == 0 {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+        }
+        Ok(line.trim_end().to_string())
+    }
+}
+
+fn parse_create_options(options: &[&str]) -> Option<(usize, f64)> {
+    let (mut capacity, mut probability) = (DEFAULT_CAPACITY, DEFAULT_PROBABILITY);
+    for option in options {
+        match option.split_once('=')? {
+            ("capacity", value) => capacity = value.parse().ok().filter(|&c| c <= MAX_CAPACITY)?,
+            ("prob", value) => probability = value.parse().ok()?,
+            ("in_memory", "0" | "1") => {}
+            _ => return None,
+        }
+    }
+    Some((capacity, probability))
+}
+
+fn yes_no(answer: bool) -> &'static str {
+    match answer {
+        true => "Yes",
+        false => "No",
+    }
+}
+
+fn check_key(key: &str) -> Result<(), Error> {
+    match key.is_empty() || key.contains(char::is_whitespace) {
+        true => Err(Error::InvalidKey(key.to_string())),
+        false => Ok(()),
+    }
+}
+
+fn unexpected(response: &str, name: &str) -> Error {
+    match response {
+        NO_FILTER => Error::NoSuchFilter(name.to_string()),
+        _ if response.contains("Error") => Error::Rejected(response.to_string()),
+        _ => Error::UnexpectedResponse(response.to_string()),
+    }
+}
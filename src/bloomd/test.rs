@@ -0,0 +1,151 @@
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream},
+    thread,
+};
+
+use super::{BloomdClient, BloomdServer, Error};
+
+#[test]
+fn test_handle_line() {
+    let server = BloomdServer::new();
+    assert_eq!(
+        "Done",
+        server.handle_line("create foo capacity=1000 prob=0.01")
+    );
+    assert_eq!("Exists", server.handle_line("create foo"));
+    assert_eq!("Done", server.handle_line("create foobar"));
+    assert_eq!("Done", server.handle_line("create other"));
+    assert_eq!(
+        "Client Error: Bad arguments",
+        server.handle_line("create bad prob=2")
+    );
+    // too large to allocate, rejected rather than aborting the server
+    assert_eq!(
+        "Client Error: Bad arguments",
+        server.handle_line("create big capacity=100000000000 prob=0.0001")
+    );
+    // a tiny probability would need far more bits than the capacity suggests
+    assert_eq!(
+        "Client Error: Bad arguments",
+        server.handle_line("create tiny capacity=100000000 prob=1e-300")
+    );
+    assert_eq!(
+        "Client Error: Bad arguments",
+        server.handle_line("create tiny capacity=100000000 prob=1e-30")
+    );
+
+    assert_eq!("Yes", server.handle_line("set foo a"));
+    assert_eq!("No", server.handle_line("s foo a"));
+    assert_eq!("Yes", server.handle_line("check foo a"));
+    assert_eq!("No", server.handle_line("c foo z"));
+    assert_eq!("Yes Yes No", server.handle_line("bulk foo b c a"));
+    assert_eq!("Yes Yes No", server.handle_line("m foo a b z"));
+    assert_eq!("Filter does not exist", server.handle_line("check nope a"));
+
+    let list = server.handle_line("list foo");
+    let lines: Vec<_> = list.lines().collect();
+    assert_eq!("START", lines[0]);
+    assert!(lines[1].starts_with("foo 0.010000 "), "{}", lines[1]);
+    assert!(lines[1].ends_with(" 1000 3"), "{}", lines[1]);
+    assert!(lines[2].starts_with("foobar "), "{}", lines[2]);
+    assert_eq!("END", lines[3]);
+
+    let info = server.handle_line("info foo");
+    assert!(
+        info.contains("\nsets 5\nset_hits 3\nset_misses 2\n"),
+        "{}",
+        info
+    );
+    assert!(
+        info.contains("\nchecks 5\ncheck_hits 3\ncheck_misses 2\n"),
+        "{}",
+        info
+    );
+
+    assert_eq!("Done", server.handle_line("drop foo"));
+    assert_eq!("Filter does not exist", server.handle_line("drop foo"));
+    assert_eq!(
+        "Client Error: Bad arguments",
+        server.handle_line("check other")
+    );
+    assert_eq!(
+        "Client Error: Command not supported",
+        server.handle_line("frobnicate")
+    );
+}
+
+#[test]
+fn test_client_server() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = BloomdServer::new();
+    thread::spawn(move || server.serve(listener));
+
+    let mut client = BloomdClient::connect(addr).unwrap();
+    assert!(client.create("users", Some(10_000), Some(0.001)).unwrap());
+    assert!(!client.create("users", None, None).unwrap());
+    assert!(client.set("users", "alice").unwrap());
+    assert!(!client.set("users", "alice").unwrap());
+    assert_eq!(
+        vec![true, false],
+        client.bulk("users", &["bob", "bob"]).unwrap()
+    );
+    assert!(client.check("users", "alice").unwrap());
+    assert_eq!(
+        vec![true, true, false],
+        client.multi("users", &["alice", "bob", "carol"]).unwrap()
+    );
+    assert_eq!(vec!["users"], client.list("").unwrap());
+
+    // a second connection sees the same filters
+    let mut other = BloomdClient::connect(addr).unwrap();
+    assert!(other.check("users", "bob").unwrap());
+    other.drop("users").unwrap();
+    assert!(matches!(
+        client.check("users", "alice"),
+        Err(Error::NoSuchFilter(_))
+    ));
+    assert!(matches!(
+        client.set("users", "has space"),
+        Err(Error::InvalidKey(_))
+    ));
+}
+
+#[test]
+fn test_max_storage() {
+    let server = BloomdServer::new().with_max_storage(1 << 20);
+    assert_eq!(
+        "Done",
+        server.handle_line("create a capacity=600000 prob=0.01")
+    );
+    assert_eq!(
+        "Internal Error: Server storage limit reached",
+        server.handle_line("create b capacity=600000 prob=0.01")
+    );
+    assert_eq!("Done", server.handle_line("drop a"));
+    assert_eq!(
+        "Done",
+        server.handle_line("create b capacity=600000 prob=0.01")
+    );
+}
+
+#[test]
+fn test_line_too_long() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = BloomdServer::new();
+    thread::spawn(move || server.serve(listener));
+
+    let mut stream = TcpStream::connect(addr).unwrap();
+    stream.write_all(b"create foo\n").unwrap();
+    // exactly one byte over, so the server reads all of it before hanging up
+    stream.write_all(&vec![b'a'; (1 << 20) + 1]).unwrap();
+    let mut lines = BufReader::new(stream).lines();
+    assert_eq!("Done", lines.next().unwrap().unwrap());
+    assert_eq!(
+        "Client Error: Command too long",
+        lines.next().unwrap().unwrap()
+    );
+    assert!(lines.next().is_none());
+}
@@ -0,0 +1,204 @@
+use std::{collections::TryReserveError, marker::PhantomData, mem};
+use thiserror::Error;
+
+use crate::{
+    hasher::{fastrange, mix, SeedableHasher},
+    mem::{heap_size, try_filled},
+};
+
+#[cfg(test)]
+mod test;
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("Invalid width {0}: must be > 0")]
+    InvalidWidth(usize),
+
+    #[error("Invalid depth {0}: must be > 0")]
+    InvalidDepth(u32),
+
+    #[error("Sketches were created with different parameters")]
+    IncompatibleSketch,
+
+    #[error("Invalid scale factor {0}: must be finite and >= 0")]
+    InvalidScaleFactor(f64),
+
+    #[error("Too many counters: {0} * {1} overflows usize")]
+    TooManyCounters(usize, u32),
+
+    #[error("Failed to allocate the sketch: {0}")]
+    AllocationFailed(#[from] TryReserveError),
+}
+
+/// Implementation of a [count-min sketch](https://en.wikipedia.org/wiki/Count%E2%80%93min_sketch).
+///
+/// The sketch has `depth` rows of `width` counters, each row hashing entries with a different
+/// seed.  Estimates never undercount, and overcount by at most `e * N / width` with probability
+/// `1 - e^-depth`, where `N` is the total of all counts.
+pub struct CountMinSketch<T>
+where
+    T: SeedableHasher,
+{
+    counters: Vec<u64>,
+    width: usize,
+    depth: u32,
+    _hasher: PhantomData<T>,
+}
+
+impl<T> CountMinSketch<T>
+where
+    T: SeedableHasher,
+{
+    /// Create a new sketch of `depth` rows with `width` counters each.
+    ///
+    /// `width` and `depth` must be greater than 0.
+    pub fn new(width: usize, depth: u32) -> Result<Self, Error> {
+        if width == 0 {
+            return Err(Error::InvalidWidth(width));
+        }
+        if depth == 0 {
+            return Err(Error::InvalidDepth(depth));
+        }
+        let len = width
+            .checked_mul(depth as usize)
+            .ok_or(Error::TooManyCounters(width, depth))?;
+        Ok(CountMinSketch {
+            counters: try_filled(len, 0)?,
+            width,
+            depth,
+            _hasher: PhantomData,
+        })
+    }
+
+    /// Add an entry to the sketch, incrementing one counter in each row.  Counters saturate at
+    /// [u64::MAX].
+    pub fn add<I>(&mut self, entry: I)
+    where
+        I: AsRef<[u8]>,
+    {
+        for row in 0..self.depth {
+            let idx = self.index(row, entry.as_ref());
+            self.counters[idx] = self.counters[idx].saturating_add(1);
+        }
+    }
+
+    /// Add an entry as [CountMinSketch::add] does, returning its estimate from before it was
+    /// added, from the same pass over its counters.
+    pub fn add_and_estimate<I>(&mut self, entry: I) -> u64
+    where
+        I: AsRef<[u8]>,
+    {
+        let mut prior = u64::MAX;
+        for row in 0..self.depth {
+            let idx = self.index(row, entry.as_ref());
+            prior = prior.min(self.counters[idx]);
+            self.counters[idx] = self.counters[idx].saturating_add(1);
+        }
+        prior
+    }
+
+    /// Returns an estimate of the number of times entry was added, the minimum of its counters.
+    pub fn estimate<I>(&self, entry: I) -> u64
+    where
+        I: AsRef<[u8]>,
+    {
+        (0..self.depth)
+            .map(|row| self.counters[self.index(row, entry.as_ref())])
+            .min()
+            .unwrap_or_default()
+    }
+
+    /// Subtract `factor` times the counts of `other` from this sketch, counter by counter, for
+    /// maintaining "recent minus baseline" profiles such as an exponentially smoothed baseline.
+    /// Each counter drops by `factor` times the other's count, rounded, and stops at 0, so
+    /// estimates of the difference can undercount as well as overcount.
+    ///
+    /// `factor` must be finite and >= 0, and the sketches must have the same width and depth.
+    pub fn subtract_scaled(&mut self, other: &Self, factor: f64) -> Result<(), Error> {
+        if !factor.is_finite() || factor < 0.0 {
+            return Err(Error::InvalidScaleFactor(factor));
+        }
+        self.check_compatible(other)?;
+        for (ours, &theirs) in self.counters.iter_mut().zip(other.counters.iter()) {
+            *ours = ours.saturating_sub((factor * theirs as f64).round() as u64);
+        }
+        Ok(())
+    }
+
+    /// Bytes used by the sketch, its own size plus the heap allocated for its counters,
+    /// including spare capacity.
+    pub fn mem_usage(&self) -> usize {
+        mem::size_of::<Self>() + heap_size(&self.counters)
+    }
+
+    /// Estimate the inner product of the frequency vectors of two sketches, as the minimum of
+    /// the per row inner products.  Like [CountMinSketch::estimate], this never underestimates.
+    pub fn inner_product(&self, other: &Self) -> Result<f64, Error> {
+        self.check_compatible(other)?;
+        Ok(self
+            .row_sums(other, |a, b| a * b)
+            .fold(f64::INFINITY, f64::min))
+    }
+
+    /// Estimate the cosine similarity of the frequency vectors of two sketches, between 0 (no
+    /// entries in common) and 1 (identical profiles).  Returns 0 if either sketch is empty.
+    ///
+    /// Collisions inflate both the inner product and the norms, so similarity of sparse
+    /// profiles in a narrow sketch is overestimated.
+    pub fn cosine_similarity(&self, other: &Self) -> Result<f64, Error> {
+        let dot = self.inner_product(other)?;
+        let norms = self.inner_product(self)?.sqrt() * other.inner_product(other)?.sqrt();
+        if norms == 0.0 {
+            return Ok(0.0);
+        }
+        Ok((dot / norms).min(1.0))
+    }
+
+    /// Estimate the euclidean distance between the frequency vectors of two sketches.
+    ///
+    /// The difference of two sketches has negative entries, so rows can under or overestimate,
+    /// and the median of the rows is used instead of the minimum.
+    pub fn l2_distance(&self, other: &Self) -> Result<f64, Error> {
+        self.check_compatible(other)?;
+        let mut rows = self
+            .row_sums(other, |a, b| (a - b) * (a - b))
+            .collect::<Vec<_>>();
+        rows.sort_by(f64::total_cmp);
+        let mid = rows.len() / 2;
+        let median = if rows.len() % 2 == 0 {
+            (rows[mid - 1] + rows[mid]) / 2.0
+        } else {
+            rows[mid]
+        };
+        Ok(median.sqrt())
+    }
+
+    fn row_sums<'a>(
+        &'a self,
+        other: &'a Self,
+        f: impl Fn(f64, f64) -> f64 + 'a,
+    ) -> impl Iterator<Item = f64> + 'a {
+        self.counters
+            .chunks(self.width)
+            .zip(other.counters.chunks(other.width))
+            .map(move |(a, b)| {
+                a.iter()
+                    .zip(b.iter())
+                    .map(|(&a, &b)| f(a as f64, b as f64))
+                    .sum()
+            })
+    }
+
+    fn check_compatible(&self, other: &Self) -> Result<(), Error> {
+        if self.width != other.width || self.depth != other.depth {
+            return Err(Error::IncompatibleSketch);
+        }
+        Ok(())
+    }
+
+    fn index(&self, row: u32, entry: &[u8]) -> usize {
+        let mut h = T::with_seed(row);
+        h.write(entry);
+        row as usize * self.width + fastrange(mix(h.finish()), self.width as u64) as usize
+    }
+}
@@ -0,0 +1,116 @@
+use fasthash::metro;
+
+use super::*;
+
+fn sketch(counts: &[(&str, usize)]) -> CountMinSketch<metro::Hasher64_1> {
+    let mut cms = CountMinSketch::new(2048, 4).unwrap();
+    for (key, n) in counts {
+        for _ in 0..*n {
+            cms.add(key);
+        }
+    }
+    cms
+}
+
+#[test]
+fn test_estimate() {
+    let cms = sketch(&[("a", 5), ("b", 2)]);
+    assert_eq!(5, cms.estimate("a"));
+    assert_eq!(2, cms.estimate("b"));
+    assert_eq!(0, cms.estimate("c"));
+}
+
+#[test]
+fn test_add_and_estimate() {
+    let mut cms = sketch(&[("a", 5)]);
+    assert_eq!(5, cms.add_and_estimate("a"));
+    assert_eq!(0, cms.add_and_estimate("b"));
+    assert_eq!(6, cms.estimate("a"));
+    assert_eq!(1, cms.estimate("b"));
+}
+
+#[test]
+fn test_inner_product() {
+    let a = sketch(&[("x", 3), ("y", 4)]);
+    let b = sketch(&[("x", 2), ("z", 10)]);
+    assert_eq!(6.0, a.inner_product(&b).unwrap());
+    assert_eq!(25.0, a.inner_product(&a).unwrap());
+}
+
+#[test]
+fn test_cosine_similarity() {
+    let a = sketch(&[("x", 3), ("y", 4)]);
+    let scaled = sketch(&[("x", 30), ("y", 40)]);
+    let disjoint = sketch(&[("p", 1), ("q", 7)]);
+    assert!((a.cosine_similarity(&scaled).unwrap() - 1.0).abs() < 1e-9);
+    assert_eq!(0.0, a.cosine_similarity(&disjoint).unwrap());
+    let empty = sketch(&[]);
+    assert_eq!(0.0, a.cosine_similarity(&empty).unwrap());
+}
+
+#[test]
+fn test_l2_distance() {
+    let a = sketch(&[("x", 3), ("y", 4)]);
+    let b = sketch(&[("x", 3)]);
+    assert_eq!(4.0, a.l2_distance(&b).unwrap());
+    assert_eq!(0.0, a.l2_distance(&a).unwrap());
+}
+
+#[test]
+fn test_errors() {
+    assert!(matches!(
+        CountMinSketch::<metro::Hasher64_1>::new(0, 1),
+        Err(Error::InvalidWidth(0))
+    ));
+    assert!(matches!(
+        CountMinSketch::<metro::Hasher64_1>::new(1, 0),
+        Err(Error::InvalidDepth(0))
+    ));
+    assert!(matches!(
+        CountMinSketch::<metro::Hasher64_1>::new(usize::MAX, 2),
+        Err(Error::TooManyCounters(usize::MAX, 2))
+    ));
+    let a = CountMinSketch::<metro::Hasher64_1>::new(8, 2).unwrap();
+    let b = CountMinSketch::<metro::Hasher64_1>::new(8, 3).unwrap();
+    assert!(matches!(
+        a.inner_product(&b),
+        Err(Error::IncompatibleSketch)
+    ));
+}
+
+#[test]
+fn test_mem_usage() {
+    let cms = CountMinSketch::<metro::Hasher64_1>::new(100, 4).unwrap();
+    assert_eq!(std::mem::size_of_val(&cms) + 400 * 8, cms.mem_usage());
+}
+
+#[test]
+fn test_subtract_scaled() {
+    let mut recent = CountMinSketch::<metro::Hasher64_1>::new(1024, 4).unwrap();
+    let mut baseline = CountMinSketch::<metro::Hasher64_1>::new(1024, 4).unwrap();
+    for _ in 0..10 {
+        recent.add("spike");
+        baseline.add("steady");
+    }
+    for _ in 0..4 {
+        recent.add("steady");
+        baseline.add("spike");
+    }
+    recent.subtract_scaled(&baseline, 0.5).unwrap();
+    assert_eq!(8, recent.estimate("spike"));
+    assert_eq!(0, recent.estimate("steady"));
+
+    assert!(matches!(
+        recent.subtract_scaled(&baseline, -1.0),
+        Err(Error::InvalidScaleFactor(_))
+    ));
+    assert!(matches!(
+        recent.subtract_scaled(&baseline, f64::NAN),
+        Err(Error::InvalidScaleFactor(_))
+    ));
+    let other = CountMinSketch::<metro::Hasher64_1>::new(512, 4).unwrap();
+    assert!(matches!(
+        recent.subtract_scaled(&other, 1.0),
+        Err(Error::IncompatibleSketch)
+    ));
+}
@@ -0,0 +1,264 @@
+use std::{marker::PhantomData, mem};
+
+use rand::{rngs::StdRng, RngCore, SeedableRng};
+
+use super::{
+    check_params, Error, InsertError, DEFAULT_ENTRIES_PER_BIN, DEFAULT_FINGERPRINT_BITS,
+    DEFAULT_MAX_KICKS, FINGERPRINT_SEED,
+};
+use crate::{hasher::SeedableHasher, mem::heap_size};
+
+// each slot picks one of this many fingerprint functions
+const SELECTORS: u8 = 4;
+// seeds for the two candidate bins, fingerprints use FINGERPRINT_SEED + selector
+const PRIMARY_SEED: u32 = 0;
+const SECONDARY_SEED: u32 = 1;
+
+/// An [adaptive cuckoo filter](https://arxiv.org/abs/1704.06818), which stops repeating a false
+/// positive once it has been reported.
+///
+/// Next to the fingerprints, the filter keeps a backing table with the key stored in every slot,
+/// which would normally live in slower memory, and a small selector per slot choosing which of
+/// 4 hash functions produced that slot's fingerprint.  Lookups only read the fingerprints.
+/// When a lookup turns out to be a false positive, the selectors of the slots that matched are
+/// advanced and their fingerprints recomputed from the backing table, so the same query is very
+/// likely to miss next time.
+///
+/// With the keys at hand, both candidate bins come straight from the key rather than from the
+/// fingerprint, so fingerprints can change without moving.
+pub struct AdaptiveCuckooFilter<T>
+where
+    T: SeedableHasher,
+{
+    fingerprints: Vec<u32>,
+    selectors: Vec<u8>,
+    keys: Vec<Option<Vec<u8>>>,
+    num_bins: usize,
+    entries_per_bin: usize,
+    fingerprint_bits: u32,
+    max_kicks: u32,
+    len: usize,
+    rng: StdRng,
+    _hasher: PhantomData<T>,
+}
+
+impl<T> AdaptiveCuckooFilter<T>
+where
+    T: SeedableHasher,
+{
+    /// Create a new filter with at least `num_bins` bins, rounded up to a power of two.
+    pub fn new(num_bins: usize) -> Result<Self, Error> {
+        Self::with_fingerprint_bits(
+            num_bins,
+            DEFAULT_ENTRIES_PER_BIN,
+            DEFAULT_MAX_KICKS,
+            DEFAULT_FINGERPRINT_BITS,
+        )
+    }
+
+    /// Fails unless `num_bins` and `entries_per_bin` are non-zero, and 4 <= `fingerprint_bits`
+    /// <= 32.
+    pub fn with_fingerprint_bits(
+        num_bins: usize,
+        entries_per_bin: usize,
+        max_kicks: u32,
+        fingerprint_bits: u32,
+    ) -> Result<Self, Error> {
+        let num_bins = check_params(num_bins, entries_per_bin, fingerprint_bits)?;
+        let slots = num_bins * entries_per_bin;
+        Ok(AdaptiveCuckooFilter {
+            fingerprints: vec![0; slots],
+            selectors: vec![0; slots],
+            keys: vec![None; slots],
+            num_bins,
+            entries_per_bin,
+            fingerprint_bits,
+            max_kicks,
+            len: 0,
+            rng: StdRng::from_entropy(),
+            _hasher: PhantomData,
+        })
+    }
+
+    /// Number of entries stored.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Bytes used by the filter, its own size plus the heap allocated for its slots and the
+    /// keys it keeps to adapt with, including spare capacity.
+    pub fn mem_usage(&self) -> usize {
+        mem::size_of::<Self>()
+            + heap_size(&self.fingerprints)
+            + heap_size(&self.selectors)
+            + heap_size(&self.keys)
+            + self.keys.iter().flatten().map(heap_size).sum::<usize>()
+    }
+
+    /// Add an entry, returning [InsertError::Full] if no room could be made for it.  On failure
+    /// the filter is unchanged.
+    pub fn add<I>(&mut self, entry: I) -> Result<(), InsertError>
+    where
+        I: AsRef<[u8]>,
+    {
+        let (i, alt) = self.bins_for(entry.as_ref());
+        let mut carried = (entry.as_ref().to_vec(), 0);
+        if self.insert_in_bin(i, &carried) || self.insert_in_bin(alt, &carried) {
+            return Ok(());
+        }
+
+        // remember the kicks, so a failed insert can be rolled back
+        let mut kicked = vec![];
+        let mut bin = alt;
+        for _ in 0..self.max_kicks {
+            let slot =
+                bin * self.entries_per_bin + self.rng.next_u32() as usize % self.entries_per_bin;
+            let victim = (self.keys[slot].take().unwrap(), self.selectors[slot]);
+            self.place(slot, carried);
+            kicked.push((slot, victim.clone()));
+            carried = victim;
+
+            // kicked keys keep their selector, so adapted fingerprints stay adapted
+            let (i, alt) = self.bins_for(&carried.0);
+            bin = if bin == i { alt } else { i };
+            if self.insert_in_bin(bin, &carried) {
+                return Ok(());
+            }
+        }
+        for (slot, victim) in kicked.into_iter().rev() {
+            self.place(slot, victim);
+        }
+        Err(InsertError::Full {
+            bin: i,
+            victim: self.fingerprint(entry.as_ref(), 0),
+        })
+    }
+
+    /// Remove an entry, returning false if it wasn't stored.  Unlike other cuckoo filters this
+    /// is exact, as the backing table holds the keys.
+    pub fn remove<I>(&mut self, entry: I) -> bool
+    where
+        I: AsRef<[u8]>,
+    {
+        let (i, alt) = self.bins_for(entry.as_ref());
+        match self
+            .find_key(i, entry.as_ref())
+            .or(self.find_key(alt, entry.as_ref()))
+        {
+            Some(slot) => {
+                self.keys[slot] = None;
+                self.fingerprints[slot] = 0;
+                self.selectors[slot] = 0;
+                self.len -= 1;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Check the fingerprints for an entry, subject to the false positive probability.
+    pub fn contains<I>(&self, entry: I) -> bool
+    where
+        I: AsRef<[u8]>,
+    {
+        !self.matching_slots(entry.as_ref()).is_empty()
+    }
+
+    /// Check for an entry, confirming any match with `is_member`, the ground truth such as a
+    /// database lookup.  A match that `is_member` rejects is a false positive, and is adapted
+    /// away before returning false.
+    pub fn contains_checked<I, F>(&mut self, entry: I, is_member: F) -> bool
+    where
+        I: AsRef<[u8]>,
+        F: FnOnce(&[u8]) -> bool,
+    {
+        if !self.contains(entry.as_ref()) {
+            return false;
+        }
+        if is_member(entry.as_ref()) {
+            return true;
+        }
+        self.adapt(entry);
+        false
+    }
+
+    /// Report that a positive answer for `entry` was false.  Every slot whose fingerprint
+    /// matched, but holds a different key, switches to its next fingerprint function.
+    pub fn adapt<I>(&mut self, entry: I)
+    where
+        I: AsRef<[u8]>,
+    {
+        for slot in self.matching_slots(entry.as_ref()) {
+            let key = self.keys[slot].take().unwrap();
+            if key != entry.as_ref() {
+                self.selectors[slot] = (self.selectors[slot] + 1) % SELECTORS;
+                self.fingerprints[slot] = self.fingerprint(&key, self.selectors[slot]);
+            }
+            self.keys[slot] = Some(key);
+        }
+    }
+
+    fn matching_slots(&self, entry: &[u8]) -> Vec<usize> {
+        let (i, alt) = self.bins_for(entry);
+        let fingerprints: Vec<u32> = (0..SELECTORS).map(|s| self.fingerprint(entry, s)).collect();
+        let mut bins = vec![i];
+        if alt != i {
+            bins.push(alt);
+        }
+        bins.into_iter()
+            .flat_map(|bin| bin * self.entries_per_bin..(bin + 1) * self.entries_per_bin)
+            .filter(|&slot| {
+                self.keys[slot].is_some()
+                    && self.fingerprints[slot] == fingerprints[self.selectors[slot] as usize]
+            })
+            .collect()
+    }
+
+    // items are (key, selector) pairs
+    fn insert_in_bin(&mut self, bin: usize, item: &(Vec<u8>, u8)) -> bool {
+        let slots = bin * self.entries_per_bin..(bin + 1) * self.entries_per_bin;
+        match slots.into_iter().find(|&slot| self.keys[slot].is_none()) {
+            Some(slot) => {
+                self.place(slot, item.clone());
+                self.len += 1;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn place(&mut self, slot: usize, (key, selector): (Vec<u8>, u8)) {
+        self.fingerprints[slot] = self.fingerprint(&key, selector);
+        self.selectors[slot] = selector;
+        self.keys[slot] = Some(key);
+    }
+
+    fn find_key(&self, bin: usize, key: &[u8]) -> Option<usize> {
+        (bin * self.entries_per_bin..(bin + 1) * self.entries_per_bin)
+            .find(|&slot| self.keys[slot].as_deref() == Some(key))
+    }
+
+    fn bins_for(&self, key: &[u8]) -> (usize, usize) {
+        let mask = self.num_bins as u64 - 1;
+        (
+            (Self::hash(key, PRIMARY_SEED) & mask) as usize,
+            (Self::hash(key, SECONDARY_SEED) & mask) as usize,
+        )
+    }
+
+    // 0 marks an empty slot, so fingerprints are mapped into 1..2^fingerprint_bits
+    fn fingerprint(&self, key: &[u8], selector: u8) -> u32 {
+        let hash = Self::hash(key, FINGERPRINT_SEED.wrapping_add(selector as u32));
+        (hash % ((1 << self.fingerprint_bits) - 1)) as u32 + 1
+    }
+
+    fn hash(bytes: &[u8], seed: u32) -> u64 {
+        let mut hash = T::with_seed(seed);
+        hash.write(bytes);
+        hash.finish()
+    }
+}
@@ -0,0 +1,452 @@
+use std::{collections::TryReserveError, sync::OnceLock};
+
+use crate::mem::{heap_size, try_filled};
+
+// Semi-sorted bins always hold 4 fingerprints, whose sorted high nibbles are encoded together.
+pub(super) const SEMI_SORTED_ENTRIES: usize = 4;
+// number of non-decreasing sequences of 4 nibbles, C(16 + 4 - 1, 4) = 3876 < 2^12
+const NIBBLE_CODE_BITS: u32 = 12;
+
+/// How fingerprints are laid out within a bin.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Layout {
+    /// Every slot holds a full fingerprint.
+    Plain,
+    /// The bin's fingerprints are kept sorted, so the 4 high bits of each can be stored as a
+    /// 12 bit index into the table of sorted nibble sequences, saving 1 bit per entry.
+    SemiSorted,
+}
+
+/// Fixed size bins of fingerprints, bit packed into one contiguous buffer.
+///
+/// With the plain layout, slot `j` of bin `i` is stored at bit offset
+/// `(i * entries_per_bin + j) * fingerprint_bits`, so fingerprints may straddle two words.
+/// A fingerprint of 0 marks an empty slot.
+///
+/// Bins are usually backed by an owned buffer, but can also be read in place from serialized
+/// bytes, see [Bins::view].
+#[derive(Clone, PartialEq, Eq)]
+pub(super) struct Bins<W = Vec<u64>> {
+    words: W,
+    num_bins: usize,
+    entries_per_bin: usize,
+    fingerprint_bits: u32,
+    layout: Layout,
+    occupied: usize,
+}
+
+impl Bins {
+    pub(super) fn new(num_bins: usize, entries_per_bin: usize, fingerprint_bits: u32) -> Self {
+        Self::with_layout(num_bins, entries_per_bin, fingerprint_bits, Layout::Plain)
+    }
+
+    /// Create bins as [Bins::new] does, or return an error if they can't be allocated.
+    pub(super) fn try_new(
+        num_bins: usize,
+        entries_per_bin: usize,
+        fingerprint_bits: u32,
+    ) -> Result<Self, TryReserveError> {
+        Self::try_with_layout(num_bins, entries_per_bin, fingerprint_bits, Layout::Plain)
+    }
+
+    /// Create bins with the semi-sorted layout, which always have 4 entries per bin and need
+    /// at least 4 bits per fingerprint.
+    pub(super) fn semi_sorted(num_bins: usize, fingerprint_bits: u32) -> Self {
+        Self::with_layout(
+            num_bins,
+            SEMI_SORTED_ENTRIES,
+            fingerprint_bits,
+            Layout::SemiSorted,
+        )
+    }
+
+    fn with_layout(
+        num_bins: usize,
+        entries_per_bin: usize,
+        fingerprint_bits: u32,
+        layout: Layout,
+    ) -> Self {
+        let mut bins = Self::unallocated(num_bins, entries_per_bin, fingerprint_bits, layout);
+        bins.words = vec![0; bins.words_needed()];
+        bins
+    }
+
+    /// Create bins with any layout, or return an error if they can't be allocated.
+    pub(super) fn try_with_layout(
+        num_bins: usize,
+        entries_per_bin: usize,
+        fingerprint_bits: u32,
+        layout: Layout,
+    ) -> Result<Self, TryReserveError> {
+        let mut bins = Self::unallocated(num_bins, entries_per_bin, fingerprint_bits, layout);
+        bins.words = try_filled(bins.words_needed(), 0)?;
+        Ok(bins)
+    }
+
+    fn unallocated(
+        num_bins: usize,
+        entries_per_bin: usize,
+        fingerprint_bits: u32,
+        layout: Layout,
+    ) -> Self {
+        Bins {
+            words: vec![],
+            num_bins,
+            entries_per_bin,
+            fingerprint_bits,
+            layout,
+            occupied: 0,
+        }
+    }
+
+    fn words_needed(&self) -> usize {
+        (self.num_bins * self.bin_bits()).div_ceil(u64::BITS as usize)
+    }
+
+    /// Create empty bins with the same layout, but `num_bins` bins.
+    pub(super) fn empty_like(&self, num_bins: usize) -> Self {
+        Self::with_layout(
+            num_bins,
+            self.entries_per_bin,
+            self.fingerprint_bits,
+            self.layout,
+        )
+    }
+
+    /// The packed buffer, with the first slot in the low bits of the first word.
+    pub(super) fn words(&self) -> &[u64] {
+        &self.words
+    }
+
+    /// Replace the packed buffer, returning false if it is the wrong length.
+    pub(super) fn load_words(&mut self, words: Vec<u64>) -> bool {
+        if words.len() != self.words.len() {
+            return false;
+        }
+        if self.layout == Layout::SemiSorted {
+            let codes = nibble_tables().decode.len() as u64;
+            let bin_bits = self.bin_bits();
+            let valid = |bin: usize| {
+                let offset = bin * bin_bits;
+                let (word, shift) = (offset / u64::BITS as usize, offset % u64::BITS as usize);
+                let mut code = words[word] >> shift;
+                if shift + NIBBLE_CODE_BITS as usize > u64::BITS as usize {
+                    code |= words[word + 1] << (u64::BITS as usize - shift);
+                }
+                code & mask(NIBBLE_CODE_BITS) < codes
+            };
+            if !(0..self.num_bins).all(valid) {
+                return false;
+            }
+        }
+        self.words = words;
+        self.occupied = (0..self.num_bins)
+            .map(|bin| self.fingerprints(bin).count())
+            .sum();
+        true
+    }
+
+    /// Bytes allocated for the packed words.
+    pub(super) fn heap_size(&self) -> usize {
+        heap_size(&self.words)
+    }
+
+    /// Number of occupied slots across all bins.
+    pub(super) fn occupied(&self) -> usize {
+        self.occupied
+    }
+
+    fn set(&mut self, bin: usize, slot: usize, fingerprint: u32) {
+        match self.layout {
+            Layout::Plain => {
+                let offset = self.slot_offset(bin, slot);
+                self.write_bits(offset, self.fingerprint_bits, fingerprint as u64);
+            }
+            Layout::SemiSorted => {
+                let mut fingerprints = self.load_sorted(bin);
+                fingerprints[slot] = fingerprint;
+                self.store_sorted(bin, fingerprints);
+            }
+        }
+    }
+
+    /// Store a fingerprint in the first empty slot of a bin, returning false if the bin is full.
+    pub(super) fn insert(&mut self, bin: usize, fingerprint: u32) -> bool {
+        match self.find(bin, 0) {
+            Some(slot) => {
+                self.set(bin, slot, fingerprint);
+                self.occupied += 1;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Remove one copy of a fingerprint from a bin, returning false if it wasn't there.
+    pub(super) fn remove(&mut self, bin: usize, fingerprint: u32) -> bool {
+        match self.find(bin, fingerprint) {
+            Some(slot) => {
+                self.set(bin, slot, 0);
+                self.occupied -= 1;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Replace the fingerprint in an occupied slot, returning the previous fingerprint.
+    ///
+    /// With the semi-sorted layout the bin is re-sorted, so slots don't keep their positions.
+    pub(super) fn swap(&mut self, bin: usize, slot: usize, fingerprint: u32) -> u32 {
+        let previous = self.get(bin, slot);
+        self.set(bin, slot, fingerprint);
+        previous
+    }
+
+    fn store_sorted(&mut self, bin: usize, mut fingerprints: [u32; SEMI_SORTED_ENTRIES]) {
+        fingerprints.sort_unstable();
+        let low_bits = self.fingerprint_bits - 4;
+        let nibbles = fingerprints
+            .iter()
+            .fold(0, |acc, fp| (acc << 4) | (fp >> low_bits) as usize);
+        let mut offset = bin * self.bin_bits();
+        let code = nibble_tables().encode[nibbles];
+        self.write_bits(offset, NIBBLE_CODE_BITS, code as u64);
+        offset += NIBBLE_CODE_BITS as usize;
+        for fp in fingerprints {
+            self.write_bits(offset, low_bits, fp as u64);
+            offset += low_bits as usize;
+        }
+    }
+
+    fn write_bits(&mut self, offset: usize, width: u32, value: u64) {
+        if width == 0 {
+            return;
+        }
+        let (word, shift) = (
+            offset / u64::BITS as usize,
+            (offset % u64::BITS as usize) as u32,
+        );
+        let mask = mask(width);
+        let value = value & mask;
+        self.words[word] = (self.words[word] & !(mask << shift)) | (value << shift);
+        if shift + width > u64::BITS {
+            let carry = u64::BITS - shift;
+            self.words[word + 1] = (self.words[word + 1] & !(mask >> carry)) | (value >> carry);
+        }
+    }
+}
+
+impl<'a> Bins<LeWords<'a>> {
+    /// Read bins in place from the little endian bytes of [Bins::words], returning None if
+    /// `bytes` is the wrong length.
+    ///
+    /// Nothing is validated up front, so that opening a view doesn't read the whole buffer.
+    /// Semi-sorted bins with an invalid nibble code read as empty.
+    pub(super) fn view(
+        bytes: &'a [u8],
+        num_bins: usize,
+        entries_per_bin: usize,
+        fingerprint_bits: u32,
+        layout: Layout,
+    ) -> Option<Self> {
+        let bins = Bins {
+            words: LeWords(bytes),
+            num_bins,
+            entries_per_bin,
+            fingerprint_bits,
+            layout,
+            occupied: 0, // not tracked, views are never modified
+        };
+        let num_words = words_len(num_bins, entries_per_bin, fingerprint_bits, layout)?;
+        (num_words * 8 == bytes.len()).then_some(bins)
+    }
+}
+
+impl<W: Words> Bins<W> {
+    pub(super) fn num_bins(&self) -> usize {
+        self.num_bins
+    }
+
+    pub(super) fn entries_per_bin(&self) -> usize {
+        self.entries_per_bin
+    }
+
+    pub(super) fn fingerprint_bits(&self) -> u32 {
+        self.fingerprint_bits
+    }
+
+    pub(super) fn layout(&self) -> Layout {
+        self.layout
+    }
+
+    /// Number of bits used to store one bin.
+    pub(super) fn bin_bits(&self) -> usize {
+        bin_bits(self.entries_per_bin, self.fingerprint_bits, self.layout)
+    }
+
+    pub(super) fn get(&self, bin: usize, slot: usize) -> u32 {
+        match self.layout {
+            Layout::Plain => {
+                let offset = self.slot_offset(bin, slot);
+                self.read_bits(offset, self.fingerprint_bits) as u32
+            }
+            Layout::SemiSorted => self.load_sorted(bin)[slot],
+        }
+    }
+
+    /// Iterate over the fingerprints stored in a bin, skipping empty slots.
+    pub(super) fn fingerprints(&self, bin: usize) -> impl Iterator<Item = u32> + '_ {
+        (0..self.entries_per_bin)
+            .map(move |slot| self.get(bin, slot))
+            .filter(|&fp| fp != 0)
+    }
+
+    pub(super) fn contains(&self, bin: usize, fingerprint: u32) -> bool {
+        self.find(bin, fingerprint).is_some()
+    }
+
+    /// Number of copies of a fingerprint stored in a bin.
+    pub(super) fn count(&self, bin: usize, fingerprint: u32) -> usize {
+        (0..self.entries_per_bin)
+            .filter(|&slot| self.get(bin, slot) == fingerprint)
+            .count()
+    }
+
+    pub(super) fn is_full(&self, bin: usize) -> bool {
+        (0..self.entries_per_bin).all(|slot| self.get(bin, slot) != 0)
+    }
+
+    /// First slot of a bin holding `fingerprint`, where 0 finds an empty slot.
+    fn find(&self, bin: usize, fingerprint: u32) -> Option<usize> {
+        #[cfg(feature = "simd")]
+        if self.layout == Layout::Plain && self.bin_bits() <= u64::BITS as usize {
+            return self.find_in_word(bin, fingerprint);
+        }
+        (0..self.entries_per_bin).find(|&slot| self.get(bin, slot) == fingerprint)
+    }
+
+    // Compare every slot of a bin that fits in one word at once.  XOR with the fingerprint
+    // repeated in every lane leaves zero lanes where it matches, and the classic zero lane test
+    // `(x - 0x0101..) & !x & 0x8080..` sets the high bit of the lowest zero lane.
+    #[cfg(feature = "simd")]
+    fn find_in_word(&self, bin: usize, fingerprint: u32) -> Option<usize> {
+        let bin_bits = self.bin_bits();
+        let lanes = self.read_bits(bin * bin_bits, bin_bits as u32);
+        let ones = (0..self.entries_per_bin).fold(0_u64, |acc, _| acc << self.fingerprint_bits | 1);
+        let highs = ones << (self.fingerprint_bits - 1);
+        let x = lanes ^ ones.wrapping_mul(fingerprint as u64);
+        match x.wrapping_sub(ones) & !x & highs {
+            0 => None,
+            found => Some(found.trailing_zeros() as usize / self.fingerprint_bits as usize),
+        }
+    }
+
+    fn slot_offset(&self, bin: usize, slot: usize) -> usize {
+        (bin * self.entries_per_bin + slot) * self.fingerprint_bits as usize
+    }
+
+    // Semi-sorted bins are a 12 bit nibble code, followed by the low bits of each fingerprint
+    // in sorted order.
+    fn load_sorted(&self, bin: usize) -> [u32; SEMI_SORTED_ENTRIES] {
+        let low_bits = self.fingerprint_bits - 4;
+        let mut offset = bin * self.bin_bits();
+        let code = self.read_bits(offset, NIBBLE_CODE_BITS) as usize;
+        offset += NIBBLE_CODE_BITS as usize;
+        // codes are checked when owned bins are loaded, but not in views, which read as empty
+        let mut fingerprints = match nibble_tables().decode.get(code) {
+            Some(&nibbles) => nibbles,
+            None => return [0; SEMI_SORTED_ENTRIES],
+        };
+        for fp in fingerprints.iter_mut() {
+            *fp = (*fp << low_bits) | self.read_bits(offset, low_bits) as u32;
+            offset += low_bits as usize;
+        }
+        fingerprints
+    }
+
+    fn read_bits(&self, offset: usize, width: u32) -> u64 {
+        if width == 0 {
+            return 0;
+        }
+        let (word, shift) = (
+            offset / u64::BITS as usize,
+            (offset % u64::BITS as usize) as u32,
+        );
+        let mut value = self.words.word(word) >> shift;
+        if shift + width > u64::BITS {
+            value |= self.words.word(word + 1) << (u64::BITS - shift);
+        }
+        value & mask(width)
+    }
+}
+
+/// Number of u64 words needed to store `num_bins` bins, or None if it overflows.
+pub(super) fn words_len(
+    num_bins: usize,
+    entries_per_bin: usize,
+    fingerprint_bits: u32,
+    layout: Layout,
+) -> Option<usize> {
+    let total_bits = num_bins.checked_mul(bin_bits(entries_per_bin, fingerprint_bits, layout))?;
+    Some(total_bits.div_ceil(u64::BITS as usize))
+}
+
+fn bin_bits(entries_per_bin: usize, fingerprint_bits: u32, layout: Layout) -> usize {
+    match layout {
+        Layout::Plain => entries_per_bin * fingerprint_bits as usize,
+        Layout::SemiSorted => {
+            NIBBLE_CODE_BITS as usize + SEMI_SORTED_ENTRIES * (fingerprint_bits as usize - 4)
+        }
+    }
+}
+
+/// Word access to the buffer backing [Bins].
+pub(super) trait Words {
+    fn word(&self, i: usize) -> u64;
+}
+
+impl Words for Vec<u64> {
+    fn word(&self, i: usize) -> u64 {
+        self[i]
+    }
+}
+
+/// Little endian u64 words in a byte buffer with any alignment, such as a memory mapped file.
+pub(super) struct LeWords<'a>(&'a [u8]);
+
+impl Words for LeWords<'_> {
+    fn word(&self, i: usize) -> u64 {
+        u64::from_le_bytes(self.0[i * 8..i * 8 + 8].try_into().unwrap())
+    }
+}
+
+fn mask(width: u32) -> u64 {
+    u64::MAX >> (u64::BITS - width)
+}
+
+struct NibbleTables {
+    // code -> sorted high nibbles
+    decode: Vec<[u32; SEMI_SORTED_ENTRIES]>,
+    // 4 sorted nibbles packed into 16 bits -> code
+    encode: Vec<u16>,
+}
+
+fn nibble_tables() -> &'static NibbleTables {
+    static TABLES: OnceLock<NibbleTables> = OnceLock::new();
+    TABLES.get_or_init(|| {
+        let mut decode = vec![];
+        let mut encode = vec![0; 1 << 16];
+        for a in 0..16 {
+            for b in a..16 {
+                for c in b..16 {
+                    for d in c..16 {
+                        encode[(a << 12 | b << 8 | c << 4 | d) as usize] = decode.len() as u16;
+                        decode.push([a, b, c, d]);
+                    }
+                }
+            }
+        }
+        NibbleTables { decode, encode }
+    })
+}
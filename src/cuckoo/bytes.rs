@@ -0,0 +1,351 @@
+use std::io::{self, Read, Write};
+
+use rand::RngCore;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use super::{
+    bins::{words_len, Bins, Layout},
+    CuckooFilter, Error, InsertStrategy, MAX_FINGERPRINT_BITS, MIN_FINGERPRINT_BITS, STASH_SIZE,
+};
+use crate::{
+    format::{self, ChecksumReader, ChecksumWriter, CHECKSUM_LEN, MAGIC_LEN},
+    hasher::{HashSchemeVersion, SeedableHasher},
+    stream,
+};
+
+const MAGIC: [u8; MAGIC_LEN] = *b"PFCF";
+const FORMAT_VERSION: u8 = 3;
+// magic, version, num_bins, entries_per_bin, fingerprint_bits, layout, max_kicks, growths,
+// max_load_factor (NaN if disabled), strategy, stash length, hash scheme
+const HEADER_LEN: usize = MAGIC_LEN + 1 + 8 + 4 + 4 + 1 + 4 + 4 + 8 + 1 + 1 + 1;
+// bin, fingerprint
+const STASH_ENTRY_LEN: usize = 8 + 4;
+
+impl<T, R> CuckooFilter<T, R>
+where
+    T: SeedableHasher,
+    R: RngCore,
+{
+    /// Serialize the filter, including its stash and parameters.
+    ///
+    /// The format is the magic number `PFCF`, a version byte, the parameters, the
+    /// [HashSchemeVersion] byte and the stash as little endian integers, the packed bins as
+    /// little endian u64 words, then the CRC32C of everything before it.  The random number
+    /// generator isn't saved.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(
+            HEADER_LEN
+                + self.stash.len() * STASH_ENTRY_LEN
+                + self.bins.words().len() * 8
+                + CHECKSUM_LEN,
+        );
+        self.write_to(&mut bytes)
+            .expect("writing to a Vec can't fail");
+        bytes
+    }
+
+    /// A 128 bit digest of the filter's parameters and contents, so replicas can cheaply check
+    /// they hold identical filters.  Filters with the same serialized form have the same
+    /// digest on every platform; the hasher isn't part of it.
+    pub fn digest(&self) -> u128 {
+        format::digest(&self.to_bytes())
+    }
+
+    /// Stream the filter in the format of [CuckooFilter::to_bytes], a chunk at a time, so a
+    /// large filter can be written without holding a serialized copy in memory.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all, fields(num_bins = self.bins.num_bins(), len = self.len()), err))]
+    pub fn write_to<W>(&self, writer: W) -> io::Result<()>
+    where
+        W: Write,
+    {
+        let mut header = Vec::with_capacity(HEADER_LEN + self.stash.len() * STASH_ENTRY_LEN);
+        header.extend_from_slice(&MAGIC);
+        header.push(FORMAT_VERSION);
+        header.extend_from_slice(&(self.bins.num_bins() as u64).to_le_bytes());
+        header.extend_from_slice(&(self.bins.entries_per_bin() as u32).to_le_bytes());
+        header.extend_from_slice(&self.bins.fingerprint_bits().to_le_bytes());
+        header.push(match self.bins.layout() {
+            Layout::Plain => 0,
+            Layout::SemiSorted => 1,
+        });
+        header.extend_from_slice(&self.max_kicks.to_le_bytes());
+        header.extend_from_slice(&self.growths.to_le_bytes());
+        header.extend_from_slice(&self.max_load_factor.unwrap_or(f64::NAN).to_le_bytes());
+        header.push(match self.strategy {
+            InsertStrategy::RandomWalk => 0,
+            InsertStrategy::BreadthFirst => 1,
+        });
+        header.push(self.stash.len() as u8);
+        header.push(HashSchemeVersion::CURRENT.as_u8());
+        for &(bin, fingerprint) in &self.stash {
+            header.extend_from_slice(&(bin as u64).to_le_bytes());
+            header.extend_from_slice(&fingerprint.to_le_bytes());
+        }
+        let mut writer = ChecksumWriter::new(writer);
+        writer.write_all(&header)?;
+        stream::write_words(&mut writer, self.bins.words().iter().copied())?;
+        writer.finish()
+    }
+}
+
+impl<T> CuckooFilter<T>
+where
+    T: SeedableHasher,
+{
+    /// Deserialize a filter produced by [CuckooFilter::to_bytes].
+    ///
+    /// Returns [Error::Truncated] if `bytes` is too short, [Error::ChecksumMismatch] if it was
+    /// corrupted, and [Error::InvalidMagic] or [Error::UnsupportedVersion] if it isn't a cuckoo
+    /// filter this version can read.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all, fields(len = bytes.len()), err))]
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        let (mut parts, rest) = parse(bytes)?;
+        let num_words = parts.num_words()?;
+        format::check_body(bytes, framed_len(bytes.len() - rest.len(), num_words))?;
+        parts.words = rest[..num_words * 8]
+            .chunks(8)
+            .map(|chunk| u64::from_le_bytes(chunk.try_into().unwrap()))
+            .collect();
+        Self::from_parts(parts)
+    }
+
+    /// Serialize the filter as [CuckooFilter::to_bytes] does, compressed with zstd.
+    #[cfg(feature = "zstd")]
+    pub fn to_bytes_compressed(&self) -> Vec<u8> {
+        format::compress(|encoder| self.write_to(encoder))
+    }
+
+    /// Deserialize a filter produced by either [CuckooFilter::to_bytes] or
+    /// [CuckooFilter::to_bytes_compressed].
+    #[cfg(feature = "zstd")]
+    pub fn from_bytes_auto(bytes: &[u8]) -> Result<Self, Error> {
+        match format::is_compressed(bytes) {
+            true => format::read_compressed(bytes, |reader| Self::read_from(reader)),
+            false => Self::from_bytes(bytes),
+        }
+    }
+
+    /// Read a filter written by [CuckooFilter::write_to], a chunk at a time.  Reading stops at
+    /// the end of the filter, so it may be followed by other data.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, err)
+    )]
+    pub fn read_from<R>(reader: R) -> Result<Self, Error>
+    where
+        R: Read,
+    {
+        let mut reader = ChecksumReader::new(reader);
+        let mut header = stream::read_array::<_, HEADER_LEN>(&mut reader)?.to_vec();
+        format::check_header(&header, &MAGIC, FORMAT_VERSION)?;
+        header.resize(HEADER_LEN + header[39] as usize * STASH_ENTRY_LEN, 0);
+        reader.read_exact(&mut header[HEADER_LEN..])?;
+        let (mut parts, _) = parse(&header)?;
+
+        let num_words = parts.num_words()?;
+        // the header is only checksummed once the words are read, so don't trust it to abort
+        parts.words.try_reserve_exact(num_words)?;
+        stream::read_words(&mut reader, num_words, |word| parts.words.push(word))?;
+        reader.finish::<Error>()?;
+        Self::from_parts(parts)
+    }
+
+    // Rebuild a filter, rejecting anything that to_parts couldn't have produced.
+    fn from_parts(parts: Parts) -> Result<Self, Error> {
+        let mut filter = Self::with_bins(parts.empty_bins()?, parts.max_kicks);
+        if !filter.bins.load_words(parts.words) {
+            return Err(Error::Malformed);
+        }
+        filter.stash = parts.stash;
+        filter.growths = parts.growths;
+        filter.strategy = parts.strategy;
+        filter.max_load_factor = parts.max_load_factor;
+        Ok(filter)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<T, R> CuckooFilter<T, R>
+where
+    T: SeedableHasher,
+    R: RngCore,
+{
+    fn to_parts(&self) -> Parts {
+        Parts {
+            num_bins: self.bins.num_bins(),
+            entries_per_bin: self.bins.entries_per_bin(),
+            fingerprint_bits: self.bins.fingerprint_bits(),
+            semi_sorted: self.bins.layout() == Layout::SemiSorted,
+            max_kicks: self.max_kicks,
+            growths: self.growths,
+            max_load_factor: self.max_load_factor,
+            strategy: self.strategy,
+            stash: self.stash.clone(),
+            words: self.bins.words().to_vec(),
+        }
+    }
+}
+
+// Everything needed to rebuild a filter apart from its random number generator.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub(super) struct Parts {
+    pub(super) num_bins: usize,
+    pub(super) entries_per_bin: usize,
+    pub(super) fingerprint_bits: u32,
+    pub(super) semi_sorted: bool,
+    pub(super) max_kicks: u32,
+    pub(super) growths: u32,
+    pub(super) max_load_factor: Option<f64>,
+    pub(super) strategy: InsertStrategy,
+    pub(super) stash: Vec<(usize, u32)>,
+    pub(super) words: Vec<u64>,
+}
+
+impl Parts {
+    /// Check everything but the packed bins, returning their layout.
+    pub(super) fn check(&self) -> Result<Layout, Error> {
+        let (num_bins, growths) = (self.num_bins, self.growths);
+        if !num_bins.is_power_of_two()
+            || self.entries_per_bin == 0
+            || !(MIN_FINGERPRINT_BITS..=MAX_FINGERPRINT_BITS).contains(&self.fingerprint_bits)
+            || growths > self.fingerprint_bits - MIN_FINGERPRINT_BITS
+            || growths >= usize::BITS
+            || num_bins >> growths == 0
+            || self.stash.len() > STASH_SIZE
+        {
+            return Err(Error::Malformed);
+        }
+        let max_fingerprint = (1_u64 << self.fingerprint_bits) - 1;
+        if self.stash.iter().any(|&(bin, fingerprint)| {
+            bin >= num_bins || fingerprint == 0 || fingerprint as u64 > max_fingerprint
+        }) {
+            return Err(Error::Malformed);
+        }
+        if let Some(max_load_factor) = self.max_load_factor {
+            if max_load_factor <= 0.0 || max_load_factor > 1.0 {
+                return Err(Error::Malformed);
+            }
+        }
+        match self.semi_sorted {
+            false => Ok(Layout::Plain),
+            true if self.entries_per_bin == super::bins::SEMI_SORTED_ENTRIES => {
+                Ok(Layout::SemiSorted)
+            }
+            true => Err(Error::Malformed),
+        }
+    }
+
+    /// Number of u64 words holding the packed bins.
+    pub(super) fn num_words(&self) -> Result<usize, Error> {
+        let layout = self.check()?;
+        words_len(
+            self.num_bins,
+            self.entries_per_bin,
+            self.fingerprint_bits,
+            layout,
+        )
+        .ok_or(Error::Malformed)
+    }
+
+    /// Empty bins with the checked layout.
+    fn empty_bins(&self) -> Result<Bins, Error> {
+        let layout = self.check()?;
+        Ok(Bins::try_with_layout(
+            self.num_bins,
+            self.entries_per_bin,
+            self.fingerprint_bits,
+            layout,
+        )?)
+    }
+}
+
+/// Split the output of [CuckooFilter::to_bytes] into its parts, without the packed bins, and
+/// the bytes that follow the stash.
+pub(super) fn parse(bytes: &[u8]) -> Result<(Parts, &[u8]), Error> {
+    format::check_header(bytes, &MAGIC, FORMAT_VERSION)?;
+    if bytes.len() < HEADER_LEN {
+        return Err(Error::Truncated);
+    }
+    let u32_at = |at: usize| u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap());
+    let u64_at = |at: usize| u64::from_le_bytes(bytes[at..at + 8].try_into().unwrap());
+
+    let semi_sorted = match bytes[21] {
+        0 => false,
+        1 => true,
+        _ => return Err(Error::Malformed),
+    };
+    let strategy = match bytes[38] {
+        0 => InsertStrategy::RandomWalk,
+        1 => InsertStrategy::BreadthFirst,
+        _ => return Err(Error::Malformed),
+    };
+    let max_load_factor = f64::from_bits(u64_at(30));
+
+    if HashSchemeVersion::from_u8(bytes[40]).is_none() {
+        return Err(Error::UnsupportedHashScheme(bytes[40]));
+    }
+    let stash_len = bytes[39] as usize;
+    let words_at = HEADER_LEN + stash_len * STASH_ENTRY_LEN;
+    if bytes.len() < words_at {
+        return Err(Error::Truncated);
+    }
+    let stash = (HEADER_LEN..words_at)
+        .step_by(STASH_ENTRY_LEN)
+        .map(|at| Ok((to_usize(u64_at(at))?, u32_at(at + 8))))
+        .collect::<Result<_, Error>>()?;
+
+    let parts = Parts {
+        num_bins: to_usize(u64_at(5))?,
+        entries_per_bin: u32_at(13) as usize,
+        fingerprint_bits: u32_at(17),
+        semi_sorted,
+        max_kicks: u32_at(22),
+        growths: u32_at(26),
+        max_load_factor: (!max_load_factor.is_nan()).then_some(max_load_factor),
+        strategy,
+        stash,
+        words: vec![],
+    };
+    Ok((parts, &bytes[words_at..]))
+}
+
+/// Length of a serialized filter whose header and stash take `header_len` bytes, including
+/// the checksum.
+pub(super) fn framed_len(header_len: usize, num_words: usize) -> u64 {
+    (num_words as u64)
+        .saturating_mul(8)
+        .saturating_add((header_len + CHECKSUM_LEN) as u64)
+}
+
+/// Serializes the same parameters, stash and packed bins as [CuckooFilter::to_bytes].
+#[cfg(feature = "serde")]
+impl<T, R> Serialize for CuckooFilter<T, R>
+where
+    T: SeedableHasher,
+    R: RngCore,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.to_parts().serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, T> Deserialize<'de> for CuckooFilter<T>
+where
+    T: SeedableHasher,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Self::from_parts(Parts::deserialize(deserializer)?).map_err(serde::de::Error::custom)
+    }
+}
+
+fn to_usize(value: u64) -> Result<usize, Error> {
+    usize::try_from(value).map_err(|_| Error::Malformed)
+}
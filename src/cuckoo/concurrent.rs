@@ -0,0 +1,271 @@
+use std::{
+    marker::PhantomData,
+    mem,
+    sync::{
+        atomic::{AtomicU32, AtomicUsize, Ordering},
+        Mutex, MutexGuard,
+    },
+};
+
+use super::{
+    alt_index_of, check_params, fingerprint_of, index_of, Error, InsertError,
+    DEFAULT_ENTRIES_PER_BIN, DEFAULT_FINGERPRINT_BITS, DEFAULT_MAX_KICKS,
+};
+use crate::{aligned::CachePadded, hasher::SeedableHasher, mem::heap_size};
+
+const MAX_LOCK_STRIPES: usize = 1024;
+
+/// A cuckoo filter that can be shared between threads, without an external lock.
+///
+/// Bins are guarded by a fixed set of striped locks.  Inserts and removes lock the stripes of
+/// both candidate bins, always in ascending order so two writers can't deadlock.  When both bins
+/// are full, the insert takes every stripe and searches breadth first for the shortest chain of
+/// moves that frees a slot, so nothing is ever evicted.  Lookups don't lock at all.
+///
+/// Each fingerprint move writes the new slot before clearing the old one, and bumps a sequence
+/// number before and after, so a lookup that misses while a move is in flight retries rather
+/// than returning a false negative.
+///
+/// Fingerprints are stored in one 32 bit slot each, and are computed the same way as in
+/// [super::CuckooFilter].
+pub struct ConcurrentCuckooFilter<T>
+where
+    T: SeedableHasher,
+{
+    slots: Vec<AtomicU32>,
+    // each lock and counter on a line of its own, so writers to different stripes don't
+    // contend for the same line
+    locks: Vec<CachePadded<Mutex<()>>>,
+    moves: CachePadded<AtomicUsize>, // odd while fingerprints are being moved
+    len: CachePadded<AtomicUsize>,
+    num_bins: usize,
+    entries_per_bin: usize,
+    fingerprint_bits: u32,
+    max_kicks: u32,
+    // the hasher is only used for its associated functions, so it needn't be Send or Sync
+    _hasher: PhantomData<fn() -> T>,
+}
+
+impl<T> ConcurrentCuckooFilter<T>
+where
+    T: SeedableHasher,
+{
+    /// Create a new filter with at least `num_bins` bins, rounded up to a power of two.
+    pub fn new(num_bins: usize) -> Result<Self, Error> {
+        Self::with_fingerprint_bits(
+            num_bins,
+            DEFAULT_ENTRIES_PER_BIN,
+            DEFAULT_MAX_KICKS,
+            DEFAULT_FINGERPRINT_BITS,
+        )
+    }
+
+    /// Create a new filter, where `max_kicks` bounds the number of bins searched to make room.
+    ///
+    /// Fails unless `num_bins` and `entries_per_bin` are non-zero, and 4 <= `fingerprint_bits`
+    /// <= 32.
+    pub fn with_fingerprint_bits(
+        num_bins: usize,
+        entries_per_bin: usize,
+        max_kicks: u32,
+        fingerprint_bits: u32,
+    ) -> Result<Self, Error> {
+        let num_bins = check_params(num_bins, entries_per_bin, fingerprint_bits)?;
+        Ok(ConcurrentCuckooFilter {
+            slots: (0..num_bins * entries_per_bin)
+                .map(|_| AtomicU32::new(0))
+                .collect(),
+            locks: (0..num_bins.min(MAX_LOCK_STRIPES))
+                .map(|_| CachePadded(Mutex::new(())))
+                .collect(),
+            moves: CachePadded(AtomicUsize::new(0)),
+            len: CachePadded(AtomicUsize::new(0)),
+            num_bins,
+            entries_per_bin,
+            fingerprint_bits,
+            max_kicks,
+            _hasher: PhantomData,
+        })
+    }
+
+    /// Number of fingerprints stored.
+    pub fn len(&self) -> usize {
+        self.len.load(Ordering::Relaxed)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Bytes used by the filter, its own size plus the heap allocated for its slots and locks,
+    /// including spare capacity.
+    pub fn mem_usage(&self) -> usize {
+        mem::size_of::<Self>() + heap_size(&self.slots) + heap_size(&self.locks)
+    }
+
+    /// Add an entry, returning [InsertError::Full] if no room can be made for it.
+    pub fn add<I>(&self, entry: I) -> Result<(), InsertError>
+    where
+        I: AsRef<[u8]>,
+    {
+        let (i, fingerprint) = self.locate(entry.as_ref());
+        let alt = self.alt_index(i, fingerprint);
+        {
+            let _guards = self.lock_bins(i, alt);
+            if self.insert_in_bin(i, fingerprint) || self.insert_in_bin(alt, fingerprint) {
+                return Ok(());
+            }
+        }
+
+        // both bins are full, so make room with every stripe held
+        let _guards = self
+            .locks
+            .iter()
+            .map(|l| l.lock().unwrap())
+            .collect::<Vec<_>>();
+        if self.insert_in_bin(i, fingerprint) || self.insert_in_bin(alt, fingerprint) {
+            return Ok(());
+        }
+        let path = self.shortest_path(i, alt).ok_or(InsertError::Full {
+            bin: i,
+            victim: fingerprint,
+        })?;
+        self.moves.fetch_add(1, Ordering::AcqRel);
+        for pair in path.windows(2).rev() {
+            let (from, _) = pair[0];
+            let (to, fp) = pair[1];
+            self.insert_in_bin(to, fp);
+            self.remove_from_bin(from, fp);
+        }
+        self.moves.fetch_add(1, Ordering::AcqRel);
+        self.insert_in_bin(path[0].0, fingerprint);
+        Ok(())
+    }
+
+    /// Remove one copy of an entry, returning false if it wasn't found.
+    pub fn remove<I>(&self, entry: I) -> bool
+    where
+        I: AsRef<[u8]>,
+    {
+        let (i, fingerprint) = self.locate(entry.as_ref());
+        let alt = self.alt_index(i, fingerprint);
+        let _guards = self.lock_bins(i, alt);
+        self.remove_from_bin(i, fingerprint) || self.remove_from_bin(alt, fingerprint)
+    }
+
+    pub fn contains<I>(&self, entry: I) -> bool
+    where
+        I: AsRef<[u8]>,
+    {
+        let (i, fingerprint) = self.locate(entry.as_ref());
+        let alt = self.alt_index(i, fingerprint);
+        loop {
+            let before = self.moves.load(Ordering::Acquire);
+            if self.bin_contains(i, fingerprint) || self.bin_contains(alt, fingerprint) {
+                return true;
+            }
+            if before.is_multiple_of(2) && self.moves.load(Ordering::Acquire) == before {
+                return false;
+            }
+            std::hint::spin_loop();
+        }
+    }
+
+    // Breadth first search for the shortest chain of moves ending in a bin with room, as
+    // (bin, fingerprint moved into it) pairs starting from a candidate bin.
+    fn shortest_path(&self, i: usize, alt: usize) -> Option<Vec<(usize, u32)>> {
+        let mut nodes = vec![(i, 0, usize::MAX), (alt, 0, usize::MAX)];
+        let mut next = 0;
+        while next < nodes.len() && next < self.max_kicks as usize {
+            let (bin, _, _) = nodes[next];
+            for slot in self.bin(bin) {
+                let fp = slot.load(Ordering::Relaxed);
+                let to = self.alt_index(bin, fp);
+                if nodes.iter().any(|&(visited, _, _)| visited == to) {
+                    continue;
+                }
+                nodes.push((to, fp, next));
+                if self.bin(to).iter().any(|s| s.load(Ordering::Relaxed) == 0) {
+                    let mut path = vec![];
+                    let mut node = nodes.len() - 1;
+                    while node != usize::MAX {
+                        let (bin, fp, parent) = nodes[node];
+                        path.push((bin, fp));
+                        node = parent;
+                    }
+                    path.reverse();
+                    return Some(path);
+                }
+            }
+            next += 1;
+        }
+        None
+    }
+
+    // callers must hold the bin's stripe
+    fn insert_in_bin(&self, bin: usize, fingerprint: u32) -> bool {
+        match self
+            .bin(bin)
+            .iter()
+            .find(|s| s.load(Ordering::Relaxed) == 0)
+        {
+            Some(slot) => {
+                slot.store(fingerprint, Ordering::Release);
+                self.len.fetch_add(1, Ordering::Relaxed);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // callers must hold the bin's stripe
+    fn remove_from_bin(&self, bin: usize, fingerprint: u32) -> bool {
+        match self
+            .bin(bin)
+            .iter()
+            .find(|s| s.load(Ordering::Relaxed) == fingerprint)
+        {
+            Some(slot) => {
+                slot.store(0, Ordering::Release);
+                self.len.fetch_sub(1, Ordering::Relaxed);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn bin_contains(&self, bin: usize, fingerprint: u32) -> bool {
+        self.bin(bin)
+            .iter()
+            .any(|s| s.load(Ordering::Acquire) == fingerprint)
+    }
+
+    fn bin(&self, bin: usize) -> &[AtomicU32] {
+        &self.slots[bin * self.entries_per_bin..(bin + 1) * self.entries_per_bin]
+    }
+
+    // lock the stripes for both bins, in ascending order
+    fn lock_bins(&self, i: usize, alt: usize) -> Vec<MutexGuard<'_, ()>> {
+        let (a, b) = (i % self.locks.len(), alt % self.locks.len());
+        let mut stripes = vec![a.min(b)];
+        if a != b {
+            stripes.push(a.max(b));
+        }
+        stripes
+            .into_iter()
+            .map(|s| self.locks[s].lock().unwrap())
+            .collect()
+    }
+
+    pub(super) fn locate(&self, bytes: &[u8]) -> (usize, u32) {
+        let fingerprint = fingerprint_of::<T>(bytes, self.fingerprint_bits);
+        (
+            index_of::<T>(bytes, fingerprint, self.num_bins, 0),
+            fingerprint,
+        )
+    }
+
+    fn alt_index(&self, i: usize, fingerprint: u32) -> usize {
+        alt_index_of::<T>(i, fingerprint, self.num_bins, 0)
+    }
+}
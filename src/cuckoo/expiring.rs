@@ -0,0 +1,220 @@
+use std::{marker::PhantomData, mem};
+
+use rand::{rngs::StdRng, RngCore, SeedableRng};
+
+use super::{
+    alt_index_of, check_params, fingerprint_of, index_of, Error, InsertError,
+    DEFAULT_ENTRIES_PER_BIN, DEFAULT_FINGERPRINT_BITS, DEFAULT_MAX_KICKS,
+};
+use crate::{hasher::SeedableHasher, mem::heap_size};
+
+/// A cuckoo filter whose entries expire `ttl` time units after they were last added, for
+/// rate limiting and deduplication windows.
+///
+/// Each slot holds a fingerprint and the 32 bit time it expires at.  Time is whatever the
+/// caller passes as `now`, such as seconds since some epoch, and only needs to never go
+/// backwards.  Expired entries are no longer found, and are evicted lazily: every add and
+/// remove clears the expired slots of the bins it touches, and [ExpiringCuckooFilter::purge_expired]
+/// clears all of them.
+///
+/// Adding an entry whose fingerprint is already in one of its bins pushes that copy's expiry
+/// back rather than storing another one.
+pub struct ExpiringCuckooFilter<T>
+where
+    T: SeedableHasher,
+{
+    fingerprints: Vec<u32>,
+    expires: Vec<u32>,
+    num_bins: usize,
+    entries_per_bin: usize,
+    fingerprint_bits: u32,
+    max_kicks: u32,
+    ttl: u32,
+    len: usize,
+    rng: StdRng,
+    _hasher: PhantomData<T>,
+}
+
+impl<T> ExpiringCuckooFilter<T>
+where
+    T: SeedableHasher,
+{
+    /// Create a new filter with at least `num_bins` bins, rounded up to a power of two, whose
+    /// entries expire `ttl` after being added.
+    pub fn new(num_bins: usize, ttl: u32) -> Result<Self, Error> {
+        Self::with_fingerprint_bits(
+            num_bins,
+            DEFAULT_ENTRIES_PER_BIN,
+            DEFAULT_MAX_KICKS,
+            DEFAULT_FINGERPRINT_BITS,
+            ttl,
+        )
+    }
+
+    /// Fails unless `num_bins` and `entries_per_bin` are non-zero, and 4 <= `fingerprint_bits`
+    /// <= 32.
+    pub fn with_fingerprint_bits(
+        num_bins: usize,
+        entries_per_bin: usize,
+        max_kicks: u32,
+        fingerprint_bits: u32,
+        ttl: u32,
+    ) -> Result<Self, Error> {
+        let num_bins = check_params(num_bins, entries_per_bin, fingerprint_bits)?;
+        Ok(ExpiringCuckooFilter {
+            fingerprints: vec![0; num_bins * entries_per_bin],
+            expires: vec![0; num_bins * entries_per_bin],
+            num_bins,
+            entries_per_bin,
+            fingerprint_bits,
+            max_kicks,
+            ttl,
+            len: 0,
+            rng: StdRng::from_entropy(),
+            _hasher: PhantomData,
+        })
+    }
+
+    /// Number of entries stored, including expired entries that haven't been evicted yet.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Bytes used by the filter, its own size plus the heap allocated for its fingerprints and
+    /// expiry times, including spare capacity.
+    pub fn mem_usage(&self) -> usize {
+        mem::size_of::<Self>() + heap_size(&self.fingerprints) + heap_size(&self.expires)
+    }
+
+    /// Add an entry at time `now`, returning [InsertError::Full] if no room could be made for
+    /// it.  On failure the filter is unchanged, apart from evicted entries.
+    pub fn add<I>(&mut self, entry: I, now: u32) -> Result<(), InsertError>
+    where
+        I: AsRef<[u8]>,
+    {
+        let (i, alt, fingerprint) = self.locate(entry.as_ref());
+        self.evict(i, now);
+        self.evict(alt, now);
+        let expires = now.saturating_add(self.ttl);
+        if let Some(slot) = self.find(i, fingerprint).or(self.find(alt, fingerprint)) {
+            self.expires[slot] = self.expires[slot].max(expires);
+            return Ok(());
+        }
+        let mut carried = (fingerprint, expires);
+        if self.insert_in_bin(i, carried) || self.insert_in_bin(alt, carried) {
+            return Ok(());
+        }
+
+        // remember the kicks, so a failed insert can be rolled back
+        let mut kicked = vec![];
+        let mut bin = alt;
+        for _ in 0..self.max_kicks {
+            let slot =
+                bin * self.entries_per_bin + self.rng.next_u32() as usize % self.entries_per_bin;
+            let victim = (self.fingerprints[slot], self.expires[slot]);
+            (self.fingerprints[slot], self.expires[slot]) = carried;
+            kicked.push((slot, victim));
+            carried = victim;
+
+            bin = alt_index_of::<T>(bin, carried.0, self.num_bins, 0);
+            self.evict(bin, now);
+            if self.insert_in_bin(bin, carried) {
+                return Ok(());
+            }
+        }
+        for (slot, victim) in kicked.into_iter().rev() {
+            (self.fingerprints[slot], self.expires[slot]) = victim;
+        }
+        Err(InsertError::Full {
+            bin: i,
+            victim: fingerprint,
+        })
+    }
+
+    /// Remove one unexpired copy of an entry, returning false if it wasn't found.
+    pub fn remove<I>(&mut self, entry: I, now: u32) -> bool
+    where
+        I: AsRef<[u8]>,
+    {
+        let (i, alt, fingerprint) = self.locate(entry.as_ref());
+        self.evict(i, now);
+        self.evict(alt, now);
+        match self.find(i, fingerprint).or(self.find(alt, fingerprint)) {
+            Some(slot) => {
+                self.clear(slot);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Check for an unexpired copy of an entry at time `now`.
+    pub fn contains<I>(&self, entry: I, now: u32) -> bool
+    where
+        I: AsRef<[u8]>,
+    {
+        let (i, alt, fingerprint) = self.locate(entry.as_ref());
+        [i, alt].into_iter().any(|bin| {
+            self.slots(bin)
+                .any(|slot| self.fingerprints[slot] == fingerprint && self.expires[slot] > now)
+        })
+    }
+
+    /// Evict every entry that has expired by `now`, returning how many were evicted.
+    pub fn purge_expired(&mut self, now: u32) -> usize {
+        let before = self.len;
+        for bin in 0..self.num_bins {
+            self.evict(bin, now);
+        }
+        before - self.len
+    }
+
+    fn evict(&mut self, bin: usize, now: u32) {
+        for slot in self.slots(bin) {
+            if self.fingerprints[slot] != 0 && self.expires[slot] <= now {
+                self.clear(slot);
+            }
+        }
+    }
+
+    fn clear(&mut self, slot: usize) {
+        self.fingerprints[slot] = 0;
+        self.expires[slot] = 0;
+        self.len -= 1;
+    }
+
+    fn find(&self, bin: usize, fingerprint: u32) -> Option<usize> {
+        self.slots(bin)
+            .find(|&slot| self.fingerprints[slot] == fingerprint)
+    }
+
+    fn insert_in_bin(&mut self, bin: usize, (fingerprint, expires): (u32, u32)) -> bool {
+        match self.find(bin, 0) {
+            Some(slot) => {
+                self.fingerprints[slot] = fingerprint;
+                self.expires[slot] = expires;
+                self.len += 1;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn slots(&self, bin: usize) -> std::ops::Range<usize> {
+        bin * self.entries_per_bin..(bin + 1) * self.entries_per_bin
+    }
+
+    fn locate(&self, bytes: &[u8]) -> (usize, usize, u32) {
+        let fingerprint = fingerprint_of::<T>(bytes, self.fingerprint_bits);
+        let i = index_of::<T>(bytes, fingerprint, self.num_bins, 0);
+        (
+            i,
+            alt_index_of::<T>(i, fingerprint, self.num_bins, 0),
+            fingerprint,
+        )
+    }
+}
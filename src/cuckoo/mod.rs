@@ -1,13 +1,174 @@
 use std::{
-    hash::{DefaultHasher, Hasher},
+    any,
+    collections::TryReserveError,
+    fmt,
+    hash::Hash,
     marker::PhantomData,
+    mem,
+    sync::atomic::{AtomicU64, Ordering},
 };
 
-use fasthash::FastHasher;
-use rand::{thread_rng, RngCore};
+use rand::{rngs::StdRng, RngCore, SeedableRng};
+use thiserror::Error;
 
+use crate::{
+    event::{EventHook, FilterEvent},
+    format::FrameError,
+    hasher::SeedableHasher,
+    key::key_bytes,
+    measure::{self, FppMeasurement},
+    mem::heap_size,
+    sizing,
+};
+
+mod adaptive;
+mod bins;
+mod bytes;
+mod concurrent;
+mod expiring;
+mod taffy;
 #[cfg(test)]
 mod test;
+mod view;
+
+use self::bins::{Bins, Layout};
+
+pub use self::adaptive::AdaptiveCuckooFilter;
+pub use self::concurrent::ConcurrentCuckooFilter;
+pub use self::expiring::ExpiringCuckooFilter;
+pub use self::taffy::TaffyCuckooFilter;
+pub use self::view::CuckooFilterView;
+
+const DEFAULT_ENTRIES_PER_BIN: usize = 4;
+pub(crate) const DEFAULT_MAX_KICKS: u32 = 100;
+const DEFAULT_FINGERPRINT_BITS: u32 = 8;
+const MIN_FINGERPRINT_BITS: u32 = 4;
+const MAX_FINGERPRINT_BITS: u32 = 32;
+const STASH_SIZE: usize = 4;
+// fingerprints come from the same hasher as bin indexes, with a different seed
+const FINGERPRINT_SEED: u32 = 0x9e37_79b9;
+// load factor targeted when sizing a filter for a known set of items
+const BULK_LOAD_FACTOR: f64 = 0.9;
+// times a bulk build can double the bins it sized for before giving up
+const MAX_BULK_DOUBLINGS: u32 = 8;
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error(
+        "Invalid bin count {0}: must be > 0 and round up to a power of two that fits in usize"
+    )]
+    InvalidBinCount(usize),
+
+    #[error("Invalid entries per bin {0}: must be > 0")]
+    InvalidEntriesPerBin(usize),
+
+    #[error("Invalid fingerprint bits {0}: must be {1} <= fingerprint_bits <= {2}")]
+    InvalidFingerprintBits(u32, u32, u32),
+
+    #[error("Invalid false positive probability {0}: must be 0 < fpp < 1")]
+    InvalidFpp(f64),
+
+    #[error("Invalid max load factor {0}: must be 0 < max_load_factor <= 1")]
+    InvalidLoadFactor(f64),
+
+    #[error("Unsupported serialization format version {0}")]
+    UnsupportedVersion(u8),
+
+    #[error("Unsupported hash scheme version {0}")]
+    UnsupportedHashScheme(u8),
+
+    #[error("Malformed serialized filter")]
+    Malformed,
+
+    #[error("Serialized filter is truncated")]
+    Truncated,
+
+    #[error("Not a serialized filter of this type")]
+    InvalidMagic,
+
+    #[error("Checksum mismatch: expected {expected:#010x}, computed {actual:#010x}")]
+    ChecksumMismatch { expected: u32, actual: u32 },
+
+    #[error("Failed to allocate the filter: {0}")]
+    AllocationFailed(#[from] TryReserveError),
+
+    #[error("Filters were created with different parameters")]
+    IncompatibleFilter,
+
+    #[error("{0} copies of one item can't fit in its two bins and the stash")]
+    TooManyCopies(usize),
+
+    #[error("Items still don't fit in {0} bins")]
+    BulkBuildFailed(usize),
+
+    #[error("I/O error: {0}")]
+    Io(std::io::Error),
+}
+
+// A stream that ends early holds a truncated filter.
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        match err.kind() {
+            std::io::ErrorKind::UnexpectedEof => Error::Truncated,
+            _ => Error::Io(err),
+        }
+    }
+}
+
+impl From<FrameError> for Error {
+    fn from(err: FrameError) -> Self {
+        match err {
+            FrameError::Truncated => Error::Truncated,
+            FrameError::InvalidMagic => Error::InvalidMagic,
+            FrameError::UnsupportedVersion(version) => Error::UnsupportedVersion(version),
+            FrameError::TrailingBytes => Error::Malformed,
+            FrameError::ChecksumMismatch { expected, actual } => {
+                Error::ChecksumMismatch { expected, actual }
+            }
+        }
+    }
+}
+
+/// Returned by [CuckooFilter::add] when an entry can't be stored.
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InsertError {
+    /// Both candidate bins and the stash are full, and the filter can't grow.  The filter is
+    /// unchanged, `victim` is the fingerprint that couldn't be placed and `bin` is one of its
+    /// candidate bins, so callers can keep it elsewhere.
+    #[error("Cuckoo filter is full: no room for fingerprint {victim:#x} in bin {bin}")]
+    Full { bin: usize, victim: u32 },
+}
+
+/// Cumulative insertion statistics for a [CuckooFilter], from [CuckooFilter::stats].
+///
+/// Rising kick counts and a stash that keeps being used are early signs that the filter is
+/// close to full, before inserts start failing.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CuckooStats {
+    /// Total number of fingerprints moved to make room for others.
+    pub kicks: u64,
+    /// Longest chain of moves made by a single insert.
+    pub max_kick_chain: u64,
+    /// Number of inserts, or merged fingerprints, that couldn't be placed.
+    pub failed_inserts: u64,
+    /// Number of fingerprints put in the stash.
+    pub stashed: u64,
+    /// Number of lookups and removes answered from the stash.
+    pub stash_hits: u64,
+}
+
+/// How [CuckooFilter] makes room when both bins for a new fingerprint are full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum InsertStrategy {
+    /// Repeatedly kick a random fingerprint to its alternate bin, up to `max_kicks` times.
+    #[default]
+    RandomWalk,
+    /// Search breadth first for the shortest chain of moves that frees a slot, visiting at most
+    /// `max_kicks` bins.  This reaches higher load factors, and never evicts anything when the
+    /// search fails.
+    BreadthFirst,
+}
 
 /// Implements a cuckoo filter.  This is a probablisitic data structure akin to the bloom filter,
 /// which is used for set membership queries with some amount of error.
@@ -15,106 +176,1015 @@ mod test;
 /// A cuckoo filter stores the fingerprint for a key in an array. There are 2 possible candidate locations in the array, if the first
 /// location is full, the other location is used.  If both are full, then the filter initiates a series of swaps, moving an existing
 /// fingerprint to its alternate location. The number of swaps is bounded by the implementation. Each location can 1 or more entries.
-
-pub struct CuckooFilter<T>
+///
+/// Bins have a fixed number of slots, and all bins are bit packed into one contiguous buffer.
+/// With the `simd` feature, bins that fit in 64 bits are searched with a single word-parallel
+/// compare rather than slot by slot.
+///
+/// The number of bins is always rounded up to a power of two.  The alternate location is
+/// `i ^ hash(fingerprint)` masked to the bin count, which is only its own inverse when the mask
+/// covers whole bits, so either location can be recovered from the other and the fingerprint.
+///
+/// When an insert runs out of kicks, the fingerprint left over is kept in a small stash rather
+/// than dropped, so it is still found.  Stashed fingerprints are moved back into the table by
+/// later inserts when there is room.  While the stash is full, inserts don't kick at all, and
+/// fail if both candidate bins are full.
+///
+/// With [CuckooFilter::with_auto_resize], the filter doubles its bins instead of failing or
+/// filling up.  To make that possible without the original keys, only the low bits of an index
+/// come from the key's hash, and each doubling takes one more high bit from the fingerprint, so
+/// every fingerprint's new bin is known.  The catch is that fingerprints sharing a bin also share
+/// those bits, so each doubling costs a bit of fingerprint and doubles the false positive
+/// probability.  Growth stops once only 4 bits of fingerprint are left unshared.
+///
+/// Bin indexes and fingerprints are both derived from `T`, using different seeds, so the
+/// placement of every entry only depends on `T` and the filter's parameters.  A filter can be
+/// persisted and reloaded by another build as long as `T` produces the same hashes.  With the
+/// `serde` feature, filters also implement `Serialize` and `Deserialize`.
+///
+/// Kicks pick a random slot using `R`, which is seeded from the OS by default.  Use
+/// [CuckooFilter::with_rng] to supply a seeded generator for reproducible behavior, or another
+/// source of randomness.
+pub struct CuckooFilter<T, R = StdRng>
 where
-    T: FastHasher<Seed = u32>,
+    T: SeedableHasher,
+    R: RngCore,
 {
-    // this probably doesn't need to be a Vec<Vec<u8>> -- will convert to Vec<usize> and limit the fingerprint
-    // bit length
-    bins: Vec<Vec<u8>>,
-    max_kicks: u32, // how many times can we move fingerprints between bins
+    bins: Bins,
+    stash: Vec<(usize, u32)>, // (bin, fingerprint) of entries evicted when kicks ran out
+    max_kicks: u32,           // how many times can we move fingerprints between bins
+    growths: u32,             // how many times the bins have doubled
+    max_load_factor: Option<f64>,
+    strategy: InsertStrategy,
+    stats: CuckooStats,
+    stash_hits: AtomicU64, // counted by lookups, which only borrow the filter
+    hook: EventHook,
+    rng: R,
     _hasher: PhantomData<T>,
 }
 
 #[allow(dead_code)]
 impl<T> CuckooFilter<T>
 where
-    T: FastHasher<Seed = u32>,
+    T: SeedableHasher,
 {
-    pub fn new(num_bins: usize) -> Self {
-        Self::with_all_the_levers(num_bins, 4, 100)
+    /// Create a new cuckoo filter with at least `num_bins` bins, rounded up to a power of two.
+    pub fn new(num_bins: usize) -> Result<Self, Error> {
+        Self::with_all_the_levers(num_bins, DEFAULT_ENTRIES_PER_BIN, DEFAULT_MAX_KICKS)
+    }
+
+    /// Create a new cuckoo filter as [CuckooFilter::new] does, but return
+    /// [Error::AllocationFailed] rather than aborting if the bins can't be allocated.
+    pub fn try_new(num_bins: usize) -> Result<Self, Error> {
+        Self::try_with_fingerprint_bits(
+            num_bins,
+            DEFAULT_ENTRIES_PER_BIN,
+            DEFAULT_MAX_KICKS,
+            DEFAULT_FINGERPRINT_BITS,
+        )
+    }
+
+    pub fn with_all_the_levers(
+        num_bins: usize,
+        entries_per_bin: usize,
+        max_kicks: u32,
+    ) -> Result<Self, Error> {
+        Self::with_fingerprint_bits(
+            num_bins,
+            entries_per_bin,
+            max_kicks,
+            DEFAULT_FINGERPRINT_BITS,
+        )
+    }
+
+    /// Create a new cuckoo filter storing `fingerprint_bits` bits per fingerprint.
+    ///
+    /// The false positive probability is roughly `2 * entries_per_bin / 2^fingerprint_bits`, so
+    /// each extra bit halves it.
+    ///
+    /// Fails unless `num_bins` and `entries_per_bin` are non-zero, and 4 <= `fingerprint_bits`
+    /// <= 32.
+    pub fn with_fingerprint_bits(
+        num_bins: usize,
+        entries_per_bin: usize,
+        max_kicks: u32,
+        fingerprint_bits: u32,
+    ) -> Result<Self, Error> {
+        let num_bins = check_params(num_bins, entries_per_bin, fingerprint_bits)?;
+        Ok(Self::with_bins(
+            Bins::new(num_bins, entries_per_bin, fingerprint_bits),
+            max_kicks,
+        ))
     }
 
-    pub fn with_all_the_levers(num_bins: usize, entries_per_bin: usize, max_kicks: u32) -> Self {
-        // Can't use vec![Vec::with_capacity(4); num_bins] as the macro uses
-        // clone, and clone carries len forward, not capacity.
+    fn try_with_fingerprint_bits(
+        num_bins: usize,
+        entries_per_bin: usize,
+        max_kicks: u32,
+        fingerprint_bits: u32,
+    ) -> Result<Self, Error> {
+        let num_bins = check_params(num_bins, entries_per_bin, fingerprint_bits)?;
+        Ok(Self::with_bins(
+            Bins::try_new(num_bins, entries_per_bin, fingerprint_bits)?,
+            max_kicks,
+        ))
+    }
+
+    /// Create a new cuckoo filter with semi-sorted bins of 4 entries, which saves one bit per
+    /// entry compared to [CuckooFilter::with_fingerprint_bits] with the same false positive
+    /// probability, at the cost of decoding bins on every access.
+    ///
+    /// Fingerprints are sorted within each bin, so the top 4 bits of the 4 fingerprints form a
+    /// non-decreasing sequence, of which there are only 3876.  That sequence is stored as a 12 bit
+    /// index rather than 16 bits of nibbles, as described in the
+    /// [cuckoo filter paper](https://www.cs.cmu.edu/~dga/papers/cuckoo-conext2014.pdf).
+    ///
+    /// Fails unless `num_bins` is non-zero and 4 <= `fingerprint_bits` <= 32.
+    pub fn with_semi_sorted_bins(
+        num_bins: usize,
+        max_kicks: u32,
+        fingerprint_bits: u32,
+    ) -> Result<Self, Error> {
+        let num_bins = check_params(num_bins, bins::SEMI_SORTED_ENTRIES, fingerprint_bits)?;
+        Ok(Self::with_bins(
+            Bins::semi_sorted(num_bins, fingerprint_bits),
+            max_kicks,
+        ))
+    }
+
+    fn with_bins(bins: Bins, max_kicks: u32) -> Self {
         CuckooFilter {
-            bins: (0..num_bins)
-                .map(|_| Vec::with_capacity(entries_per_bin))
-                .collect::<Vec<_>>(),
+            bins,
+            stash: Vec::with_capacity(STASH_SIZE),
             max_kicks,
+            growths: 0,
+            max_load_factor: None,
+            strategy: InsertStrategy::default(),
+            stats: CuckooStats::default(),
+            stash_hits: AtomicU64::new(0),
+            hook: EventHook::default(),
+            rng: StdRng::from_entropy(),
             _hasher: PhantomData,
         }
     }
 
-    pub fn add<I>(&mut self, entry: I) -> bool
+    /// Create a new cuckoo filter with 4 entries per bin, and the smallest fingerprint that
+    /// keeps the false positive probability at or below `fpp` once the filter is full.
+    ///
+    /// Fingerprints are limited to 32 bits, so `fpp` below ~2e-9 can't be reached.
+    ///
+    /// Fails unless `num_bins` is non-zero and 0 < `fpp` < 1.
+    pub fn with_fpp(num_bins: usize, fpp: f64) -> Result<Self, Error> {
+        check_fpp(fpp)?;
+        Self::with_fingerprint_bits(
+            num_bins,
+            DEFAULT_ENTRIES_PER_BIN,
+            DEFAULT_MAX_KICKS,
+            fingerprint_bits_for_fpp(fpp, DEFAULT_ENTRIES_PER_BIN),
+        )
+    }
+
+    /// Create a new cuckoo filter for `n` entries with a false positive probability of at most
+    /// `fpp`, choosing the bin size, fingerprint width and number of bins.
+    ///
+    /// Following the cuckoo filter paper, bins hold 2 entries for `fpp` above 0.002, 4 down to
+    /// 0.00001 and 8 below that, as larger bins reach higher load factors (84%, 95% and 98%)
+    /// but need longer fingerprints for the same `fpp`.
+    ///
+    /// Fails unless 0 < `fpp` < 1.
+    pub fn with_capacity_and_fpp(n: usize, fpp: f64) -> Result<Self, Error> {
+        let (num_bins, entries_per_bin, fingerprint_bits) = capacity_params(n, fpp)?;
+        Self::with_fingerprint_bits(
+            num_bins,
+            entries_per_bin,
+            DEFAULT_MAX_KICKS,
+            fingerprint_bits,
+        )
+    }
+
+    /// Create a new cuckoo filter as [CuckooFilter::with_capacity_and_fpp] does, but return
+    /// [Error::AllocationFailed] rather than aborting if the bins can't be allocated.
+    pub fn try_with_capacity_and_fpp(n: usize, fpp: f64) -> Result<Self, Error> {
+        let (num_bins, entries_per_bin, fingerprint_bits) = capacity_params(n, fpp)?;
+        Self::try_with_fingerprint_bits(
+            num_bins,
+            entries_per_bin,
+            DEFAULT_MAX_KICKS,
+            fingerprint_bits,
+        )
+    }
+
+    /// Build a filter holding every item from `items`, sized for ~90% load of as many items as
+    /// their [Iterator::size_hint] promises.  If an item fails to insert, the build starts over
+    /// with twice as many bins, so no items are lost; the items added so far are kept for that.
+    ///
+    /// Fails unless `entries_per_bin` is non-zero and 4 <= `fingerprint_bits` <= 32, with
+    /// [Error::TooManyCopies] if one item appears more often than its two bins and the stash can
+    /// hold at any size, and with [Error::BulkBuildFailed] if the items still don't fit in 256
+    /// times the bins they were sized for.
+    pub fn from_iter_with_params<I, K>(
+        items: I,
+        entries_per_bin: usize,
+        max_kicks: u32,
+        fingerprint_bits: u32,
+    ) -> Result<Self, Error>
+    where
+        I: IntoIterator<Item = K>,
+        K: AsRef<[u8]>,
+    {
+        let items = items.into_iter();
+        let (lower, upper) = items.size_hint();
+        let expected = upper.unwrap_or(lower);
+        let mut filter = Self::try_with_fingerprint_bits(
+            bulk_bins(expected, entries_per_bin),
+            entries_per_bin,
+            max_kicks,
+            fingerprint_bits,
+        )?;
+        let mut added = Vec::with_capacity(expected);
+        for item in items {
+            if filter.add(&item).is_ok() {
+                added.push(item);
+                continue;
+            }
+            let copies = 1 + added
+                .iter()
+                .filter(|added| added.as_ref() == item.as_ref())
+                .count();
+            if copies > 2 * entries_per_bin + STASH_SIZE {
+                return Err(Error::TooManyCopies(copies));
+            }
+            added.push(item);
+            let limit =
+                bulk_bins(added.len(), entries_per_bin).saturating_mul(1 << MAX_BULK_DOUBLINGS);
+            let mut num_bins = filter.bins.num_bins() * 2;
+            filter = loop {
+                if num_bins > limit {
+                    return Err(Error::BulkBuildFailed(num_bins));
+                }
+                let mut rebuilt = Self::try_with_fingerprint_bits(
+                    num_bins,
+                    entries_per_bin,
+                    max_kicks,
+                    fingerprint_bits,
+                )?;
+                if added.iter().all(|item| rebuilt.add(item).is_ok()) {
+                    break rebuilt;
+                }
+                num_bins = rebuilt.bins.num_bins() * 2;
+            };
+        }
+        Ok(filter)
+    }
+}
+
+impl<T, R> CuckooFilter<T, R>
+where
+    T: SeedableHasher,
+    R: RngCore,
+{
+    /// Use `rng` to choose which fingerprint to kick out of a full bin.
+    pub fn with_rng<S>(self, rng: S) -> CuckooFilter<T, S>
+    where
+        S: RngCore,
+    {
+        CuckooFilter {
+            bins: self.bins,
+            stash: self.stash,
+            max_kicks: self.max_kicks,
+            growths: self.growths,
+            max_load_factor: self.max_load_factor,
+            strategy: self.strategy,
+            stats: self.stats,
+            stash_hits: self.stash_hits,
+            hook: self.hook,
+            rng,
+            _hasher: PhantomData,
+        }
+    }
+
+    /// Call `hook` when an insert exhausts its kicks, uses the stash, or fails, so fingerprints
+    /// don't pile up in the stash or get dropped unnoticed.  Clones of the filter share the hook,
+    /// and it isn't serialized.
+    pub fn with_event_hook<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(FilterEvent) + Send + Sync + 'static,
+    {
+        self.hook = EventHook::new(hook);
+        self
+    }
+
+    /// Choose how room is made for new fingerprints when both of their bins are full.
+    pub fn with_insert_strategy(mut self, strategy: InsertStrategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    /// Let the filter grow, doubling its bins when more than `max_load_factor` of its slots
+    /// would be occupied, or when an insert can't otherwise be placed.
+    ///
+    /// Every doubling doubles the false positive probability, see [CuckooFilter].
+    ///
+    /// Fails unless 0 < `max_load_factor` <= 1.
+    pub fn with_auto_resize(mut self, max_load_factor: f64) -> Result<Self, Error> {
+        if !(max_load_factor > 0.0 && max_load_factor <= 1.0) {
+            return Err(Error::InvalidLoadFactor(max_load_factor));
+        }
+        self.max_load_factor = Some(max_load_factor);
+        Ok(self)
+    }
+
+    /// Cumulative insertion statistics since the filter was created or deserialized.
+    pub fn stats(&self) -> CuckooStats {
+        CuckooStats {
+            stash_hits: self.stash_hits.load(Ordering::Relaxed),
+            ..self.stats
+        }
+    }
+
+    /// Number of fingerprints stored, including those held in the stash.
+    pub fn len(&self) -> usize {
+        self.bins.occupied() + self.stash.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Total number of slots across all bins.  Inserts start failing well before every slot is
+    /// used, see [CuckooFilter::load_factor].
+    pub fn capacity(&self) -> usize {
+        self.bins.num_bins() * self.bins.entries_per_bin()
+    }
+
+    /// Number of bins, which doubles each time the filter grows.
+    pub fn num_bins(&self) -> usize {
+        self.bins.num_bins()
+    }
+
+    pub fn entries_per_bin(&self) -> usize {
+        self.bins.entries_per_bin()
+    }
+
+    /// Bits per fingerprint, including any spent on growing.
+    pub fn fingerprint_bits(&self) -> u32 {
+        self.bins.fingerprint_bits()
+    }
+
+    /// Returns true if bins are semi-sorted, see [CuckooFilter::with_semi_sorted_bins].
+    pub fn is_semi_sorted(&self) -> bool {
+        self.bins.layout() == Layout::SemiSorted
+    }
+
+    pub fn max_kicks(&self) -> u32 {
+        self.max_kicks
+    }
+
+    pub fn insert_strategy(&self) -> InsertStrategy {
+        self.strategy
+    }
+
+    /// The load factor the filter grows at, or None if auto resizing is disabled.
+    pub fn max_load_factor(&self) -> Option<f64> {
+        self.max_load_factor
+    }
+
+    /// Number of times the filter has doubled its bins.
+    pub fn growths(&self) -> u32 {
+        self.growths
+    }
+
+    /// Fraction of slots that are occupied.  Inserts start needing long chains of kicks, and
+    /// eventually fail, as this approaches ~0.95 for 4 entries per bin.
+    pub fn load_factor(&self) -> f64 {
+        self.bins.occupied() as f64 / self.capacity() as f64
+    }
+
+    /// Estimate the current false positive probability from the fingerprint width, bin size and
+    /// load factor.
+    ///
+    /// A lookup compares against the `2 * entries_per_bin * load_factor` fingerprints expected in
+    /// its two bins, each matching by chance with probability `1 / (2^f - 1)`, where `f` is the
+    /// fingerprint width less any bits spent on growing.
+    pub fn current_fpp(&self) -> f64 {
+        let f = self.bins.fingerprint_bits() - self.growths;
+        let expected = 2.0 * self.bins.entries_per_bin() as f64 * self.load_factor();
+        1.0 - (1.0 - 1.0 / ((1_u64 << f) - 1) as f64).powf(expected)
+    }
+
+    /// Number of occupied slots in the bins, not counting entries held in the stash.
+    pub fn occupied_slots(&self) -> usize {
+        self.bins.occupied()
+    }
+
+    /// Bytes used by the filter, its own size plus the heap allocated for its bins and stash,
+    /// including spare capacity.
+    pub fn mem_usage(&self) -> usize {
+        mem::size_of::<Self>() + self.bins.heap_size() + heap_size(&self.stash)
+    }
+
+    /// Histogram of bin occupancy, where element `k` is the number of bins holding exactly `k`
+    /// fingerprints.
+    pub fn bin_fill_histogram(&self) -> Vec<usize> {
+        let mut histogram = vec![0; self.bins.entries_per_bin() + 1];
+        for bin in 0..self.bins.num_bins() {
+            histogram[self.bins.fingerprints(bin).count()] += 1;
+        }
+        histogram
+    }
+
+    /// Iterate over `(bin, fingerprint)` pairs for every stored fingerprint, including those held
+    /// in the stash, which are reported with one of their candidate bins.
+    pub fn iter(&self) -> impl Iterator<Item = (usize, u32)> + '_ {
+        (0..self.bins.num_bins())
+            .flat_map(move |bin| self.bins.fingerprints(bin).map(move |fp| (bin, fp)))
+            .chain(self.stash.iter().copied())
+    }
+
+    /// Merge the fingerprints of `other` into this filter, such as when combining filters built
+    /// for separate shards of a data set.  Fingerprints are placed in the same bins they
+    /// occupied in `other`, or their alternate bins, kicking as usual.
+    ///
+    /// Returns the `(bin, fingerprint)` pairs that couldn't be placed, which are no longer found
+    /// by this filter.  The filter doesn't grow while merging.
+    ///
+    /// Panics unless both filters have the same number of bins and fingerprint bits, see
+    /// [CuckooFilter::try_merge].
+    pub fn merge<S>(&mut self, other: &CuckooFilter<T, S>) -> Vec<(usize, u32)>
+    where
+        S: RngCore,
+    {
+        self.try_merge(other).expect(
+            "can only merge cuckoo filters with the same number of bins and fingerprint bits",
+        )
+    }
+
+    /// Merge as [CuckooFilter::merge] does, but return [Error::IncompatibleFilter] rather than
+    /// panicking if the filters have different numbers of bins or fingerprint bits.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all, fields(num_bins = self.bins.num_bins(), merged = other.len()), err))]
+    pub fn try_merge<S>(&mut self, other: &CuckooFilter<T, S>) -> Result<Vec<(usize, u32)>, Error>
+    where
+        S: RngCore,
+    {
+        if self.bins.num_bins() != other.bins.num_bins()
+            || self.growths != other.growths
+            || self.bins.fingerprint_bits() != other.bins.fingerprint_bits()
+        {
+            return Err(Error::IncompatibleFilter);
+        }
+        let mut unplaced = vec![];
+        for (bin, fingerprint) in other.iter() {
+            if let Some(left_over) = self.place(bin, fingerprint) {
+                if self.stash.len() < STASH_SIZE {
+                    self.push_stash(left_over);
+                } else {
+                    unplaced.push(left_over);
+                    self.record_failure(left_over);
+                }
+            }
+        }
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            unplaced = unplaced.len(),
+            len = self.len(),
+            "merged cuckoo filters"
+        );
+        Ok(unplaced)
+    }
+
+    /// Add an entry, returning [InsertError::Full] if there is no room for it.
+    pub fn add<I>(&mut self, entry: I) -> Result<(), InsertError>
     where
         I: AsRef<[u8]>,
     {
-        let mut fingerprint = Self::fingerprint(entry.as_ref());
-        let mut i = Self::hash(entry.as_ref()) as usize % self.bins.len();
-
-        for attempt in 0..self.max_kicks {
-            let bin = &mut self.bins[i];
-            if bin.len() < bin.capacity() {
-                bin.push(fingerprint);
-                return true;
+        self.prepare_insert();
+        let fingerprint = self.fingerprint(entry.as_ref());
+        let i = self.index(entry.as_ref(), fingerprint);
+        self.insert(entry.as_ref(), i, fingerprint)
+    }
+
+    /// Add any [Hash] value, encoded as the bytes its [Hash] implementation writes, with
+    /// integers in little endian.
+    pub fn add_item<K>(&mut self, item: &K) -> Result<(), InsertError>
+    where
+        K: Hash + ?Sized,
+    {
+        self.add(key_bytes(item))
+    }
+
+    /// Remove a value added with [CuckooFilter::add_item].
+    pub fn remove_item<K>(&mut self, item: &K) -> bool
+    where
+        K: Hash + ?Sized,
+    {
+        self.remove(key_bytes(item))
+    }
+
+    /// Check for a value added with [CuckooFilter::add_item].
+    pub fn contains_item<K>(&self, item: &K) -> bool
+    where
+        K: Hash + ?Sized,
+    {
+        self.contains(key_bytes(item))
+    }
+
+    /// Add an entry only if the filter doesn't already contain it, hashing the entry once for
+    /// both the check and the insert.
+    ///
+    /// Returns true if the entry was added, or false if it was already present (subject to the
+    /// false positive probability).
+    pub fn insert_if_absent<I>(&mut self, entry: I) -> Result<bool, InsertError>
+    where
+        I: AsRef<[u8]>,
+    {
+        self.prepare_insert();
+        let fingerprint = self.fingerprint(entry.as_ref());
+        let i = self.index(entry.as_ref(), fingerprint);
+        let alt = self.alt_index(i, fingerprint);
+        if self.bins.contains(i, fingerprint)
+            || self.bins.contains(alt, fingerprint)
+            || self.stash_position(i, alt, fingerprint).is_some()
+        {
+            return Ok(false);
+        }
+        self.insert(entry.as_ref(), i, fingerprint).map(|_| true)
+    }
+
+    // grow ahead of the load factor limit, and make room in the stash
+    fn prepare_insert(&mut self) {
+        if let Some(max_load_factor) = self.max_load_factor {
+            let capacity = self.bins.num_bins() * self.bins.entries_per_bin();
+            if (self.bins.occupied() + 1) as f64 > max_load_factor * capacity as f64 {
+                self.grow();
+            }
+        }
+        self.drain_stash();
+    }
+
+    // `entry` is only hashed again if the filter grows, which moves its bins
+    fn insert(&mut self, entry: &[u8], i: usize, fingerprint: u32) -> Result<(), InsertError> {
+        let (bin, victim) = match self.place(i, fingerprint) {
+            Some(left_over) => left_over,
+            None => return Ok(()),
+        };
+        if self.stash.len() < STASH_SIZE {
+            self.push_stash((bin, victim));
+            if self.grow() {
+                self.drain_stash();
+            }
+            return Ok(());
+        }
+        // with a full stash, placing leaves the filter unchanged on failure
+        if self.grow() {
+            return self.insert(entry, self.index(entry, fingerprint), fingerprint);
+        }
+        self.record_failure((bin, victim));
+        Err(InsertError::Full { bin, victim })
+    }
+
+    fn push_stash(&mut self, left_over: (usize, u32)) {
+        self.stash.push(left_over);
+        self.stats.stashed += 1;
+        self.hook.emit(FilterEvent::Stashed {
+            len: self.stash.len(),
+        });
+    }
+
+    fn record_failure(&mut self, (bin, fingerprint): (usize, u32)) {
+        self.stats.failed_inserts += 1;
+        self.hook
+            .emit(FilterEvent::InsertFailed { bin, fingerprint });
+    }
+
+    // Place a fingerprint in one of its bins, returning whatever fingerprint was left without a
+    // bin, along with one of its candidate bins.
+    fn place(&mut self, i: usize, fingerprint: u32) -> Option<(usize, u32)> {
+        let alt = self.alt_index(i, fingerprint);
+        if self.bins.insert(i, fingerprint) || self.bins.insert(alt, fingerprint) {
+            return None;
+        }
+        match self.strategy {
+            // kicking could leave a victim with nowhere to go
+            InsertStrategy::RandomWalk if self.stash.len() == STASH_SIZE => Some((i, fingerprint)),
+            InsertStrategy::RandomWalk => self.random_walk(alt, fingerprint),
+            InsertStrategy::BreadthFirst => match self.shortest_path(i, alt) {
+                Some(path) => {
+                    self.follow_path(path, fingerprint);
+                    None
+                }
+                None => {
+                    self.hook.emit(FilterEvent::KickChainExhausted {
+                        kicks: self.max_kicks as u64,
+                    });
+                    Some((i, fingerprint))
+                }
+            },
+        }
+    }
+
+    // Kick random fingerprints to their alternate bin until one lands in a bin with room.
+    fn random_walk(&mut self, mut i: usize, mut fingerprint: u32) -> Option<(usize, u32)> {
+        for kicks in 1..=self.max_kicks as u64 {
+            let kick_idx = self.rng.next_u32() as usize % self.bins.entries_per_bin();
+            fingerprint = self.bins.swap(i, kick_idx, fingerprint);
+            i = self.alt_index(i, fingerprint);
+            if self.bins.insert(i, fingerprint) {
+                self.record_kicks(kicks);
+                return None;
             }
-            if attempt != 0 {
-                let kick_idx = thread_rng().next_u32() as usize % bin.len();
-                let kicked = bin.swap_remove(kick_idx);
-                bin.push(fingerprint);
-                fingerprint = kicked;
+        }
+        self.record_kicks(self.max_kicks as u64);
+        self.hook.emit(FilterEvent::KickChainExhausted {
+            kicks: self.max_kicks as u64,
+        });
+        Some((i, fingerprint))
+    }
+
+    fn record_kicks(&mut self, kicks: u64) {
+        self.stats.kicks += kicks;
+        self.stats.max_kick_chain = self.stats.max_kick_chain.max(kicks);
+    }
+
+    // Breadth first search from both candidate bins for the shortest chain of moves ending in
+    // a bin with room, visiting at most max_kicks bins.  Returns (bin, fingerprint moved into
+    // it from the previous bin) pairs, starting from the candidate bin.
+    fn shortest_path(&self, i: usize, alt: usize) -> Option<Vec<(usize, u32)>> {
+        // (bin, fingerprint moved in, parent node)
+        let mut nodes = vec![(i, 0, usize::MAX), (alt, 0, usize::MAX)];
+        let mut next = 0;
+        while next < nodes.len() && next < self.max_kicks as usize {
+            let (bin, _, _) = nodes[next];
+            for fp in self.bins.fingerprints(bin).collect::<Vec<_>>() {
+                let to = self.alt_index(bin, fp);
+                if nodes.iter().any(|&(visited, _, _)| visited == to) {
+                    continue;
+                }
+                nodes.push((to, fp, next));
+                if !self.bins.is_full(to) {
+                    let mut path = vec![];
+                    let mut node = nodes.len() - 1;
+                    while node != usize::MAX {
+                        let (bin, fp, parent) = nodes[node];
+                        path.push((bin, fp));
+                        node = parent;
+                    }
+                    path.reverse();
+                    return Some(path);
+                }
             }
-            i = (i ^ Self::hash(&fingerprint.to_ne_bytes()) as usize) % self.bins.len();
+            next += 1;
         }
-        false
+        None
+    }
+
+    // Move fingerprints along a path from its end, so each move has room, then insert.
+    fn follow_path(&mut self, path: Vec<(usize, u32)>, fingerprint: u32) {
+        for pair in path.windows(2).rev() {
+            let (from, _) = pair[0];
+            let (to, fp) = pair[1];
+            self.bins.insert(to, fp);
+            self.bins.remove(from, fp);
+        }
+        self.record_kicks(path.len() as u64 - 1);
+        self.bins.insert(path[0].0, fingerprint);
     }
 
     pub fn remove<I>(&mut self, entry: I) -> bool
     where
         I: AsRef<[u8]>,
     {
-        let fingerprint = Self::fingerprint(entry.as_ref());
-        let i = Self::hash(entry.as_ref()) as usize % self.bins.len();
-
-        let bin = &mut self.bins[i];
-        if let Some(rmi) = bin.iter().position(|v| *v == fingerprint) {
-            bin.swap_remove(rmi);
+        let fingerprint = self.fingerprint(entry.as_ref());
+        let i = self.index(entry.as_ref(), fingerprint);
+        let alt = self.alt_index(i, fingerprint);
+        if self.bins.remove(i, fingerprint) || self.bins.remove(alt, fingerprint) {
             return true;
         }
-        let i = (i ^ Self::hash(&fingerprint.to_ne_bytes()) as usize) % self.bins.len();
-        let bin = &mut self.bins[i];
-        if let Some(rmi) = bin.iter().position(|v| *v == fingerprint) {
-            bin.swap_remove(rmi);
-            return true;
+        match self.stash_position(i, alt, fingerprint) {
+            Some(pos) => {
+                self.stash.swap_remove(pos);
+                self.stash_hits.fetch_add(1, Ordering::Relaxed);
+                true
+            }
+            None => false,
         }
-        false
     }
 
     pub fn contains<I>(&self, entry: I) -> bool
     where
         I: AsRef<[u8]>,
     {
-        let fingerprint = Self::fingerprint(entry.as_ref());
-        let i = Self::hash(entry.as_ref()) as usize % self.bins.len();
-        !self.bins[i].is_empty() && self.bins[i].contains(&fingerprint) || {
-            let i = (i ^ Self::hash(&fingerprint.to_ne_bytes()) as usize) % self.bins.len();
-            !self.bins[i].is_empty() && self.bins[i].contains(&fingerprint)
+        let fingerprint = self.fingerprint(entry.as_ref());
+        let i = self.index(entry.as_ref(), fingerprint);
+        let alt = self.alt_index(i, fingerprint);
+        if self.bins.contains(i, fingerprint) || self.bins.contains(alt, fingerprint) {
+            return true;
         }
+        let stashed = self.stash_position(i, alt, fingerprint).is_some();
+        if stashed {
+            self.stash_hits.fetch_add(1, Ordering::Relaxed);
+        }
+        stashed
     }
 
-    // Technically doesn't need to be in the impl block, but hash is, so it feels odd to leave this out
-    fn fingerprint(bytes: &[u8]) -> u8 {
-        let mut hasher = DefaultHasher::new();
-        hasher.write(bytes);
-        hasher.finish() as u8
+    /// Probe the filter with `trials` random entries, as [crate::BloomFilter::measure_fpp]
+    /// does.  Probes found in the stash aren't counted in [CuckooFilter::stats].
+    pub fn measure_fpp<G>(&self, rng: &mut G, trials: u64) -> FppMeasurement
+    where
+        G: RngCore + ?Sized,
+    {
+        measure::measure_fpp(rng, trials, |probe| {
+            let fingerprint = self.fingerprint(probe);
+            let i = self.index(probe, fingerprint);
+            let alt = self.alt_index(i, fingerprint);
+            self.bins.contains(i, fingerprint)
+                || self.bins.contains(alt, fingerprint)
+                || self.stash_position(i, alt, fingerprint).is_some()
+        })
     }
 
-    fn hash(bytes: &[u8]) -> u64 {
-        let mut hash = T::new();
-        hash.write(bytes);
-        hash.finish()
+    /// Estimate how many times an entry has been added, by counting the copies of its
+    /// fingerprint in both candidate bins and the stash.
+    ///
+    /// As with [CuckooFilter::contains], other entries with the same fingerprint and bins are
+    /// counted too, so this can overestimate.  An entry can only be held
+    /// `2 * entries_per_bin` times in the bins, after which further duplicates end up kicking
+    /// each other into the stash, and then fail to insert.
+    pub fn estimate<I>(&self, entry: I) -> usize
+    where
+        I: AsRef<[u8]>,
+    {
+        let fingerprint = self.fingerprint(entry.as_ref());
+        let i = self.index(entry.as_ref(), fingerprint);
+        let alt = self.alt_index(i, fingerprint);
+        let mut count = self.bins.count(i, fingerprint);
+        if alt != i {
+            count += self.bins.count(alt, fingerprint);
+        }
+        count
+            + self
+                .stash
+                .iter()
+                .filter(|&&(bin, fp)| fp == fingerprint && (bin == i || bin == alt))
+                .count()
+    }
+
+    // move stashed fingerprints back into the table if either of their bins has room
+    fn drain_stash(&mut self) {
+        let mut pos = 0;
+        while pos < self.stash.len() {
+            let (i, fingerprint) = self.stash[pos];
+            let alt = self.alt_index(i, fingerprint);
+            if self.bins.insert(i, fingerprint) || self.bins.insert(alt, fingerprint) {
+                self.stash.swap_remove(pos);
+            } else {
+                pos += 1;
+            }
+        }
     }
+
+    fn stash_position(&self, i: usize, alt: usize, fingerprint: u32) -> Option<usize> {
+        self.stash
+            .iter()
+            .position(|&(bin, fp)| fp == fingerprint && (bin == i || bin == alt))
+    }
+
+    fn index(&self, bytes: &[u8], fingerprint: u32) -> usize {
+        index_of::<T>(bytes, fingerprint, self.bins.num_bins(), self.growths)
+    }
+
+    fn alt_index(&self, i: usize, fingerprint: u32) -> usize {
+        alt_index_of::<T>(i, fingerprint, self.bins.num_bins(), self.growths)
+    }
+
+    // Double the bins if auto resizing is enabled and there are fingerprint bits to spare.  Each
+    // fingerprint in bin i moves to bin i or i + num_bins depending on its next bit, which is
+    // the same for both of its candidate bins, so a bin never receives more than it had.
+    fn grow(&mut self) -> bool {
+        if self.max_load_factor.is_none()
+            || self.growths >= self.bins.fingerprint_bits() - MIN_FINGERPRINT_BITS
+        {
+            return false;
+        }
+        let num_bins = self.bins.num_bins();
+        let split = |fp: u32| ((fp >> self.growths) & 1) as usize * num_bins;
+
+        let mut bins = self.bins.empty_like(num_bins * 2);
+        for i in 0..num_bins {
+            for fp in self.bins.fingerprints(i) {
+                bins.insert(i + split(fp), fp);
+            }
+        }
+        for (i, fp) in self.stash.iter_mut() {
+            *i += split(*fp);
+        }
+        self.bins = bins;
+        self.growths += 1;
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            num_bins = self.bins.num_bins(),
+            growths = self.growths,
+            len = self.len(),
+            "grew cuckoo filter"
+        );
+        true
+    }
+
+    fn fingerprint(&self, bytes: &[u8]) -> u32 {
+        fingerprint_of::<T>(bytes, self.bins.fingerprint_bits())
+    }
+}
+
+impl<T, R> fmt::Debug for CuckooFilter<T, R>
+where
+    T: SeedableHasher,
+    R: RngCore,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CuckooFilter")
+            .field("hasher", &any::type_name::<T>())
+            .field("num_bins", &self.bins.num_bins())
+            .field("entries_per_bin", &self.bins.entries_per_bin())
+            .field("fingerprint_bits", &self.bins.fingerprint_bits())
+            .field("layout", &self.bins.layout())
+            .field("growths", &self.growths)
+            .field("max_load_factor", &self.max_load_factor)
+            .field("strategy", &self.strategy)
+            .field("len", &self.len())
+            .field("stash_len", &self.stash.len())
+            .finish()
+    }
+}
+
+/// A one line summary: the number of fingerprints stored, the load factor, and the estimated
+/// false positive probability from [CuckooFilter::current_fpp].
+impl<T, R> fmt::Display for CuckooFilter<T, R>
+where
+    T: SeedableHasher,
+    R: RngCore,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "CuckooFilter: items≈{}, fill {:.1}%, fpp≈{:.2e}",
+            self.len(),
+            self.load_factor() * 100.0,
+            self.current_fpp()
+        )
+    }
+}
+
+// derived impls would require T: Clone/PartialEq, which hashers don't implement
+impl<T, R> Clone for CuckooFilter<T, R>
+where
+    T: SeedableHasher,
+    R: RngCore + Clone,
+{
+    fn clone(&self) -> Self {
+        CuckooFilter {
+            bins: self.bins.clone(),
+            stash: self.stash.clone(),
+            max_kicks: self.max_kicks,
+            growths: self.growths,
+            max_load_factor: self.max_load_factor,
+            strategy: self.strategy,
+            stats: self.stats,
+            stash_hits: AtomicU64::new(self.stash_hits.load(Ordering::Relaxed)),
+            hook: self.hook.clone(),
+            rng: self.rng.clone(),
+            _hasher: PhantomData,
+        }
+    }
+}
+
+/// Filters are equal if they have the same parameters and the same fingerprints in the same
+/// slots.  Statistics and the random number generator aren't compared, but the order entries
+/// were added in can be: it decides which bin each fingerprint ended up in.
+impl<T, R> PartialEq for CuckooFilter<T, R>
+where
+    T: SeedableHasher,
+    R: RngCore,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.bins == other.bins
+            && self.stash == other.stash
+            && self.max_kicks == other.max_kicks
+            && self.growths == other.growths
+            && self.max_load_factor.map(f64::to_bits) == other.max_load_factor.map(f64::to_bits)
+            && self.strategy == other.strategy
+    }
+}
+
+impl<T, R> Eq for CuckooFilter<T, R>
+where
+    T: SeedableHasher,
+    R: RngCore,
+{
+}
+
+// 0 marks an empty slot, so fingerprints are mapped into 1..2^fingerprint_bits
+fn fingerprint_of<T>(bytes: &[u8], fingerprint_bits: u32) -> u32
+where
+    T: SeedableHasher,
+{
+    let mut hasher = T::with_seed(FINGERPRINT_SEED);
+    hasher.write(bytes);
+    (hasher.finish() % ((1 << fingerprint_bits) - 1)) as u32 + 1
+}
+
+// Bins holding `n` items at the bulk load factor.
+fn bulk_bins(n: usize, entries_per_bin: usize) -> usize {
+    (n as f64 / (entries_per_bin as f64 * BULK_LOAD_FACTOR))
+        .ceil()
+        .max(1.0) as usize
+}
+
+// The low bits of an index come from the key's hash, and any bits added by growing come from
+// the fingerprint, so both candidate bins stay within the same block of base bins.
+fn index_of<T>(bytes: &[u8], fingerprint: u32, num_bins: usize, growths: u32) -> usize
+where
+    T: SeedableHasher,
+{
+    let base_bins = num_bins >> growths;
+    let grown_bits = ((fingerprint as usize) & ((1 << growths) - 1)) << base_bins.trailing_zeros();
+    (hash::<T>(bytes) as usize & (base_bins - 1)) | grown_bits
+}
+
+// base bin counts are powers of two, so alt_index_of(alt_index_of(i, fp), fp) == i
+fn alt_index_of<T>(i: usize, fingerprint: u32, num_bins: usize, growths: u32) -> usize
+where
+    T: SeedableHasher,
+{
+    i ^ (hash::<T>(&fingerprint.to_le_bytes()) as usize & ((num_bins >> growths) - 1))
+}
+
+fn hash<T>(bytes: &[u8]) -> u64
+where
+    T: SeedableHasher,
+{
+    let mut hash = T::new();
+    hash.write(bytes);
+    hash.finish()
+}
+
+// Number of bins, entries per bin and fingerprint bits for `n` entries at `fpp`, see
+// [CuckooFilter::with_capacity_and_fpp].
+fn capacity_params(n: usize, fpp: f64) -> Result<(usize, usize, u32), Error> {
+    check_fpp(fpp)?;
+    let slots = sizing::cuckoo_slots(n, fpp, entries_per_bin_for_fpp(fpp))
+        .map_err(|_| Error::InvalidBinCount(usize::MAX))?;
+    Ok((
+        slots.num_bins,
+        slots.entries_per_bin,
+        slots.fingerprint_bits,
+    ))
+}
+
+// Following the cuckoo filter paper, larger bins reach higher load factors but need longer
+// fingerprints for the same fpp, so they're only worth it for low fpp.
+pub(crate) fn entries_per_bin_for_fpp(fpp: f64) -> usize {
+    match fpp {
+        fpp if fpp > 0.002 => 2,
+        fpp if fpp > 0.00001 => 4,
+        _ => 8,
+    }
+}
+
+// Validates constructor parameters, returning `num_bins` rounded up to a power of two.
+fn check_params(
+    num_bins: usize,
+    entries_per_bin: usize,
+    fingerprint_bits: u32,
+) -> Result<usize, Error> {
+    if entries_per_bin == 0 {
+        return Err(Error::InvalidEntriesPerBin(entries_per_bin));
+    }
+    if !(MIN_FINGERPRINT_BITS..=MAX_FINGERPRINT_BITS).contains(&fingerprint_bits) {
+        return Err(Error::InvalidFingerprintBits(
+            fingerprint_bits,
+            MIN_FINGERPRINT_BITS,
+            MAX_FINGERPRINT_BITS,
+        ));
+    }
+    check_bin_count(num_bins)
+}
+
+fn check_bin_count(num_bins: usize) -> Result<usize, Error> {
+    match num_bins.checked_next_power_of_two() {
+        Some(rounded) if num_bins > 0 => Ok(rounded),
+        _ => Err(Error::InvalidBinCount(num_bins)),
+    }
+}
+
+fn check_fpp(fpp: f64) -> Result<(), Error> {
+    if fpp > 0.0 && fpp < 1.0 {
+        Ok(())
+    } else {
+        Err(Error::InvalidFpp(fpp))
+    }
+}
+
+// From the cuckoo filter paper, a full filter has fpp ~= 2 * entries_per_bin / 2^f
+pub(crate) fn fingerprint_bits_for_fpp(fpp: f64, entries_per_bin: usize) -> u32 {
+    ((2.0 * entries_per_bin as f64 / fpp).log2().ceil() as u32)
+        .clamp(MIN_FINGERPRINT_BITS, MAX_FINGERPRINT_BITS)
 }
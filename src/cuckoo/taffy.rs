@@ -0,0 +1,285 @@
+use std::{marker::PhantomData, mem};
+
+use rand::{thread_rng, RngCore};
+
+use super::{check_bin_count, Error, DEFAULT_ENTRIES_PER_BIN, DEFAULT_MAX_KICKS};
+use crate::{hasher::SeedableHasher, mem::heap_size};
+
+const TAG_BITS: u32 = 8;
+const DEFAULT_FINGERPRINT_BITS: u32 = 16;
+const MIN_FINGERPRINT_BITS: u32 = TAG_BITS + 1;
+const MAX_FINGERPRINT_BITS: u32 = 32;
+// hash bits available to bin indexes and tails, the top TAG_BITS are the tag
+const INDEX_AND_TAIL_BITS: u32 = u64::BITS - TAG_BITS;
+
+/// A cuckoo filter that grows without access to the original keys, based on the
+/// [taffy cuckoo filter](https://arxiv.org/abs/2109.02512).
+///
+/// Each key is hashed to 64 bits.  The low bits pick a bin, the top 8 bits are a tag that picks
+/// the alternate bin, and the bits in between are a variable length tail stored alongside the
+/// tag.  When an insert can't be placed, the number of bins doubles, and every stored entry
+/// moves to bin `i` or `i + num_bins` according to the first bit of its tail, which is then
+/// dropped.  Entries added after growing get full length tails, so only the entries that were
+/// already present lose precision, and the false positive probability creeps up slowly rather
+/// than doubling each time.
+///
+/// An entry whose tail runs out is copied into both halves, as it can no longer be told apart.
+/// Entries can't be removed, since a copy or a shortened tail may be shared by several keys.
+pub struct TaffyCuckooFilter<T>
+where
+    T: SeedableHasher,
+{
+    // one u64 per slot, 0 is empty, otherwise (1 << len | tail) << TAG_BITS | tag
+    slots: Vec<u64>,
+    base_bits: u32, // log2 of the initial bin count
+    growths: u32,   // how many times the bins have doubled
+    tail_bits: u32,
+    max_kicks: u32,
+    occupied: usize,
+    _hasher: PhantomData<T>,
+}
+
+impl<T> TaffyCuckooFilter<T>
+where
+    T: SeedableHasher,
+{
+    /// Create a new taffy cuckoo filter with at least `num_bins` bins, rounded up to a power of
+    /// two, and 16 bit fingerprints.
+    pub fn new(num_bins: usize) -> Result<Self, Error> {
+        Self::with_fingerprint_bits(num_bins, DEFAULT_FINGERPRINT_BITS)
+    }
+
+    /// Create a new taffy cuckoo filter whose fresh fingerprints have `fingerprint_bits` bits, of
+    /// which 8 are the tag.
+    ///
+    /// Fails unless `num_bins` is non-zero and 9 <= `fingerprint_bits` <= 32.
+    pub fn with_fingerprint_bits(num_bins: usize, fingerprint_bits: u32) -> Result<Self, Error> {
+        if !(MIN_FINGERPRINT_BITS..=MAX_FINGERPRINT_BITS).contains(&fingerprint_bits) {
+            return Err(Error::InvalidFingerprintBits(
+                fingerprint_bits,
+                MIN_FINGERPRINT_BITS,
+                MAX_FINGERPRINT_BITS,
+            ));
+        }
+        let num_bins = check_bin_count(num_bins)?;
+        Ok(TaffyCuckooFilter {
+            slots: vec![0; num_bins * DEFAULT_ENTRIES_PER_BIN],
+            base_bits: num_bins.trailing_zeros(),
+            growths: 0,
+            tail_bits: fingerprint_bits - TAG_BITS,
+            max_kicks: DEFAULT_MAX_KICKS,
+            occupied: 0,
+            _hasher: PhantomData,
+        })
+    }
+
+    /// Current number of bins.
+    pub fn num_bins(&self) -> usize {
+        self.slots.len() / DEFAULT_ENTRIES_PER_BIN
+    }
+
+    /// Bytes used by the filter, its own size plus the heap allocated for its slots, including
+    /// spare capacity.
+    pub fn mem_usage(&self) -> usize {
+        mem::size_of::<Self>() + heap_size(&self.slots)
+    }
+
+    /// Add an entry, growing the filter if it can't be placed.  Returns false if the filter has
+    /// used up every hash bit for bin indexes and can't grow any further, or if growing wouldn't
+    /// make room, as when both bins of the entry are full of copies of it.  The filter is left
+    /// unchanged when it returns false.
+    pub fn add<I>(&mut self, entry: I) -> bool
+    where
+        I: AsRef<[u8]>,
+    {
+        let hash = Self::hash(entry.as_ref());
+        let tail_len = self.tail_bits.min(self.available_tail_bits());
+        let tail = (hash >> (self.base_bits + self.growths)) & mask(tail_len);
+        let entry = ((1 << tail_len | tail) << TAG_BITS) | hash >> INDEX_AND_TAIL_BITS;
+        self.insert(self.index(hash), entry)
+    }
+
+    pub fn contains<I>(&self, entry: I) -> bool
+    where
+        I: AsRef<[u8]>,
+    {
+        let hash = Self::hash(entry.as_ref());
+        let tag = hash >> INDEX_AND_TAIL_BITS;
+        let tail = hash >> (self.base_bits + self.growths);
+        let i = self.index(hash);
+        [i, self.alt_index(i, tag)].into_iter().any(|bin| {
+            self.bin(bin).iter().any(|&slot| {
+                if slot == 0 || slot & mask(TAG_BITS) != tag {
+                    return false;
+                }
+                let (len, stored) = decode_tail(slot);
+                tail & mask(len) == stored
+            })
+        })
+    }
+
+    // Place an entry, growing the filter if needed.  If it can't be placed, the filter is left
+    // as it was, so no entry it displaced is lost.
+    fn insert(&mut self, mut i: usize, mut entry: u64) -> bool {
+        let mut kicks = vec![];
+        for attempt in 0..self.max_kicks {
+            if self.insert_in_bin(i, entry) {
+                return true;
+            }
+            if attempt != 0 {
+                let kick = i * DEFAULT_ENTRIES_PER_BIN
+                    + thread_rng().next_u32() as usize % DEFAULT_ENTRIES_PER_BIN;
+                std::mem::swap(&mut self.slots[kick], &mut entry);
+                kicks.push(kick);
+            }
+            i = self.alt_index(i, entry & mask(TAG_BITS));
+        }
+        // the victim is placed after growing, keeping the bin it was headed for
+        if self.growing_makes_room(i, entry) {
+            let (growths, occupied) = (self.growths, self.occupied);
+            if let Some(old) = self.grow() {
+                if self.place_split(i, entry) {
+                    return true;
+                }
+                self.slots = old;
+                self.growths = growths;
+                self.occupied = occupied;
+            }
+        }
+        for &kick in kicks.iter().rev() {
+            std::mem::swap(&mut self.slots[kick], &mut entry);
+        }
+        false
+    }
+
+    // Place a victim headed for bin `i` before the bins doubled.  One without a tail has a copy
+    // in each half, and placing one may grow the filter again, which moves the other.
+    fn place_split(&mut self, i: usize, entry: u64) -> bool {
+        let mut pending = split(i, entry, self.num_bins() / 2);
+        while let Some((i, entry)) = pending.pop() {
+            let growths = self.growths;
+            if !self.insert(i, entry) {
+                return false;
+            }
+            for growth in growths..self.growths {
+                let num_bins = 1 << (self.base_bits + growth);
+                pending = pending
+                    .into_iter()
+                    .flat_map(|(i, entry)| split(i, entry, num_bins))
+                    .collect();
+            }
+        }
+        true
+    }
+
+    // Whether growing can ever make room for the victim: entries only move apart from it once
+    // their tails differ from its tail within both their lengths, or if it has no tail, once
+    // they have one.  Copies of one key never do, so their bins stay as full however it grows.
+    fn growing_makes_room(&self, i: usize, entry: u64) -> bool {
+        let (victim_len, victim_tail) = decode_tail(entry);
+        let alt = self.alt_index(i, entry & mask(TAG_BITS));
+        [i, alt]
+            .into_iter()
+            .flat_map(|bin| self.bin(bin))
+            .any(|&slot| {
+                if slot == 0 {
+                    return true;
+                }
+                let (len, tail) = decode_tail(slot);
+                match victim_len {
+                    0 => len > 0,
+                    _ => (tail ^ victim_tail) & mask(len.min(victim_len)) != 0,
+                }
+            })
+    }
+
+    fn insert_in_bin(&mut self, i: usize, entry: u64) -> bool {
+        let bin = i * DEFAULT_ENTRIES_PER_BIN;
+        match self.slots[bin..bin + DEFAULT_ENTRIES_PER_BIN]
+            .iter()
+            .position(|&slot| slot == 0)
+        {
+            Some(slot) => {
+                self.slots[bin + slot] = entry;
+                self.occupied += 1;
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Double the bins, moving each entry by the first bit of its tail, and return the old
+    // slots.  Both candidate bins of an entry share everything above the base bits, so a bin
+    // never receives more than it had.
+    fn grow(&mut self) -> Option<Vec<u64>> {
+        if self.available_tail_bits() == 0 {
+            return None;
+        }
+        let num_bins = self.num_bins();
+        let old = std::mem::replace(
+            &mut self.slots,
+            vec![0; num_bins * 2 * DEFAULT_ENTRIES_PER_BIN],
+        );
+        self.occupied = 0;
+        for (i, bin) in old.chunks(DEFAULT_ENTRIES_PER_BIN).enumerate() {
+            for &slot in bin.iter().filter(|&&slot| slot != 0) {
+                for (bin, entry) in split(i, slot, num_bins) {
+                    self.insert_in_bin(bin, entry);
+                }
+            }
+        }
+        self.growths += 1;
+        Some(old)
+    }
+
+    fn bin(&self, i: usize) -> &[u64] {
+        &self.slots[i * DEFAULT_ENTRIES_PER_BIN..(i + 1) * DEFAULT_ENTRIES_PER_BIN]
+    }
+
+    fn index(&self, hash: u64) -> usize {
+        (hash & mask(self.base_bits + self.growths)) as usize
+    }
+
+    // only flips base bits, so alt_index(alt_index(i, tag), tag) == i, and both candidate bins
+    // take the same side whenever the bins double
+    fn alt_index(&self, i: usize, tag: u64) -> usize {
+        let offset = (tag + 1).wrapping_mul(0x9E37_79B9_7F4A_7C15) >> 32;
+        i ^ (offset & mask(self.base_bits)) as usize
+    }
+
+    fn available_tail_bits(&self) -> u32 {
+        INDEX_AND_TAIL_BITS - self.base_bits - self.growths
+    }
+
+    // 32 bit hashers only fill the low half, so two seeds are combined into 64 bits
+    fn hash(bytes: &[u8]) -> u64 {
+        let mut low = T::with_seed(0);
+        low.write(bytes);
+        let mut high = T::with_seed(1);
+        high.write(bytes);
+        low.finish() ^ high.finish().rotate_left(32)
+    }
+}
+
+fn mask(width: u32) -> u64 {
+    (1 << width) - 1
+}
+
+// Where an entry in bin `i` goes when `num_bins` bins double, entries without a tail go to both.
+fn split(i: usize, entry: u64, num_bins: usize) -> Vec<(usize, u64)> {
+    let tag = entry & mask(TAG_BITS);
+    let (len, tail) = decode_tail(entry);
+    if len == 0 {
+        return vec![(i, entry), (i + num_bins, entry)];
+    }
+    let bin = i + (tail & 1) as usize * num_bins;
+    let shortened = (1 << (len - 1) | tail >> 1) << TAG_BITS | tag;
+    vec![(bin, shortened)]
+}
+
+// returns the length and bits of the tail stored in a slot
+fn decode_tail(slot: u64) -> (u32, u64) {
+    let tail = slot >> TAG_BITS;
+    let len = u64::BITS - 1 - tail.leading_zeros();
+    (len, tail & mask(len))
+}
@@ -1,28 +1,32 @@
-use fasthash::murmur3;
+use fasthash::{metro, murmur3};
+use rand::{rngs::StdRng, SeedableRng};
 
-use super::CuckooFilter;
+use super::{
+    alt_index_of, bins::Bins, fingerprint_bits_for_fpp, fingerprint_of, index_of,
+    AdaptiveCuckooFilter, ConcurrentCuckooFilter, CuckooFilter, CuckooFilterView, CuckooStats,
+    Error, ExpiringCuckooFilter, InsertError, InsertStrategy, TaffyCuckooFilter, STASH_SIZE,
+};
+use crate::FilterEvent;
 
 #[test]
 fn test_add() {
-    let mut cf = CuckooFilter::<murmur3::Hasher32>::new(5);
+    let mut cf = CuckooFilter::<murmur3::Hasher32>::new(5).unwrap();
     let v = "value";
-    cf.add(v.as_bytes());
-    assert_eq!(1, cf.bins.iter().map(|v| v.len()).sum::<usize>());
+    cf.add(v.as_bytes()).unwrap();
+    assert_eq!(1, cf.bins.occupied());
     assert!(cf.contains(v.as_bytes()));
 }
 
 #[test]
 fn test_add_duplicate() {
-    let mut cf = CuckooFilter::<murmur3::Hasher32>::new(13);
+    let mut cf = CuckooFilter::<murmur3::Hasher32>::new(13).unwrap();
     let v = "value";
-    cf.add(v.as_bytes());
+    cf.add(v.as_bytes()).unwrap();
     assert!(cf.contains(v.as_bytes()));
-    cf.add(v.as_bytes());
+    cf.add(v.as_bytes()).unwrap();
     assert!(cf.contains(v.as_bytes()));
-    let fingerprints = cf
-        .bins
-        .iter()
-        .flat_map(|arr| arr.iter())
+    let fingerprints = (0..cf.bins.num_bins())
+        .flat_map(|bin| cf.bins.fingerprints(bin))
         .collect::<Vec<_>>();
     assert_eq!(2, fingerprints.len());
     assert_eq!(fingerprints[0], fingerprints[1]);
@@ -30,9 +34,9 @@ fn test_add_duplicate() {
 
 #[test]
 fn test_remove() {
-    let mut cf = CuckooFilter::<murmur3::Hasher32>::new(8);
+    let mut cf = CuckooFilter::<murmur3::Hasher32>::new(8).unwrap();
     let v = "value";
-    cf.add(v.as_bytes());
+    cf.add(v.as_bytes()).unwrap();
     assert!(cf.contains(v.as_bytes()));
     cf.remove(v.as_bytes());
     assert!(!cf.contains(v.as_bytes()));
@@ -41,13 +45,1162 @@ fn test_remove() {
 
 #[test]
 fn test_fill() {
-    let mut cf = CuckooFilter::<murmur3::Hasher32>::new(512);
+    let mut cf = CuckooFilter::<murmur3::Hasher32>::new(512).unwrap();
+    assert!(cf.is_empty());
+    assert_eq!(2048, cf.capacity());
+    for i in 0..1024u64 {
+        assert!(cf.add(i.to_ne_bytes()).is_ok(), "{}", i);
+    }
+    assert_eq!(1024, cf.len());
+    for i in 0..1024u64 {
+        assert!(cf.remove(i.to_ne_bytes()), "{}", i);
+    }
+    assert!(cf.is_empty());
+}
+
+#[test]
+fn test_bins_packing() {
+    // 12 bit fingerprints straddle word boundaries
+    let mut bins = Bins::new(7, 3, 12);
+    for bin in 0..7 {
+        for slot in 0..3 {
+            assert!(bins.insert(bin, (bin * 3 + slot + 4000) as u32));
+        }
+        assert!(bins.is_full(bin));
+        assert!(!bins.insert(bin, 1));
+    }
+    assert_eq!(21, bins.occupied());
+    for bin in 0..7 {
+        for slot in 0..3 {
+            assert_eq!((bin * 3 + slot + 4000) as u32, bins.get(bin, slot));
+        }
+    }
+    assert!(bins.remove(3, 4010));
+    assert!(!bins.contains(3, 4010));
+    assert!(bins.contains(2, 4008));
+    assert!(bins.contains(4, 4012));
+    assert_eq!(20, bins.occupied());
+    assert_eq!(vec![4009, 4011], bins.fingerprints(3).collect::<Vec<_>>());
+}
+
+#[test]
+fn test_fingerprint_bits() {
+    for bits in [4, 7, 12, 16, 25, 32] {
+        let mut cf =
+            CuckooFilter::<murmur3::Hasher32>::with_fingerprint_bits(64, 4, 100, bits).unwrap();
+        for i in 0..200u64 {
+            assert!(cf.add(i.to_ne_bytes()).is_ok(), "{} {}", bits, i);
+        }
+        for i in 0..200u64 {
+            assert!(cf.contains(i.to_ne_bytes()), "{} {}", bits, i);
+        }
+        let fp = cf.fingerprint(b"anything");
+        assert!(fp > 0 && (fp as u64) < 1 << bits);
+    }
+}
+
+#[test]
+fn test_wider_fingerprints_fewer_false_positives() {
+    let false_positives = |bits| {
+        let mut cf =
+            CuckooFilter::<murmur3::Hasher32>::with_fingerprint_bits(1024, 4, 100, bits).unwrap();
+        for i in 0..3000u64 {
+            cf.add(i.to_ne_bytes()).unwrap();
+        }
+        (3000..23_000u64)
+            .filter(|i| cf.contains(i.to_ne_bytes()))
+            .count()
+    };
+    let narrow = false_positives(6);
+    let wide = false_positives(16);
+    assert!(narrow > 1000, "{}", narrow);
+    assert!(wide < 20, "{}", wide);
+}
+
+#[test]
+fn test_with_fpp() {
+    assert_eq!(10, fingerprint_bits_for_fpp(0.01, 4));
+    assert_eq!(4, fingerprint_bits_for_fpp(0.9, 4));
+    assert_eq!(32, fingerprint_bits_for_fpp(1e-12, 4));
+    let cf = CuckooFilter::<murmur3::Hasher32>::with_fpp(64, 0.001).unwrap();
+    assert_eq!(13, cf.bins.fingerprint_bits());
+}
+
+#[test]
+fn test_fingerprint_bits_too_large() {
+    let cf = CuckooFilter::<murmur3::Hasher32>::with_fingerprint_bits(64, 4, 100, 33);
+    assert!(matches!(cf, Err(Error::InvalidFingerprintBits(33, 4, 32))));
+    let taffy = TaffyCuckooFilter::<murmur3::Hasher32>::with_fingerprint_bits(64, 8);
+    assert!(matches!(
+        taffy,
+        Err(Error::InvalidFingerprintBits(8, 9, 32))
+    ));
+}
+
+#[test]
+fn test_invalid_params() {
+    type Filter = CuckooFilter<murmur3::Hasher32>;
+    assert!(matches!(Filter::new(0), Err(Error::InvalidBinCount(0))));
+    assert!(matches!(
+        Filter::new(usize::MAX),
+        Err(Error::InvalidBinCount(_))
+    ));
+    assert!(matches!(
+        Filter::with_all_the_levers(64, 0, 100),
+        Err(Error::InvalidEntriesPerBin(0))
+    ));
+    assert!(matches!(
+        Filter::with_fpp(64, 0.0),
+        Err(Error::InvalidFpp(_))
+    ));
+    assert!(matches!(
+        Filter::with_capacity_and_fpp(100, 1.0),
+        Err(Error::InvalidFpp(_))
+    ));
+    assert!(matches!(
+        Filter::new(64).unwrap().with_auto_resize(1.5),
+        Err(Error::InvalidLoadFactor(_))
+    ));
+    assert!(matches!(
+        ConcurrentCuckooFilter::<murmur3::Hasher32>::new(0),
+        Err(Error::InvalidBinCount(0))
+    ));
+}
+
+#[test]
+fn test_semi_sorted_bins() {
+    for bits in [4, 5, 8, 13, 32] {
+        let mut bins = Bins::semi_sorted(5, bits);
+        assert_eq!(12 + 4 * (bits as usize - 4), bins.bin_bits());
+        let max = ((1_u64 << bits) - 1) as u32;
+        let values = [max, 1, max / 2, max / 3];
+        for v in values {
+            assert!(bins.insert(2, v));
+        }
+        assert!(!bins.insert(2, 1));
+        let mut expected = values.to_vec();
+        expected.sort_unstable();
+        assert_eq!(expected, bins.fingerprints(2).collect::<Vec<_>>());
+        assert_eq!(0, bins.fingerprints(1).count());
+        assert_eq!(0, bins.fingerprints(3).count());
+        assert!(bins.remove(2, max / 2));
+        assert!(!bins.contains(2, max / 2));
+        assert!(bins.contains(2, max));
+        assert_eq!(3, bins.occupied());
+    }
+}
+
+#[test]
+fn test_semi_sorted_filter() {
+    let mut cf = CuckooFilter::<murmur3::Hasher32>::with_semi_sorted_bins(512, 100, 9).unwrap();
+    assert_eq!(12 + 4 * 5, cf.bins.bin_bits());
+    for i in 0..1024u64 {
+        assert!(cf.add(i.to_ne_bytes()).is_ok(), "{}", i);
+    }
     for i in 0..1024u64 {
-        assert!(cf.add(&i.to_ne_bytes()), "{}", i);
+        assert!(cf.contains(i.to_ne_bytes()), "{}", i);
     }
-    assert_eq!(1024, cf.bins.iter().map(|v| v.len()).sum::<usize>());
     for i in 0..1024u64 {
-        assert!(cf.remove(&i.to_ne_bytes()), "{}", i);
+        assert!(cf.remove(i.to_ne_bytes()), "{}", i);
     }
-    assert_eq!(0, cf.bins.iter().map(|v| v.len()).sum::<usize>());
+    assert_eq!(0, cf.bins.occupied());
+}
+
+#[test]
+fn test_bins_rounded_to_power_of_two() {
+    for (requested, actual) in [(1, 1), (5, 8), (64, 64), (100, 128)] {
+        let cf = CuckooFilter::<murmur3::Hasher32>::new(requested).unwrap();
+        assert_eq!(actual, cf.bins.num_bins());
+    }
+}
+
+#[test]
+fn test_alt_index_is_involution() {
+    let cf = CuckooFilter::<murmur3::Hasher32>::new(100).unwrap();
+    for i in 0..cf.bins.num_bins() {
+        for fp in 1..256 {
+            assert_eq!(i, cf.alt_index(cf.alt_index(i, fp), fp));
+        }
+    }
+}
+
+#[test]
+fn test_kicked_entries_are_found() {
+    // 100 bins rounds up to 128, so 460 entries needs plenty of kicks to reach ~90% load
+    let mut cf = CuckooFilter::<murmur3::Hasher32>::with_all_the_levers(100, 4, 500).unwrap();
+    for i in 0..460u64 {
+        assert!(cf.add(i.to_ne_bytes()).is_ok(), "{}", i);
+    }
+    for i in 0..460u64 {
+        assert!(cf.contains(i.to_ne_bytes()), "{}", i);
+    }
+    for i in 0..460u64 {
+        assert!(cf.remove(i.to_ne_bytes()), "{}", i);
+    }
+    assert_eq!(0, cf.bins.occupied());
+}
+
+#[test]
+fn test_stash_keeps_victims() {
+    // 2 slots in the table, so later entries end up evicting into the stash
+    let mut cf = CuckooFilter::<murmur3::Hasher32>::with_all_the_levers(2, 1, 10).unwrap();
+    let added: Vec<u64> = (0..64u64)
+        .filter(|i| cf.add(i.to_ne_bytes()).is_ok())
+        .collect();
+    assert!(!cf.stash.is_empty());
+    assert_eq!(added.len(), cf.len());
+    assert!(added.len() <= 2 + cf.stash.len());
+    for i in &added {
+        assert!(cf.contains(i.to_ne_bytes()), "{}", i);
+    }
+    for i in &added {
+        assert!(cf.remove(i.to_ne_bytes()), "{}", i);
+    }
+    assert!(cf.stash.is_empty());
+    assert_eq!(0, cf.bins.occupied());
+}
+
+#[test]
+fn test_stash_drained_on_insert() {
+    // wide fingerprints, so stashed entries can be told apart from the ones in the table
+    let mut cf = CuckooFilter::<murmur3::Hasher32>::with_fingerprint_bits(2, 1, 10, 32).unwrap();
+    let added: Vec<u64> = (0..64u64)
+        .filter(|i| cf.add(i.to_ne_bytes()).is_ok())
+        .collect();
+    let stashed = cf.stash.len();
+    assert!(stashed > 0);
+
+    // free up both table slots, then the next insert moves stashed entries back
+    let in_table: Vec<u64> = added
+        .iter()
+        .copied()
+        .filter(|i| {
+            let fp = cf.fingerprint(&i.to_ne_bytes());
+            cf.stash.iter().all(|&(_, s)| s != fp)
+        })
+        .collect();
+    for i in &in_table {
+        assert!(cf.remove(i.to_ne_bytes()));
+    }
+    assert_eq!(0, cf.bins.occupied());
+    cf.add(1000u64.to_ne_bytes()).unwrap();
+    assert!(cf.stash.len() < stashed);
+    for i in added.iter().filter(|i| !in_table.contains(i)) {
+        assert!(cf.contains(i.to_ne_bytes()), "{}", i);
+    }
+}
+
+#[test]
+fn test_auto_resize() {
+    let mut cf = CuckooFilter::<murmur3::Hasher32>::with_fingerprint_bits(8, 4, 100, 16)
+        .unwrap()
+        .with_auto_resize(0.9)
+        .unwrap();
+    for i in 0..2000u64 {
+        assert!(cf.add(i.to_ne_bytes()).is_ok(), "{}", i);
+    }
+    assert!(cf.bins.num_bins() >= 512);
+    assert!(cf.bins.occupied() as f64 <= 0.9 * (cf.bins.num_bins() * 4) as f64);
+    for i in 0..2000u64 {
+        assert!(cf.contains(i.to_ne_bytes()), "{}", i);
+    }
+    for i in 0..2000u64 {
+        assert!(cf.remove(i.to_ne_bytes()), "{}", i);
+    }
+    assert_eq!(0, cf.bins.occupied());
+}
+
+#[test]
+fn test_auto_resize_limited_by_fingerprint_bits() {
+    // 8 bit fingerprints leave 4 doublings before fingerprints get too short
+    let mut cf = CuckooFilter::<murmur3::Hasher32>::new(4)
+        .unwrap()
+        .with_auto_resize(1.0)
+        .unwrap();
+    let added = (0..1000u64)
+        .filter(|i| cf.add(i.to_ne_bytes()).is_ok())
+        .count();
+    assert_eq!(64, cf.bins.num_bins());
+    assert!(added < 1000);
+}
+
+#[test]
+fn test_taffy_grows() {
+    let mut cf = TaffyCuckooFilter::<metro::Hasher64_1>::new(4).unwrap();
+    for i in 0..10_000u64 {
+        assert!(cf.add(i.to_ne_bytes()), "{}", i);
+    }
+    assert!(cf.num_bins() >= 2048);
+    for i in 0..10_000u64 {
+        assert!(cf.contains(i.to_ne_bytes()), "{}", i);
+    }
+}
+
+#[test]
+fn test_taffy_false_positives() {
+    // grown from 1 bin, the oldest entries have lost their whole tail, but most don't
+    let mut cf = TaffyCuckooFilter::<murmur3::Hasher32>::with_fingerprint_bits(1, 20).unwrap();
+    for i in 0..20_000u64 {
+        assert!(cf.add(i.to_ne_bytes()), "{}", i);
+    }
+    for i in 0..20_000u64 {
+        assert!(cf.contains(i.to_ne_bytes()), "{}", i);
+    }
+    let false_positives = (20_000..120_000u64)
+        .filter(|i| cf.contains(i.to_ne_bytes()))
+        .count();
+    // a full filter of fresh 20 bit fingerprints is ~2 * 4 / 2^20 ~= 0.0008%
+    assert!(false_positives < 100, "{}", false_positives);
+}
+
+#[test]
+fn test_taffy_duplicates() {
+    // copies of one key always move together, so growing never makes room for another
+    let mut cf = TaffyCuckooFilter::<metro::Hasher64_1>::new(4).unwrap();
+    let added = (0..9).filter(|_| cf.add("same")).count();
+    assert!(added < 9);
+    assert_eq!(4, cf.num_bins());
+    assert!(cf.contains("same"));
+    assert!(cf.add("other"));
+}
+
+#[test]
+fn test_taffy_tailless_victims() {
+    // 1 bit tails run out after the first growth, so later victims are copied to both halves,
+    // and neither copy may be lost
+    let mut cf = TaffyCuckooFilter::<murmur3::Hasher32>::with_fingerprint_bits(1, 9).unwrap();
+    let mut added = vec![];
+    for i in 0..200u64 {
+        if cf.add(i.to_ne_bytes()) {
+            added.push(i);
+        }
+        for j in &added {
+            assert!(cf.contains(j.to_ne_bytes()), "{} after adding {}", j, i);
+        }
+    }
+    assert!(added.len() > 8);
+}
+
+#[test]
+fn test_occupancy() {
+    let mut cf = CuckooFilter::<murmur3::Hasher32>::new(64).unwrap();
+    assert_eq!(0.0, cf.load_factor());
+    assert_eq!(vec![64, 0, 0, 0, 0], cf.bin_fill_histogram());
+
+    for i in 0..128u64 {
+        assert!(cf.add(i.to_ne_bytes()).is_ok());
+    }
+    assert_eq!(128, cf.occupied_slots());
+    assert_eq!(0.5, cf.load_factor());
+    let histogram = cf.bin_fill_histogram();
+    assert_eq!(64, histogram.iter().sum::<usize>());
+    assert_eq!(
+        128,
+        histogram
+            .iter()
+            .enumerate()
+            .map(|(k, n)| k * n)
+            .sum::<usize>()
+    );
+}
+
+#[test]
+fn test_estimate() {
+    let mut cf = CuckooFilter::<murmur3::Hasher32>::with_fingerprint_bits(64, 4, 100, 32).unwrap();
+    assert_eq!(0, cf.estimate("value"));
+    for n in 1..=8 {
+        assert!(cf.add("value").is_ok());
+        assert_eq!(n, cf.estimate("value"));
+    }
+    // both bins are full of copies, so the next ones are stashed
+    assert!(cf.add("value").is_ok());
+    assert_eq!(9, cf.estimate("value"));
+    assert_eq!(1, cf.stash.len());
+
+    for n in (0..9).rev() {
+        assert!(cf.remove("value"));
+        assert_eq!(n, cf.estimate("value"));
+    }
+    assert_eq!(0, cf.estimate("other"));
+}
+
+#[test]
+fn test_seeded_rng_is_reproducible() {
+    let fill = || {
+        let mut cf = CuckooFilter::<murmur3::Hasher32>::new(16)
+            .unwrap()
+            .with_rng(StdRng::seed_from_u64(7));
+        for i in 0..60u64 {
+            let _ = cf.add(i.to_ne_bytes());
+        }
+        (0..cf.bins.num_bins())
+            .flat_map(|bin| cf.bins.fingerprints(bin).collect::<Vec<_>>())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(fill(), fill());
+}
+
+#[test]
+fn test_add_full() {
+    let mut cf = CuckooFilter::<murmur3::Hasher32>::with_fingerprint_bits(2, 1, 10, 32).unwrap();
+    let err = (0..64u64)
+        .find_map(|i| cf.add(i.to_ne_bytes()).err())
+        .unwrap();
+    let InsertError::Full { bin, victim } = err;
+    assert!(bin < 2);
+    assert_ne!(0, victim);
+    assert_eq!(STASH_SIZE, cf.stash.len());
+}
+
+#[test]
+fn test_insert_if_absent() {
+    let mut cf = CuckooFilter::<murmur3::Hasher32>::new(64).unwrap();
+    assert_eq!(Ok(true), cf.insert_if_absent("value"));
+    assert_eq!(Ok(false), cf.insert_if_absent("value"));
+    assert_eq!(1, cf.estimate("value"));
+    assert_eq!(Ok(true), cf.insert_if_absent("other"));
+    assert_eq!(2, cf.occupied_slots());
+}
+
+#[test]
+fn test_from_iter_with_params() {
+    let cf = CuckooFilter::<murmur3::Hasher32>::from_iter_with_params(
+        (0..1000u64).map(u64::to_ne_bytes),
+        4,
+        100,
+        12,
+    )
+    .unwrap();
+    assert_eq!(512, cf.bins.num_bins());
+    for i in 0..1000u64 {
+        assert!(cf.contains(i.to_ne_bytes()), "{}", i);
+    }
+
+    // too few kicks to fill a table this tightly, so it has to grow
+    let cf = CuckooFilter::<murmur3::Hasher32>::from_iter_with_params(
+        (0..1000u64).map(u64::to_ne_bytes),
+        1,
+        0,
+        12,
+    )
+    .unwrap();
+    assert!(cf.bins.num_bins() > 2048);
+    for i in 0..1000u64 {
+        assert!(cf.contains(i.to_ne_bytes()), "{}", i);
+    }
+}
+
+#[test]
+fn test_breadth_first_insert() {
+    let mut cf = CuckooFilter::<murmur3::Hasher32>::with_fingerprint_bits(128, 4, 500, 16)
+        .unwrap()
+        .with_insert_strategy(InsertStrategy::BreadthFirst);
+    // ~95% load
+    for i in 0..486u64 {
+        assert!(cf.add(i.to_ne_bytes()).is_ok(), "{}", i);
+    }
+    assert!(cf.stash.is_empty());
+    assert_eq!(486, cf.occupied_slots());
+    for i in 0..486u64 {
+        assert!(cf.contains(i.to_ne_bytes()), "{}", i);
+    }
+    for i in 0..486u64 {
+        assert!(cf.remove(i.to_ne_bytes()), "{}", i);
+    }
+    assert_eq!(0, cf.occupied_slots());
+}
+
+#[test]
+fn test_fingerprint_is_stable() {
+    // persisted filters depend on fingerprints never changing for a given hasher
+    let cf = CuckooFilter::<murmur3::Hasher32>::with_fingerprint_bits(64, 4, 100, 32).unwrap();
+    assert_eq!(1994468736, cf.fingerprint(b"value"));
+}
+
+#[test]
+fn test_iter() {
+    let mut cf = CuckooFilter::<murmur3::Hasher32>::new(16).unwrap();
+    assert_eq!(0, cf.iter().count());
+    for v in ["a", "b", "c", "a"] {
+        cf.add(v).unwrap();
+    }
+    let entries = cf.iter().collect::<Vec<_>>();
+    assert_eq!(4, entries.len());
+    for v in ["a", "b", "c"] {
+        let fp = cf.fingerprint(v.as_bytes());
+        let i = cf.index(v.as_bytes(), fp);
+        let alt = cf.alt_index(i, fp);
+        assert!(entries
+            .iter()
+            .any(|&(bin, f)| f == fp && (bin == i || bin == alt)));
+    }
+}
+
+#[test]
+fn test_merge() {
+    let mut a = CuckooFilter::<murmur3::Hasher32>::new(64).unwrap();
+    let mut b = CuckooFilter::<murmur3::Hasher32>::new(64).unwrap();
+    for i in 0..100u64 {
+        a.add(i.to_ne_bytes()).unwrap();
+        b.add((i + 100).to_ne_bytes()).unwrap();
+    }
+    assert!(a.merge(&b).is_empty());
+    assert_eq!(200, a.occupied_slots());
+    for i in 0..200u64 {
+        assert!(a.contains(i.to_ne_bytes()), "{}", i);
+    }
+}
+
+#[test]
+fn test_merge_overflow() {
+    let mut a = CuckooFilter::<murmur3::Hasher32>::with_fingerprint_bits(4, 4, 100, 16).unwrap();
+    let mut b = CuckooFilter::<murmur3::Hasher32>::with_fingerprint_bits(4, 4, 100, 16).unwrap();
+    for i in 0..16u64 {
+        a.add(i.to_ne_bytes()).unwrap();
+        b.add((i + 100).to_ne_bytes()).unwrap();
+    }
+    let unplaced = a.merge(&b);
+    assert!(!unplaced.is_empty());
+    assert_eq!(32, a.occupied_slots() + a.stash.len() + unplaced.len());
+}
+
+#[test]
+#[should_panic]
+fn test_merge_incompatible() {
+    let mut a = CuckooFilter::<murmur3::Hasher32>::new(64).unwrap();
+    let b = CuckooFilter::<murmur3::Hasher32>::new(128).unwrap();
+    a.merge(&b);
+}
+
+#[test]
+fn test_try_merge_incompatible() {
+    let mut a = CuckooFilter::<murmur3::Hasher32>::new(64).unwrap();
+    let b = CuckooFilter::<murmur3::Hasher32>::new(128).unwrap();
+    assert!(matches!(a.try_merge(&b), Err(Error::IncompatibleFilter)));
+}
+
+#[test]
+fn test_read_from_huge_header() {
+    let mut bytes = CuckooFilter::<murmur3::Hasher32>::new(64)
+        .unwrap()
+        .to_bytes();
+    bytes[5..13].copy_from_slice(&(1_u64 << 50).to_le_bytes());
+    assert!(matches!(
+        CuckooFilter::<murmur3::Hasher32>::read_from(bytes.as_slice()),
+        Err(Error::AllocationFailed(_))
+    ));
+}
+
+#[test]
+fn test_current_fpp() {
+    let mut cf = CuckooFilter::<murmur3::Hasher32>::with_fingerprint_bits(1024, 4, 100, 8).unwrap();
+    assert_eq!(0.0, cf.current_fpp());
+    for i in 0..3800u64 {
+        cf.add(i.to_ne_bytes()).unwrap();
+    }
+    let expected = cf.current_fpp();
+    // close to full, ~2 * 4 / 255
+    assert!(expected > 0.025 && expected < 0.032, "{}", expected);
+
+    let false_positives = (10_000..110_000u64)
+        .filter(|i| cf.contains(i.to_ne_bytes()))
+        .count() as f64
+        / 100_000.0;
+    assert!(
+        (false_positives - expected).abs() < 0.005,
+        "{} {}",
+        false_positives,
+        expected
+    );
+}
+
+#[test]
+fn test_serialization_roundtrip() {
+    let mut cf = CuckooFilter::<murmur3::Hasher32>::with_fingerprint_bits(2, 1, 10, 13)
+        .unwrap()
+        .with_auto_resize(1.0)
+        .unwrap()
+        .with_insert_strategy(InsertStrategy::BreadthFirst);
+    for i in 0..100u64 {
+        cf.add(i.to_ne_bytes()).unwrap();
+    }
+    let bytes = cf.to_bytes();
+    let restored = CuckooFilter::<murmur3::Hasher32>::from_bytes(&bytes).unwrap();
+    assert_eq!(
+        cf.iter().collect::<Vec<_>>(),
+        restored.iter().collect::<Vec<_>>()
+    );
+    assert_eq!(cf.occupied_slots(), restored.occupied_slots());
+    assert_eq!(cf.growths, restored.growths);
+    assert_eq!(Some(1.0), restored.max_load_factor);
+    assert_eq!(InsertStrategy::BreadthFirst, restored.strategy);
+    for i in 0..100u64 {
+        assert!(restored.contains(i.to_ne_bytes()), "{}", i);
+    }
+    assert_eq!(bytes, restored.to_bytes());
+}
+
+#[test]
+fn test_serialization_with_stash() {
+    let mut cf = CuckooFilter::<murmur3::Hasher32>::with_semi_sorted_bins(2, 10, 9).unwrap();
+    let added: Vec<u64> = (0..64u64)
+        .filter(|i| cf.add(i.to_ne_bytes()).is_ok())
+        .collect();
+    assert_eq!(STASH_SIZE, cf.stash.len());
+    let restored = CuckooFilter::<murmur3::Hasher32>::from_bytes(&cf.to_bytes()).unwrap();
+    assert_eq!(cf.stash, restored.stash);
+    for i in added {
+        assert!(restored.contains(i.to_ne_bytes()), "{}", i);
+    }
+}
+
+#[test]
+fn test_clone_eq() {
+    let mut cf = CuckooFilter::<murmur3::Hasher32>::new(16).unwrap();
+    cf.add("x").unwrap();
+    let mut copy = cf.clone();
+    assert!(cf == copy);
+    copy.add("y").unwrap();
+    assert!(cf != copy);
+    copy.remove("y");
+    assert!(cf == copy);
+    let restored = CuckooFilter::<murmur3::Hasher32>::from_bytes(&cf.to_bytes()).unwrap();
+    assert!(cf == restored);
+}
+
+#[test]
+fn test_debug_display() {
+    let mut cf = CuckooFilter::<murmur3::Hasher32>::new(16).unwrap();
+    for i in 0..32u64 {
+        cf.add(i.to_ne_bytes()).unwrap();
+    }
+    let debug = format!("{:?}", cf);
+    assert!(
+        debug.contains("num_bins: 16, entries_per_bin: 4"),
+        "{}",
+        debug
+    );
+    assert!(debug.contains("len: 32"), "{}", debug);
+    assert!(
+        cf.to_string()
+            .starts_with("CuckooFilter: items≈32, fill 50.0%, fpp≈"),
+        "{}",
+        cf
+    );
+}
+
+#[test]
+fn test_u64_seeded_hasher() {
+    let mut cf = CuckooFilter::<fasthash::xx::Hasher64>::new(1024).unwrap();
+    for i in 0..1000u64 {
+        cf.add(i.to_ne_bytes()).unwrap();
+    }
+    assert!((0..1000u64).all(|i| cf.contains(i.to_ne_bytes())));
+}
+
+#[test]
+fn test_mem_usage() {
+    let cf = CuckooFilter::<murmur3::Hasher32>::new(1024).unwrap();
+    // 1024 bins of 4 8 bit fingerprints, and room for the stash
+    let stash = STASH_SIZE * std::mem::size_of::<(usize, u32)>();
+    assert_eq!(std::mem::size_of_val(&cf) + 4096 + stash, cf.mem_usage());
+    let concurrent = ConcurrentCuckooFilter::<murmur3::Hasher32>::new(1024).unwrap();
+    assert!(concurrent.mem_usage() > 4096 * 4);
+}
+
+#[test]
+fn test_digest() {
+    let mut cf = CuckooFilter::<murmur3::Hasher32>::new(16).unwrap();
+    let empty = cf.digest();
+    cf.add("x").unwrap();
+    let digest = cf.digest();
+    assert_ne!(empty, digest);
+    let restored = CuckooFilter::<murmur3::Hasher32>::from_bytes(&cf.to_bytes()).unwrap();
+    assert_eq!(digest, restored.digest());
+    cf.remove("x");
+    assert_eq!(empty, cf.digest());
+}
+
+#[test]
+fn test_deserialize_malformed() {
+    let cf = CuckooFilter::<murmur3::Hasher32>::new(16).unwrap();
+    let bytes = cf.to_bytes();
+    assert!(matches!(
+        CuckooFilter::<murmur3::Hasher32>::from_bytes(&bytes[..bytes.len() - 1]),
+        Err(Error::Truncated)
+    ));
+    assert!(matches!(
+        CuckooFilter::<murmur3::Hasher32>::from_bytes(&bytes[..10]),
+        Err(Error::Truncated)
+    ));
+    let mut corrupted = bytes.clone();
+    corrupted[60] ^= 1;
+    assert!(matches!(
+        CuckooFilter::<murmur3::Hasher32>::from_bytes(&corrupted),
+        Err(Error::ChecksumMismatch { .. })
+    ));
+    let mut bad_magic = bytes.clone();
+    bad_magic[0] = b'X';
+    assert!(matches!(
+        CuckooFilter::<murmur3::Hasher32>::from_bytes(&bad_magic),
+        Err(Error::InvalidMagic)
+    ));
+    let mut bad_version = bytes.clone();
+    bad_version[4] = 9;
+    assert!(matches!(
+        CuckooFilter::<murmur3::Hasher32>::from_bytes(&bad_version),
+        Err(Error::UnsupportedVersion(9))
+    ));
+    let mut bad_scheme = bytes.clone();
+    bad_scheme[40] = 9;
+    assert!(matches!(
+        CuckooFilter::<murmur3::Hasher32>::from_bytes(&bad_scheme),
+        Err(Error::UnsupportedHashScheme(9))
+    ));
+    let mut bad_bins = bytes;
+    bad_bins[5] = 3;
+    assert!(matches!(
+        CuckooFilter::<murmur3::Hasher32>::from_bytes(&bad_bins),
+        Err(Error::Malformed)
+    ));
+}
+
+#[test]
+fn test_stream_roundtrip() {
+    let mut cf = CuckooFilter::<murmur3::Hasher32>::with_semi_sorted_bins(2, 10, 9).unwrap();
+    for i in 0..64u64 {
+        let _ = cf.add(i.to_ne_bytes());
+    }
+    let mut stream = vec![];
+    cf.write_to(&mut stream).unwrap();
+    assert_eq!(cf.to_bytes(), stream);
+
+    // a second filter follows the first in the stream
+    let other = CuckooFilter::<murmur3::Hasher32>::new(16_384).unwrap();
+    other.write_to(&mut stream).unwrap();
+    let mut reader = stream.as_slice();
+    let restored = CuckooFilter::<murmur3::Hasher32>::read_from(&mut reader).unwrap();
+    assert_eq!(cf.to_bytes(), restored.to_bytes());
+    let restored = CuckooFilter::<murmur3::Hasher32>::read_from(&mut reader).unwrap();
+    assert_eq!(other.to_bytes(), restored.to_bytes());
+    assert!(reader.is_empty());
+
+    assert!(CuckooFilter::<murmur3::Hasher32>::read_from(&stream[..stream.len() - 1]).is_ok());
+    let truncated = &other.to_bytes()[..100];
+    assert!(matches!(
+        CuckooFilter::<murmur3::Hasher32>::read_from(truncated),
+        Err(Error::Truncated)
+    ));
+    let mut corrupted = cf.to_bytes();
+    *corrupted.last_mut().unwrap() ^= 1;
+    assert!(matches!(
+        CuckooFilter::<murmur3::Hasher32>::read_from(corrupted.as_slice()),
+        Err(Error::ChecksumMismatch { .. })
+    ));
+}
+
+#[cfg(feature = "zstd")]
+#[test]
+fn test_compressed_roundtrip() {
+    let mut cf = CuckooFilter::<murmur3::Hasher32>::new(16_384).unwrap();
+    for i in 0..100u64 {
+        cf.add(i.to_ne_bytes()).unwrap();
+    }
+    let compressed = cf.to_bytes_compressed();
+    assert!(compressed.len() < cf.to_bytes().len());
+    for bytes in [cf.to_bytes(), compressed] {
+        let restored = CuckooFilter::<murmur3::Hasher32>::from_bytes_auto(&bytes).unwrap();
+        assert_eq!(cf.to_bytes(), restored.to_bytes());
+    }
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_serde_roundtrip() {
+    let mut cf = CuckooFilter::<murmur3::Hasher32>::with_fingerprint_bits(2, 1, 10, 13)
+        .unwrap()
+        .with_auto_resize(1.0)
+        .unwrap();
+    for i in 0..100u64 {
+        cf.add(i.to_ne_bytes()).unwrap();
+    }
+    let json = serde_json::to_string(&cf).unwrap();
+    let restored: CuckooFilter<murmur3::Hasher32> = serde_json::from_str(&json).unwrap();
+    assert_eq!(cf.to_bytes(), restored.to_bytes());
+    for i in 0..100u64 {
+        assert!(restored.contains(i.to_ne_bytes()), "{}", i);
+    }
+
+    let tampered = json.replace("\"fingerprint_bits\":13", "\"fingerprint_bits\":40");
+    assert!(serde_json::from_str::<CuckooFilter<murmur3::Hasher32>>(&tampered).is_err());
+}
+
+#[test]
+fn test_view() {
+    for mut cf in [
+        CuckooFilter::<murmur3::Hasher32>::with_fingerprint_bits(2, 1, 10, 13)
+            .unwrap()
+            .with_auto_resize(1.0)
+            .unwrap(),
+        CuckooFilter::<murmur3::Hasher32>::with_semi_sorted_bins(2, 10, 9).unwrap(),
+    ] {
+        let added: Vec<u64> = (0..100u64)
+            .filter(|i| cf.add(i.to_ne_bytes()).is_ok())
+            .collect();
+        // offset the bytes by one, so the words aren't aligned
+        let mut buffer = vec![0];
+        buffer.extend(cf.to_bytes());
+        let view = CuckooFilterView::<murmur3::Hasher32>::new(&buffer[1..]).unwrap();
+        assert_eq!(cf.capacity(), view.capacity());
+        for i in 0..1000u64 {
+            assert_eq!(cf.contains(i.to_ne_bytes()), view.contains(i.to_ne_bytes()));
+        }
+        for i in added {
+            assert!(view.contains(i.to_ne_bytes()), "{}", i);
+        }
+        assert!(matches!(
+            CuckooFilterView::<murmur3::Hasher32>::new(&buffer[1..buffer.len() - 8]),
+            Err(Error::Truncated)
+        ));
+    }
+}
+
+#[test]
+fn test_concurrent_inserts() {
+    let cf = ConcurrentCuckooFilter::<murmur3::Hasher32>::new(1024).unwrap();
+    std::thread::scope(|s| {
+        for t in 0..4u64 {
+            let cf = &cf;
+            s.spawn(move || {
+                for i in (t * 900)..((t + 1) * 900) {
+                    cf.add(i.to_ne_bytes()).unwrap();
+                    // earlier entries stay visible while other threads move fingerprints
+                    let earlier = i - (i - t * 900) / 2;
+                    assert!(cf.contains(earlier.to_ne_bytes()), "{}", earlier);
+                }
+            });
+        }
+    });
+    // ~88% load, so plenty of fingerprints were moved
+    assert_eq!(3600, cf.len());
+    for i in 0..3600u64 {
+        assert!(cf.contains(i.to_ne_bytes()), "{}", i);
+    }
+    for i in 0..3600u64 {
+        assert!(cf.remove(i.to_ne_bytes()), "{}", i);
+    }
+    assert!(cf.is_empty());
+}
+
+#[test]
+fn test_concurrent_matches_cuckoo_fingerprints() {
+    let cf = CuckooFilter::<murmur3::Hasher32>::new(64).unwrap();
+    let ccf = ConcurrentCuckooFilter::<murmur3::Hasher32>::new(64).unwrap();
+    let (i, fp) = ccf.locate(b"value");
+    assert_eq!(cf.fingerprint(b"value"), fp);
+    assert_eq!(cf.index(b"value", fp), i);
+}
+
+#[test]
+fn test_bins_find_matches_scalar() {
+    for (entries, bits) in [(4, 4), (4, 8), (3, 13), (4, 16), (2, 32), (5, 16)] {
+        let mut bins = Bins::new(8, entries, bits);
+        let max = (1_u64 << bits) - 1;
+        let values = [1, max as u32, (max / 2) as u32, (max / 2 + 1) as u32, 2, 3];
+        for bin in 0..8 {
+            for v in values.iter().skip(bin % 3).take(entries - 1) {
+                assert!(bins.insert(bin, *v));
+            }
+        }
+        for bin in 0..8 {
+            for v in values.iter().chain([max as u32 - 1].iter()) {
+                let expected = (0..entries).any(|slot| bins.get(bin, slot) == *v);
+                assert_eq!(expected, bins.contains(bin, *v), "{} {} {}", bits, bin, v);
+            }
+        }
+        // removing takes out exactly one copy, from the slot that held it
+        for bin in 0..8 {
+            let v = values[bin % 3];
+            assert!(bins.remove(bin, v));
+            assert!(!bins.contains(bin, v));
+            assert!(bins.insert(bin, v));
+            assert!(bins.insert(bin, v));
+            assert!(bins.is_full(bin));
+        }
+    }
+}
+
+#[test]
+fn test_with_capacity_and_fpp() {
+    for (fpp, entries, bits) in [(0.01, 2, 9), (0.001, 4, 13), (0.000001, 8, 24)] {
+        let mut cf = CuckooFilter::<murmur3::Hasher32>::with_capacity_and_fpp(10_000, fpp).unwrap();
+        assert_eq!(entries, cf.bins.entries_per_bin());
+        assert_eq!(bits, cf.bins.fingerprint_bits());
+        for i in 0..10_000u64 {
+            cf.add(i.to_ne_bytes()).unwrap();
+        }
+        assert!(cf.stash.is_empty());
+        assert!(cf.current_fpp() <= fpp, "{} {}", fpp, cf.current_fpp());
+    }
+}
+
+#[test]
+fn test_typed_items() {
+    #[derive(Hash)]
+    struct Point {
+        x: i32,
+        y: i32,
+    }
+
+    let mut cf = CuckooFilter::<murmur3::Hasher32>::new(64).unwrap();
+    cf.add_item(&Point { x: 1, y: -2 }).unwrap();
+    cf.add_item("text").unwrap();
+    cf.add_item(&42_u64).unwrap();
+    assert!(cf.contains_item(&Point { x: 1, y: -2 }));
+    assert!(!cf.contains_item(&Point { x: -2, y: 1 }));
+    assert!(cf.contains_item(&String::from("text")));
+    assert!(cf.contains_item(&42_u64));
+    // integers are encoded little endian, whatever the platform
+    assert!(cf.contains(42_u64.to_le_bytes()));
+    assert!(cf.remove_item(&42_u64));
+    assert!(!cf.contains_item(&42_u64));
+}
+
+#[test]
+fn test_stats() {
+    let mut cf = CuckooFilter::<murmur3::Hasher32>::with_fingerprint_bits(2, 1, 10, 32)
+        .unwrap()
+        .with_rng(StdRng::seed_from_u64(1));
+    assert_eq!(CuckooStats::default(), cf.stats());
+    let added: Vec<u64> = (0..64u64)
+        .filter(|i| cf.add(i.to_ne_bytes()).is_ok())
+        .collect();
+    let stats = cf.stats();
+    assert!(stats.kicks > 0);
+    assert!(stats.max_kick_chain > 0 && stats.max_kick_chain <= 10);
+    assert_eq!(64 - added.len() as u64, stats.failed_inserts);
+    assert_eq!(STASH_SIZE as u64, stats.stashed);
+    assert_eq!(0, stats.stash_hits);
+
+    for i in &added {
+        assert!(cf.contains(i.to_ne_bytes()));
+    }
+    assert_eq!(STASH_SIZE as u64, cf.stats().stash_hits);
+}
+
+#[test]
+fn test_adaptive_removes_false_positives() {
+    let mut cf =
+        AdaptiveCuckooFilter::<murmur3::Hasher32>::with_fingerprint_bits(256, 4, 500, 8).unwrap();
+    for i in 0..800u64 {
+        cf.add(i.to_ne_bytes()).unwrap();
+    }
+    assert_eq!(800, cf.len());
+    let is_member = |key: &[u8]| u64::from_ne_bytes(key.try_into().unwrap()) < 800;
+
+    let queries = 10_000..20_000u64;
+    let first_time = queries
+        .clone()
+        .filter(|i| cf.contains(i.to_ne_bytes()))
+        .count();
+    assert!(first_time > 100, "{}", first_time);
+    for i in queries.clone() {
+        assert!(!cf.contains_checked(i.to_ne_bytes(), is_member));
+    }
+    let repeated = queries.filter(|i| cf.contains(i.to_ne_bytes())).count();
+    assert!(repeated * 4 < first_time, "{} {}", repeated, first_time);
+
+    // adapting never loses real entries
+    for i in 0..800u64 {
+        assert!(cf.contains_checked(i.to_ne_bytes(), is_member), "{}", i);
+    }
+    for i in 0..800u64 {
+        assert!(cf.remove(i.to_ne_bytes()), "{}", i);
+    }
+    assert!(cf.is_empty());
+}
+
+#[test]
+fn test_adaptive_full_is_unchanged() {
+    let mut cf =
+        AdaptiveCuckooFilter::<murmur3::Hasher32>::with_fingerprint_bits(2, 2, 20, 8).unwrap();
+    let added: Vec<u64> = (0..32u64)
+        .filter(|i| cf.add(i.to_ne_bytes()).is_ok())
+        .collect();
+    assert_eq!(4, added.len());
+    for i in added {
+        assert!(cf.contains(i.to_ne_bytes()), "{}", i);
+    }
+}
+
+#[test]
+fn test_expiring() {
+    let mut cf = ExpiringCuckooFilter::<murmur3::Hasher32>::new(64, 10).unwrap();
+    for i in 0..100u64 {
+        cf.add(i.to_ne_bytes(), i as u32 / 10).unwrap();
+    }
+    assert_eq!(100, cf.len());
+    assert!(cf.contains(0u64.to_ne_bytes(), 9));
+    assert!(!cf.contains(0u64.to_ne_bytes(), 10));
+    assert!(cf.contains(99u64.to_ne_bytes(), 18));
+
+    // re-adding pushes the expiry back without storing another copy
+    cf.add(0u64.to_ne_bytes(), 6).unwrap();
+    assert_eq!(100, cf.len());
+    assert!(cf.contains(0u64.to_ne_bytes(), 15));
+
+    assert_eq!(59, cf.purge_expired(15));
+    assert_eq!(41, cf.len());
+    assert!(cf.contains(0u64.to_ne_bytes(), 15));
+    for i in 60..100u64 {
+        assert!(cf.contains(i.to_ne_bytes(), 15), "{}", i);
+    }
+    assert!(cf.remove(95u64.to_ne_bytes(), 15));
+    assert!(!cf.contains(95u64.to_ne_bytes(), 15));
+    assert!(!cf.remove(0u64.to_ne_bytes(), 16));
+    assert_eq!(39, cf.len());
+}
+
+#[test]
+fn test_expiring_reuses_expired_slots() {
+    let mut cf =
+        ExpiringCuckooFilter::<murmur3::Hasher32>::with_fingerprint_bits(2, 2, 10, 16, 1).unwrap();
+    let mut added = 0;
+    for i in 0..100u64 {
+        if cf.add(i.to_ne_bytes(), 0).is_ok() {
+            added += 1;
+        }
+    }
+    assert_eq!(4, added);
+    assert!(cf.add(1000u64.to_ne_bytes(), 0).is_err());
+    assert_eq!(4, cf.len());
+
+    // once everything has expired, adds evict the stale entries in their bins
+    for i in 100..104u64 {
+        cf.add(i.to_ne_bytes(), 1).unwrap();
+    }
+    assert!(!cf.contains(0u64.to_ne_bytes(), 1));
+    assert_eq!(4, cf.len());
+}
+
+#[test]
+fn test_try_new() {
+    let cf = CuckooFilter::<metro::Hasher64_1>::try_with_capacity_and_fpp(1000, 0.01).unwrap();
+    assert_eq!(CuckooFilter::with_capacity_and_fpp(1000, 0.01).unwrap(), cf);
+    assert!(CuckooFilter::<metro::Hasher64_1>::try_new(1000).is_ok());
+    // 2^57 bytes of bins, more than any address space
+    assert!(matches!(
+        CuckooFilter::<metro::Hasher64_1>::try_new(1 << 55),
+        Err(Error::AllocationFailed(_))
+    ));
+}
+
+#[test]
+fn test_event_hook() {
+    use std::sync::{Arc, Mutex};
+
+    let events = Arc::new(Mutex::new(vec![]));
+    let sink = Arc::clone(&events);
+    let mut cf = CuckooFilter::<murmur3::Hasher32>::with_fingerprint_bits(4, 4, 10, 16)
+        .unwrap()
+        .with_rng(StdRng::seed_from_u64(1))
+        .with_event_hook(move |event| sink.lock().unwrap().push(event));
+    let mut i = 0_u64;
+    while cf.add(i.to_le_bytes()).is_ok() {
+        i += 1;
+    }
+    let events = events.lock().unwrap();
+    assert!(events.contains(&FilterEvent::KickChainExhausted { kicks: 10 }));
+    assert!(events.contains(&FilterEvent::Stashed { len: 1 }));
+    assert!(events.contains(&FilterEvent::Stashed { len: STASH_SIZE }));
+    assert!(matches!(
+        events.last(),
+        Some(FilterEvent::InsertFailed { .. })
+    ));
+    assert_eq!(
+        cf.stats().stashed as usize,
+        events
+            .iter()
+            .filter(|e| matches!(e, FilterEvent::Stashed { .. }))
+            .count()
+    );
+}
+
+#[test]
+fn test_measure_fpp() {
+    let mut rng = StdRng::seed_from_u64(7);
+    let mut cf = CuckooFilter::<metro::Hasher64_1>::with_capacity_and_fpp(1000, 0.01).unwrap();
+    for i in 0..1000 {
+        cf.add(format!("entry-{i}")).unwrap();
+    }
+    let measured = cf.measure_fpp(&mut rng, 100_000);
+    // sized for 0.01 when full, which it isn't quite
+    assert!(measured.upper < 0.01, "{measured:?}");
+    assert!(measured.false_positives > 0);
+    assert_eq!(0, cf.stats().stash_hits);
+}
+
+#[test]
+fn test_hash_scheme_stable() {
+    // persisted filters depend on these never changing for HashSchemeVersion::V1
+    let fingerprint = fingerprint_of::<metro::Hasher64_1>(b"stable", 12);
+    let i = index_of::<metro::Hasher64_1>(b"stable", fingerprint, 1 << 10, 0);
+    let alt = alt_index_of::<metro::Hasher64_1>(i, fingerprint, 1 << 10, 0);
+    assert_eq!((3778, 262, 740), (fingerprint, i, alt));
+}
+
+#[test]
+fn test_config_getters() {
+    let cf = CuckooFilter::<metro::Hasher64_1>::with_fingerprint_bits(100, 2, 50, 12)
+        .unwrap()
+        .with_insert_strategy(InsertStrategy::BreadthFirst);
+    assert_eq!(
+        (128, 2, 12, 50, InsertStrategy::BreadthFirst),
+        (
+            cf.num_bins(),
+            cf.entries_per_bin(),
+            cf.fingerprint_bits(),
+            cf.max_kicks(),
+            cf.insert_strategy()
+        )
+    );
+    assert!(!cf.is_semi_sorted());
+    assert_eq!((None, 0), (cf.max_load_factor(), cf.growths()));
+
+    let mut cf = CuckooFilter::<metro::Hasher64_1>::with_semi_sorted_bins(1, 10, 9)
+        .unwrap()
+        .with_auto_resize(0.5)
+        .unwrap();
+    assert!(cf.is_semi_sorted());
+    for i in 0..8_u64 {
+        cf.add(i.to_le_bytes()).unwrap();
+    }
+    assert_eq!(Some(0.5), cf.max_load_factor());
+    assert!(cf.growths() > 0);
+    assert_eq!(1 << cf.growths(), cf.num_bins());
+}
+
+#[test]
+fn test_from_iter_with_params_limits() {
+    // sized from the size hint, and rebuilt when a filter can't know how many items come
+    let cf = CuckooFilter::<murmur3::Hasher32>::from_iter_with_params(
+        (0..1000u64).filter(|i| i % 3 != 0).map(u64::to_ne_bytes),
+        4,
+        100,
+        12,
+    )
+    .unwrap();
+    for i in (0..1000u64).filter(|i| i % 3 != 0) {
+        assert!(cf.contains(i.to_ne_bytes()), "{}", i);
+    }
+
+    // copies of one item share their two bins at any size
+    let copies = std::iter::repeat_n("same", 2 * 4 + STASH_SIZE + 1);
+    assert!(matches!(
+        CuckooFilter::<murmur3::Hasher32>::from_iter_with_params(copies, 4, 100, 12),
+        Err(Error::TooManyCopies(13))
+    ));
+    let copies = std::iter::repeat_n("same", 2 * 4 + STASH_SIZE);
+    let cf = CuckooFilter::<murmur3::Hasher32>::from_iter_with_params(copies, 4, 100, 12).unwrap();
+    assert_eq!(12, cf.len());
 }
@@ -0,0 +1,79 @@
+use std::marker::PhantomData;
+
+use super::{
+    alt_index_of,
+    bins::{Bins, LeWords},
+    bytes::{framed_len, parse},
+    fingerprint_of, index_of, Error,
+};
+use crate::format::{self, CHECKSUM_LEN};
+use crate::hasher::SeedableHasher;
+
+/// A read-only [super::CuckooFilter] that answers lookups straight from the bytes written by
+/// [super::CuckooFilter::to_bytes], without copying the bins.
+///
+/// Backed by a memory mapped file, for instance from the `memmap2` crate, this queries filters
+/// far larger than memory, as only the pages holding the bins that are looked up get read.
+/// Opening a view only checks the header and the length of the buffer, not the checksum, so
+/// corruption in the bins themselves can cause false negatives, where
+/// [super::CuckooFilter::from_bytes] would have rejected the buffer.
+pub struct CuckooFilterView<'a, T>
+where
+    T: SeedableHasher,
+{
+    bins: Bins<LeWords<'a>>,
+    stash: Vec<(usize, u32)>,
+    growths: u32,
+    _hasher: PhantomData<T>,
+}
+
+impl<'a, T> CuckooFilterView<'a, T>
+where
+    T: SeedableHasher,
+{
+    /// Open a view over a serialized filter.  The bins may start at any alignment.
+    pub fn new(bytes: &'a [u8]) -> Result<Self, Error> {
+        let (parts, rest) = parse(bytes)?;
+        let layout = parts.check()?;
+        format::check_len(
+            bytes,
+            framed_len(bytes.len() - rest.len(), parts.num_words()?),
+        )?;
+        let bins = Bins::view(
+            &rest[..rest.len() - CHECKSUM_LEN],
+            parts.num_bins,
+            parts.entries_per_bin,
+            parts.fingerprint_bits,
+            layout,
+        )
+        .ok_or(Error::Malformed)?;
+        Ok(CuckooFilterView {
+            bins,
+            stash: parts.stash,
+            growths: parts.growths,
+            _hasher: PhantomData,
+        })
+    }
+
+    /// Total number of slots in the bins.
+    pub fn capacity(&self) -> usize {
+        self.bins.num_bins() * self.bins.entries_per_bin()
+    }
+
+    pub fn contains<I>(&self, entry: I) -> bool
+    where
+        I: AsRef<[u8]>,
+    {
+        let bytes = entry.as_ref();
+        let num_bins = self.bins.num_bins();
+        let fingerprint = fingerprint_of::<T>(bytes, self.bins.fingerprint_bits());
+        let i = index_of::<T>(bytes, fingerprint, num_bins, self.growths);
+        let alt = alt_index_of::<T>(i, fingerprint, num_bins, self.growths);
+        self.bins.contains(i, fingerprint)
+            || self.bins.contains(alt, fingerprint)
+            || self
+                .stash
+                .iter()
+                .any(|&(bin, fp)| fp == fingerprint && (bin == i || bin == alt))
+    }
+}
@@ -0,0 +1,177 @@
+use std::{
+    borrow::Borrow,
+    collections::{HashMap, HashSet},
+    hash::Hash,
+    marker::PhantomData,
+};
+use thiserror::Error;
+
+use crate::hasher::{mix, SeedableHasher};
+
+#[cfg(test)]
+mod test;
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("Invalid precision {0}: must be 4 <= precision <= 16")]
+    InvalidPrecision(u8),
+}
+
+const MIN_PRECISION: u8 = 4;
+const MAX_PRECISION: u8 = 16;
+// an exact set holds 8 byte hashes, and is promoted once it would outgrow the registers
+const HASH_BYTES: usize = 8;
+
+/// The distinct values of one key: their hashes while there are few, then a HyperLogLog.
+#[derive(Clone)]
+enum Distinct {
+    Exact(HashSet<u64>),
+    Sketch(Vec<u8>),
+}
+
+/// Distinct counts of the values seen with each key, such as unique users per endpoint.
+///
+/// Each key starts with an exact set of its values' hashes, and is promoted to a
+/// [HyperLogLog](https://en.wikipedia.org/wiki/HyperLogLog) of `2^precision` one byte registers
+/// once the set would take more memory than the registers.  Below that, counts are exact but
+/// for hash collisions, and above it they have a relative error of about
+/// `1.04 / sqrt(2^precision)`.
+pub struct DistinctCountMap<K, T>
+where
+    K: Eq + Hash,
+    T: SeedableHasher,
+{
+    counts: HashMap<K, Distinct>,
+    precision: u8,
+    _hasher: PhantomData<T>,
+}
+
+impl<K, T> DistinctCountMap<K, T>
+where
+    K: Eq + Hash,
+    T: SeedableHasher,
+{
+    /// Create an empty map whose promoted keys have `2^precision` registers.
+    ///
+    /// `precision` must be between 4 and 16.
+    pub fn new(precision: u8) -> Result<Self, Error> {
+        if !(MIN_PRECISION..=MAX_PRECISION).contains(&precision) {
+            return Err(Error::InvalidPrecision(precision));
+        }
+        Ok(DistinctCountMap {
+            counts: HashMap::new(),
+            precision,
+            _hasher: PhantomData,
+        })
+    }
+
+    /// Record that `value` was seen with `key`.
+    pub fn insert<V>(&mut self, key: K, value: V)
+    where
+        V: AsRef<[u8]>,
+    {
+        let mut h = T::with_seed(0);
+        h.write(value.as_ref());
+        let hash = mix(h.finish());
+        let precision = self.precision;
+        let distinct = self
+            .counts
+            .entry(key)
+            .or_insert_with(|| Distinct::Exact(HashSet::new()));
+        match distinct {
+            Distinct::Exact(hashes) => {
+                hashes.insert(hash);
+                if hashes.len() * HASH_BYTES > 1 << precision {
+                    let mut registers = vec![0; 1 << precision];
+                    for &hash in hashes.iter() {
+                        observe(&mut registers, precision, hash);
+                    }
+                    *distinct = Distinct::Sketch(registers);
+                }
+            }
+            Distinct::Sketch(registers) => observe(registers, precision, hash),
+        }
+    }
+
+    /// Estimate the number of distinct values seen with `key`, 0 if it wasn't seen.
+    pub fn count<Q>(&self, key: &Q) -> u64
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.counts.get(key).map(estimate).unwrap_or_default()
+    }
+
+    /// Whether `key` has been promoted to a HyperLogLog, so its count is an estimate.  Returns
+    /// false if it wasn't seen.
+    pub fn is_estimated<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        matches!(self.counts.get(key), Some(Distinct::Sketch(_)))
+    }
+
+    /// Forget `key` and its values, returning its count.
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<u64>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.counts.remove(key).as_ref().map(estimate)
+    }
+
+    /// Every key with its count, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = (&K, u64)> + '_ {
+        self.counts
+            .iter()
+            .map(|(key, distinct)| (key, estimate(distinct)))
+    }
+
+    /// Number of keys.
+    pub fn len(&self) -> usize {
+        self.counts.len()
+    }
+
+    /// Whether no keys have been seen.
+    pub fn is_empty(&self) -> bool {
+        self.counts.is_empty()
+    }
+}
+
+// The high `precision` bits of the hash pick a register, which keeps the longest run of
+// leading zeros in the rest, plus one.
+fn observe(registers: &mut [u8], precision: u8, hash: u64) {
+    let index = (hash >> (u64::BITS - precision as u32)) as usize;
+    let rank = (hash << precision)
+        .leading_zeros()
+        .min(u64::BITS - precision as u32)
+        + 1;
+    registers[index] = registers[index].max(rank as u8);
+}
+
+fn estimate(distinct: &Distinct) -> u64 {
+    let registers = match distinct {
+        Distinct::Exact(hashes) => return hashes.len() as u64,
+        Distinct::Sketch(registers) => registers,
+    };
+    let m = registers.len() as f64;
+    let alpha = match registers.len() {
+        16 => 0.673,
+        32 => 0.697,
+        64 => 0.709,
+        _ => 0.7213 / (1.0 + 1.079 / m),
+    };
+    let sum = registers
+        .iter()
+        .map(|&rank| 2_f64.powi(-(rank as i32)))
+        .sum::<f64>();
+    let raw = alpha * m * m / sum;
+    let zeros = registers.iter().filter(|&&rank| rank == 0).count();
+    // linear counting is more accurate while registers are still empty
+    let estimate = match raw <= 2.5 * m && zeros > 0 {
+        true => m * (m / zeros as f64).ln(),
+        false => raw,
+    };
+    estimate.round() as u64
+}
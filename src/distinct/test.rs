@@ -0,0 +1,60 @@
+use fasthash::metro;
+
+use super::*;
+
+#[test]
+fn test_new() {
+    assert!(matches!(
+        DistinctCountMap::<&str, metro::Hasher64_1>::new(3),
+        Err(Error::InvalidPrecision(3))
+    ));
+    assert!(matches!(
+        DistinctCountMap::<&str, metro::Hasher64_1>::new(17),
+        Err(Error::InvalidPrecision(17))
+    ));
+}
+
+#[test]
+fn test_exact() {
+    let mut map = DistinctCountMap::<&str, metro::Hasher64_1>::new(10).unwrap();
+    assert!(map.is_empty());
+    for user in ["alice", "bob", "alice", "carol"] {
+        map.insert("/login", user);
+    }
+    map.insert("/logout", "alice");
+    assert_eq!(3, map.count("/login"));
+    assert_eq!(1, map.count("/logout"));
+    assert_eq!(0, map.count("/admin"));
+    assert!(!map.is_estimated("/login"));
+    assert_eq!(2, map.len());
+
+    let mut counts = map.iter().collect::<Vec<_>>();
+    counts.sort();
+    assert_eq!(vec![(&"/login", 3), (&"/logout", 1)], counts);
+    assert_eq!(Some(1), map.remove("/logout"));
+    assert_eq!(None, map.remove("/logout"));
+}
+
+#[test]
+fn test_promotion() {
+    let mut map = DistinctCountMap::<String, metro::Hasher64_1>::new(10).unwrap();
+    // promoted past 1024 / 8 values
+    for user in 0..128 {
+        map.insert("/home".to_string(), format!("user{user}"));
+    }
+    assert_eq!(128, map.count("/home"));
+    assert!(!map.is_estimated("/home"));
+    map.insert("/home".to_string(), "user128");
+    assert!(map.is_estimated("/home"));
+    // linear counting keeps small counts close
+    assert!(map.count("/home").abs_diff(129) < 10);
+
+    for round in 0..2 {
+        for user in 0..50_000 {
+            map.insert("/home".to_string(), format!("user{user}"));
+        }
+        // 1.04 / sqrt(1024) is about 3%, so 10% is over 3 standard errors
+        let count = map.count("/home");
+        assert!(count.abs_diff(50_000) < 5_000, "round {round}: {count}");
+    }
+}
@@ -0,0 +1,39 @@
+use std::sync::Arc;
+
+/// Something a filter did that loses fidelity, passed to a hook registered with
+/// [CountingBloomFilter::with_event_hook](crate::CountingBloomFilter::with_event_hook) or
+/// [CuckooFilter::with_event_hook](crate::CuckooFilter::with_event_hook), so it can be logged
+/// or alerted on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterEvent {
+    /// An add was refused because one of the entry's counters was saturated.
+    CounterSaturated,
+    /// Counters were widened to `bits_per_counter` bits, as too many had saturated.
+    CountersWidened { bits_per_counter: u32 },
+    /// An insert moved fingerprints `kicks` times without finding room for the last one.
+    KickChainExhausted { kicks: u64 },
+    /// A fingerprint was put in the stash, which now holds `len`.
+    Stashed { len: usize },
+    /// A fingerprint couldn't be placed, even in the stash, so an insert or merge dropped it.
+    InsertFailed { bin: usize, fingerprint: u32 },
+}
+
+/// The hook a filter calls for each [FilterEvent], shared by its clones.  Calling a filter
+/// without one costs a branch.
+#[derive(Clone, Default)]
+pub(crate) struct EventHook(Option<Arc<dyn Fn(FilterEvent) + Send + Sync>>);
+
+impl EventHook {
+    pub(crate) fn new<F>(hook: F) -> Self
+    where
+        F: Fn(FilterEvent) + Send + Sync + 'static,
+    {
+        EventHook(Some(Arc::new(hook)))
+    }
+
+    pub(crate) fn emit(&self, event: FilterEvent) {
+        if let Some(hook) = &self.0 {
+            hook(event);
+        }
+    }
+}
@@ -0,0 +1,268 @@
+//! C bindings, so C and C++ services can build and query filters.  The header is
+//! `include/probably_filters.h`, generated with `cbindgen --config cbindgen.toml`.  Build a
+//! library to link against with
+//! `cargo rustc --release --lib --features ffi --crate-type staticlib` (or `cdylib`), and link
+//! the C++ runtime too, which the hashers need.
+//!
+//! Filters are opaque pointers, created by a `*_new` or `*_from_bytes` function, which return
+//! null for invalid parameters or input, and released with the matching `*_free` function.
+//! Entries are passed as a pointer and a length.  Every filter hashes with
+//! [metro::Hasher64_1], as the command line tool does, so serialized filters are interchangeable
+//! between them.
+//!
+//! A filter may be read from several threads at once, but must not be modified while it is
+//! being used by another thread.
+use std::{ptr, slice};
+
+use fasthash::metro;
+
+use crate::{BloomFilter, CountingBloomFilter, CuckooFilter};
+
+/// Opaque handle to a [BloomFilter].
+pub struct PfBloomFilter(BloomFilter<metro::Hasher64_1>);
+
+/// Opaque handle to a [CountingBloomFilter].
+pub struct PfCountingBloomFilter(CountingBloomFilter<metro::Hasher64_1>);
+
+/// Opaque handle to a [CuckooFilter].
+pub struct PfCuckooFilter(CuckooFilter<metro::Hasher64_1>);
+
+/// Create a bloom filter with `num_bits` bits and `num_hashes` hashes, or null if either is
+/// invalid.
+#[no_mangle]
+pub extern "C" fn pf_bloom_new(num_bits: usize, num_hashes: u32) -> *mut PfBloomFilter {
+    into_raw(BloomFilter::new(num_bits, num_hashes).map(PfBloomFilter))
+}
+
+/// Load a bloom filter serialized by [pf_bloom_to_bytes], or null if the bytes are malformed.
+///
+/// # Safety
+///
+/// `bytes` must point to `len` readable bytes, or be null if `len` is 0.
+#[no_mangle]
+pub unsafe extern "C" fn pf_bloom_from_bytes(bytes: *const u8, len: usize) -> *mut PfBloomFilter {
+    into_raw(BloomFilter::from_bytes(as_slice(bytes, len)).map(PfBloomFilter))
+}
+
+/// Add an entry, returning true if any bit was set.
+///
+/// # Safety
+///
+/// `filter` must come from this library and not have been freed, and `entry` must point to
+/// `len` readable bytes, or be null if `len` is 0.
+#[no_mangle]
+pub unsafe extern "C" fn pf_bloom_add(
+    filter: *mut PfBloomFilter,
+    entry: *const u8,
+    len: usize,
+) -> bool {
+    (*filter).0.add(as_slice(entry, len))
+}
+
+/// # Safety
+///
+/// As for [pf_bloom_add].
+#[no_mangle]
+pub unsafe extern "C" fn pf_bloom_contains(
+    filter: *const PfBloomFilter,
+    entry: *const u8,
+    len: usize,
+) -> bool {
+    (*filter).0.contains(as_slice(entry, len))
+}
+
+/// Serialize a bloom filter, storing the length in `len`.  The buffer must be released with
+/// [pf_bytes_free].
+///
+/// # Safety
+///
+/// `filter` must come from this library and not have been freed, and `len` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn pf_bloom_to_bytes(
+    filter: *const PfBloomFilter,
+    len: *mut usize,
+) -> *mut u8 {
+    into_buffer((*filter).0.to_bytes(), len)
+}
+
+/// # Safety
+///
+/// `filter` must come from this library, or be null, and must not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn pf_bloom_free(filter: *mut PfBloomFilter) {
+    free(filter)
+}
+
+/// Create a counting bloom filter with `num_counters` 4 bit counters and `num_hashes` hashes,
+/// or null if either is invalid.
+#[no_mangle]
+pub extern "C" fn pf_counting_bloom_new(
+    num_counters: usize,
+    num_hashes: u32,
+) -> *mut PfCountingBloomFilter {
+    into_raw(CountingBloomFilter::new(num_counters, num_hashes).map(PfCountingBloomFilter))
+}
+
+/// Add an entry, returning false if a counter was already at its maximum.
+///
+/// # Safety
+///
+/// `filter` must come from this library and not have been freed, and `entry` must point to
+/// `len` readable bytes, or be null if `len` is 0.
+#[no_mangle]
+pub unsafe extern "C" fn pf_counting_bloom_add(
+    filter: *mut PfCountingBloomFilter,
+    entry: *const u8,
+    len: usize,
+) -> bool {
+    (*filter).0.add(as_slice(entry, len))
+}
+
+/// Remove an entry, returning false if it wasn't present.
+///
+/// # Safety
+///
+/// As for [pf_counting_bloom_add].
+#[no_mangle]
+pub unsafe extern "C" fn pf_counting_bloom_remove(
+    filter: *mut PfCountingBloomFilter,
+    entry: *const u8,
+    len: usize,
+) -> bool {
+    (*filter).0.remove(as_slice(entry, len))
+}
+
+/// # Safety
+///
+/// As for [pf_counting_bloom_add].
+#[no_mangle]
+pub unsafe extern "C" fn pf_counting_bloom_contains(
+    filter: *const PfCountingBloomFilter,
+    entry: *const u8,
+    len: usize,
+) -> bool {
+    (*filter).0.contains(as_slice(entry, len))
+}
+
+/// # Safety
+///
+/// `filter` must come from this library, or be null, and must not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn pf_counting_bloom_free(filter: *mut PfCountingBloomFilter) {
+    free(filter)
+}
+
+/// Create a cuckoo filter with at least `num_bins` bins, or null if `num_bins` is invalid.
+#[no_mangle]
+pub extern "C" fn pf_cuckoo_new(num_bins: usize) -> *mut PfCuckooFilter {
+    into_raw(CuckooFilter::new(num_bins).map(PfCuckooFilter))
+}
+
+/// Load a cuckoo filter serialized by [pf_cuckoo_to_bytes], or null if the bytes are malformed.
+///
+/// # Safety
+///
+/// `bytes` must point to `len` readable bytes, or be null if `len` is 0.
+#[no_mangle]
+pub unsafe extern "C" fn pf_cuckoo_from_bytes(bytes: *const u8, len: usize) -> *mut PfCuckooFilter {
+    into_raw(CuckooFilter::from_bytes(as_slice(bytes, len)).map(PfCuckooFilter))
+}
+
+/// Add an entry, returning false if the filter is full.
+///
+/// # Safety
+///
+/// `filter` must come from this library and not have been freed, and `entry` must point to
+/// `len` readable bytes, or be null if `len` is 0.
+#[no_mangle]
+pub unsafe extern "C" fn pf_cuckoo_add(
+    filter: *mut PfCuckooFilter,
+    entry: *const u8,
+    len: usize,
+) -> bool {
+    (*filter).0.add(as_slice(entry, len)).is_ok()
+}
+
+/// Remove an entry, returning false if it wasn't found.
+///
+/// # Safety
+///
+/// As for [pf_cuckoo_add].
+#[no_mangle]
+pub unsafe extern "C" fn pf_cuckoo_remove(
+    filter: *mut PfCuckooFilter,
+    entry: *const u8,
+    len: usize,
+) -> bool {
+    (*filter).0.remove(as_slice(entry, len))
+}
+
+/// # Safety
+///
+/// As for [pf_cuckoo_add].
+#[no_mangle]
+pub unsafe extern "C" fn pf_cuckoo_contains(
+    filter: *const PfCuckooFilter,
+    entry: *const u8,
+    len: usize,
+) -> bool {
+    (*filter).0.contains(as_slice(entry, len))
+}
+
+/// Serialize a cuckoo filter, storing the length in `len`.  The buffer must be released with
+/// [pf_bytes_free].
+///
+/// # Safety
+///
+/// `filter` must come from this library and not have been freed, and `len` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn pf_cuckoo_to_bytes(
+    filter: *const PfCuckooFilter,
+    len: *mut usize,
+) -> *mut u8 {
+    into_buffer((*filter).0.to_bytes(), len)
+}
+
+/// # Safety
+///
+/// `filter` must come from this library, or be null, and must not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn pf_cuckoo_free(filter: *mut PfCuckooFilter) {
+    free(filter)
+}
+
+/// Release a buffer returned by one of the `*_to_bytes` functions.
+///
+/// # Safety
+///
+/// `bytes` and `len` must be exactly as returned by a `*_to_bytes` function, or `bytes` must be
+/// null, and the buffer must not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn pf_bytes_free(bytes: *mut u8, len: usize) {
+    if !bytes.is_null() {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(bytes, len)));
+    }
+}
+
+fn into_raw<F, E>(filter: Result<F, E>) -> *mut F {
+    filter.map_or(ptr::null_mut(), |f| Box::into_raw(Box::new(f)))
+}
+
+unsafe fn into_buffer(bytes: Vec<u8>, len: *mut usize) -> *mut u8 {
+    *len = bytes.len();
+    Box::into_raw(bytes.into_boxed_slice()) as *mut u8
+}
+
+unsafe fn free<F>(filter: *mut F) {
+    if !filter.is_null() {
+        drop(Box::from_raw(filter));
+    }
+}
+
+unsafe fn as_slice<'a>(bytes: *const u8, len: usize) -> &'a [u8] {
+    if len == 0 {
+        &[]
+    } else {
+        slice::from_raw_parts(bytes, len)
+    }
+}
@@ -0,0 +1,203 @@
+//! Framing shared by the serialized filters: a 4 byte magic number naming the type of filter,
+//! a version byte, the filter's parameters and payload, then the CRC32C of everything before
+//! it, little endian.
+use std::{
+    cmp::Ordering,
+    io::{self, Read, Write},
+};
+
+use crc::{Crc, Digest, CRC_32_ISCSI};
+use fasthash::{murmur3::Hash128_x64, FastHash};
+
+/// Length of the magic number that starts every serialized filter.
+pub(crate) const MAGIC_LEN: usize = 4;
+/// Length of the CRC32C that ends every serialized filter.
+pub(crate) const CHECKSUM_LEN: usize = 4;
+
+static CRC32C: Crc<u32> = Crc::<u32>::new(&CRC_32_ISCSI);
+
+/// Magic number that starts every zstd frame.
+#[cfg(feature = "zstd")]
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+/// Why a buffer isn't a serialized filter, converted into each module's own error.
+#[derive(Debug)]
+pub(crate) enum FrameError {
+    Truncated,
+    InvalidMagic,
+    UnsupportedVersion(u8),
+    TrailingBytes,
+    ChecksumMismatch { expected: u32, actual: u32 },
+}
+
+/// Check the magic number and version at the start of `bytes`.
+pub(crate) fn check_header(
+    bytes: &[u8],
+    magic: &[u8; MAGIC_LEN],
+    version: u8,
+) -> Result<(), FrameError> {
+    if bytes.len() < MAGIC_LEN + 1 {
+        return Err(FrameError::Truncated);
+    }
+    if &bytes[..MAGIC_LEN] != magic {
+        return Err(FrameError::InvalidMagic);
+    }
+    if bytes[MAGIC_LEN] != version {
+        return Err(FrameError::UnsupportedVersion(bytes[MAGIC_LEN]));
+    }
+    Ok(())
+}
+
+/// Check that `bytes` is exactly `len` bytes, including the checksum.
+pub(crate) fn check_len(bytes: &[u8], len: u64) -> Result<(), FrameError> {
+    match (bytes.len() as u64).cmp(&len) {
+        Ordering::Less => Err(FrameError::Truncated),
+        Ordering::Greater => Err(FrameError::TrailingBytes),
+        Ordering::Equal => Ok(()),
+    }
+}
+
+/// Check that `bytes` is exactly `len` bytes, including the checksum, and that the checksum
+/// matches.
+pub(crate) fn check_body(bytes: &[u8], len: u64) -> Result<(), FrameError> {
+    check_len(bytes, len)?;
+    let (body, checksum) = bytes.split_at(bytes.len() - CHECKSUM_LEN);
+    let expected = u32::from_le_bytes(checksum.try_into().unwrap());
+    let actual = CRC32C.checksum(body);
+    match expected == actual {
+        true => Ok(()),
+        false => Err(FrameError::ChecksumMismatch { expected, actual }),
+    }
+}
+
+/// Append the checksum of `bytes`.
+pub(crate) fn push_checksum(bytes: &mut Vec<u8>) {
+    let checksum = CRC32C.checksum(bytes);
+    bytes.extend_from_slice(&checksum.to_le_bytes());
+}
+
+/// 128 bit murmur3 of a serialized filter, which is the same on every platform.
+pub(crate) fn digest(bytes: &[u8]) -> u128 {
+    Hash128_x64::hash_with_seed(bytes, 0)
+}
+
+/// Computes the checksum of everything written through it, for [ChecksumWriter::finish] to
+/// append.
+pub(crate) struct ChecksumWriter<W> {
+    inner: W,
+    digest: Digest<'static, u32>,
+}
+
+impl<W> ChecksumWriter<W>
+where
+    W: Write,
+{
+    pub(crate) fn new(inner: W) -> Self {
+        ChecksumWriter {
+            inner,
+            digest: CRC32C.digest(),
+        }
+    }
+
+    /// Write the checksum.
+    pub(crate) fn finish(mut self) -> io::Result<()> {
+        self.inner.write_all(&self.digest.finalize().to_le_bytes())
+    }
+}
+
+impl<W> Write for ChecksumWriter<W>
+where
+    W: Write,
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.digest.update(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Computes the checksum of everything read through it, for [ChecksumReader::finish] to
+/// compare with the one that follows.
+pub(crate) struct ChecksumReader<R> {
+    inner: R,
+    digest: Digest<'static, u32>,
+}
+
+impl<R> ChecksumReader<R>
+where
+    R: Read,
+{
+    pub(crate) fn new(inner: R) -> Self {
+        ChecksumReader {
+            inner,
+            digest: CRC32C.digest(),
+        }
+    }
+
+    /// Read the checksum and compare it with the bytes read so far.
+    pub(crate) fn finish<E>(mut self) -> Result<(), E>
+    where
+        E: From<io::Error> + From<FrameError>,
+    {
+        let mut checksum = [0; CHECKSUM_LEN];
+        self.inner.read_exact(&mut checksum)?;
+        let expected = u32::from_le_bytes(checksum);
+        let actual = self.digest.finalize();
+        match expected == actual {
+            true => Ok(()),
+            false => Err(FrameError::ChecksumMismatch { expected, actual }.into()),
+        }
+    }
+}
+
+impl<R> Read for ChecksumReader<R>
+where
+    R: Read,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.digest.update(&buf[..read]);
+        Ok(read)
+    }
+}
+
+/// Compress the bytes written by `write` with zstd, at the default level.
+#[cfg(feature = "zstd")]
+pub(crate) fn compress<F>(write: F) -> Vec<u8>
+where
+    F: FnOnce(&mut zstd::Encoder<'static, Vec<u8>>) -> io::Result<()>,
+{
+    let compress = || {
+        let mut encoder = zstd::Encoder::new(vec![], zstd::DEFAULT_COMPRESSION_LEVEL)?;
+        write(&mut encoder)?;
+        encoder.finish()
+    };
+    compress().expect("compressing into a Vec can't fail")
+}
+
+/// True if `bytes` start with a zstd frame rather than a serialized filter.
+#[cfg(feature = "zstd")]
+pub(crate) fn is_compressed(bytes: &[u8]) -> bool {
+    bytes.starts_with(&ZSTD_MAGIC)
+}
+
+/// Read a filter from the zstd frame in `bytes` with `read_from`, decompressing a chunk at a
+/// time, so the header is checked before the body is allocated and a frame that inflates to
+/// far more than the filter is never held in memory.  Nothing may follow the filter.
+#[cfg(feature = "zstd")]
+pub(crate) fn read_compressed<T, E, F>(bytes: &[u8], read_from: F) -> Result<T, E>
+where
+    F: FnOnce(&mut dyn Read) -> Result<T, E>,
+    E: From<io::Error> + From<FrameError>,
+{
+    let mut decoder = zstd::Decoder::new(bytes)?;
+    let filter = read_from(&mut decoder)?;
+    match decoder.read(&mut [0])? {
+        0 => Ok(filter),
+        _ => Err(FrameError::TrailingBytes.into()),
+    }
+}
@@ -0,0 +1,226 @@
+use std::{marker::PhantomData, mem};
+use thiserror::Error;
+
+use crate::{
+    format::{self, FrameError, CHECKSUM_LEN, MAGIC_LEN},
+    hasher::SeedableHasher,
+    mem::heap_size,
+};
+
+#[cfg(test)]
+mod test;
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("Invalid max range {0}: must be > 0")]
+    InvalidMaxRange(u64),
+
+    #[error("Invalid false positive probability {0}: must be 0 < fpp < 1")]
+    InvalidFpp(f64),
+
+    #[error("Unsupported format version {0}")]
+    UnsupportedVersion(u8),
+
+    #[error("Serialized filter is truncated or malformed")]
+    Malformed,
+
+    #[error("Serialized filter is truncated")]
+    Truncated,
+
+    #[error("Not a serialized filter of this type")]
+    InvalidMagic,
+
+    #[error("Checksum mismatch: expected {expected:#010x}, computed {actual:#010x}")]
+    ChecksumMismatch { expected: u32, actual: u32 },
+}
+
+impl From<FrameError> for Error {
+    fn from(err: FrameError) -> Self {
+        match err {
+            FrameError::Truncated => Error::Truncated,
+            FrameError::InvalidMagic => Error::InvalidMagic,
+            FrameError::UnsupportedVersion(version) => Error::UnsupportedVersion(version),
+            FrameError::TrailingBytes => Error::Malformed,
+            FrameError::ChecksumMismatch { expected, actual } => {
+                Error::ChecksumMismatch { expected, actual }
+            }
+        }
+    }
+}
+
+const MAGIC: [u8; MAGIC_LEN] = *b"PFGR";
+const FORMAT_VERSION: u8 = 2;
+const HEADER_LEN: usize = MAGIC_LEN + 1 + 8 + 8 + 4 + 8;
+
+/// Implementation of the [Grafite](https://arxiv.org/abs/2311.15380) range filter.
+///
+/// Grafite is static: it is built once from a set of `u64` keys, and answers whether a range
+/// may contain any of them.  Keys are hashed into a reduced universe of size
+/// `r = n * max_range / fpp` with `h(x) = (q(x / max_range) + x) mod r`, which keeps keys of
+/// the same block of `max_range` keys contiguous.  A range query therefore only needs to check
+/// whether a hashed interval holds any code, which is a binary search in the sorted codes.
+///
+/// For ranges spanning at most `max_range` keys, the false positive probability is at most
+/// `fpp` regardless of how keys and queries are distributed.
+pub struct Grafite<T>
+where
+    T: SeedableHasher,
+{
+    codes: Vec<u64>,
+    max_range: u64,
+    reduced_universe: u64,
+    seed: u32,
+    _hasher: PhantomData<T>,
+}
+
+impl<T> Grafite<T>
+where
+    T: SeedableHasher,
+{
+    /// Build a filter over `keys`, sized so that ranges of up to `max_range` keys have a false
+    /// positive probability of at most `fpp`.
+    ///
+    /// `max_range` must be greater than 0 and `fpp` must be between 0 and 1 (exclusive).
+    pub fn new(keys: &[u64], max_range: u64, fpp: f64) -> Result<Self, Error> {
+        Self::with_seed(keys, max_range, fpp, 0)
+    }
+
+    /// Build a filter like [Grafite::new], with a specific seed for the block hash.
+    pub fn with_seed(keys: &[u64], max_range: u64, fpp: f64, seed: u32) -> Result<Self, Error> {
+        if max_range == 0 {
+            return Err(Error::InvalidMaxRange(max_range));
+        }
+        if !(fpp > 0.0 && fpp < 1.0) {
+            return Err(Error::InvalidFpp(fpp));
+        }
+        let reduced_universe = ((keys.len() as f64 * max_range as f64 / fpp).ceil() as u64).max(1);
+        let mut grafite = Grafite {
+            codes: vec![],
+            max_range,
+            reduced_universe,
+            seed,
+            _hasher: PhantomData,
+        };
+        let mut codes = keys.iter().map(|&k| grafite.code(k)).collect::<Vec<_>>();
+        codes.sort_unstable();
+        codes.dedup();
+        grafite.codes = codes;
+        Ok(grafite)
+    }
+
+    /// Determine if filter contains the provided key.
+    pub fn contains(&self, key: u64) -> bool {
+        self.contains_range(key, key)
+    }
+
+    /// Determine if filter may contain any key in `lo..=hi`.
+    ///
+    /// Ranges wider than `max_range` are checked one block at a time, so the cost grows with
+    /// the width of the range and the false positive guarantee no longer holds.
+    pub fn contains_range(&self, lo: u64, hi: u64) -> bool {
+        if lo > hi || self.codes.is_empty() {
+            return false;
+        }
+        let first_block = lo / self.max_range;
+        let last_block = hi / self.max_range;
+        // checking more blocks than there are codes costs more than it can save
+        if last_block - first_block >= self.codes.len() as u64 {
+            return true;
+        }
+        (first_block..=last_block).any(|block| {
+            let start = (block * self.max_range).max(lo);
+            let end = block
+                .checked_mul(self.max_range)
+                .and_then(|v| v.checked_add(self.max_range - 1))
+                .unwrap_or(u64::MAX)
+                .min(hi);
+            let (start, end) = (self.code(start), self.code(end));
+            if start <= end {
+                self.any_code_in(start, end)
+            } else {
+                // the block wrapped around the end of the reduced universe
+                self.any_code_in(start, self.reduced_universe - 1) || self.any_code_in(0, end)
+            }
+        })
+    }
+
+    /// Bytes used by the filter, its own size plus the heap allocated for its sorted codes,
+    /// including spare capacity.
+    pub fn mem_usage(&self) -> usize {
+        mem::size_of::<Self>() + heap_size(&self.codes)
+    }
+
+    /// Serialize the filter, as the magic number `PFGR`, a version byte, the parameters and the
+    /// sorted codes as little endian integers, then the CRC32C of everything before it.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_LEN + self.codes.len() * 8 + CHECKSUM_LEN);
+        bytes.extend_from_slice(&MAGIC);
+        bytes.push(FORMAT_VERSION);
+        bytes.extend_from_slice(&self.max_range.to_le_bytes());
+        bytes.extend_from_slice(&self.reduced_universe.to_le_bytes());
+        bytes.extend_from_slice(&self.seed.to_le_bytes());
+        bytes.extend_from_slice(&(self.codes.len() as u64).to_le_bytes());
+        for code in &self.codes {
+            bytes.extend_from_slice(&code.to_le_bytes());
+        }
+        format::push_checksum(&mut bytes);
+        bytes
+    }
+
+    /// A 128 bit digest of the filter's parameters and contents, so replicas can cheaply check
+    /// they hold identical filters.  Filters with the same serialized form have the same
+    /// digest on every platform; the hasher isn't part of it.
+    pub fn digest(&self) -> u128 {
+        format::digest(&self.to_bytes())
+    }
+
+    /// Deserialize a filter produced by [Grafite::to_bytes].  The filter must be used with the
+    /// same hasher it was built with.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        format::check_header(bytes, &MAGIC, FORMAT_VERSION)?;
+        if bytes.len() < HEADER_LEN {
+            return Err(Error::Truncated);
+        }
+        let u64_at = |i: usize| u64::from_le_bytes(bytes[i..i + 8].try_into().unwrap());
+        let max_range = u64_at(5);
+        let reduced_universe = u64_at(13);
+        let seed = u32::from_le_bytes(bytes[21..25].try_into().unwrap());
+        let len = u64_at(25);
+        format::check_body(
+            bytes,
+            len.saturating_mul(8)
+                .saturating_add((HEADER_LEN + CHECKSUM_LEN) as u64),
+        )?;
+        if max_range == 0 || reduced_universe == 0 {
+            return Err(Error::Malformed);
+        }
+        let codes = bytes[HEADER_LEN..bytes.len() - CHECKSUM_LEN]
+            .chunks_exact(8)
+            .map(|c| u64::from_le_bytes(c.try_into().unwrap()))
+            .collect::<Vec<_>>();
+        if codes.windows(2).any(|w| w[0] >= w[1])
+            || codes.last().is_some_and(|&c| c >= reduced_universe)
+        {
+            return Err(Error::Malformed);
+        }
+        Ok(Grafite {
+            codes,
+            max_range,
+            reduced_universe,
+            seed,
+            _hasher: PhantomData,
+        })
+    }
+
+    fn code(&self, key: u64) -> u64 {
+        let mut h = T::with_seed(self.seed);
+        h.write(&(key / self.max_range).to_le_bytes());
+        let r = self.reduced_universe as u128;
+        ((h.finish() as u128 % r + key as u128 % r) % r) as u64
+    }
+
+    fn any_code_in(&self, start: u64, end: u64) -> bool {
+        let i = self.codes.partition_point(|&c| c < start);
+        i < self.codes.len() && self.codes[i] <= end
+    }
+}
@@ -0,0 +1,84 @@
+use fasthash::metro;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use super::*;
+
+#[test]
+fn test_contains_range() {
+    let keys = [5, 100, 1_000, 50_000, u64::MAX];
+    let g = Grafite::<metro::Hasher64_1>::new(&keys, 64, 0.01).unwrap();
+    for k in keys {
+        assert!(g.contains(k));
+    }
+    assert!(g.contains_range(0, 10));
+    assert!(g.contains_range(90, 130));
+    assert!(g.contains_range(u64::MAX - 3, u64::MAX));
+    assert!(!g.contains_range(200, 250));
+    assert!(!g.contains_range(10, 5));
+}
+
+#[test]
+fn test_no_false_negatives() {
+    let mut rng = StdRng::seed_from_u64(11);
+    let keys = (0..1000)
+        .map(|_| rng.gen::<u64>() >> 32)
+        .collect::<Vec<_>>();
+    let g = Grafite::<metro::Hasher64_1>::new(&keys, 32, 0.01).unwrap();
+    let mut false_positives = 0;
+    for _ in 0..10_000 {
+        let lo = rng.gen::<u64>() >> 32;
+        let hi = lo + rng.gen_range(0..32);
+        let found = g.contains_range(lo, hi);
+        if keys.iter().any(|k| (lo..=hi).contains(k)) {
+            assert!(found, "{}..={}", lo, hi);
+        } else if found {
+            false_positives += 1;
+        }
+    }
+    // expect ~1%, allow some slack
+    assert!(false_positives < 200, "{}", false_positives);
+}
+
+#[test]
+fn test_serialization() {
+    let keys = (0..100).map(|i| i * 1_000).collect::<Vec<u64>>();
+    let g = Grafite::<metro::Hasher64_1>::with_seed(&keys, 16, 0.05, 42).unwrap();
+    let bytes = g.to_bytes();
+    let restored = Grafite::<metro::Hasher64_1>::from_bytes(&bytes).unwrap();
+    assert_eq!(g.codes, restored.codes);
+    assert_eq!(g.digest(), restored.digest());
+    for k in keys {
+        assert!(restored.contains_range(k.saturating_sub(3), k + 3));
+    }
+
+    assert!(matches!(
+        Grafite::<metro::Hasher64_1>::from_bytes(&bytes[..bytes.len() - 1]),
+        Err(Error::Truncated)
+    ));
+    let mut corrupted = bytes.clone();
+    corrupted[40] ^= 1;
+    assert!(matches!(
+        Grafite::<metro::Hasher64_1>::from_bytes(&corrupted),
+        Err(Error::ChecksumMismatch { .. })
+    ));
+    let mut bad_version = bytes.clone();
+    bad_version[4] = 9;
+    assert!(matches!(
+        Grafite::<metro::Hasher64_1>::from_bytes(&bad_version),
+        Err(Error::UnsupportedVersion(9))
+    ));
+}
+
+#[test]
+fn test_invalid_params() {
+    assert!(matches!(
+        Grafite::<metro::Hasher64_1>::new(&[1], 0, 0.01),
+        Err(Error::InvalidMaxRange(0))
+    ));
+    assert!(matches!(
+        Grafite::<metro::Hasher64_1>::new(&[1], 8, 1.0),
+        Err(Error::InvalidFpp(_))
+    ));
+    let empty = Grafite::<metro::Hasher64_1>::new(&[], 8, 0.1).unwrap();
+    assert!(!empty.contains_range(0, u64::MAX));
+}
@@ -0,0 +1,119 @@
+use std::hash::Hasher;
+
+use fasthash::FastHasher;
+
+/// A hasher the filters and sketches can create with a `u32` seed.
+///
+/// It's implemented for every [FastHasher], whatever the type of its seed, so any of the
+/// `fasthash` hashers can be used.  Other hashers can implement it directly.  Hashers built from
+/// different seeds must hash independently, and a hasher must hash the same way on every
+/// platform for filters to be portable.
+pub trait SeedableHasher: Hasher + Sized {
+    /// A hasher with the default seed.
+    fn new() -> Self;
+
+    fn with_seed(seed: u32) -> Self;
+}
+
+impl<T> SeedableHasher for T
+where
+    T: FastHasher,
+    T::Seed: Seed,
+{
+    fn new() -> Self {
+        <T as FastHasher>::new()
+    }
+
+    fn with_seed(seed: u32) -> Self {
+        <T as FastHasher>::with_seed(Seed::from_u32(seed))
+    }
+}
+
+/// Seed types a `u32` seed can be widened into.  A u32 is used as is, so hashers seeded with
+/// a u32 hash exactly as they did before other seeds were supported.
+pub trait Seed {
+    fn from_u32(seed: u32) -> Self;
+}
+
+impl Seed for u32 {
+    fn from_u32(seed: u32) -> Self {
+        seed
+    }
+}
+
+impl Seed for u64 {
+    fn from_u32(seed: u32) -> Self {
+        seed.into()
+    }
+}
+
+impl Seed for u128 {
+    fn from_u32(seed: u32) -> Self {
+        seed.into()
+    }
+}
+
+impl Seed for (u64, u64) {
+    fn from_u32(seed: u32) -> Self {
+        (seed.into(), seed.into())
+    }
+}
+
+impl Seed for (u64, u64, u64, u64) {
+    fn from_u32(seed: u32) -> Self {
+        let seed = seed.into();
+        (seed, seed, seed, seed)
+    }
+}
+
+/// Version of the scheme that derives a filter's positions and fingerprints from an entry's
+/// hash, recorded in serialized bloom, counting bloom and cuckoo filters.
+///
+/// A version's derivation never changes between releases.  A release that changes it adds a
+/// version, so a filter serialized by an older release either keeps answering as it did or is
+/// refused with an `UnsupportedHashScheme` error, rather than silently giving different
+/// answers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum HashSchemeVersion {
+    /// Bloom filters mix the entry's 64 bit hash into two with splitmix64 (or SipHash-1-3 it to
+    /// 128 bits if keyed), derive positions by enhanced double hashing and reduce them with
+    /// fastrange.  Cuckoo filters take the fingerprint from a separately seeded hash, the bin from
+    /// the low bits of the entry's hash, and the alternate bin by xoring in the fingerprint's
+    /// hash.
+    V1 = 1,
+}
+
+impl HashSchemeVersion {
+    /// The scheme filters built by this release use.
+    pub const CURRENT: HashSchemeVersion = HashSchemeVersion::V1;
+
+    /// The version recorded as `byte`, or None if this release doesn't implement it.
+    pub fn from_u8(byte: u8) -> Option<Self> {
+        match byte {
+            1 => Some(HashSchemeVersion::V1),
+            _ => None,
+        }
+    }
+
+    pub fn as_u8(self) -> u8 {
+        self as u8
+    }
+}
+
+/// splitmix64's finalizer, which spreads every bit of `hash` over all 64 bits, so hashers that
+/// only fill the low 32 can still be reduced with [fastrange].
+pub(crate) fn mix(hash: u64) -> u64 {
+    let mut z = hash.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// Map `hash` onto `0..len` with Lemire's multiply-shift
+/// ([fastrange](https://lemire.me/blog/2016/06/27/a-fast-alternative-to-the-modulo-reduction/)),
+/// `hash * len / 2^64`, which is as uniform as `hash % len` for any `len` without a division.
+/// It uses the high bits of `hash`, so they must be as well mixed as the low ones.
+pub(crate) fn fastrange(hash: u64, len: u64) -> u64 {
+    ((hash as u128 * len as u128) >> 64) as u64
+}
@@ -0,0 +1,56 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures_util::{Stream, StreamExt};
+
+use crate::Sketch;
+
+#[cfg(test)]
+mod test;
+
+/// Most items inserted between yields to the runtime.
+const BATCH_SIZE: usize = 1024;
+
+/// Insert every item of `stream` into `sketch` as it arrives, returning how many were
+/// inserted.
+///
+/// Items that are already available are inserted in batches of up to 1024, and the task
+/// yields to the runtime after each batch, so a fast stream can't starve other tasks while a
+/// filter is built.  Works with any executor, including tokio, and any item type that derefs
+/// to bytes, such as `bytes::Bytes`.
+pub async fn fill_from_stream<S, St>(sketch: &mut S, stream: St) -> usize
+where
+    S: Sketch + ?Sized,
+    St: Stream,
+    St::Item: AsRef<[u8]>,
+{
+    let mut batches = std::pin::pin!(stream.ready_chunks(BATCH_SIZE));
+    let mut inserted = 0;
+    while let Some(batch) = batches.next().await {
+        for item in &batch {
+            sketch.update(item.as_ref());
+        }
+        inserted += batch.len();
+        YieldNow(false).await;
+    }
+    inserted
+}
+
+// Returns pending once, after asking to be polled again, so other tasks get a turn.
+struct YieldNow(bool);
+
+impl Future for YieldNow {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.0 {
+            return Poll::Ready(());
+        }
+        self.0 = true;
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
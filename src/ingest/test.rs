@@ -0,0 +1,34 @@
+use fasthash::metro;
+use futures_executor::block_on;
+use futures_util::{stream, FutureExt};
+
+use super::{fill_from_stream, BATCH_SIZE};
+use crate::{BloomFilter, CuckooFilter};
+
+#[test]
+fn test_fill_from_stream() {
+    let mut bf = BloomFilter::<metro::Hasher64_1>::new(100_000, 3).unwrap();
+    let items = (0..5_000_u64).map(|i| i.to_le_bytes().to_vec());
+    assert_eq!(
+        5_000,
+        block_on(fill_from_stream(&mut bf, stream::iter(items)))
+    );
+    for i in 0..5_000_u64 {
+        assert!(bf.contains(i.to_le_bytes()), "{}", i);
+    }
+
+    let mut cf = CuckooFilter::<metro::Hasher64_1>::new(1024).unwrap();
+    let words = ["alpha", "beta", "gamma"];
+    assert_eq!(3, block_on(fill_from_stream(&mut cf, stream::iter(words))));
+    assert!(words.iter().all(|w| cf.contains(w)));
+}
+
+#[test]
+fn test_fill_from_stream_yields() {
+    let mut bf = BloomFilter::<metro::Hasher64_1>::new(100_000, 3).unwrap();
+    let items = (0..BATCH_SIZE as u64 * 2).map(|i| i.to_le_bytes());
+    let mut fill = Box::pin(fill_from_stream(&mut bf, stream::iter(items)));
+    // the first poll inserts a batch, then yields
+    assert!((&mut fill).now_or_never().is_none());
+    assert_eq!(BATCH_SIZE * 2, block_on(fill));
+}
@@ -0,0 +1,69 @@
+use std::hash::{Hash, Hasher};
+
+/// Encode a [Hash] value as the bytes it feeds to a hasher, so typed entries can go through the
+/// same byte-slice hashing as raw ones.
+///
+/// Integers are written little endian, so the encoding doesn't depend on the platform.  It does
+/// depend on the type's [Hash] implementation, so a `String` and a `&str` with the same contents
+/// are the same entry, but a `u32` and a `u64` with the same value are not.
+pub(crate) fn key_bytes<K>(item: &K) -> Vec<u8>
+where
+    K: Hash + ?Sized,
+{
+    let mut writer = KeyWriter(Vec::new());
+    item.hash(&mut writer);
+    writer.0
+}
+
+struct KeyWriter(Vec<u8>);
+
+impl Hasher for KeyWriter {
+    fn finish(&self) -> u64 {
+        unreachable!("KeyWriter only collects bytes")
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        self.0.extend_from_slice(bytes);
+    }
+
+    fn write_u16(&mut self, i: u16) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_u32(&mut self, i: u32) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_u64(&mut self, i: u64) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_u128(&mut self, i: u128) {
+        self.write(&i.to_le_bytes());
+    }
+
+    // lengths and sizes are written as u64, to match between 32 and 64 bit platforms
+    fn write_usize(&mut self, i: usize) {
+        self.write_u64(i as u64);
+    }
+
+    fn write_i16(&mut self, i: i16) {
+        self.write_u16(i as u16);
+    }
+
+    fn write_i32(&mut self, i: i32) {
+        self.write_u32(i as u32);
+    }
+
+    fn write_i64(&mut self, i: i64) {
+        self.write_u64(i as u64);
+    }
+
+    fn write_i128(&mut self, i: i128) {
+        self.write_u128(i as u128);
+    }
+
+    fn write_isize(&mut self, i: isize) {
+        self.write_u64(i as u64);
+    }
+}
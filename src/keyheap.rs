@@ -0,0 +1,129 @@
+//! A min-heap of keys by count, indexed by key, so the lightest of the heaviest keys can be
+//! found and replaced in O(log n) as counts change.
+use std::collections::HashMap;
+
+#[derive(Clone)]
+struct Entry<T> {
+    count: u64,
+    key: Vec<u8>,
+    value: T,
+}
+
+/// Keys with a count and a value, ordered by count and then by key, lightest first.
+#[derive(Clone)]
+pub(crate) struct KeyHeap<T> {
+    heap: Vec<Entry<T>>,
+    positions: HashMap<Vec<u8>, usize>,
+}
+
+impl<T> KeyHeap<T> {
+    pub(crate) fn new() -> Self {
+        Self::with_capacity(0)
+    }
+
+    pub(crate) fn with_capacity(capacity: usize) -> Self {
+        KeyHeap {
+            heap: Vec::with_capacity(capacity),
+            positions: HashMap::with_capacity(capacity),
+        }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.heap.len()
+    }
+
+    /// The count and value of `key`, if it's in the heap.
+    pub(crate) fn get(&self, key: &[u8]) -> Option<(u64, &T)> {
+        let entry = &self.heap[*self.positions.get(key)?];
+        Some((entry.count, &entry.value))
+    }
+
+    /// The lightest key and its count.
+    pub(crate) fn peek_min(&self) -> Option<(&[u8], u64)> {
+        self.heap
+            .first()
+            .map(|entry| (entry.key.as_slice(), entry.count))
+    }
+
+    /// Add `key`, which must not be in the heap yet.
+    pub(crate) fn insert(&mut self, key: Vec<u8>, count: u64, value: T) {
+        debug_assert!(!self.positions.contains_key(&key));
+        let i = self.heap.len();
+        self.positions.insert(key.clone(), i);
+        self.heap.push(Entry { count, key, value });
+        self.sift_up(i);
+    }
+
+    /// Change the count of `key`, returning false if it isn't in the heap.
+    pub(crate) fn set_count(&mut self, key: &[u8], count: u64) -> bool {
+        let Some(&i) = self.positions.get(key) else {
+            return false;
+        };
+        self.heap[i].count = count;
+        let i = self.sift_up(i);
+        self.sift_down(i);
+        true
+    }
+
+    /// Remove `key`, returning its count and value.
+    pub(crate) fn remove(&mut self, key: &[u8]) -> Option<(u64, T)> {
+        let i = self.positions.remove(key)?;
+        let entry = self.heap.swap_remove(i);
+        if i < self.heap.len() {
+            *self.positions.get_mut(&self.heap[i].key).unwrap() = i;
+            let i = self.sift_up(i);
+            self.sift_down(i);
+        }
+        Some((entry.count, entry.value))
+    }
+
+    /// Remove the lightest key, returning its count and value.
+    pub(crate) fn pop_min(&mut self) -> Option<(Vec<u8>, u64, T)> {
+        let key = self.heap.first()?.key.clone();
+        let (count, value) = self.remove(&key)?;
+        Some((key, count, value))
+    }
+
+    /// The keys with their counts and values, in no particular order.
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (&[u8], u64, &T)> {
+        self.heap
+            .iter()
+            .map(|entry| (entry.key.as_slice(), entry.count, &entry.value))
+    }
+
+    fn less(&self, a: usize, b: usize) -> bool {
+        let (a, b) = (&self.heap[a], &self.heap[b]);
+        (a.count, &a.key) < (b.count, &b.key)
+    }
+
+    fn swap(&mut self, a: usize, b: usize) {
+        self.heap.swap(a, b);
+        *self.positions.get_mut(&self.heap[a].key).unwrap() = a;
+        *self.positions.get_mut(&self.heap[b].key).unwrap() = b;
+    }
+
+    // Move the entry at `i` up while it's lighter than its parent, returning where it ends up.
+    fn sift_up(&mut self, mut i: usize) -> usize {
+        while i > 0 && self.less(i, (i - 1) / 2) {
+            self.swap(i, (i - 1) / 2);
+            i = (i - 1) / 2;
+        }
+        i
+    }
+
+    fn sift_down(&mut self, mut i: usize) {
+        loop {
+            let mut lightest = i;
+            for child in [2 * i + 1, 2 * i + 2] {
+                if child < self.heap.len() && self.less(child, lightest) {
+                    lightest = child;
+                }
+            }
+            if lightest == i {
+                return;
+            }
+            self.swap(i, lightest);
+            i = lightest;
+        }
+    }
+}
@@ -1,5 +1,71 @@
+mod advisor;
+mod aligned;
+#[cfg(feature = "arrow")]
+mod arrow;
 mod bloom;
+#[cfg(feature = "bloomd")]
+mod bloomd;
+mod cms;
 mod cuckoo;
+mod distinct;
+mod event;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+mod format;
+mod grafite;
+mod hasher;
+#[cfg(feature = "async")]
+mod ingest;
+mod key;
+mod keyheap;
+mod measure;
+mod mem;
+mod minhash;
+mod morris;
+mod pinsketch;
+mod prefetch;
+mod quantiles;
+mod registry;
+mod riblt;
+mod shared;
+pub mod sizing;
+mod stream;
+mod summary;
+mod tinylfu;
+#[cfg(feature = "wasm")]
+mod wasm;
 
-pub use self::bloom::CountingBloomFilter;
-pub use self::cuckoo::CuckooFilter;
+pub use self::advisor::{advise, AdvisedFilter, Recommendation, Workload};
+#[cfg(feature = "arrow")]
+pub use self::arrow::{fill_from_array, fill_from_column};
+pub use self::bloom::{
+    bloom_join, BloomFilter, BloomFilterView, BloomJoin, BloomRf, CountingBloomEntry,
+    CountingBloomFilter, CountingBloomStats, DiffSummary, FilterDiff, FixedBloomFilter,
+    FixedPrefix, GuavaBloomFilter, GuavaStrategy, HashedKey, JoinSide, LearnedBloomFilter,
+    PrefixBloomFilter, PrefixExtractor, RemovalBloomFilter,
+};
+#[cfg(feature = "bloomd")]
+pub use self::bloomd::{BloomdClient, BloomdServer};
+pub use self::cms::CountMinSketch;
+pub use self::cuckoo::{
+    AdaptiveCuckooFilter, ConcurrentCuckooFilter, CuckooFilter, CuckooFilterView, CuckooStats,
+    ExpiringCuckooFilter, InsertError, InsertStrategy, TaffyCuckooFilter,
+};
+pub use self::distinct::DistinctCountMap;
+pub use self::event::FilterEvent;
+pub use self::grafite::Grafite;
+pub use self::hasher::{HashSchemeVersion, Seed, SeedableHasher};
+#[cfg(feature = "async")]
+pub use self::ingest::fill_from_stream;
+pub use self::measure::FppMeasurement;
+pub use self::minhash::{MinHashSignature, MinHasher, Shingling};
+pub use self::morris::{MorrisCounter, MorrisCounterArray};
+pub use self::pinsketch::PinSketch;
+pub use self::quantiles::WindowedQuantiles;
+pub use self::registry::{Sketch, SketchRegistry};
+pub use self::riblt::{CodedSymbol, RatelessDecoder, RatelessEncoder};
+pub use self::shared::SharedFilter;
+pub use self::summary::{StreamSummary, TopKEntry};
+pub use self::tinylfu::TinyLfu;
+#[cfg(feature = "wasm")]
+pub use self::wasm::{WasmBloomFilter, WasmCountingBloomFilter, WasmCuckooFilter};
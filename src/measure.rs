@@ -0,0 +1,66 @@
+use rand::RngCore;
+
+// random bytes per probe, so a probe matching an added entry is as unlikely as a hash collision
+const PROBE_LEN: usize = 16;
+// z for a 95% confidence interval
+const Z: f64 = 1.959_963_984_540_054;
+
+/// False positive rate observed by probing a filter with random entries, from the filters'
+/// `measure_fpp` methods.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FppMeasurement {
+    /// Number of entries probed.
+    pub trials: u64,
+    /// Number of them the filter claimed to contain.
+    pub false_positives: u64,
+    /// `false_positives / trials`, or 0 without any trials.
+    pub rate: f64,
+    /// Lower bound of the 95% Wilson score interval around `rate`.
+    pub lower: f64,
+    /// Upper bound of the 95% Wilson score interval around `rate`.
+    pub upper: f64,
+}
+
+impl FppMeasurement {
+    fn new(trials: u64, false_positives: u64) -> Self {
+        if trials == 0 {
+            return FppMeasurement {
+                trials,
+                false_positives,
+                rate: 0.0,
+                lower: 0.0,
+                upper: 1.0,
+            };
+        }
+        let n = trials as f64;
+        let rate = false_positives as f64 / n;
+        let z2 = Z * Z;
+        let center = (rate + z2 / (2.0 * n)) / (1.0 + z2 / n);
+        let half = Z / (1.0 + z2 / n) * (rate * (1.0 - rate) / n + z2 / (4.0 * n * n)).sqrt();
+        FppMeasurement {
+            trials,
+            false_positives,
+            rate,
+            lower: (center - half).max(0.0),
+            upper: (center + half).min(1.0),
+        }
+    }
+}
+
+/// Probe `contains` with `trials` random entries, which almost certainly weren't added, and
+/// count how many it claims to contain.
+pub(crate) fn measure_fpp<R, F>(rng: &mut R, trials: u64, contains: F) -> FppMeasurement
+where
+    R: RngCore + ?Sized,
+    F: Fn(&[u8]) -> bool,
+{
+    let mut probe = [0; PROBE_LEN];
+    let mut false_positives = 0;
+    for _ in 0..trials {
+        rng.fill_bytes(&mut probe);
+        if contains(&probe) {
+            false_positives += 1;
+        }
+    }
+    FppMeasurement::new(trials, false_positives)
+}
@@ -0,0 +1,19 @@
+use std::{collections::TryReserveError, mem};
+
+/// Bytes allocated for the elements of `v`, including spare capacity, which is what the
+/// `mem_usage` methods count on top of the size of the filter itself.
+pub(crate) fn heap_size<T>(v: &Vec<T>) -> usize {
+    v.capacity() * mem::size_of::<T>()
+}
+
+/// `len` copies of `value`, or an error rather than an abort if they can't be allocated, for
+/// the `try_*` constructors.
+pub(crate) fn try_filled<T>(len: usize, value: T) -> Result<Vec<T>, TryReserveError>
+where
+    T: Clone,
+{
+    let mut v = Vec::new();
+    v.try_reserve_exact(len)?;
+    v.resize(len, value);
+    Ok(v)
+}
@@ -0,0 +1,185 @@
+use std::marker::PhantomData;
+use thiserror::Error;
+
+use crate::hasher::{mix, SeedableHasher};
+
+#[cfg(test)]
+mod test;
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("Invalid shingle size {0}: must be > 0")]
+    InvalidShingleSize(usize),
+
+    #[error("Invalid hash count {0}: must be > 0")]
+    InvalidHashCount(usize),
+
+    #[error("Signatures were created with different hash counts ({0} and {1})")]
+    IncompatibleSignature(usize, usize),
+}
+
+/// How [MinHasher] cuts a document into shingles, the overlapping n-grams whose sets are
+/// compared.  A document shorter than one shingle is a single shingle of all of it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shingling {
+    /// Runs of `n` bytes, for text in any language or binary documents.
+    Bytes(usize),
+    /// Runs of `n` words, split on ASCII whitespace, so spacing doesn't matter.
+    Words(usize),
+}
+
+/// Hashes documents for near-duplicate detection: cuts them into shingles and summarizes the
+/// set of shingles as a [MinHash](https://en.wikipedia.org/wiki/MinHash) signature, whose
+/// agreement estimates the Jaccard similarity of two documents, or as a 64 bit
+/// [SimHash](https://en.wikipedia.org/wiki/SimHash), whose Hamming distance shrinks as they
+/// grow alike.
+///
+/// Each shingle is hashed once with `T`, and mixed with a different salt for each of the
+/// signature's hashes.  Signatures are only comparable between hashers with the same shingling
+/// and hash count.
+pub struct MinHasher<T>
+where
+    T: SeedableHasher,
+{
+    shingling: Shingling,
+    num_hashes: usize,
+    _hasher: PhantomData<T>,
+}
+
+impl<T> MinHasher<T>
+where
+    T: SeedableHasher,
+{
+    /// Create a hasher cutting documents into `shingling` and computing signatures of
+    /// `num_hashes` minimums.  The error of a Jaccard estimate is about `1 / sqrt(num_hashes)`.
+    ///
+    /// The shingle size and `num_hashes` must be greater than 0.
+    pub fn new(shingling: Shingling, num_hashes: usize) -> Result<Self, Error> {
+        let (Shingling::Bytes(n) | Shingling::Words(n)) = shingling;
+        if n == 0 {
+            return Err(Error::InvalidShingleSize(n));
+        }
+        if num_hashes == 0 {
+            return Err(Error::InvalidHashCount(num_hashes));
+        }
+        Ok(MinHasher {
+            shingling,
+            num_hashes,
+            _hasher: PhantomData,
+        })
+    }
+
+    /// The MinHash signature of `doc`: for each hash, the minimum over its shingles.  An empty
+    /// document has no shingles, and a signature of [u64::MAX].
+    pub fn signature<D>(&self, doc: D) -> MinHashSignature
+    where
+        D: AsRef<[u8]>,
+    {
+        let mut mins = vec![u64::MAX; self.num_hashes];
+        for shingle in self.shingle_hashes(doc.as_ref()) {
+            for (i, min) in mins.iter_mut().enumerate() {
+                *min = (*min).min(salted(shingle, i));
+            }
+        }
+        MinHashSignature { mins }
+    }
+
+    /// The 64 bit SimHash of `doc`: each bit is set if more of its shingles' hashes have it
+    /// set than not.  Near-duplicates differ in few bits, see [MinHashSignature] for
+    /// estimating the similarity instead.
+    pub fn simhash<D>(&self, doc: D) -> u64
+    where
+        D: AsRef<[u8]>,
+    {
+        let mut votes = [0_i64; u64::BITS as usize];
+        for shingle in self.shingle_hashes(doc.as_ref()) {
+            let hash = salted(shingle, 0);
+            for (bit, vote) in votes.iter_mut().enumerate() {
+                *vote += if hash >> bit & 1 == 1 { 1 } else { -1 };
+            }
+        }
+        votes
+            .iter()
+            .enumerate()
+            .filter(|&(_, &vote)| vote > 0)
+            .fold(0, |simhash, (bit, _)| simhash | 1 << bit)
+    }
+
+    /// How documents are cut into shingles.
+    pub fn shingling(&self) -> Shingling {
+        self.shingling
+    }
+
+    /// Number of minimums in a signature.
+    pub fn num_hashes(&self) -> usize {
+        self.num_hashes
+    }
+
+    // The hash of each of the document's shingles, repeated shingles included.
+    fn shingle_hashes<'a>(&self, doc: &'a [u8]) -> Box<dyn Iterator<Item = u64> + 'a> {
+        match self.shingling {
+            Shingling::Bytes(n) => Box::new(doc.windows(n.min(doc.len()).max(1)).map(|shingle| {
+                let mut h = T::with_seed(0);
+                h.write(shingle);
+                h.finish()
+            })),
+            Shingling::Words(n) => {
+                let words = doc
+                    .split(u8::is_ascii_whitespace)
+                    .filter(|word| !word.is_empty())
+                    .collect::<Vec<_>>();
+                let hashes = words
+                    .windows(n.min(words.len()).max(1))
+                    .map(|shingle| {
+                        let mut h = T::with_seed(0);
+                        // length prefixed, so word boundaries are part of the shingle
+                        for word in shingle {
+                            h.write_u64(word.len() as u64);
+                            h.write(word);
+                        }
+                        h.finish()
+                    })
+                    .collect::<Vec<_>>();
+                Box::new(hashes.into_iter())
+            }
+        }
+    }
+}
+
+// The `i`th hash of a shingle, a different permutation of its hash for each `i`.
+fn salted(hash: u64, i: usize) -> u64 {
+    mix(hash ^ mix(i as u64))
+}
+
+/// A MinHash signature of a document, from [MinHasher::signature].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MinHashSignature {
+    mins: Vec<u64>,
+}
+
+impl MinHashSignature {
+    /// Estimate the Jaccard similarity of the two documents' shingle sets, the fraction of
+    /// minimums they share, from 0 (nothing in common) to 1 (near-duplicates).
+    ///
+    /// Returns [Error::IncompatibleSignature] unless both have the same number of hashes.
+    pub fn jaccard(&self, other: &Self) -> Result<f64, Error> {
+        if self.mins.len() != other.mins.len() {
+            return Err(Error::IncompatibleSignature(
+                self.mins.len(),
+                other.mins.len(),
+            ));
+        }
+        let shared = self
+            .mins
+            .iter()
+            .zip(other.mins.iter())
+            .filter(|(a, b)| a == b)
+            .count();
+        Ok(shared as f64 / self.mins.len() as f64)
+    }
+
+    /// The minimums, one per hash, for storing or banding into an LSH index.
+    pub fn as_slice(&self) -> &[u64] {
+        &self.mins
+    }
+}
@@ -0,0 +1,62 @@
+use fasthash::metro;
+
+use super::*;
+
+const DOC: &str = "the quick brown fox jumps over the lazy dog near the river bank today";
+
+#[test]
+fn test_new() {
+    assert!(matches!(
+        MinHasher::<metro::Hasher64_1>::new(Shingling::Words(0), 64),
+        Err(Error::InvalidShingleSize(0))
+    ));
+    assert!(matches!(
+        MinHasher::<metro::Hasher64_1>::new(Shingling::Bytes(4), 0),
+        Err(Error::InvalidHashCount(0))
+    ));
+}
+
+#[test]
+fn test_jaccard() {
+    let hasher = MinHasher::<metro::Hasher64_1>::new(Shingling::Words(2), 256).unwrap();
+    let doc = hasher.signature(DOC);
+    assert_eq!(1.0, doc.jaccard(&doc).unwrap());
+    // whitespace doesn't change word shingles
+    let respaced = hasher.signature(DOC.replace(' ', "  \n"));
+    assert_eq!(1.0, doc.jaccard(&respaced).unwrap());
+
+    // one word changed replaces 2 of 13 bigrams, a Jaccard similarity of 11 / 15
+    let edited = hasher.signature(DOC.replace("lazy", "sleepy"));
+    let similarity = doc.jaccard(&edited).unwrap();
+    assert!((similarity - 11.0 / 15.0).abs() < 0.1, "{similarity}");
+
+    let unrelated = hasher.signature("pack my box with five dozen liquor jugs");
+    assert!(doc.jaccard(&unrelated).unwrap() < 0.05);
+
+    let other = MinHasher::<metro::Hasher64_1>::new(Shingling::Words(2), 128).unwrap();
+    assert!(matches!(
+        doc.jaccard(&other.signature(DOC)),
+        Err(Error::IncompatibleSignature(256, 128))
+    ));
+}
+
+#[test]
+fn test_short_documents() {
+    let hasher = MinHasher::<metro::Hasher64_1>::new(Shingling::Bytes(8), 16).unwrap();
+    assert_eq!(&[u64::MAX; 16], hasher.signature("").as_slice());
+    assert_eq!(0, hasher.simhash(""));
+    // shorter than a shingle, so the whole document is one
+    let short = hasher.signature("abc");
+    assert_eq!(1.0, short.jaccard(&hasher.signature("abc")).unwrap());
+    assert_eq!(0.0, short.jaccard(&hasher.signature("abd")).unwrap());
+}
+
+#[test]
+fn test_simhash() {
+    let hasher = MinHasher::<metro::Hasher64_1>::new(Shingling::Bytes(4), 1).unwrap();
+    let doc = hasher.simhash(DOC);
+    let edited = hasher.simhash(DOC.replace("lazy", "lazy old"));
+    let unrelated = hasher.simhash("pack my box with five dozen liquor jugs");
+    assert!((doc ^ edited).count_ones() < (doc ^ unrelated).count_ones());
+    assert_eq!(doc, hasher.simhash(DOC));
+}
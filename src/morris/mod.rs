@@ -0,0 +1,206 @@
+use rand::{thread_rng, Rng};
+use std::mem;
+use thiserror::Error;
+
+use crate::mem::heap_size;
+
+#[cfg(test)]
+mod test;
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("Invalid counter count {0}: must be > 0")]
+    InvalidCounterCount(usize),
+
+    #[error("Invalid bits per counter {0}: must be 0 < bits_per_counter <= 8")]
+    BitsPerCounterOutOfRange(u32),
+
+    #[error("Invalid base {0}: must be > 1")]
+    InvalidBase(f64),
+
+    #[error("Index {index} out of bounds for {len} counters")]
+    IndexOutOfBounds { index: usize, len: usize },
+}
+
+const MAX_BITS_PER_COUNTER: u32 = 8;
+
+/// Estimated count for a Morris counter holding `exponent`, (base^exponent - 1) / (base - 1).
+pub(crate) fn estimate(exponent: usize, base: f64) -> f64 {
+    (base.powf(exponent as f64) - 1.0) / (base - 1.0)
+}
+
+/// The exponent whose estimate is nearest `count`.
+pub(crate) fn exponent_for(count: f64, base: f64) -> usize {
+    (count * (base - 1.0) + 1.0).log(base).round() as usize
+}
+
+/// Probability of moving a counter from `exponent` to `exponent + 1`.  Incrementing with this
+/// probability keeps the estimate unbiased.
+pub(crate) fn increment_probability(exponent: usize, base: f64) -> f64 {
+    base.powf(-(exponent as f64))
+}
+
+/// Probability of moving a counter from `exponent` to `exponent - 1`, so that the estimate
+/// drops by 1 on average.
+pub(crate) fn decrement_probability(exponent: usize, base: f64) -> f64 {
+    base.powf(-(exponent as f64 - 1.0))
+}
+
+/// Implementation of a base 2 [Morris counter](https://en.wikipedia.org/wiki/Approximate_counting_algorithm).
+///
+/// Only the exponent of the count is stored, and it is incremented with probability
+/// 2^-exponent, so a single byte can count up to about 2^255 with a standard deviation of
+/// roughly `count / sqrt(2)`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct MorrisCounter(u8);
+
+impl MorrisCounter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Count one event.  The stored exponent saturates at 255.
+    pub fn increment(&mut self) {
+        self.increment_with_rng(&mut thread_rng());
+    }
+
+    /// Count one event using the provided source of randomness.
+    pub fn increment_with_rng<R: Rng>(&mut self, rng: &mut R) {
+        if self.0 < u8::MAX && rng.gen_bool(increment_probability(self.0 as usize, 2.0)) {
+            self.0 += 1;
+        }
+    }
+
+    /// Returns the estimated number of events counted.
+    pub fn estimate(&self) -> f64 {
+        estimate(self.0 as usize, 2.0)
+    }
+
+    /// Returns the stored exponent.
+    pub fn exponent(&self) -> u8 {
+        self.0
+    }
+}
+
+/// An array of Morris counters, bit packed like the counters of
+/// [CountingBloomFilter](crate::CountingBloomFilter), with a configurable base.
+///
+/// Smaller bases are more accurate but count less before saturating, the largest count is
+/// (base^(2^bits_per_counter - 1) - 1) / (base - 1).
+pub struct MorrisCounterArray {
+    bins: Vec<u64>,
+    len: usize,
+    bits_per_counter: u32,
+    base: f64,
+}
+
+impl MorrisCounterArray {
+    /// Create `len` counters of `bits_per_counter` bits each, counting in powers of `base`.
+    ///
+    /// `bits_per_counter` must be greater than 0 and less than, or equal to, 8, and `base` must
+    /// be greater than 1.
+    pub fn new(len: usize, bits_per_counter: u32, base: f64) -> Result<Self, Error> {
+        if len == 0 {
+            return Err(Error::InvalidCounterCount(len));
+        }
+        if bits_per_counter == 0 || bits_per_counter > MAX_BITS_PER_COUNTER {
+            return Err(Error::BitsPerCounterOutOfRange(bits_per_counter));
+        }
+        if base.is_nan() || base <= 1.0 {
+            return Err(Error::InvalidBase(base));
+        }
+        let counters_per_bin = (u64::BITS / bits_per_counter) as usize;
+        Ok(MorrisCounterArray {
+            bins: vec![0; len.div_ceil(counters_per_bin)],
+            len,
+            bits_per_counter,
+            base,
+        })
+    }
+
+    /// Number of counters in the array.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true if the array has no counters.  Always false, as arrays can't be empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Bytes used by the array, its own size plus the heap allocated for its packed counters,
+    /// including spare capacity.
+    pub fn mem_usage(&self) -> usize {
+        mem::size_of::<Self>() + heap_size(&self.bins)
+    }
+
+    /// Count one event at `index`.
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn increment(&mut self, index: usize) {
+        self.increment_with_rng(index, &mut thread_rng());
+    }
+
+    /// Count one event at `index` using the provided source of randomness.
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn increment_with_rng<R: Rng>(&mut self, index: usize, rng: &mut R) {
+        let exponent = self.exponent(index);
+        let max = (1 << self.bits_per_counter) - 1;
+        if exponent < max && rng.gen_bool(increment_probability(exponent, self.base)) {
+            self.set_exponent(index, exponent + 1);
+        }
+    }
+
+    /// Count one event at `index`, or return [Error::IndexOutOfBounds] rather than panicking.
+    pub fn try_increment(&mut self, index: usize) -> Result<(), Error> {
+        self.check_index(index)?;
+        self.increment(index);
+        Ok(())
+    }
+
+    /// Returns the estimated number of events counted at `index`.
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn estimate(&self, index: usize) -> f64 {
+        estimate(self.exponent(index), self.base)
+    }
+
+    /// Returns the estimated number of events counted at `index`, or
+    /// [Error::IndexOutOfBounds] rather than panicking.
+    pub fn try_estimate(&self, index: usize) -> Result<f64, Error> {
+        self.check_index(index)?;
+        Ok(self.estimate(index))
+    }
+
+    /// Returns the exponent stored at `index`.
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn exponent(&self, index: usize) -> usize {
+        let (bin, bitshift, mask) = self.offsets(index);
+        ((self.bins[bin] & mask) >> bitshift) as usize
+    }
+
+    fn set_exponent(&mut self, index: usize, exponent: usize) {
+        let (bin, bitshift, mask) = self.offsets(index);
+        self.bins[bin] = (self.bins[bin] & !mask) | ((exponent as u64) << bitshift);
+    }
+
+    fn check_index(&self, index: usize) -> Result<(), Error> {
+        match index < self.len {
+            true => Ok(()),
+            false => Err(Error::IndexOutOfBounds {
+                index,
+                len: self.len,
+            }),
+        }
+    }
+
+    fn offsets(&self, index: usize) -> (usize, usize, u64) {
+        assert!(index < self.len, "index {} out of bounds", index);
+        let counters_per_bin = (u64::BITS / self.bits_per_counter) as usize;
+        let bitshift = (index % counters_per_bin) * self.bits_per_counter as usize;
+        let mask = ((1_u64 << self.bits_per_counter) - 1) << bitshift;
+        (index / counters_per_bin, bitshift, mask)
+    }
+}
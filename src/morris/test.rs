@@ -0,0 +1,85 @@
+use rand::{rngs::StdRng, SeedableRng};
+
+use super::*;
+
+#[test]
+fn test_morris_counter() {
+    let mut rng = StdRng::seed_from_u64(1);
+    let mut c = MorrisCounter::new();
+    assert_eq!(0.0, c.estimate());
+    c.increment_with_rng(&mut rng);
+    // the first increment always happens
+    assert_eq!(1, c.exponent());
+    assert_eq!(1.0, c.estimate());
+}
+
+#[test]
+fn test_morris_counter_unbiased() {
+    let mut rng = StdRng::seed_from_u64(2);
+    let trials = 200;
+    let n = 1000;
+    let mean = (0..trials)
+        .map(|_| {
+            let mut c = MorrisCounter::new();
+            (0..n).for_each(|_| c.increment_with_rng(&mut rng));
+            c.estimate()
+        })
+        .sum::<f64>()
+        / trials as f64;
+    assert!((mean - n as f64).abs() < n as f64 * 0.15, "{}", mean);
+}
+
+#[test]
+fn test_array() {
+    let mut rng = StdRng::seed_from_u64(3);
+    let mut arr = MorrisCounterArray::new(100, 4, 2.0).unwrap();
+    assert_eq!(100, arr.len());
+    for _ in 0..10_000 {
+        arr.increment_with_rng(42, &mut rng);
+    }
+    assert_eq!(0, arr.exponent(41));
+    assert_eq!(0, arr.exponent(43));
+    let estimate = arr.estimate(42);
+    assert!(estimate > 3_000.0 && estimate < 30_000.0, "{}", estimate);
+}
+
+#[test]
+fn test_array_saturates() {
+    let mut rng = StdRng::seed_from_u64(4);
+    let mut arr = MorrisCounterArray::new(3, 2, 1.01).unwrap();
+    for _ in 0..1000 {
+        arr.increment_with_rng(1, &mut rng);
+    }
+    assert_eq!(3, arr.exponent(1));
+}
+
+#[test]
+fn test_invalid_params() {
+    assert!(matches!(
+        MorrisCounterArray::new(0, 4, 2.0),
+        Err(Error::InvalidCounterCount(0))
+    ));
+    assert!(matches!(
+        MorrisCounterArray::new(1, 9, 2.0),
+        Err(Error::BitsPerCounterOutOfRange(9))
+    ));
+    assert!(matches!(
+        MorrisCounterArray::new(1, 4, 1.0),
+        Err(Error::InvalidBase(_))
+    ));
+}
+
+#[test]
+fn test_try_index() {
+    let mut arr = MorrisCounterArray::new(3, 4, 2.0).unwrap();
+    arr.try_increment(2).unwrap();
+    assert_eq!(1.0, arr.try_estimate(2).unwrap());
+    assert!(matches!(
+        arr.try_increment(3),
+        Err(Error::IndexOutOfBounds { index: 3, len: 3 })
+    ));
+    assert!(matches!(
+        arr.try_estimate(3),
+        Err(Error::IndexOutOfBounds { index: 3, len: 3 })
+    ));
+}
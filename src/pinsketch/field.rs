@@ -0,0 +1,194 @@
+// Arithmetic over GF(2^64), and polynomials with coefficients in GF(2^64).
+//
+// Polynomials are stored lowest degree first, without trailing zero coefficients, so the
+// zero polynomial is an empty Vec.
+
+// x^64 + x^4 + x^3 + x + 1, with the x^64 term implied
+const MODULUS: u64 = 0x1b;
+
+pub(super) fn mul(mut a: u64, mut b: u64) -> u64 {
+    let mut r = 0;
+    while b != 0 {
+        if b & 1 != 0 {
+            r ^= a;
+        }
+        b >>= 1;
+        let carry = a >> 63;
+        a <<= 1;
+        if carry != 0 {
+            a ^= MODULUS;
+        }
+    }
+    r
+}
+
+pub(super) fn inv(a: u64) -> u64 {
+    // a^(2^64 - 2), the multiplicative group has order 2^64 - 1
+    let (mut base, mut exp, mut r) = (a, u64::MAX - 1, 1);
+    while exp != 0 {
+        if exp & 1 != 0 {
+            r = mul(r, base);
+        }
+        base = mul(base, base);
+        exp >>= 1;
+    }
+    r
+}
+
+fn trim(mut p: Vec<u64>) -> Vec<u64> {
+    while p.last() == Some(&0) {
+        p.pop();
+    }
+    p
+}
+
+fn degree(p: &[u64]) -> usize {
+    p.len().saturating_sub(1)
+}
+
+fn add(a: &[u64], b: &[u64]) -> Vec<u64> {
+    let mut r = vec![0; a.len().max(b.len())];
+    for (i, v) in a.iter().enumerate() {
+        r[i] ^= v;
+    }
+    for (i, v) in b.iter().enumerate() {
+        r[i] ^= v;
+    }
+    trim(r)
+}
+
+fn poly_mul(a: &[u64], b: &[u64]) -> Vec<u64> {
+    if a.is_empty() || b.is_empty() {
+        return vec![];
+    }
+    let mut r = vec![0; a.len() + b.len() - 1];
+    for (i, &x) in a.iter().enumerate() {
+        for (j, &y) in b.iter().enumerate() {
+            r[i + j] ^= mul(x, y);
+        }
+    }
+    trim(r)
+}
+
+// Returns (quotient, remainder), `b` must not be zero.
+fn div_rem(a: &[u64], b: &[u64]) -> (Vec<u64>, Vec<u64>) {
+    let mut rem = a.to_vec();
+    if rem.len() < b.len() {
+        return (vec![], rem);
+    }
+    let lead_inv = inv(b[b.len() - 1]);
+    let mut quot = vec![0; rem.len() - b.len() + 1];
+    while rem.len() >= b.len() {
+        let shift = rem.len() - b.len();
+        let coef = mul(rem[rem.len() - 1], lead_inv);
+        quot[shift] = coef;
+        for (i, &v) in b.iter().enumerate() {
+            rem[shift + i] ^= mul(coef, v);
+        }
+        rem = trim(rem);
+    }
+    (trim(quot), rem)
+}
+
+fn gcd(a: &[u64], b: &[u64]) -> Vec<u64> {
+    let (mut a, mut b) = (a.to_vec(), b.to_vec());
+    while !b.is_empty() {
+        let (_, r) = div_rem(&a, &b);
+        a = b;
+        b = r;
+    }
+    a
+}
+
+/// Berlekamp-Massey, returning the connection polynomial of the syndromes `s`, where `s[0]` is
+/// the first syndrome.
+pub(super) fn berlekamp_massey(s: &[u64]) -> Vec<u64> {
+    let mut c = vec![1];
+    let mut b = vec![1];
+    let mut len = 0;
+    let mut shift = 1;
+    let mut last_discrepancy = 1;
+    for n in 0..s.len() {
+        let mut d = s[n];
+        for i in 1..=len.min(c.len() - 1) {
+            d ^= mul(c[i], s[n - i]);
+        }
+        if d == 0 {
+            shift += 1;
+            continue;
+        }
+        let coef = mul(d, inv(last_discrepancy));
+        let prev = c.clone();
+        c.resize(c.len().max(b.len() + shift), 0);
+        for (i, &v) in b.iter().enumerate() {
+            c[i + shift] ^= mul(coef, v);
+        }
+        if 2 * len <= n {
+            len = n + 1 - len;
+            b = prev;
+            last_discrepancy = d;
+            shift = 1;
+        } else {
+            shift += 1;
+        }
+    }
+    c.resize(len + 1, 0);
+    c
+}
+
+/// Finds the roots of `f`, returning None unless `f` has exactly degree(f) distinct roots.
+pub(super) fn roots(f: &[u64]) -> Option<Vec<u64>> {
+    let f = trim(f.to_vec());
+    if f.is_empty() {
+        return None;
+    }
+    // f splits into distinct linear factors iff it divides x^(2^64) - x
+    let x = vec![0, 1];
+    let frobenius = frobenius_powers(&f);
+    if !add(&frobenius[64], &div_rem(&x, &f).1).is_empty() {
+        return None;
+    }
+    let mut found = vec![];
+    split(&f, &mut found).then_some(found)
+}
+
+// x^(2^i) mod f for i in 0..=64
+fn frobenius_powers(f: &[u64]) -> Vec<Vec<u64>> {
+    let mut powers = vec![div_rem(&[0, 1], f).1];
+    for i in 0..64 {
+        let sq = poly_mul(&powers[i], &powers[i]);
+        powers.push(div_rem(&sq, f).1);
+    }
+    powers
+}
+
+// Berlekamp's trace algorithm: gcd(f, Tr(beta * x)) splits the roots of f by the trace of
+// beta * root, and some element of the basis {2^k} separates every pair of distinct roots.
+fn split(f: &[u64], found: &mut Vec<u64>) -> bool {
+    match degree(f) {
+        0 => return true,
+        1 => {
+            found.push(mul(f[0], inv(f[1])));
+            return true;
+        }
+        _ => {}
+    }
+    let powers = frobenius_powers(f);
+    for k in 0..64 {
+        let mut beta_pow = 1_u64 << k;
+        let mut trace = vec![];
+        for power in powers.iter().take(64) {
+            trace = add(
+                &trace,
+                &power.iter().map(|&c| mul(c, beta_pow)).collect::<Vec<_>>(),
+            );
+            beta_pow = mul(beta_pow, beta_pow);
+        }
+        let g = gcd(f, &trace);
+        if degree(&g) > 0 && degree(&g) < degree(f) {
+            let (h, _) = div_rem(f, &g);
+            return split(&g, found) && split(&h, found);
+        }
+    }
+    false
+}
@@ -0,0 +1,127 @@
+use thiserror::Error;
+
+mod field;
+#[cfg(test)]
+mod test;
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("Invalid capacity {0}: must be > 0")]
+    InvalidCapacity(usize),
+
+    #[error("Sketches have different capacities ({0} and {1})")]
+    CapacityMismatch(usize, usize),
+
+    #[error("Serialized sketch is truncated or malformed")]
+    Malformed,
+}
+
+/// Implementation of a [PinSketch](https://github.com/sipa/minisketch) set reconciliation
+/// sketch over 64 bit elements.
+///
+/// A sketch of capacity `d` stores the odd power sums x, x^3, ..., x^(2d-1) of its elements in
+/// GF(2^64), which is a BCH syndrome.  Merging two sketches yields the sketch of the symmetric
+/// difference of their sets, and [PinSketch::decode] recovers that difference exactly as long
+/// as it holds at most `d` elements.  The serialized sketch is `8 * d` bytes, independent of
+/// the size of the sets.
+///
+/// Elements must be non-zero, as 0 has no effect on the power sums.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PinSketch {
+    syndromes: Vec<u64>,
+}
+
+impl PinSketch {
+    /// Create an empty sketch able to recover up to `capacity` differences.
+    pub fn new(capacity: usize) -> Result<Self, Error> {
+        if capacity == 0 {
+            return Err(Error::InvalidCapacity(capacity));
+        }
+        Ok(PinSketch {
+            syndromes: vec![0; capacity],
+        })
+    }
+
+    /// Maximum number of differences this sketch can recover.
+    pub fn capacity(&self) -> usize {
+        self.syndromes.len()
+    }
+
+    /// Add an element to the sketch.  Adding the same element again removes it.
+    pub fn add(&mut self, element: u64) {
+        let square = field::mul(element, element);
+        let mut power = element;
+        for s in self.syndromes.iter_mut() {
+            *s ^= power;
+            power = field::mul(power, square);
+        }
+    }
+
+    /// Merge another sketch into this one, leaving the sketch of the symmetric difference.
+    pub fn merge(&mut self, other: &PinSketch) -> Result<(), Error> {
+        if self.capacity() != other.capacity() {
+            return Err(Error::CapacityMismatch(self.capacity(), other.capacity()));
+        }
+        for (s, o) in self.syndromes.iter_mut().zip(other.syndromes.iter()) {
+            *s ^= o;
+        }
+        Ok(())
+    }
+
+    /// Recover the elements in the sketch.  After a [PinSketch::merge] these are the elements
+    /// present in only one of the two sets.
+    ///
+    /// Returns None if the sketch holds more elements than its capacity.
+    pub fn decode(&self) -> Option<Vec<u64>> {
+        // even syndromes follow from the odd ones, as S(2k) = S(k)^2 in characteristic 2
+        let mut all = vec![0; 2 * self.capacity()];
+        for i in 0..all.len() {
+            all[i] = if i % 2 == 0 {
+                self.syndromes[i / 2]
+            } else {
+                field::mul(all[i / 2], all[i / 2])
+            };
+        }
+
+        let connection = field::berlekamp_massey(&all);
+        if connection.len() - 1 > self.capacity() {
+            return None;
+        }
+        // the roots of the reversed connection polynomial are the elements
+        let locator = connection.into_iter().rev().collect::<Vec<_>>();
+        let mut elements = field::roots(&locator)?;
+        if elements.contains(&0) {
+            return None;
+        }
+
+        // more differences than the capacity can decode to the wrong set, so check the result
+        let mut check = PinSketch::new(self.capacity()).ok()?;
+        elements.iter().for_each(|&e| check.add(e));
+        if check != *self {
+            return None;
+        }
+        elements.sort_unstable();
+        Some(elements)
+    }
+
+    /// Serialize the sketch.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.syndromes
+            .iter()
+            .flat_map(|s| s.to_le_bytes())
+            .collect()
+    }
+
+    /// Deserialize a sketch produced by [PinSketch::to_bytes].
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        if bytes.is_empty() || !bytes.len().is_multiple_of(8) {
+            return Err(Error::Malformed);
+        }
+        Ok(PinSketch {
+            syndromes: bytes
+                .chunks_exact(8)
+                .map(|c| u64::from_le_bytes(c.try_into().unwrap()))
+                .collect(),
+        })
+    }
+}
@@ -0,0 +1,78 @@
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use super::*;
+
+#[test]
+fn test_field() {
+    let mut rng = StdRng::seed_from_u64(1);
+    for _ in 0..100 {
+        let (a, b, c) = (rng.gen::<u64>(), rng.gen::<u64>(), rng.gen::<u64>());
+        assert_eq!(field::mul(a, b), field::mul(b, a));
+        assert_eq!(field::mul(a, b ^ c), field::mul(a, b) ^ field::mul(a, c));
+        if a != 0 {
+            assert_eq!(1, field::mul(a, field::inv(a)));
+        }
+    }
+}
+
+#[test]
+fn test_reconcile() {
+    let mut rng = StdRng::seed_from_u64(2);
+    let shared = (0..1000).map(|_| rng.gen::<u64>()).collect::<Vec<_>>();
+    let only_a = (0..7).map(|_| rng.gen::<u64>()).collect::<Vec<_>>();
+    let only_b = (0..5).map(|_| rng.gen::<u64>()).collect::<Vec<_>>();
+
+    let mut a = PinSketch::new(16).unwrap();
+    let mut b = PinSketch::new(16).unwrap();
+    shared.iter().chain(only_a.iter()).for_each(|&e| a.add(e));
+    shared.iter().chain(only_b.iter()).for_each(|&e| b.add(e));
+
+    // ship a to b over the wire
+    let mut diff = PinSketch::from_bytes(&a.to_bytes()).unwrap();
+    diff.merge(&b).unwrap();
+    let mut expected = only_a
+        .iter()
+        .chain(only_b.iter())
+        .copied()
+        .collect::<Vec<_>>();
+    expected.sort_unstable();
+    assert_eq!(Some(expected), diff.decode());
+}
+
+#[test]
+fn test_decode_edges() {
+    let mut s = PinSketch::new(4).unwrap();
+    assert_eq!(Some(vec![]), s.decode());
+    s.add(1);
+    assert_eq!(Some(vec![1]), s.decode());
+    s.add(1);
+    assert_eq!(Some(vec![]), s.decode());
+    for e in [u64::MAX, 2, 3, 4] {
+        s.add(e);
+    }
+    assert_eq!(Some(vec![2, 3, 4, u64::MAX]), s.decode());
+}
+
+#[test]
+fn test_over_capacity() {
+    let mut rng = StdRng::seed_from_u64(3);
+    let mut s = PinSketch::new(4).unwrap();
+    for _ in 0..9 {
+        s.add(rng.gen());
+    }
+    assert_eq!(None, s.decode());
+}
+
+#[test]
+fn test_errors() {
+    assert!(matches!(PinSketch::new(0), Err(Error::InvalidCapacity(0))));
+    let mut a = PinSketch::new(2).unwrap();
+    assert!(matches!(
+        a.merge(&PinSketch::new(3).unwrap()),
+        Err(Error::CapacityMismatch(2, 3))
+    ));
+    assert!(matches!(
+        PinSketch::from_bytes(&[0; 7]),
+        Err(Error::Malformed)
+    ));
+}
@@ -0,0 +1,35 @@
+/// Items looked ahead in batch queries.  Each has a few cache lines to fetch, and this many
+/// in flight covers a DRAM round trip without evicting lines before they're used.
+const DISTANCE: usize = 8;
+
+/// Hint that the cache line holding `value` will be read soon.  A no-op where there's no stable
+/// prefetch instruction.
+#[inline(always)]
+pub(crate) fn prefetch<T>(value: &T) {
+    #[cfg(target_arch = "x86_64")]
+    // SAFETY: a prefetch doesn't access memory, and sse is part of the x86_64 baseline
+    unsafe {
+        use std::arch::x86_64::{_mm_prefetch, _MM_HINT_T0};
+        _mm_prefetch::<_MM_HINT_T0>(value as *const T as *const i8);
+    }
+    #[cfg(not(target_arch = "x86_64"))]
+    let _ = value;
+}
+
+/// Apply `query` to each of `items`, calling `fetch` on the item [DISTANCE] ahead first, so
+/// the memory it needs is on its way by the time it's queried.
+pub(crate) fn pipeline<T, R, F, Q>(items: &[T], mut fetch: F, mut query: Q) -> Vec<R>
+where
+    F: FnMut(&T),
+    Q: FnMut(&T) -> R,
+{
+    items.iter().take(DISTANCE).for_each(&mut fetch);
+    (0..items.len())
+        .map(|i| {
+            if let Some(ahead) = items.get(i + DISTANCE) {
+                fetch(ahead);
+            }
+            query(&items[i])
+        })
+        .collect()
+}
@@ -0,0 +1,64 @@
+use std::mem;
+
+/// A [KLL](https://arxiv.org/abs/1603.05346) quantile sketch of one segment of a
+/// [super::WindowedQuantiles].  Level `h` holds values standing for `2^h` values each, and
+/// when a level outgrows its capacity it's sorted and every other value moves up a level.
+/// Capacities shrink by 2/3 per level below the top, so the sketch keeps about `3k` values.
+#[derive(Debug, Clone)]
+pub(super) struct Kll {
+    k: usize,
+    levels: Vec<Vec<f64>>,
+    // alternates which half of a compacted level moves up, so neither end is favored
+    odd: bool,
+}
+
+impl Kll {
+    pub(super) fn new(k: usize) -> Self {
+        Kll {
+            k,
+            levels: vec![Vec::new()],
+            odd: false,
+        }
+    }
+
+    pub(super) fn insert(&mut self, value: f64) {
+        self.levels[0].push(value);
+        self.compact();
+    }
+
+    /// Every retained value with the number of values it stands for.
+    pub(super) fn weighted(&self) -> impl Iterator<Item = (f64, u64)> + '_ {
+        self.levels
+            .iter()
+            .enumerate()
+            .flat_map(|(level, values)| values.iter().map(move |&value| (value, 1 << level)))
+    }
+
+    // Compact the lowest level over its capacity, if any.
+    fn compact(&mut self) {
+        let Some(level) =
+            (0..self.levels.len()).find(|&l| self.levels[l].len() >= self.capacity(l))
+        else {
+            return;
+        };
+        if level + 1 == self.levels.len() {
+            self.levels.push(Vec::new());
+        }
+        let mut values = mem::take(&mut self.levels[level]);
+        values.sort_by(f64::total_cmp);
+        let leftover = match values.len().is_multiple_of(2) {
+            true => None,
+            false => values.pop(),
+        };
+        let offset = usize::from(self.odd);
+        self.odd = !self.odd;
+        let promoted = values.iter().skip(offset).step_by(2);
+        self.levels[level + 1].extend(promoted);
+        self.levels[level].extend(leftover);
+    }
+
+    fn capacity(&self, level: usize) -> usize {
+        let depth = (self.levels.len() - 1 - level) as i32;
+        ((self.k as f64 * (2.0_f64 / 3.0).powi(depth)).ceil() as usize).max(2)
+    }
+}
@@ -0,0 +1,145 @@
+use std::collections::VecDeque;
+use thiserror::Error;
+
+use self::kll::Kll;
+
+mod kll;
+#[cfg(test)]
+mod test;
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("Invalid window {0}: must be > 0")]
+    InvalidWindow(u32),
+
+    #[error("Invalid segment count {0}: must be 0 < segments <= window")]
+    InvalidSegmentCount(usize),
+
+    #[error("Invalid k {0}: must be >= 8")]
+    InvalidK(usize),
+}
+
+const MIN_K: usize = 8;
+
+/// Quantiles of the values inserted over the last `window` time units, such as p50 and p99
+/// latency over the last minute, rather than over all time.
+///
+/// The window is split into a ring of segments, each summarizing the values inserted during
+/// it in a [KLL](https://arxiv.org/abs/1603.05346) sketch of about `3k` values, whose rank
+/// error is about `1.7 / k`.  Queries merge the sketches of the segments overlapping the
+/// window, so the window is rounded out to whole segments, and segments that fall out of it
+/// are dropped by inserts.
+///
+/// Time is whatever the caller passes as `now`, such as seconds since some epoch, and only
+/// needs to never go backwards.
+pub struct WindowedQuantiles {
+    window: u32,
+    span: u32,
+    k: usize,
+    // oldest first, each with the time it starts at, a multiple of span
+    segments: VecDeque<(u32, Kll)>,
+}
+
+impl WindowedQuantiles {
+    /// Create a summary of the last `window` time units, split into `segments` segments whose
+    /// sketches are sized by `k`.
+    ///
+    /// Fails unless `window` > 0, 0 < `segments` <= `window` and `k` >= 8.
+    pub fn new(window: u32, segments: usize, k: usize) -> Result<Self, Error> {
+        if window == 0 {
+            return Err(Error::InvalidWindow(window));
+        }
+        if segments == 0 || segments > window as usize {
+            return Err(Error::InvalidSegmentCount(segments));
+        }
+        if k < MIN_K {
+            return Err(Error::InvalidK(k));
+        }
+        Ok(WindowedQuantiles {
+            window,
+            span: window.div_ceil(segments as u32),
+            k,
+            segments: VecDeque::with_capacity(segments + 1),
+        })
+    }
+
+    /// Insert a value at time `now`.  NaN is ignored, as it has no rank.
+    pub fn insert(&mut self, value: f64, now: u32) {
+        if value.is_nan() {
+            return;
+        }
+        let start = now - now % self.span;
+        match self.segments.back_mut() {
+            Some((newest, sketch)) if *newest >= start => sketch.insert(value),
+            _ => {
+                let mut sketch = Kll::new(self.k);
+                sketch.insert(value);
+                self.segments.push_back((start, sketch));
+            }
+        }
+        while self
+            .segments
+            .front()
+            .is_some_and(|&(start, _)| !self.is_live(start, now))
+        {
+            self.segments.pop_front();
+        }
+    }
+
+    /// Estimate the `q` quantile of the values in the window at time `now`, `q` being clamped
+    /// to 0..=1, or None if the window is empty.
+    pub fn quantile(&self, q: f64, now: u32) -> Option<f64> {
+        self.quantiles(&[q], now).map(|values| values[0])
+    }
+
+    /// Estimate several quantiles at once, merging the segments once, as
+    /// [WindowedQuantiles::quantile] does for one.
+    pub fn quantiles(&self, qs: &[f64], now: u32) -> Option<Vec<f64>> {
+        let mut weighted = self.live(now).flat_map(Kll::weighted).collect::<Vec<_>>();
+        if weighted.is_empty() {
+            return None;
+        }
+        weighted.sort_by(|a, b| a.0.total_cmp(&b.0));
+        let total = weighted.iter().map(|&(_, weight)| weight).sum::<u64>();
+        let quantiles = qs
+            .iter()
+            .map(|&q| {
+                let rank = (q.clamp(0.0, 1.0) * total as f64).ceil().max(1.0) as u64;
+                let mut seen = 0;
+                weighted
+                    .iter()
+                    .find(|&&(_, weight)| {
+                        seen += weight;
+                        seen >= rank
+                    })
+                    .map_or(weighted[weighted.len() - 1].0, |&(value, _)| value)
+            })
+            .collect();
+        Some(quantiles)
+    }
+
+    /// Estimate the number of values in the window at time `now`.
+    pub fn count(&self, now: u32) -> u64 {
+        self.live(now)
+            .flat_map(Kll::weighted)
+            .map(|(_, weight)| weight)
+            .sum()
+    }
+
+    /// Length of the window.
+    pub fn window(&self) -> u32 {
+        self.window
+    }
+
+    fn live(&self, now: u32) -> impl Iterator<Item = &Kll> + '_ {
+        self.segments
+            .iter()
+            .filter(move |&&(start, _)| self.is_live(start, now))
+            .map(|(_, sketch)| sketch)
+    }
+
+    // Whether the segment starting at `start` overlaps the window ending at `now`.
+    fn is_live(&self, start: u32, now: u32) -> bool {
+        start.saturating_add(self.span) > now.saturating_sub(self.window)
+    }
+}
@@ -0,0 +1,70 @@
+use super::*;
+
+#[test]
+fn test_new() {
+    assert!(matches!(
+        WindowedQuantiles::new(0, 1, 200),
+        Err(Error::InvalidWindow(0))
+    ));
+    assert!(matches!(
+        WindowedQuantiles::new(60, 0, 200),
+        Err(Error::InvalidSegmentCount(0))
+    ));
+    assert!(matches!(
+        WindowedQuantiles::new(60, 61, 200),
+        Err(Error::InvalidSegmentCount(61))
+    ));
+    assert!(matches!(
+        WindowedQuantiles::new(60, 6, 4),
+        Err(Error::InvalidK(4))
+    ));
+}
+
+#[test]
+fn test_exact_when_small() {
+    let mut window = WindowedQuantiles::new(60, 6, 200).unwrap();
+    assert_eq!(None, window.quantile(0.5, 0));
+    for value in [5.0, 1.0, 4.0, 2.0, 3.0, f64::NAN] {
+        window.insert(value, 0);
+    }
+    assert_eq!(5, window.count(0));
+    assert_eq!(
+        Some(vec![1.0, 3.0, 5.0, 5.0]),
+        window.quantiles(&[0.0, 0.5, 0.99, 2.0], 0)
+    );
+}
+
+#[test]
+fn test_accuracy() {
+    let mut window = WindowedQuantiles::new(60, 6, 200).unwrap();
+    // a shuffled 0..100_000 over 50 time units
+    let n = 100_000_u64;
+    for i in 0..n {
+        let value = (i * 7919 % n) as f64;
+        window.insert(value, (i / 2000) as u32);
+    }
+    assert_eq!(n, window.count(49));
+    let quantiles = window.quantiles(&[0.5, 0.99], 49).unwrap();
+    assert!((quantiles[0] - 50_000.0).abs() < 2_000.0, "{quantiles:?}");
+    assert!((quantiles[1] - 99_000.0).abs() < 2_000.0, "{quantiles:?}");
+}
+
+#[test]
+fn test_sliding() {
+    let mut window = WindowedQuantiles::new(60, 6, 200).unwrap();
+    for i in 0..1000 {
+        window.insert(1000.0 + i as f64, 5);
+    }
+    for i in 0..1000 {
+        window.insert(i as f64, 100);
+    }
+    // the slow values fell out of the window, and their segment was dropped
+    assert_eq!(1, window.segments.len());
+    assert_eq!(1000, window.count(100));
+    let p99 = window.quantile(0.99, 100).unwrap();
+    assert!(p99 < 1000.0, "{p99}");
+
+    // the window is rounded out to the segment at 100..110
+    assert_eq!(1000, window.count(169));
+    assert_eq!(None, window.quantile(0.5, 170));
+}
@@ -0,0 +1,213 @@
+use rand::RngCore;
+use std::collections::HashMap;
+use thiserror::Error;
+
+use crate::{
+    hasher::SeedableHasher, BloomFilter, CountMinSketch, CountingBloomFilter, CuckooFilter,
+    MorrisCounter, TinyLfu,
+};
+
+#[cfg(test)]
+mod test;
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("A sketch named {0:?} is already registered")]
+    AlreadyRegistered(String),
+
+    #[error("No sketch named {0:?} is registered")]
+    NotFound(String),
+
+    #[error("Sketch {0:?} doesn't support serialization")]
+    SerializationUnsupported(String),
+}
+
+/// Uniform interface over the structures in this crate, used by [SketchRegistry].
+pub trait Sketch {
+    /// Record an item.
+    fn update(&mut self, item: &[u8]);
+
+    /// Query the sketch for an item.  Membership filters return 1.0 or 0.0, counting
+    /// structures return their estimate.
+    fn query(&self, item: &[u8]) -> f64;
+
+    /// Serialize the sketch, or None if it has no serialized form.
+    fn to_bytes(&self) -> Option<Vec<u8>> {
+        None
+    }
+}
+
+/// Owns many named sketches, so a metrics pipeline can update and query them by name.
+#[derive(Default)]
+pub struct SketchRegistry {
+    sketches: HashMap<String, Box<dyn Sketch + Send>>,
+}
+
+impl SketchRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a sketch under `name`.  Names must be unique.
+    pub fn register<S>(&mut self, name: impl Into<String>, sketch: S) -> Result<(), Error>
+    where
+        S: Sketch + Send + 'static,
+    {
+        let name = name.into();
+        if self.sketches.contains_key(&name) {
+            return Err(Error::AlreadyRegistered(name));
+        }
+        self.sketches.insert(name, Box::new(sketch));
+        Ok(())
+    }
+
+    /// Remove the sketch registered under `name`, returning it.
+    pub fn unregister(&mut self, name: &str) -> Option<Box<dyn Sketch + Send>> {
+        self.sketches.remove(name)
+    }
+
+    /// Record an item in the sketch registered under `name`.
+    pub fn update<I>(&mut self, name: &str, item: I) -> Result<(), Error>
+    where
+        I: AsRef<[u8]>,
+    {
+        self.get_mut(name)?.update(item.as_ref());
+        Ok(())
+    }
+
+    /// Query the sketch registered under `name`, see [Sketch::query].
+    pub fn query<I>(&self, name: &str, item: I) -> Result<f64, Error>
+    where
+        I: AsRef<[u8]>,
+    {
+        Ok(self.get(name)?.query(item.as_ref()))
+    }
+
+    /// Serialize the sketch registered under `name`.
+    pub fn to_bytes(&self, name: &str) -> Result<Vec<u8>, Error> {
+        self.get(name)?
+            .to_bytes()
+            .ok_or_else(|| Error::SerializationUnsupported(name.to_string()))
+    }
+
+    /// Get the sketch registered under `name`.
+    pub fn get(&self, name: &str) -> Result<&(dyn Sketch + Send), Error> {
+        self.sketches
+            .get(name)
+            .map(|s| s.as_ref())
+            .ok_or_else(|| Error::NotFound(name.to_string()))
+    }
+
+    /// Get the sketch registered under `name` for updates.
+    pub fn get_mut(&mut self, name: &str) -> Result<&mut (dyn Sketch + Send), Error> {
+        match self.sketches.get_mut(name) {
+            Some(s) => Ok(s.as_mut()),
+            None => Err(Error::NotFound(name.to_string())),
+        }
+    }
+
+    /// Iterate over the registered names, in arbitrary order.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.sketches.keys().map(String::as_str)
+    }
+
+    /// Number of registered sketches.
+    pub fn len(&self) -> usize {
+        self.sketches.len()
+    }
+
+    /// Returns true if no sketches are registered.
+    pub fn is_empty(&self) -> bool {
+        self.sketches.is_empty()
+    }
+}
+
+impl<T> Sketch for BloomFilter<T>
+where
+    T: SeedableHasher,
+{
+    fn update(&mut self, item: &[u8]) {
+        self.add(item);
+    }
+
+    fn query(&self, item: &[u8]) -> f64 {
+        self.contains(item) as u8 as f64
+    }
+
+    fn to_bytes(&self) -> Option<Vec<u8>> {
+        Some(BloomFilter::to_bytes(self))
+    }
+}
+
+impl<T> Sketch for CountingBloomFilter<T>
+where
+    T: SeedableHasher,
+{
+    fn update(&mut self, item: &[u8]) {
+        self.add(item);
+    }
+
+    fn query(&self, item: &[u8]) -> f64 {
+        self.estimate(item) as f64
+    }
+
+    fn to_bytes(&self) -> Option<Vec<u8>> {
+        Some(CountingBloomFilter::to_bytes(self))
+    }
+}
+
+impl<T> Sketch for CountMinSketch<T>
+where
+    T: SeedableHasher,
+{
+    fn update(&mut self, item: &[u8]) {
+        self.add(item);
+    }
+
+    fn query(&self, item: &[u8]) -> f64 {
+        self.estimate(item) as f64
+    }
+}
+
+impl<T, R> Sketch for CuckooFilter<T, R>
+where
+    T: SeedableHasher,
+    R: RngCore,
+{
+    // a full filter can't record the item, which a later query reports as absent
+    fn update(&mut self, item: &[u8]) {
+        let _ = self.add(item);
+    }
+
+    fn query(&self, item: &[u8]) -> f64 {
+        self.contains(item) as u8 as f64
+    }
+
+    fn to_bytes(&self) -> Option<Vec<u8>> {
+        Some(CuckooFilter::to_bytes(self))
+    }
+}
+
+impl<T> Sketch for TinyLfu<T>
+where
+    T: SeedableHasher,
+{
+    fn update(&mut self, item: &[u8]) {
+        self.increment(item);
+    }
+
+    fn query(&self, item: &[u8]) -> f64 {
+        self.estimate(item) as f64
+    }
+}
+
+/// A single counter ignores the item and counts every update.
+impl Sketch for MorrisCounter {
+    fn update(&mut self, _item: &[u8]) {
+        self.increment();
+    }
+
+    fn query(&self, _item: &[u8]) -> f64 {
+        self.estimate()
+    }
+}
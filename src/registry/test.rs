@@ -0,0 +1,97 @@
+use fasthash::{metro, murmur3};
+
+use super::*;
+use crate::Grafite;
+
+#[test]
+fn test_update_query() {
+    let mut registry = SketchRegistry::new();
+    registry
+        .register(
+            "seen",
+            BloomFilter::<metro::Hasher64_1>::new(1024, 3).unwrap(),
+        )
+        .unwrap();
+    registry
+        .register(
+            "hits",
+            CountingBloomFilter::<metro::Hasher64_1>::new(1024, 3).unwrap(),
+        )
+        .unwrap();
+    registry
+        .register("lfu", TinyLfu::<murmur3::Hasher32>::new(64).unwrap())
+        .unwrap();
+    registry
+        .register(
+            "cuckoo",
+            CuckooFilter::<murmur3::Hasher32>::new(64).unwrap(),
+        )
+        .unwrap();
+    registry.register("requests", MorrisCounter::new()).unwrap();
+    assert_eq!(5, registry.len());
+
+    for name in ["seen", "hits", "lfu", "cuckoo", "requests"] {
+        assert_eq!(0.0, registry.query(name, "/index").unwrap());
+        registry.update(name, "/index").unwrap();
+        registry.update(name, "/index").unwrap();
+    }
+    assert_eq!(1.0, registry.query("seen", "/index").unwrap());
+    assert_eq!(2.0, registry.query("hits", "/index").unwrap());
+    assert_eq!(2.0, registry.query("lfu", "/index").unwrap());
+    assert_eq!(1.0, registry.query("cuckoo", "/index").unwrap());
+    assert!(registry.query("requests", "").unwrap() >= 1.0);
+
+    let hits = registry.to_bytes("hits").unwrap();
+    let restored = CountingBloomFilter::<metro::Hasher64_1>::from_bytes(&hits).unwrap();
+    assert_eq!(2, restored.estimate("/index"));
+    for name in ["seen", "cuckoo"] {
+        assert!(registry.to_bytes(name).is_ok());
+    }
+
+    let mut names = registry.names().collect::<Vec<_>>();
+    names.sort();
+    assert_eq!(vec!["cuckoo", "hits", "lfu", "requests", "seen"], names);
+}
+
+#[test]
+fn test_errors() {
+    let mut registry = SketchRegistry::new();
+    registry.register("c", MorrisCounter::new()).unwrap();
+    assert!(matches!(
+        registry.register("c", MorrisCounter::new()),
+        Err(Error::AlreadyRegistered(_))
+    ));
+    assert!(matches!(
+        registry.update("missing", "x"),
+        Err(Error::NotFound(_))
+    ));
+    assert!(matches!(
+        registry.to_bytes("c"),
+        Err(Error::SerializationUnsupported(_))
+    ));
+    assert!(registry.unregister("c").is_some());
+    assert!(registry.is_empty());
+}
+
+// sketches from outside the crate can be registered too
+#[test]
+fn test_custom_sketch() {
+    struct Ranges(Grafite<metro::Hasher64_1>);
+    impl Sketch for Ranges {
+        fn update(&mut self, _item: &[u8]) {}
+        fn query(&self, item: &[u8]) -> f64 {
+            self.0
+                .contains(u64::from_le_bytes(item.try_into().unwrap())) as u8 as f64
+        }
+        fn to_bytes(&self) -> Option<Vec<u8>> {
+            Some(self.0.to_bytes())
+        }
+    }
+
+    let grafite = Grafite::new(&[7], 4, 0.01).unwrap();
+    let expected = grafite.to_bytes();
+    let mut registry = SketchRegistry::new();
+    registry.register("ranges", Ranges(grafite)).unwrap();
+    assert_eq!(1.0, registry.query("ranges", 7_u64.to_le_bytes()).unwrap());
+    assert_eq!(expected, registry.to_bytes("ranges").unwrap());
+}
@@ -0,0 +1,255 @@
+use std::{cmp::Reverse, collections::BinaryHeap, marker::PhantomData};
+
+use crate::hasher::SeedableHasher;
+
+#[cfg(test)]
+mod test;
+
+/// A single coded symbol of a rateless IBLT.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CodedSymbol {
+    sum: u64,
+    checksum: u64,
+    count: i64,
+}
+
+impl CodedSymbol {
+    fn apply(&mut self, element: u64, checksum: u64, count: i64) {
+        self.sum ^= element;
+        self.checksum ^= checksum;
+        self.count += count;
+    }
+
+    fn subtract(&mut self, other: &CodedSymbol) {
+        self.sum ^= other.sum;
+        self.checksum ^= other.checksum;
+        self.count -= other.count;
+    }
+
+    fn is_empty(&self) -> bool {
+        self.sum == 0 && self.checksum == 0 && self.count == 0
+    }
+
+    /// Serialize the symbol to 24 bytes.
+    pub fn to_bytes(&self) -> [u8; 24] {
+        let mut bytes = [0; 24];
+        bytes[..8].copy_from_slice(&self.sum.to_le_bytes());
+        bytes[8..16].copy_from_slice(&self.checksum.to_le_bytes());
+        bytes[16..].copy_from_slice(&self.count.to_le_bytes());
+        bytes
+    }
+
+    /// Deserialize a symbol produced by [CodedSymbol::to_bytes].
+    pub fn from_bytes(bytes: &[u8; 24]) -> Self {
+        let u64_at = |i: usize| u64::from_le_bytes(bytes[i..i + 8].try_into().unwrap());
+        CodedSymbol {
+            sum: u64_at(0),
+            checksum: u64_at(8),
+            count: u64_at(16) as i64,
+        }
+    }
+}
+
+// Generates the indexes of the coded symbols an element is mapped to.  Every element maps to
+// index 0, and to index i with probability 1 / (1 + i / 2).
+#[derive(Clone, Copy)]
+struct Mapping {
+    prng: u64,
+    index: u64,
+}
+
+impl Mapping {
+    fn new(checksum: u64) -> Self {
+        Mapping {
+            prng: checksum,
+            index: 0,
+        }
+    }
+
+    fn advance(&mut self) -> u64 {
+        self.prng = self.prng.wrapping_mul(0xda94_2042_e4dd_58b5);
+        let r = ((self.prng >> 32) as f64 + 1.0) / (1_u64 << 32) as f64;
+        let gap = ((self.index as f64 + 1.5) * (1.0 / r.sqrt() - 1.0)).ceil() as u64;
+        self.index += gap.max(1);
+        self.index
+    }
+}
+
+struct Item {
+    element: u64,
+    checksum: u64,
+    count: i64,
+    mapping: Mapping,
+}
+
+/// Produces an unbounded stream of coded symbols for a set of 64 bit elements, following
+/// [rateless IBLTs](https://arxiv.org/abs/2402.02668).
+///
+/// Unlike a regular IBLT, the number of symbols doesn't need to be chosen up front: the sender
+/// keeps calling [RatelessEncoder::next_symbol] until the receiver's [RatelessDecoder] reports
+/// that it decoded the difference.  On average about 1.35 symbols are needed per element in
+/// the symmetric difference.
+pub struct RatelessEncoder<T>
+where
+    T: SeedableHasher,
+{
+    items: Vec<Item>,
+    queue: BinaryHeap<Reverse<(u64, usize)>>,
+    produced: u64,
+    _hasher: PhantomData<T>,
+}
+
+impl<T> Default for RatelessEncoder<T>
+where
+    T: SeedableHasher,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> RatelessEncoder<T>
+where
+    T: SeedableHasher,
+{
+    pub fn new() -> Self {
+        RatelessEncoder {
+            items: vec![],
+            queue: BinaryHeap::new(),
+            produced: 0,
+            _hasher: PhantomData,
+        }
+    }
+
+    /// Add an element to the set.  Elements should be added before any symbols are produced,
+    /// as symbols that were already sent won't include it.
+    pub fn add(&mut self, element: u64) {
+        self.add_with_count(element, 1);
+    }
+
+    fn add_with_count(&mut self, element: u64, count: i64) {
+        let checksum = Self::checksum(element);
+        let mut mapping = Mapping::new(checksum);
+        while mapping.index < self.produced {
+            mapping.advance();
+        }
+        self.queue.push(Reverse((mapping.index, self.items.len())));
+        self.items.push(Item {
+            element,
+            checksum,
+            count,
+            mapping,
+        });
+    }
+
+    /// Produce the next coded symbol.
+    pub fn next_symbol(&mut self) -> CodedSymbol {
+        let mut symbol = CodedSymbol::default();
+        while let Some(&Reverse((index, slot))) = self.queue.peek() {
+            if index != self.produced {
+                break;
+            }
+            self.queue.pop();
+            let item = &mut self.items[slot];
+            symbol.apply(item.element, item.checksum, item.count);
+            self.queue.push(Reverse((item.mapping.advance(), slot)));
+        }
+        self.produced += 1;
+        symbol
+    }
+
+    fn checksum(element: u64) -> u64 {
+        let mut h = T::new();
+        h.write(&element.to_le_bytes());
+        h.finish()
+    }
+}
+
+/// Decodes the symmetric difference between a local set and the set behind a remote
+/// [RatelessEncoder], one coded symbol at a time.
+pub struct RatelessDecoder<T>
+where
+    T: SeedableHasher,
+{
+    local: RatelessEncoder<T>,
+    // elements that were already peeled, so they can be removed from later symbols
+    recovered: RatelessEncoder<T>,
+    symbols: Vec<CodedSymbol>,
+    remote_only: Vec<u64>,
+    local_only: Vec<u64>,
+}
+
+impl<T> RatelessDecoder<T>
+where
+    T: SeedableHasher,
+{
+    /// Create a decoder for the local set held by `local`, which must not have produced any
+    /// symbols yet.
+    pub fn new(local: RatelessEncoder<T>) -> Self {
+        RatelessDecoder {
+            local,
+            recovered: RatelessEncoder::new(),
+            symbols: vec![],
+            remote_only: vec![],
+            local_only: vec![],
+        }
+    }
+
+    /// Add the next coded symbol received from the remote encoder, and peel whatever can be
+    /// decoded with it.
+    pub fn add_symbol(&mut self, remote: CodedSymbol) {
+        let mut symbol = remote;
+        symbol.subtract(&self.local.next_symbol());
+        symbol.subtract(&self.recovered.next_symbol());
+        self.symbols.push(symbol);
+
+        let mut pending = vec![self.symbols.len() - 1];
+        while let Some(i) = pending.pop() {
+            let symbol = self.symbols[i];
+            if !self.is_pure(&symbol) {
+                continue;
+            }
+            if symbol.count == 1 {
+                self.remote_only.push(symbol.sum);
+            } else {
+                self.local_only.push(symbol.sum);
+            }
+
+            // remove the element from every symbol it was mapped to
+            let mut mapping = Mapping::new(symbol.checksum);
+            let mut index = 0;
+            while index < self.symbols.len() as u64 {
+                let s = &mut self.symbols[index as usize];
+                s.apply(symbol.sum, symbol.checksum, -symbol.count);
+                pending.push(index as usize);
+                index = mapping.advance();
+            }
+            self.recovered.add_with_count(symbol.sum, symbol.count);
+        }
+    }
+
+    /// Returns true once the whole difference has been decoded.
+    pub fn is_decoded(&self) -> bool {
+        self.symbols.first().is_some_and(CodedSymbol::is_empty)
+    }
+
+    /// Number of symbols received so far.
+    pub fn symbols_received(&self) -> usize {
+        self.symbols.len()
+    }
+
+    /// Elements only present in the remote set.
+    pub fn remote_only(&self) -> &[u64] {
+        &self.remote_only
+    }
+
+    /// Elements only present in the local set.
+    pub fn local_only(&self) -> &[u64] {
+        &self.local_only
+    }
+
+    fn is_pure(&self, symbol: &CodedSymbol) -> bool {
+        (symbol.count == 1 || symbol.count == -1)
+            && symbol.checksum == RatelessEncoder::<T>::checksum(symbol.sum)
+    }
+}
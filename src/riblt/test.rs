@@ -0,0 +1,83 @@
+use fasthash::metro;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use super::*;
+
+fn reconcile(
+    local: &[u64],
+    remote: &[u64],
+    max_symbols: usize,
+) -> RatelessDecoder<metro::Hasher64_1> {
+    let mut local_enc = RatelessEncoder::<metro::Hasher64_1>::new();
+    local.iter().for_each(|&e| local_enc.add(e));
+    let mut remote_enc = RatelessEncoder::<metro::Hasher64_1>::new();
+    remote.iter().for_each(|&e| remote_enc.add(e));
+
+    let mut decoder = RatelessDecoder::new(local_enc);
+    while !decoder.is_decoded() && decoder.symbols_received() < max_symbols {
+        let bytes = remote_enc.next_symbol().to_bytes();
+        decoder.add_symbol(CodedSymbol::from_bytes(&bytes));
+    }
+    decoder
+}
+
+#[test]
+fn test_reconcile() {
+    let mut rng = StdRng::seed_from_u64(5);
+    let shared = (0..2000).map(|_| rng.gen::<u64>()).collect::<Vec<_>>();
+    let only_local = (0..40).map(|_| rng.gen::<u64>()).collect::<Vec<_>>();
+    let only_remote = (0..60).map(|_| rng.gen::<u64>()).collect::<Vec<_>>();
+
+    let local = shared
+        .iter()
+        .chain(&only_local)
+        .copied()
+        .collect::<Vec<_>>();
+    let remote = shared
+        .iter()
+        .chain(&only_remote)
+        .copied()
+        .collect::<Vec<_>>();
+    let decoder = reconcile(&local, &remote, 1000);
+    assert!(decoder.is_decoded());
+    // overhead should be a small multiple of the difference
+    assert!(
+        decoder.symbols_received() < 300,
+        "{}",
+        decoder.symbols_received()
+    );
+
+    let mut got = decoder.local_only().to_vec();
+    got.sort_unstable();
+    let mut expected = only_local.clone();
+    expected.sort_unstable();
+    assert_eq!(expected, got);
+
+    let mut got = decoder.remote_only().to_vec();
+    got.sort_unstable();
+    let mut expected = only_remote.clone();
+    expected.sort_unstable();
+    assert_eq!(expected, got);
+}
+
+#[test]
+fn test_identical_sets() {
+    let set = (1..100).collect::<Vec<u64>>();
+    let decoder = reconcile(&set, &set, 10);
+    assert!(decoder.is_decoded());
+    assert_eq!(1, decoder.symbols_received());
+    assert!(decoder.local_only().is_empty());
+    assert!(decoder.remote_only().is_empty());
+}
+
+#[test]
+fn test_mapping_always_includes_first_symbol() {
+    let mut enc = RatelessEncoder::<metro::Hasher64_1>::new();
+    for e in 0..50 {
+        enc.add(e);
+    }
+    assert_eq!(50, enc.next_symbol().count);
+    // density decreases as the index grows
+    let later = (0..100).map(|_| enc.next_symbol().count).sum::<i64>();
+    assert!(later < 50 * 100 / 2, "{}", later);
+}
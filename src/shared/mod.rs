@@ -0,0 +1,155 @@
+use std::sync::{Arc, RwLock};
+
+use fasthash::{metro::Hash64_1, FastHash};
+use thiserror::Error;
+
+use crate::{aligned::CachePadded, hasher::fastrange, Sketch};
+
+#[cfg(test)]
+mod test;
+
+// shards are picked with a hash independent of the ones the filters use
+const SHARD_SEED: u32 = 0x85eb_ca6b;
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("Invalid shard count {0}: must be > 0")]
+    InvalidShardCount(usize),
+}
+
+/// A cheaply cloned handle to a filter shared between threads or async tasks, for services
+/// that query and insert from many tasks at once.
+///
+/// The filter is split into independent shards, each behind its own read-write lock, and every
+/// item is routed to one shard by a hash of its bytes.  Queries take a read lock on one shard,
+/// so they only wait for an insert into the same shard, and inserts only block operations on
+/// that shard.  Each lock is held for a single update or query and never across an `.await`,
+/// so the handle can be used from async code without an async lock, and contention stays low
+/// as long as there are a few times more shards than concurrent writers.
+///
+/// Each shard holds a separate filter, so every shard should be sized for its share of the
+/// expected items.  By default, shards' locks are padded to separate cache lines, so taking the
+/// lock of one shard doesn't slow down threads using its neighbours.
+pub struct SharedFilter<S> {
+    shards: Arc<Shards<S>>,
+}
+
+// the shards' locks, each on a cache line of its own or packed together
+enum Shards<S> {
+    Padded(Box<[CachePadded<RwLock<S>>]>),
+    Packed(Box<[RwLock<S>]>),
+}
+
+impl<S> Shards<S> {
+    fn len(&self) -> usize {
+        match self {
+            Shards::Padded(shards) => shards.len(),
+            Shards::Packed(shards) => shards.len(),
+        }
+    }
+
+    fn get(&self, i: usize) -> &RwLock<S> {
+        match self {
+            Shards::Padded(shards) => &shards[i],
+            Shards::Packed(shards) => &shards[i],
+        }
+    }
+}
+
+impl<S> SharedFilter<S>
+where
+    S: Sketch,
+{
+    /// Create a handle over `num_shards` filters built by `new_shard`.
+    pub fn new<F>(num_shards: usize, new_shard: F) -> Result<Self, Error>
+    where
+        F: FnMut() -> S,
+    {
+        Self::with_padding(num_shards, true, new_shard)
+    }
+
+    /// Create a handle as [SharedFilter::new] does, with the shards' locks padded to cache lines
+    /// only if `padded`.  Packed locks save 64 bytes or so per shard, at the cost of contention
+    /// between threads using neighbouring shards.
+    pub fn with_padding<F>(num_shards: usize, padded: bool, mut new_shard: F) -> Result<Self, Error>
+    where
+        F: FnMut() -> S,
+    {
+        Self::from_shards_with_padding((0..num_shards).map(|_| new_shard()).collect(), padded)
+    }
+
+    /// Create a handle over existing filters, one per shard.
+    pub fn from_shards(shards: Vec<S>) -> Result<Self, Error> {
+        Self::from_shards_with_padding(shards, true)
+    }
+
+    fn from_shards_with_padding(shards: Vec<S>, padded: bool) -> Result<Self, Error> {
+        if shards.is_empty() {
+            return Err(Error::InvalidShardCount(0));
+        }
+        let locks = shards.into_iter().map(RwLock::new);
+        let shards = match padded {
+            true => Shards::Padded(locks.map(CachePadded).collect()),
+            false => Shards::Packed(locks.collect()),
+        };
+        Ok(SharedFilter {
+            shards: Arc::new(shards),
+        })
+    }
+
+    /// Record an item, see [Sketch::update].
+    pub fn update<I>(&self, item: I)
+    where
+        I: AsRef<[u8]>,
+    {
+        let item = item.as_ref();
+        self.shard(item).write().unwrap().update(item);
+    }
+
+    /// Query the filter for an item, see [Sketch::query].
+    pub fn query<I>(&self, item: I) -> f64
+    where
+        I: AsRef<[u8]>,
+    {
+        let item = item.as_ref();
+        self.shard(item).read().unwrap().query(item)
+    }
+
+    /// Returns true if a query for the item returns a non-zero value.
+    pub fn contains<I>(&self, item: I) -> bool
+    where
+        I: AsRef<[u8]>,
+    {
+        self.query(item) > 0.0
+    }
+
+    /// Number of shards.
+    pub fn num_shards(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// Run `f` on every shard in turn, such as to serialize them, holding a read lock on one
+    /// shard at a time.
+    pub fn for_each_shard<F>(&self, mut f: F)
+    where
+        F: FnMut(&S),
+    {
+        for i in 0..self.shards.len() {
+            f(&self.shards.get(i).read().unwrap());
+        }
+    }
+
+    fn shard(&self, item: &[u8]) -> &RwLock<S> {
+        let hash = Hash64_1::hash_with_seed(item, SHARD_SEED);
+        self.shards
+            .get(fastrange(hash, self.shards.len() as u64) as usize)
+    }
+}
+
+impl<S> Clone for SharedFilter<S> {
+    fn clone(&self) -> Self {
+        SharedFilter {
+            shards: Arc::clone(&self.shards),
+        }
+    }
+}
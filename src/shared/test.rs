@@ -0,0 +1,77 @@
+use std::thread;
+
+use fasthash::metro;
+
+use super::{Error, SharedFilter};
+use crate::{BloomFilter, CountingBloomFilter};
+
+#[test]
+fn test_shared_filter() {
+    let filter = SharedFilter::new(8, || {
+        BloomFilter::<metro::Hasher64_1>::new(10_000, 3).unwrap()
+    })
+    .unwrap();
+    let handles: Vec<_> = (0..4_u64)
+        .map(|t| {
+            let filter = filter.clone();
+            thread::spawn(move || {
+                for i in 0..1_000_u64 {
+                    filter.update((t * 1_000 + i).to_le_bytes());
+                }
+            })
+        })
+        .collect();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+    for i in 0..4_000_u64 {
+        assert!(filter.contains(i.to_le_bytes()), "{}", i);
+    }
+
+    let mut shards = 0;
+    filter.for_each_shard(|_| shards += 1);
+    assert_eq!(8, shards);
+}
+
+#[test]
+fn test_shared_filter_unpadded() {
+    let filter = SharedFilter::with_padding(4, false, || {
+        BloomFilter::<metro::Hasher64_1>::new(10_000, 3).unwrap()
+    })
+    .unwrap();
+    for i in 0..1_000_u64 {
+        filter.update(i.to_le_bytes());
+    }
+    for i in 0..1_000_u64 {
+        assert!(filter.contains(i.to_le_bytes()), "{}", i);
+    }
+    assert_eq!(4, filter.num_shards());
+    assert!(matches!(
+        SharedFilter::with_padding(0, false, || {
+            BloomFilter::<metro::Hasher64_1>::new(10, 3).unwrap()
+        }),
+        Err(Error::InvalidShardCount(0))
+    ));
+}
+
+#[test]
+fn test_shared_filter_counts() {
+    let shards = (0..4)
+        .map(|_| CountingBloomFilter::<metro::Hasher64_1>::new(1024, 3).unwrap())
+        .collect();
+    let filter = SharedFilter::from_shards(shards).unwrap();
+    filter.update("twice");
+    filter.update("twice");
+    assert_eq!(2.0, filter.query("twice"));
+    assert!(!filter.contains("never"));
+}
+
+#[test]
+fn test_shared_filter_invalid() {
+    let filter = SharedFilter::new(0, || BloomFilter::<metro::Hasher64_1>::new(10, 1).unwrap());
+    assert!(matches!(filter, Err(Error::InvalidShardCount(0))));
+    assert!(matches!(
+        SharedFilter::<BloomFilter<metro::Hasher64_1>>::from_shards(vec![]),
+        Err(Error::InvalidShardCount(0))
+    ));
+}
@@ -0,0 +1,116 @@
+//! Sizing formulas for bloom and cuckoo filters, for planning capacity without building one.
+use std::f64::consts::LN_2;
+use thiserror::Error;
+
+use crate::cuckoo;
+
+#[cfg(test)]
+mod test;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum Error {
+    #[error("Invalid capacity {0}: must be > 0")]
+    InvalidCapacity(usize),
+
+    #[error("Invalid bit count {0}: must be > 0")]
+    InvalidBitCount(usize),
+
+    #[error("Invalid false positive probability {0}: must be 0 < fpp < 1")]
+    InvalidFpp(f64),
+
+    #[error("Invalid bucket size {0}: must be > 0")]
+    InvalidBucketSize(usize),
+
+    #[error("Filter is too large: more than usize::MAX bits or bins")]
+    TooLarge,
+}
+
+/// Bins of a cuckoo filter, from [cuckoo_slots].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CuckooSlots {
+    /// Number of bins, a power of two, as [crate::CuckooFilter] rounds it up to one.
+    pub num_bins: usize,
+    /// Entries each bin holds.
+    pub entries_per_bin: usize,
+    /// Bits of each entry's fingerprint.
+    pub fingerprint_bits: u32,
+}
+
+impl CuckooSlots {
+    /// Entries the filter has room for.
+    pub fn slots(&self) -> usize {
+        self.num_bins.saturating_mul(self.entries_per_bin)
+    }
+
+    /// Bits the filter's fingerprints take.
+    pub fn mem_bits(&self) -> u64 {
+        self.slots() as u64 * self.fingerprint_bits as u64
+    }
+}
+
+/// Bits a bloom filter needs to hold `n` entries with a false positive probability of `fpp`:
+/// `m = -n ln(fpp) / ln(2)^2`.
+pub fn optimal_bits(n: usize, fpp: f64) -> Result<usize, Error> {
+    if n == 0 {
+        return Err(Error::InvalidCapacity(n));
+    }
+    check_fpp(fpp)?;
+    let bits = (-(n as f64) * fpp.ln() / (LN_2 * LN_2)).ceil();
+    match bits < usize::MAX as f64 {
+        true => Ok(bits as usize),
+        false => Err(Error::TooLarge),
+    }
+}
+
+/// Hashes that minimize the false positive probability of a bloom filter of `m` bits holding
+/// `n` entries: `k = m / n ln(2)`, at least 1 and at most `m`.
+pub fn optimal_hashes(m: usize, n: usize) -> Result<u32, Error> {
+    if m == 0 {
+        return Err(Error::InvalidBitCount(m));
+    }
+    if n == 0 {
+        return Err(Error::InvalidCapacity(n));
+    }
+    let hashes = ((m as f64 / n as f64) * LN_2).round().max(1.0) as u32;
+    Ok(hashes.min(m.try_into().unwrap_or(u32::MAX)))
+}
+
+/// Bins a cuckoo filter with `bucket` entries per bin needs to hold `n` entries, and the
+/// fingerprint bits that keep its false positive probability at `fpp` once it does.
+///
+/// Bins are only filled to the load factor the cuckoo filter paper measured for their size,
+/// 50% for 1 entry, 84% for 2, 95% for 4 and 98% for 8 or more, and fingerprints are limited
+/// to 4 to 32 bits.  A filter always has at least one bin, even for no entries.
+pub fn cuckoo_slots(n: usize, fpp: f64, bucket: usize) -> Result<CuckooSlots, Error> {
+    check_fpp(fpp)?;
+    let num_bins = (n as f64 / (bucket as f64 * max_load(bucket)?)).ceil();
+    if num_bins >= usize::MAX as f64 {
+        return Err(Error::TooLarge);
+    }
+    let num_bins = (num_bins as usize).max(1);
+    Ok(CuckooSlots {
+        num_bins: num_bins
+            .checked_next_power_of_two()
+            .ok_or(Error::TooLarge)?,
+        entries_per_bin: bucket,
+        fingerprint_bits: cuckoo::fingerprint_bits_for_fpp(fpp, bucket),
+    })
+}
+
+// Highest load factor cuckoo filters with `bucket` entries per bin reach.
+fn max_load(bucket: usize) -> Result<f64, Error> {
+    match bucket {
+        0 => Err(Error::InvalidBucketSize(bucket)),
+        1 => Ok(0.5),
+        2 | 3 => Ok(0.84),
+        4..=7 => Ok(0.95),
+        _ => Ok(0.98),
+    }
+}
+
+fn check_fpp(fpp: f64) -> Result<(), Error> {
+    match fpp > 0.0 && fpp < 1.0 {
+        true => Ok(()),
+        false => Err(Error::InvalidFpp(fpp)),
+    }
+}
@@ -0,0 +1,43 @@
+use super::*;
+
+#[test]
+fn test_optimal_bits() {
+    assert_eq!(Ok(9586), optimal_bits(1000, 0.01));
+    assert_eq!(Ok(1438), optimal_bits(100, 0.001));
+    assert_eq!(Err(Error::InvalidCapacity(0)), optimal_bits(0, 0.01));
+    assert_eq!(Err(Error::InvalidFpp(1.0)), optimal_bits(1000, 1.0));
+    assert_eq!(Err(Error::TooLarge), optimal_bits(usize::MAX, 1e-300));
+}
+
+#[test]
+fn test_optimal_hashes() {
+    assert_eq!(Ok(7), optimal_hashes(9586, 1000));
+    assert_eq!(Ok(1), optimal_hashes(10, 1000));
+    assert_eq!(Ok(1), optimal_hashes(1, 1));
+    assert_eq!(Err(Error::InvalidBitCount(0)), optimal_hashes(0, 1000));
+    assert_eq!(Err(Error::InvalidCapacity(0)), optimal_hashes(1000, 0));
+}
+
+#[test]
+fn test_cuckoo_slots() {
+    let slots = cuckoo_slots(1000, 0.01, 4).unwrap();
+    assert_eq!(
+        CuckooSlots {
+            num_bins: 512,
+            entries_per_bin: 4,
+            fingerprint_bits: 10
+        },
+        slots
+    );
+    assert_eq!(2048, slots.slots());
+    assert_eq!(20480, slots.mem_bits());
+    // single entry bins only fill halfway
+    assert_eq!(2048, cuckoo_slots(1000, 0.01, 1).unwrap().num_bins);
+    assert_eq!(1, cuckoo_slots(0, 0.01, 4).unwrap().num_bins);
+    assert_eq!(
+        Err(Error::InvalidBucketSize(0)),
+        cuckoo_slots(1000, 0.01, 0)
+    );
+    assert_eq!(Err(Error::InvalidFpp(0.0)), cuckoo_slots(1000, 0.0, 4));
+    assert_eq!(Err(Error::TooLarge), cuckoo_slots(usize::MAX, 0.01, 1));
+}
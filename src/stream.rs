@@ -0,0 +1,85 @@
+use std::io::{self, Read, Write};
+
+/// Bytes buffered at a time when streaming a filter, so its size doesn't matter.
+const CHUNK_LEN: usize = 64 * 1024;
+
+/// Write `words` as little endian u64s, a chunk at a time.
+pub(crate) fn write_words<W, I>(writer: &mut W, words: I) -> io::Result<()>
+where
+    W: Write,
+    I: IntoIterator<Item = u64>,
+{
+    let mut buf = Vec::with_capacity(CHUNK_LEN);
+    for word in words {
+        buf.extend_from_slice(&word.to_le_bytes());
+        if buf.len() == CHUNK_LEN {
+            writer.write_all(&buf)?;
+            buf.clear();
+        }
+    }
+    writer.write_all(&buf)
+}
+
+/// Read `len` little endian u64s, a chunk at a time, passing each to `f`.
+pub(crate) fn read_words<R, F>(reader: &mut R, len: usize, mut f: F) -> io::Result<()>
+where
+    R: Read,
+    F: FnMut(u64),
+{
+    let mut buf = vec![0; CHUNK_LEN.min(len.saturating_mul(8))];
+    let mut remaining = len;
+    while remaining > 0 {
+        let chunk = &mut buf[..remaining.min(CHUNK_LEN / 8) * 8];
+        reader.read_exact(chunk)?;
+        for word in chunk.chunks_exact(8) {
+            f(u64::from_le_bytes(word.try_into().unwrap()));
+        }
+        remaining -= chunk.len() / 8;
+    }
+    Ok(())
+}
+
+/// Read `len` little endian u64s from each of `readers`, combine the words at each position
+/// with `merge` and write the results, a chunk at a time.
+pub(crate) fn merge_words<R, W, F>(
+    readers: &mut [R],
+    writer: &mut W,
+    len: usize,
+    merge: F,
+) -> io::Result<()>
+where
+    R: Read,
+    W: Write,
+    F: Fn(u64, u64) -> u64,
+{
+    let mut merged = vec![0; CHUNK_LEN.min(len.saturating_mul(8))];
+    let mut buf = merged.clone();
+    let mut remaining = len;
+    while remaining > 0 {
+        let chunk_len = remaining.min(CHUNK_LEN / 8) * 8;
+        let (merged, buf) = (&mut merged[..chunk_len], &mut buf[..chunk_len]);
+        let (first, rest) = readers.split_first_mut().expect("at least one reader");
+        first.read_exact(merged)?;
+        for reader in rest {
+            reader.read_exact(buf)?;
+            for (a, b) in merged.chunks_exact_mut(8).zip(buf.chunks_exact(8)) {
+                let a_word = u64::from_le_bytes((*a).try_into().unwrap());
+                let b_word = u64::from_le_bytes(b.try_into().unwrap());
+                a.copy_from_slice(&merge(a_word, b_word).to_le_bytes());
+            }
+        }
+        writer.write_all(merged)?;
+        remaining -= chunk_len / 8;
+    }
+    Ok(())
+}
+
+/// Read a fixed size header.
+pub(crate) fn read_array<R, const N: usize>(reader: &mut R) -> io::Result<[u8; N]>
+where
+    R: Read,
+{
+    let mut buf = [0; N];
+    reader.read_exact(&mut buf)?;
+    Ok(buf)
+}
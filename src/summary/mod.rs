@@ -0,0 +1,132 @@
+use thiserror::Error;
+
+use crate::{cms, hasher::SeedableHasher, keyheap::KeyHeap, CountMinSketch};
+
+#[cfg(test)]
+mod test;
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("Invalid k {0}: must be > 0")]
+    InvalidK(usize),
+
+    #[error("Failed to create the sketch: {0}")]
+    Sketch(#[from] cms::Error),
+}
+
+/// One of the heaviest keys of a [StreamSummary], from [StreamSummary::top_k].  The key was
+/// offered at least `lower` and at most `upper` times.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TopKEntry {
+    pub key: Vec<u8>,
+    pub lower: u64,
+    pub upper: u64,
+}
+
+/// Frequencies of a stream of keys, with its heaviest keys.
+///
+/// Every key is counted in a [CountMinSketch], and the `k` heaviest are tracked with the
+/// [Space-Saving](https://www.cs.ucsb.edu/sites/default/files/documents/2005-23.pdf)
+/// algorithm: an unseen key replaces the lightest one, inheriting its count as error.  The
+/// tracked keys are kept in a heap by count, so finding the lightest takes O(log k).  Both
+/// overcount, so the upper bound of a top key is the smaller of the two, and its lower bound
+/// is its Space-Saving count less the error.  Any key with more than `N / k` of the `N`
+/// offers is always in the top `k`.
+pub struct StreamSummary<T>
+where
+    T: SeedableHasher,
+{
+    sketch: CountMinSketch<T>,
+    k: usize,
+    // Space-Saving slots by count, with the count each key inherited as error when it took
+    // its slot
+    slots: KeyHeap<u64>,
+    total: u64,
+}
+
+impl<T> StreamSummary<T>
+where
+    T: SeedableHasher,
+{
+    /// Create a summary tracking the `k` heaviest keys, counting all of them in a sketch of
+    /// `depth` rows with `width` counters each.
+    ///
+    /// `k`, `width` and `depth` must be greater than 0.
+    pub fn new(k: usize, width: usize, depth: u32) -> Result<Self, Error> {
+        if k == 0 {
+            return Err(Error::InvalidK(k));
+        }
+        Ok(StreamSummary {
+            sketch: CountMinSketch::new(width, depth)?,
+            k,
+            slots: KeyHeap::with_capacity(k),
+            total: 0,
+        })
+    }
+
+    /// Offer one occurrence of `key`, returning its estimate after counting it.
+    pub fn offer<I>(&mut self, key: I) -> u64
+    where
+        I: AsRef<[u8]>,
+    {
+        let key = key.as_ref();
+        let estimate = self.sketch.add_and_estimate(key).saturating_add(1);
+        self.total = self.total.saturating_add(1);
+        if let Some((count, _)) = self.slots.get(key) {
+            self.slots.set_count(key, count.saturating_add(1));
+            return estimate;
+        }
+        let (mut count, mut error) = (1, 0);
+        if self.slots.len() == self.k {
+            let (_, min, _) = self.slots.pop_min().expect("k is > 0");
+            (count, error) = (min.saturating_add(1), min);
+        }
+        self.slots.insert(key.to_vec(), count, error);
+        estimate
+    }
+
+    /// Returns an estimate of the number of times `key` was offered, which never undercounts,
+    /// whether or not it's among the top `k`.
+    pub fn estimate<I>(&self, key: I) -> u64
+    where
+        I: AsRef<[u8]>,
+    {
+        let key = key.as_ref();
+        let estimate = self.sketch.estimate(key);
+        match self.slots.get(key) {
+            Some((count, _)) => estimate.min(count),
+            None => estimate,
+        }
+    }
+
+    /// The tracked keys with their bounds, heaviest first.  Ties are broken by the higher
+    /// lower bound, then by key.
+    pub fn top_k(&self) -> Vec<TopKEntry> {
+        let mut top = self
+            .slots
+            .iter()
+            .map(|(key, count, &error)| TopKEntry {
+                key: key.to_vec(),
+                lower: count - error,
+                upper: count.min(self.sketch.estimate(key)),
+            })
+            .collect::<Vec<_>>();
+        top.sort_by(|a, b| {
+            b.upper
+                .cmp(&a.upper)
+                .then(b.lower.cmp(&a.lower))
+                .then(a.key.cmp(&b.key))
+        });
+        top
+    }
+
+    /// Number of keys tracked.
+    pub fn k(&self) -> usize {
+        self.k
+    }
+
+    /// Number of offers so far.
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+}
@@ -0,0 +1,114 @@
+use fasthash::metro;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::collections::HashMap;
+
+use super::*;
+
+#[test]
+fn test_new() {
+    assert!(matches!(
+        StreamSummary::<metro::Hasher64_1>::new(0, 64, 4),
+        Err(Error::InvalidK(0))
+    ));
+    assert!(matches!(
+        StreamSummary::<metro::Hasher64_1>::new(4, 0, 4),
+        Err(Error::Sketch(cms::Error::InvalidWidth(0)))
+    ));
+}
+
+#[test]
+fn test_exact_under_k() {
+    let mut summary = StreamSummary::<metro::Hasher64_1>::new(4, 1024, 4).unwrap();
+    for (key, n) in [("a", 5), ("b", 3), ("c", 1)] {
+        for i in 1..=n {
+            assert_eq!(i, summary.offer(key));
+        }
+    }
+    assert_eq!(9, summary.total());
+    let top = summary.top_k();
+    assert_eq!(
+        vec![
+            TopKEntry {
+                key: b"a".to_vec(),
+                lower: 5,
+                upper: 5
+            },
+            TopKEntry {
+                key: b"b".to_vec(),
+                lower: 3,
+                upper: 3
+            },
+            TopKEntry {
+                key: b"c".to_vec(),
+                lower: 1,
+                upper: 1
+            },
+        ],
+        top
+    );
+    assert_eq!(0, summary.estimate("d"));
+}
+
+#[test]
+fn test_heavy_keys_survive() {
+    let mut summary = StreamSummary::<metro::Hasher64_1>::new(4, 256, 4).unwrap();
+    let mut counts = HashMap::new();
+    for i in 0..2000u32 {
+        // a third of the offers are "heavy", more than N / k, and most of the rest distinct
+        let key = match i {
+            i if i.is_multiple_of(3) => "heavy".to_string(),
+            i if i.is_multiple_of(10) => "warm".to_string(),
+            i => format!("cold{i}"),
+        };
+        summary.offer(&key);
+        *counts.entry(key).or_insert(0u64) += 1;
+    }
+    let top = summary.top_k();
+    assert_eq!(4, top.len());
+    assert_eq!(b"heavy".as_slice(), top[0].key);
+    for entry in &top {
+        let truth = counts[std::str::from_utf8(&entry.key).unwrap()];
+        assert!(entry.lower <= truth && truth <= entry.upper);
+    }
+    for (key, &truth) in &counts {
+        assert!(summary.estimate(key) >= truth);
+    }
+}
+
+#[test]
+fn test_evicts_lightest() {
+    // the heap must evict what a scan for the lightest slot, ties broken by key, would
+    let mut summary = StreamSummary::<metro::Hasher64_1>::new(8, 256, 4).unwrap();
+    let mut slots: HashMap<Vec<u8>, (u64, u64)> = HashMap::new();
+    let mut rng = StdRng::seed_from_u64(7);
+    for _ in 0..5000 {
+        let key = format!("k{}", rng.gen_range(0..40)).into_bytes();
+        summary.offer(&key);
+        if let Some((count, _)) = slots.get_mut(&key) {
+            *count += 1;
+            continue;
+        }
+        let mut slot = (1, 0);
+        if slots.len() == 8 {
+            let (lightest, &(min, _)) = slots
+                .iter()
+                .min_by(|a, b| a.1 .0.cmp(&b.1 .0).then(a.0.cmp(b.0)))
+                .unwrap();
+            slots.remove(&lightest.clone());
+            slot = (min + 1, min);
+        }
+        slots.insert(key, slot);
+    }
+    let mut expected = slots
+        .into_iter()
+        .map(|(key, (count, error))| (key, count - error))
+        .collect::<Vec<_>>();
+    expected.sort();
+    let mut top = summary
+        .top_k()
+        .into_iter()
+        .map(|entry| (entry.key, entry.lower))
+        .collect::<Vec<_>>();
+    top.sort();
+    assert_eq!(expected, top);
+}
@@ -0,0 +1,184 @@
+use std::{collections::TryReserveError, marker::PhantomData, mem};
+use thiserror::Error;
+
+use crate::{
+    hasher::SeedableHasher,
+    mem::{heap_size, try_filled},
+};
+
+#[cfg(test)]
+mod test;
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("Invalid expected entries {0}: must be > 0, with doorkeeper bits that fit in usize")]
+    InvalidExpectedEntries(usize),
+
+    #[error("Failed to allocate the sketch: {0}")]
+    AllocationFailed(#[from] TryReserveError),
+}
+
+// number of count-min rows
+const DEPTH: u32 = 4;
+const BITS_PER_COUNTER: usize = 4;
+const COUNTER_MAX: u64 = (1 << BITS_PER_COUNTER) - 1;
+const COUNTERS_PER_WORD: usize = u64::BITS as usize / BITS_PER_COUNTER;
+// clears the high bit of each counter after a word has been shifted right by one
+const HALVING_MASK: u64 = 0x7777_7777_7777_7777;
+const DOORKEEPER_BITS_PER_ENTRY: usize = 8;
+const DOORKEEPER_HASHES: u32 = 3;
+const SAMPLE_FACTOR: usize = 10;
+
+/// Implementation of the [TinyLFU](https://arxiv.org/abs/1512.00727) admission policy.
+///
+/// Frequencies are kept in a count-min sketch of 4 bit counters, fronted by a "doorkeeper"
+/// bloom filter so that keys seen only once never reach the sketch.  After a sample of
+/// `10 * expected_entries` increments, every counter is halved and the doorkeeper is cleared,
+/// which lets the sketch age out keys that were popular in the past.
+pub struct TinyLfu<T>
+where
+    T: SeedableHasher,
+{
+    table: Vec<u64>,
+    width: usize,
+    doorkeeper: Vec<u64>,
+    additions: usize,
+    sample_size: usize,
+    _hasher: PhantomData<T>,
+}
+
+impl<T> TinyLfu<T>
+where
+    T: SeedableHasher,
+{
+    /// Create a new sketch sized for a cache holding `expected_entries`.
+    ///
+    /// `expected_entries` must be greater than 0, and small enough that its doorkeeper's bits
+    /// can be counted in a usize.
+    pub fn new(expected_entries: usize) -> Result<Self, Error> {
+        let invalid = || Error::InvalidExpectedEntries(expected_entries);
+        if expected_entries == 0 {
+            return Err(invalid());
+        }
+        let width = expected_entries
+            .checked_next_power_of_two()
+            .ok_or_else(invalid)?;
+        let doorkeeper_bits = width
+            .checked_mul(DOORKEEPER_BITS_PER_ENTRY)
+            .ok_or_else(invalid)?;
+        Ok(TinyLfu {
+            table: try_filled((DEPTH as usize * width).div_ceil(COUNTERS_PER_WORD), 0)?,
+            width,
+            doorkeeper: try_filled(doorkeeper_bits.div_ceil(u64::BITS as usize), 0)?,
+            additions: 0,
+            sample_size: expected_entries.saturating_mul(SAMPLE_FACTOR),
+            _hasher: PhantomData,
+        })
+    }
+
+    /// Record an access to `entry`.
+    ///
+    /// The first access only sets the doorkeeper, later ones increment the sketch counters,
+    /// saturating at 15.  Counters are halved once the sample size is reached.
+    pub fn increment<I>(&mut self, entry: I)
+    where
+        I: AsRef<[u8]>,
+    {
+        let entry = entry.as_ref();
+        if self.doorkeeper_insert(entry) {
+            for row in 0..DEPTH {
+                let (word, bitshift) = self.counter_offset(row, entry);
+                if (self.table[word] >> bitshift) & COUNTER_MAX < COUNTER_MAX {
+                    self.table[word] += 1 << bitshift;
+                }
+            }
+        }
+
+        self.additions += 1;
+        if self.additions >= self.sample_size {
+            self.reset();
+        }
+    }
+
+    /// Returns an estimate of how often `entry` was accessed in the current sample period.
+    pub fn estimate<I>(&self, entry: I) -> u64
+    where
+        I: AsRef<[u8]>,
+    {
+        let entry = entry.as_ref();
+        let sketch = (0..DEPTH)
+            .map(|row| {
+                let (word, bitshift) = self.counter_offset(row, entry);
+                (self.table[word] >> bitshift) & COUNTER_MAX
+            })
+            .min()
+            .unwrap_or_default();
+        sketch + self.doorkeeper_contains(entry) as u64
+    }
+
+    /// Decide whether `candidate` should replace `victim` in the cache.  This returns true
+    /// only if the candidate has been accessed more often than the victim.
+    pub fn admit<C, V>(&self, candidate: C, victim: V) -> bool
+    where
+        C: AsRef<[u8]>,
+        V: AsRef<[u8]>,
+    {
+        self.estimate(candidate) > self.estimate(victim)
+    }
+
+    /// Halve every counter and clear the doorkeeper.
+    pub fn reset(&mut self) {
+        for word in self.table.iter_mut() {
+            *word = (*word >> 1) & HALVING_MASK;
+        }
+        self.doorkeeper.iter_mut().for_each(|w| *w = 0);
+        self.additions /= 2;
+    }
+
+    /// Bytes used by the admission policy, its own size plus the heap allocated for its
+    /// counters and doorkeeper, including spare capacity.
+    pub fn mem_usage(&self) -> usize {
+        mem::size_of::<Self>() + heap_size(&self.table) + heap_size(&self.doorkeeper)
+    }
+
+    fn counter_offset(&self, row: u32, entry: &[u8]) -> (usize, usize) {
+        let mut h = T::with_seed(row);
+        h.write(entry);
+        // width is a power of 2, so the mask keeps the column within the row
+        let column = h.finish() as usize & (self.width - 1);
+        let idx = row as usize * self.width + column;
+        (
+            idx / COUNTERS_PER_WORD,
+            (idx % COUNTERS_PER_WORD) * BITS_PER_COUNTER,
+        )
+    }
+
+    fn doorkeeper_bits<'a, 'b: 'a>(
+        &'a self,
+        entry: &'b [u8],
+    ) -> impl Iterator<Item = (usize, u64)> + 'a {
+        let n_bits = self.doorkeeper.len() * u64::BITS as usize;
+        (DEPTH..DEPTH + DOORKEEPER_HASHES).map(move |seed| {
+            let mut h = T::with_seed(seed);
+            h.write(entry);
+            let bit = h.finish() as usize % n_bits;
+            (bit / u64::BITS as usize, 1 << (bit % u64::BITS as usize))
+        })
+    }
+
+    fn doorkeeper_contains(&self, entry: &[u8]) -> bool {
+        self.doorkeeper_bits(entry)
+            .all(|(word, mask)| self.doorkeeper[word] & mask != 0)
+    }
+
+    // Returns true if the entry was already present.
+    fn doorkeeper_insert(&mut self, entry: &[u8]) -> bool {
+        let bits = self.doorkeeper_bits(entry).collect::<Vec<_>>();
+        let mut present = true;
+        for (word, mask) in bits {
+            present &= self.doorkeeper[word] & mask != 0;
+            self.doorkeeper[word] |= mask;
+        }
+        present
+    }
+}
@@ -0,0 +1,74 @@
+use fasthash::metro;
+
+use super::*;
+
+#[test]
+fn test_estimate() {
+    let mut lfu = TinyLfu::<metro::Hasher64_1>::new(64).unwrap();
+    assert_eq!(0, lfu.estimate("key"));
+    // first access only goes to the doorkeeper
+    lfu.increment("key");
+    assert_eq!(1, lfu.estimate("key"));
+    assert_eq!(0, lfu.table.iter().sum::<u64>());
+    for i in 2..=5 {
+        lfu.increment("key");
+        assert_eq!(i, lfu.estimate("key"));
+    }
+}
+
+#[test]
+fn test_saturate() {
+    let mut lfu = TinyLfu::<metro::Hasher64_1>::new(64).unwrap();
+    for _ in 0..100 {
+        lfu.increment("key");
+    }
+    assert_eq!(COUNTER_MAX + 1, lfu.estimate("key"));
+}
+
+#[test]
+fn test_reset() {
+    let mut lfu = TinyLfu::<metro::Hasher64_1>::new(64).unwrap();
+    for _ in 0..9 {
+        lfu.increment("key");
+    }
+    assert_eq!(9, lfu.estimate("key"));
+    lfu.reset();
+    assert_eq!(4, lfu.estimate("key"));
+    assert!(lfu.doorkeeper.iter().all(|&w| w == 0));
+}
+
+#[test]
+fn test_sample_period() {
+    let mut lfu = TinyLfu::<metro::Hasher64_1>::new(4).unwrap();
+    for _ in 0..lfu.sample_size - 1 {
+        lfu.increment("key");
+    }
+    let before = lfu.estimate("key");
+    lfu.increment("key");
+    assert!(lfu.estimate("key") < before);
+    assert_eq!(lfu.sample_size / 2, lfu.additions);
+}
+
+#[test]
+fn test_admit() {
+    let mut lfu = TinyLfu::<metro::Hasher64_1>::new(64).unwrap();
+    for _ in 0..5 {
+        lfu.increment("hot");
+    }
+    lfu.increment("cold");
+    assert!(lfu.admit("hot", "cold"));
+    assert!(!lfu.admit("cold", "hot"));
+    assert!(!lfu.admit("hot", "hot"));
+}
+
+#[test]
+fn test_invalid_expected_entries() {
+    assert!(matches!(
+        TinyLfu::<metro::Hasher64_1>::new(0),
+        Err(Error::InvalidExpectedEntries(0))
+    ));
+    assert!(matches!(
+        TinyLfu::<metro::Hasher64_1>::new(usize::MAX),
+        Err(Error::InvalidExpectedEntries(usize::MAX))
+    ));
+}
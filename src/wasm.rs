@@ -0,0 +1,124 @@
+//! [wasm-bindgen](https://rustwasm.github.io/docs/wasm-bindgen/) wrappers, so the filters can
+//! be used from JavaScript in browsers and edge runtimes.
+//!
+//! JavaScript can't name a Rust hasher, so every wrapper hashes with [metro::Hasher64_1], as the
+//! command line tool does, and filters serialized by either can be loaded by the other.  Entries
+//! are passed as `Uint8Array`s, strings should be encoded with `TextEncoder` first.
+use fasthash::metro;
+use wasm_bindgen::prelude::*;
+
+use crate::{BloomFilter, CountingBloomFilter, CuckooFilter};
+
+/// A [BloomFilter] for JavaScript.
+#[wasm_bindgen(js_name = BloomFilter)]
+pub struct WasmBloomFilter(BloomFilter<metro::Hasher64_1>);
+
+#[wasm_bindgen(js_class = BloomFilter)]
+impl WasmBloomFilter {
+    #[wasm_bindgen(constructor)]
+    pub fn new(num_bits: usize, num_hashes: u32) -> Result<WasmBloomFilter, JsError> {
+        Ok(WasmBloomFilter(BloomFilter::new(num_bits, num_hashes)?))
+    }
+
+    #[wasm_bindgen(js_name = withCapacityAndFpp)]
+    pub fn with_capacity_and_fpp(
+        expected_entries: usize,
+        fpp: f64,
+    ) -> Result<WasmBloomFilter, JsError> {
+        Ok(WasmBloomFilter(BloomFilter::with_capacity_and_fpp(
+            expected_entries,
+            fpp,
+        )?))
+    }
+
+    #[wasm_bindgen(js_name = fromBytes)]
+    pub fn from_bytes(bytes: &[u8]) -> Result<WasmBloomFilter, JsError> {
+        Ok(WasmBloomFilter(BloomFilter::from_bytes(bytes)?))
+    }
+
+    pub fn add(&mut self, entry: &[u8]) -> bool {
+        self.0.add(entry)
+    }
+
+    pub fn contains(&self, entry: &[u8]) -> bool {
+        self.0.contains(entry)
+    }
+
+    #[wasm_bindgen(js_name = toBytes)]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.0.to_bytes()
+    }
+}
+
+/// A [CountingBloomFilter] for JavaScript.
+#[wasm_bindgen(js_name = CountingBloomFilter)]
+pub struct WasmCountingBloomFilter(CountingBloomFilter<metro::Hasher64_1>);
+
+#[wasm_bindgen(js_class = CountingBloomFilter)]
+impl WasmCountingBloomFilter {
+    #[wasm_bindgen(constructor)]
+    pub fn new(num_counters: usize, num_hashes: u32) -> Result<WasmCountingBloomFilter, JsError> {
+        Ok(WasmCountingBloomFilter(CountingBloomFilter::new(
+            num_counters,
+            num_hashes,
+        )?))
+    }
+
+    pub fn add(&mut self, entry: &[u8]) -> bool {
+        self.0.add(entry)
+    }
+
+    pub fn remove(&mut self, entry: &[u8]) -> bool {
+        self.0.remove(entry)
+    }
+
+    pub fn contains(&self, entry: &[u8]) -> bool {
+        self.0.contains(entry)
+    }
+
+    pub fn estimate(&self, entry: &[u8]) -> usize {
+        self.0.estimate(entry)
+    }
+}
+
+/// A [CuckooFilter] for JavaScript.
+#[wasm_bindgen(js_name = CuckooFilter)]
+pub struct WasmCuckooFilter(CuckooFilter<metro::Hasher64_1>);
+
+#[wasm_bindgen(js_class = CuckooFilter)]
+impl WasmCuckooFilter {
+    #[wasm_bindgen(constructor)]
+    pub fn new(num_bins: usize) -> Result<WasmCuckooFilter, JsError> {
+        Ok(WasmCuckooFilter(CuckooFilter::new(num_bins)?))
+    }
+
+    #[wasm_bindgen(js_name = withCapacityAndFpp)]
+    pub fn with_capacity_and_fpp(n: usize, fpp: f64) -> Result<WasmCuckooFilter, JsError> {
+        Ok(WasmCuckooFilter(CuckooFilter::with_capacity_and_fpp(
+            n, fpp,
+        )?))
+    }
+
+    #[wasm_bindgen(js_name = fromBytes)]
+    pub fn from_bytes(bytes: &[u8]) -> Result<WasmCuckooFilter, JsError> {
+        Ok(WasmCuckooFilter(CuckooFilter::from_bytes(bytes)?))
+    }
+
+    /// Throws if the filter is full.
+    pub fn add(&mut self, entry: &[u8]) -> Result<(), JsError> {
+        Ok(self.0.add(entry)?)
+    }
+
+    pub fn remove(&mut self, entry: &[u8]) -> bool {
+        self.0.remove(entry)
+    }
+
+    pub fn contains(&self, entry: &[u8]) -> bool {
+        self.0.contains(entry)
+    }
+
+    #[wasm_bindgen(js_name = toBytes)]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.0.to_bytes()
+    }
+}